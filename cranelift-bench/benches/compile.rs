@@ -0,0 +1,89 @@
+//! Benchmarks of the compilation pipeline on the corpus.
+//!
+//! Each stage is measured separately on every function of the corpus, starting from the state
+//! left by the previous stages. The `compile` group measures the whole pipeline, including the
+//! fast compile tier, and the `translate` group measures the translation of the WebAssembly
+//! modules.
+
+use cranelift_bench::{clif_corpus, isa, prepare, run_stage, Stage};
+use cranelift_codegen::Context;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+fn stages(c: &mut Criterion) {
+    let isa = isa("default");
+    let corpus = clif_corpus();
+    for &stage in Stage::all().iter() {
+        for (name, func) in &corpus {
+            let isa = &*isa;
+            c.bench_function(&format!("{}/{}", stage.name(), name), move |b| {
+                b.iter_batched(
+                    || prepare(func, isa, stage),
+                    |(mut ctx, mut mem)| {
+                        run_stage(&mut ctx, isa, stage, &mut mem);
+                        (ctx, mem)
+                    },
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+}
+
+fn compile(c: &mut Criterion) {
+    for opt_level in &["fastest", "default", "best"] {
+        let isa = isa(opt_level);
+        for (name, func) in clif_corpus() {
+            let isa = &*isa;
+            let mut ctx = Context::new();
+            c.bench_function(&format!("compile/{}/{}", opt_level, name), move |b| {
+                b.iter(|| {
+                    ctx.clear();
+                    ctx.func = func.clone();
+                    ctx.compile(isa).unwrap()
+                })
+            });
+        }
+    }
+
+    let isa = isa("default");
+    for (name, func) in clif_corpus() {
+        use cranelift_codegen::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
+
+        let isa = &*isa;
+        let mut ctx = Context::new();
+        let mut mem = Vec::new();
+        c.bench_function(&format!("compile/fast/{}", name), move |b| {
+            b.iter(|| {
+                ctx.clear();
+                ctx.func = func.clone();
+                ctx.compile_fast(
+                    isa,
+                    &mut mem,
+                    &mut NullRelocSink {},
+                    &mut NullTrapSink {},
+                    &mut NullStackmapSink {},
+                )
+                .unwrap()
+            })
+        });
+    }
+}
+
+#[cfg(feature = "wasm")]
+fn translate(c: &mut Criterion) {
+    use cranelift_bench::{translate_wasm, wasm_corpus};
+
+    let isa = isa("default");
+    for (name, wasm) in wasm_corpus() {
+        let isa = &*isa;
+        c.bench_function(&format!("translate/{}", name), move |b| {
+            b.iter(|| translate_wasm(&wasm, isa))
+        });
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+fn translate(_: &mut Criterion) {}
+
+criterion_group!(benches, stages, compile, translate);
+criterion_main!(benches);
@@ -0,0 +1,236 @@
+//! Compile-time benchmarks for Cranelift.
+//!
+//! This crate holds a corpus of representative functions and the code driving them through the
+//! compilation pipeline one stage at a time, so each stage can be measured on its own. The
+//! benchmarks themselves live in `benches/` and use criterion; run them with `cargo bench` in
+//! this directory.
+//!
+//! The corpus consists of the CLIF files in `corpus/`, and, with the `wasm` feature, of the
+//! functions translated from the WebAssembly modules in the top-level `wasmtests/` directory.
+
+#![deny(missing_docs, trivial_numeric_casts, unused_extern_crates)]
+
+use cranelift_codegen::ir::Function;
+use cranelift_codegen::isa::{self, TargetIsa};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::{CodegenResult, Context};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use target_lexicon::triple;
+
+/// A pipeline stage that can be measured on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Legalization, including the expansion of heap accesses and calls.
+    Legalize,
+    /// Register allocation.
+    Regalloc,
+    /// Emission of the machine code into memory.
+    Binemit,
+}
+
+impl Stage {
+    /// All the stages, in pipeline order.
+    pub fn all() -> [Self; 3] {
+        [Stage::Legalize, Stage::Regalloc, Stage::Binemit]
+    }
+
+    /// Get the name of this stage, matching the pass names used by `cranelift_codegen::timing`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Legalize => "legalize",
+            Stage::Regalloc => "regalloc",
+            Stage::Binemit => "binemit",
+        }
+    }
+}
+
+/// Build the ISA the benchmarks compile for, with the given `opt_level` setting.
+pub fn isa(opt_level: &str) -> Box<dyn TargetIsa> {
+    let mut flag_builder = settings::builder();
+    flag_builder.set("opt_level", opt_level).unwrap();
+    isa::lookup(triple!("x86_64"))
+        .expect("The benchmarks require x86 support")
+        .finish(settings::Flags::new(flag_builder))
+}
+
+/// Get the directory containing the CLIF corpus.
+pub fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus")
+}
+
+/// Parse the CLIF corpus.
+///
+/// Returns the functions with their names, prefixed by the name of their file.
+pub fn clif_corpus() -> Vec<(String, Function)> {
+    let mut paths: Vec<_> = fs::read_dir(corpus_dir())
+        .expect("error reading the corpus directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "clif"))
+        .collect();
+    paths.sort();
+
+    let mut corpus = Vec::new();
+    for path in paths {
+        let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let text = fs::read_to_string(&path).expect("error reading corpus file");
+        let test_file = cranelift_reader::parse_test(&text, None, None)
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        for (func, _) in test_file.functions {
+            let name = format!("{}/{}", stem, &func.name.to_string()[1..]);
+            corpus.push((name, func));
+        }
+    }
+    corpus
+}
+
+/// Read the WebAssembly modules of the corpus.
+///
+/// Returns the binary modules with their names.
+#[cfg(feature = "wasm")]
+pub fn wasm_corpus() -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../wasmtests");
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .expect("error reading the wasmtests directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "wat"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let wat = fs::read(&path).expect("error reading wat file");
+            let wasm = wabt::wat2wasm(&wat)
+                .unwrap_or_else(|e| panic!("{}: error converting wat to wasm: {:?}", stem, e));
+            (stem, wasm)
+        })
+        .collect()
+}
+
+/// Translate a WebAssembly module to Cranelift IR for `isa`.
+#[cfg(feature = "wasm")]
+pub fn translate_wasm(wasm: &[u8], isa: &dyn TargetIsa) -> Vec<Function> {
+    use cranelift_wasm::{translate_module, DummyEnvironment, ReturnMode};
+
+    let mut environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+    translate_module(wasm, &mut environ).expect("error translating wasm module");
+    environ.info.function_bodies.values().cloned().collect()
+}
+
+/// Run the pipeline on a copy of `func` up to, but not including, `stage`.
+///
+/// The passes are the ones `Context::compile` runs with the `opt_level` of `isa`. Returns the
+/// context to pass to `run_stage`, and a buffer large enough for the machine code.
+pub fn prepare(func: &Function, isa: &dyn TargetIsa, stage: Stage) -> (Context, Vec<u8>) {
+    let mut ctx = Context::for_function(func.clone());
+    let mut mem = Vec::new();
+    prepare_context(&mut ctx, isa, stage, &mut mem)
+        .unwrap_or_else(|e| panic!("{}: {}", func.name, e));
+    (ctx, mem)
+}
+
+fn prepare_context(
+    ctx: &mut Context,
+    isa: &dyn TargetIsa,
+    stage: Stage,
+    mem: &mut Vec<u8>,
+) -> CodegenResult<()> {
+    let optimize = isa.flags().opt_level() != settings::OptLevel::Fastest;
+    ctx.compute_cfg();
+    if optimize {
+        ctx.preopt(isa)?;
+        ctx.coalesce_bounds_checks(isa)?;
+    }
+    if stage == Stage::Legalize {
+        return Ok(());
+    }
+
+    ctx.legalize(isa)?;
+    if optimize {
+        ctx.postopt(isa)?;
+    }
+    ctx.compute_domtree();
+    ctx.eliminate_unreachable_code(isa)?;
+    if optimize {
+        ctx.dce(isa)?;
+        ctx.block_placement(isa)?;
+    }
+    if stage == Stage::Regalloc {
+        return Ok(());
+    }
+
+    ctx.regalloc(isa)?;
+    ctx.prologue_epilogue(isa)?;
+    let info = ctx.relax_branches(isa)?;
+    mem.resize(info.total_size as usize, 0);
+    Ok(())
+}
+
+/// Run `stage` on a context returned by `prepare`.
+pub fn run_stage(ctx: &mut Context, isa: &dyn TargetIsa, stage: Stage, mem: &mut Vec<u8>) {
+    match stage {
+        Stage::Legalize => ctx.legalize(isa).unwrap(),
+        Stage::Regalloc => ctx.regalloc(isa).unwrap(),
+        Stage::Binemit => {
+            use cranelift_codegen::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
+            unsafe {
+                ctx.emit_to_memory(
+                    isa,
+                    mem.as_mut_ptr(),
+                    &mut NullRelocSink {},
+                    &mut NullTrapSink {},
+                    &mut NullStackmapSink {},
+                )
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corpus_compiles() {
+        let corpus = clif_corpus();
+        assert!(!corpus.is_empty());
+        for opt_level in &["fastest", "default"] {
+            let isa = isa(opt_level);
+            for (name, func) in &corpus {
+                for &stage in Stage::all().iter() {
+                    let (mut ctx, mut mem) = prepare(func, &*isa, stage);
+                    run_stage(&mut ctx, &*isa, stage, &mut mem);
+                    ctx.verify_if(&*isa)
+                        .unwrap_or_else(|e| panic!("{} after {}: {}", name, stage.name(), e));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fast_tier() {
+        use cranelift_codegen::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
+
+        let isa = isa("best");
+        for (name, func) in &clif_corpus() {
+            let mut ctx = Context::for_function(func.clone());
+            let mut mem = Vec::new();
+            let info = ctx
+                .compile_fast(
+                    &*isa,
+                    &mut mem,
+                    &mut NullRelocSink {},
+                    &mut NullTrapSink {},
+                    &mut NullStackmapSink {},
+                )
+                .unwrap();
+            assert!(mem.len() >= info.total_size as usize, "{}", name);
+            ctx.verify_if(&*isa)
+                .unwrap_or_else(|e| panic!("{}: {}", name, e));
+        }
+    }
+}
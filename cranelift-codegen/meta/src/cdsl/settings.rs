@@ -195,6 +195,21 @@ impl<'a> Into<PredicateNode> for (BoolSettingIndex, &'a SettingGroup) {
 }
 
 impl PredicateNode {
+    /// Translate indices of owned boolean settings through `new_index`.
+    fn remap_owned_bools(&mut self, new_index: &[usize]) {
+        match *self {
+            PredicateNode::OwnedBool(ref mut index) => {
+                *index = BoolSettingIndex(new_index[index.0])
+            }
+            PredicateNode::SharedBool(..) => {}
+            PredicateNode::Not(ref mut node) => node.remap_owned_bools(new_index),
+            PredicateNode::And(ref mut lhs, ref mut rhs) => {
+                lhs.remap_owned_bools(new_index);
+                rhs.remap_owned_bools(new_index);
+            }
+        }
+    }
+
     fn render(&self, group: &SettingGroup) -> String {
         match *self {
             PredicateNode::OwnedBool(bool_setting_index) => format!(
@@ -337,14 +352,19 @@ impl SettingGroupBuilder {
 
         let mut byte_offset = 0;
 
+        // Settings are reordered below, so remember where each one ends up in order to fix up
+        // the boolean setting indices held by presets and predicates.
+        let mut new_index = vec![0; self.settings.len()];
+
         // Assign the non-boolean settings first.
-        for s in &self.settings {
+        for (i, s) in self.settings.iter().enumerate() {
             let specific = match s.specific {
                 ProtoSpecificSetting::Bool(..) => continue,
                 ProtoSpecificSetting::Enum(ref values) => SpecificSetting::Enum(values.clone()),
                 ProtoSpecificSetting::Num(default) => SpecificSetting::Num(default),
             };
 
+            new_index[i] = group.settings.len();
             group.settings.push(Setting {
                 name: s.name,
                 comment: s.comment,
@@ -360,11 +380,12 @@ impl SettingGroupBuilder {
         let mut predicate_number = 0;
 
         // Then the boolean settings.
-        for s in &self.settings {
+        for (i, s) in self.settings.iter().enumerate() {
             let default = match s.specific {
                 ProtoSpecificSetting::Bool(default) => default,
                 ProtoSpecificSetting::Enum(_) | ProtoSpecificSetting::Num(_) => continue,
             };
+            new_index[i] = group.settings.len();
             group.settings.push(Setting {
                 name: s.name,
                 comment: s.comment,
@@ -390,9 +411,10 @@ impl SettingGroupBuilder {
 
         group
             .predicates
-            .extend(predicates.into_iter().map(|predicate| {
+            .extend(predicates.into_iter().map(|mut predicate| {
                 let number = predicate_number;
                 predicate_number += 1;
+                predicate.node.remap_owned_bools(&new_index);
                 return Predicate {
                     name: predicate.name,
                     node: predicate.node,
@@ -400,7 +422,16 @@ impl SettingGroupBuilder {
                 };
             }));
 
-        group.presets.extend(self.presets);
+        group.presets.extend(self.presets.into_iter().map(|preset| {
+            Preset {
+                name: preset.name,
+                values: preset
+                    .values
+                    .into_iter()
+                    .map(|index| BoolSettingIndex(new_index[index.0]))
+                    .collect(),
+            }
+        }));
 
         group
     }
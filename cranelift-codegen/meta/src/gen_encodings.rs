@@ -561,12 +561,19 @@ impl Level2Table {
         level2_hashtables: &mut Vec<Option<Level2HashTableEntry>>,
         level2_doc: &mut HashMap<usize, Vec<String>>,
     ) {
-        let hash_table = generate_table(
-            self.inst_to_encodings.values(),
-            self.inst_to_encodings.len(),
-            // TODO the Python code wanted opcode numbers to start from 1.
-            |enc_list| enc_list.inst.opcode_number.index() + 1,
-        );
+        let hash_table = if cfg!(feature = "compact-tables") {
+            // Compact tables are sorted by opcode, and searched with a binary search.
+            let mut sorted_table: Vec<_> = self.inst_to_encodings.values().map(Some).collect();
+            sorted_table.sort_by_key(|enc_list| enc_list.unwrap().inst.opcode_number);
+            sorted_table
+        } else {
+            generate_table(
+                self.inst_to_encodings.values(),
+                self.inst_to_encodings.len(),
+                // TODO the Python code wanted opcode numbers to start from 1.
+                |enc_list| enc_list.inst.opcode_number.index() + 1,
+            )
+        };
 
         let hash_table_offset = level2_hashtables.len();
         let hash_table_len = hash_table.len();
@@ -1025,9 +1032,17 @@ fn emit_tables(defs: &SharedDefinitions, isa: &TargetIsa, fmt: &mut Formatter) {
                     continue;
                 }
 
-                // Proper level 2 hash table.
-                let l2l = (level2.hash_table_len.unwrap() as f64).log2() as i32;
-                assert!(l2l > 0, "Level2 hash table was too small.");
+                // Proper level 2 table. Compact tables store their length instead of its binary
+                // logarithm.
+                let l2l = if cfg!(feature = "compact-tables") {
+                    let len = level2.hash_table_len.unwrap();
+                    assert!(len < 0xff, "Level2 table is too large for a compact table.");
+                    len as i32
+                } else {
+                    let l2l = (level2.hash_table_len.unwrap() as f64).log2() as i32;
+                    assert!(l2l > 0, "Level2 hash table was too small.");
+                    l2l
+                };
                 fmtln!(fmt, "Level1Entry {{ ty: {}, log2len: {}, offset: {:#08x}, legalize: {} }}, // {}",
                        typ_name, l2l, level2.hash_table_offset.unwrap(), legalize_code, legalize_comment);
             }
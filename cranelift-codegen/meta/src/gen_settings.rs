@@ -4,7 +4,7 @@ use crate::cdsl::settings::{
 };
 use crate::constant_hash::{generate_table, simple_hash};
 use crate::error;
-use crate::srcgen::{Formatter, Match};
+use crate::srcgen::{parse_multiline, Formatter, Match};
 use crate::unique_table::UniqueSeqTable;
 use std::collections::HashMap;
 
@@ -196,6 +196,24 @@ fn gen_getters(group: &SettingGroup, fmt: &mut Formatter) {
         });
         fmtln!(fmt, "}");
 
+        fmt.doc_comment(
+            "Iterate over the values of the settings in this group, excluding presets.",
+        );
+        fmtln!(
+            fmt,
+            "pub fn iter(&self) -> impl Iterator<Item = crate::settings::Value> {"
+        );
+        fmt.indent(|fmt| {
+            fmtln!(fmt, "let bytes = self.bytes;");
+            fmtln!(fmt, "DESCRIPTORS");
+            fmt.indent(|fmt| {
+                fmtln!(fmt, ".iter()");
+                fmtln!(fmt, ".filter(|d| !d.detail.is_preset())");
+                fmtln!(fmt, ".map(move |d| TEMPLATE.value(d, &bytes))");
+            });
+        });
+        fmtln!(fmt, "}");
+
         if group.settings.len() > 0 {
             fmt.doc_comment("Dynamic numbered predicate getter.");
             fmtln!(fmt, "fn numbered_predicate(&self, p: usize) -> bool {");
@@ -252,6 +270,11 @@ fn gen_descriptors(group: &SettingGroup, fmt: &mut Formatter) {
             fmt.indent(|fmt| {
                 fmtln!(fmt, "name: \"{}\",", setting.name);
                 fmtln!(fmt, "offset: {},", setting.byte_offset);
+                fmtln!(
+                    fmt,
+                    "description: {:?},",
+                    parse_multiline(setting.comment).join("\n")
+                );
                 match setting.specific {
                     SpecificSetting::Bool(BoolSetting { bit_offset, .. }) => {
                         fmtln!(
@@ -284,6 +307,7 @@ fn gen_descriptors(group: &SettingGroup, fmt: &mut Formatter) {
             fmt.indent(|fmt| {
                 fmtln!(fmt, "name: \"{}\",", preset.name);
                 fmtln!(fmt, "offset: {},", (idx as u8) * group.settings_size);
+                fmtln!(fmt, "description: \"\",");
                 fmtln!(fmt, "detail: detail::Detail::Preset,");
             });
             fmtln!(fmt, "},");
@@ -342,6 +342,7 @@ pub fn define(
     let copy_special = shared.by_name("copy_special");
     let ctz = shared.by_name("ctz");
     let debugtrap = shared.by_name("debugtrap");
+    let deopt = shared.by_name("deopt");
     let extractlane = shared.by_name("extractlane");
     let f32const = shared.by_name("f32const");
     let f64const = shared.by_name("f64const");
@@ -386,8 +387,10 @@ pub fn define(
     let load_complex = shared.by_name("load_complex");
     let nearest = shared.by_name("nearest");
     let null = shared.by_name("null");
+    let patchable_iconst = shared.by_name("patchable_iconst");
     let popcnt = shared.by_name("popcnt");
     let raw_bitcast = shared.by_name("raw_bitcast");
+    let raw_bytes = shared.by_name("raw_bytes");
     let regfill = shared.by_name("regfill");
     let regmove = shared.by_name("regmove");
     let regspill = shared.by_name("regspill");
@@ -439,6 +442,8 @@ pub fn define(
     let x86_pshufd = x86.by_name("x86_pshufd");
     let x86_pshufb = x86.by_name("x86_pshufb");
     let x86_push = x86.by_name("x86_push");
+    let x86_shadow_stack_pop = x86.by_name("x86_shadow_stack_pop");
+    let x86_shadow_stack_push = x86.by_name("x86_shadow_stack_push");
     let x86_sdivmodx = x86.by_name("x86_sdivmodx");
     let x86_smulx = x86.by_name("x86_smulx");
     let x86_udivmodx = x86.by_name("x86_udivmodx");
@@ -462,6 +467,7 @@ pub fn define(
     let rec_copysp = r.template("copysp");
     let rec_div = r.template("div");
     let rec_debugtrap = r.recipe("debugtrap");
+    let rec_deopt = r.recipe("deopt");
     let rec_f32imm_z = r.template("f32imm_z");
     let rec_f64imm_z = r.template("f64imm_z");
     let rec_fa = r.template("fa");
@@ -513,6 +519,7 @@ pub fn define(
     let rec_mulx = r.template("mulx");
     let rec_null = r.recipe("null");
     let rec_null_fpr = r.recipe("null_fpr");
+    let rec_patch_const = r.template("patch_const");
     let rec_pcrel_fnaddr8 = r.template("pcrel_fnaddr8");
     let rec_pcrel_gvaddr8 = r.template("pcrel_gvaddr8");
     let rec_popq = r.template("popq");
@@ -521,6 +528,7 @@ pub fn define(
     let rec_pu_id_ref = r.template("pu_id_ref");
     let rec_pu_iq = r.template("pu_iq");
     let rec_pushq = r.template("pushq");
+    let rec_raw_bytes = r.recipe("raw_bytes");
     let rec_ret = r.template("ret");
     let rec_r_ib = r.template("r_ib");
     let rec_r_ib_unsigned_gpr = r.template("r_ib_unsigned_gpr");
@@ -542,6 +550,8 @@ pub fn define(
     let rec_safepoint = r.recipe("safepoint");
     let rec_setf_abcd = r.template("setf_abcd");
     let rec_seti_abcd = r.template("seti_abcd");
+    let rec_shadow_stack_pop = r.recipe("shadow_stack_pop");
+    let rec_shadow_stack_push = r.recipe("shadow_stack_push");
     let rec_spaddr4_id = r.template("spaddr4_id");
     let rec_spaddr8_id = r.template("spaddr8_id");
     let rec_spillSib32 = r.template("spillSib32");
@@ -940,6 +950,9 @@ pub fn define(
     e.enc32(x86_pop.bind(I32), rec_popq.opcodes(vec![0x58]));
     e.enc_x86_64(x86_pop.bind(I64), rec_popq.opcodes(vec![0x58]));
 
+    e.enc64_rec(x86_shadow_stack_push, rec_shadow_stack_push, 0);
+    e.enc64_rec(x86_shadow_stack_pop, rec_shadow_stack_pop, 0);
+
     // Copy Special
     // For x86-64, only define REX forms for now, since we can't describe the
     // special regunit immediate operands with the current constraint language.
@@ -1294,6 +1307,12 @@ pub fn define(
     );
     e.enc32(jump_table_base.bind(I32), rec_jt_base.opcodes(vec![0x8d]));
 
+    // Late-bound constants are loaded from their patch site.
+    e.enc64(
+        patchable_iconst,
+        rec_patch_const.opcodes(vec![0x8b]).rex().w(),
+    );
+
     e.enc_x86_64(
         indirect_jump_table_br.bind(I64),
         rec_indirect_jmp.opcodes(vec![0xff]).rrr(4),
@@ -1309,10 +1328,18 @@ pub fn define(
     e.enc32(resumable_trap, rec_trap.opcodes(vec![0x0f, 0x0b]));
     e.enc64(resumable_trap, rec_trap.opcodes(vec![0x0f, 0x0b]));
 
+    // Deoptimization exit as ud2
+    e.enc32_rec(deopt, rec_deopt, 0);
+    e.enc64_rec(deopt, rec_deopt, 0);
+
     // Debug trap as int3
     e.enc32_rec(debugtrap, rec_debugtrap, 0);
     e.enc64_rec(debugtrap, rec_debugtrap, 0);
 
+    // Raw machine code is copied verbatim.
+    e.enc32_rec(raw_bytes, rec_raw_bytes, 0);
+    e.enc64_rec(raw_bytes, rec_raw_bytes, 0);
+
     e.enc32_rec(trapif, rec_trapif, 0);
     e.enc64_rec(trapif, rec_trapif, 0);
     e.enc32_rec(trapff, rec_trapff, 0);
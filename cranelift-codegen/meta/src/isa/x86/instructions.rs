@@ -217,6 +217,42 @@ pub fn define(
         .can_load(true),
     );
 
+    ig.push(
+        Inst::new(
+            "x86_shadow_stack_push",
+            r#"
+    Pushes the return address onto the shadow stack.
+
+    Copies the return address at the top of the stack to the shadow stack
+    pointed to by %r15, which grows towards lower addresses. %r11 is used
+    as a scratch register.
+
+    This is only implemented in 64-bit mode.
+    "#,
+        )
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_shadow_stack_pop",
+            r#"
+    Pops the return address off the shadow stack.
+
+    Traps if the return address at the top of the stack doesn't match the
+    one on the shadow stack pointed to by %r15. %r11 is used as a scratch
+    register.
+
+    This is only implemented in 64-bit mode.
+    "#,
+        )
+        .other_side_effects(true)
+        .can_load(true)
+        .can_trap(true),
+    );
+
     let y = &operand("y", iWord);
     let rflags = &operand("rflags", iflags);
 
@@ -384,6 +384,7 @@ pub fn define<'shared>(
     let f_load_complex = formats.by_name("LoadComplex");
     let f_multiary = formats.by_name("MultiAry");
     let f_nullary = formats.by_name("NullAry");
+    let f_raw_bytes = formats.by_name("RawBytes");
     let f_reg_fill = formats.by_name("RegFill");
     let f_reg_move = formats.by_name("RegMove");
     let f_reg_spill = formats.by_name("RegSpill");
@@ -398,6 +399,7 @@ pub fn define<'shared>(
     let f_unary_ieee32 = formats.by_name("UnaryIeee32");
     let f_unary_ieee64 = formats.by_name("UnaryIeee64");
     let f_unary_imm = formats.by_name("UnaryImm");
+    let f_unary_uimm32 = formats.by_name("UnaryUimm32");
 
     // Predicates shorthands.
     let use_sse41 = settings.predicate_by_name("use_sse41");
@@ -429,7 +431,30 @@ pub fn define<'shared>(
     recipes
         .add_recipe(EncodingRecipeBuilder::new("debugtrap", f_nullary, 1).emit("sink.put1(0xcc);"));
 
+    // Raw machine code. The operands are in the registers pinned by the code's signature.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("raw_bytes", f_raw_bytes, 0)
+            .compute_size("size_of_raw_code")
+            .emit(
+                r#"
+                    for &byte in &func.dfg.raw_code[raw_code].bytes {
+                        sink.put1(byte);
+                    }
+                "#,
+            ),
+    );
+
     // XX opcode, no ModR/M.
+    // ud2 reporting the locations of the captured values.
+    recipes.add_recipe(EncodingRecipeBuilder::new("deopt", f_multiary, 2).emit(
+        r#"
+            let locations = DeoptLocation::from_values(args, func, divert);
+            sink.deopt(func.srclocs[inst], &locations);
+            sink.put1(0x0f);
+            sink.put1(0x0b);
+        "#,
+    ));
+
     recipes.add_template_recipe(EncodingRecipeBuilder::new("trap", f_trap, 0).emit(
         r#"
             sink.trap(code, func.srclocs[inst]);
@@ -979,6 +1004,47 @@ pub fn define<'shared>(
             ),
     );
 
+    // Shadow stack push, using %r11 as a scratch register:
+    //
+    //     mov r11, [rsp]
+    //     sub r15, 8
+    //     mov [r15], r11
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("shadow_stack_push", f_nullary, 11).emit(
+            r#"
+                sink.put4(0x241c_8b4c);
+                sink.put4(0x08ef_8349);
+                sink.put1(0x4d);
+                sink.put1(0x89);
+                sink.put1(0x1f);
+            "#,
+        ),
+    );
+
+    // Shadow stack pop, trapping if the return address doesn't match:
+    //
+    //     mov r11, [rsp]
+    //     cmp r11, [r15]
+    //     je  1f
+    //     ud2
+    // 1:  add r15, 8
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("shadow_stack_pop", f_nullary, 15).emit(
+            r#"
+                sink.put4(0x241c_8b4c);
+                sink.put1(0x4d);
+                sink.put1(0x3b);
+                sink.put1(0x1f);
+                sink.put1(0x74);
+                sink.put1(2);
+                sink.trap(TrapCode::ReturnAddressMismatch, func.srclocs[inst]);
+                sink.put1(0x0f);
+                sink.put1(0x0b);
+                sink.put4(0x08c7_8349);
+            "#,
+        ),
+    );
+
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("popq", f_nullary, 0)
             .operands_out(vec![gpr])
@@ -1209,6 +1275,24 @@ pub fn define<'shared>(
             ),
     );
 
+    // XX /r with a RIP-relative displacement to a patch site.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("patch_const", f_unary_uimm32, 5)
+            .operands_out(vec![gpr])
+            // rex2 gets passed 0 for r/m register because the upper bit of
+            // r/m doesn't get decoded when in rip-relative addressing mode.
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(0, out_reg0), sink);
+                    modrm_riprel(out_reg0, sink);
+
+                    // No reloc is needed here as the patch sites are emitted directly after
+                    // the function body.
+                    patch_disp4(inst, func, sink);
+                "#,
+            ),
+    );
+
     // Stack addresses.
     //
     // TODO Alternative forms for 8-bit immediates, when applicable.
@@ -2903,11 +2987,15 @@ pub fn define<'shared>(
             ),
     );
 
-    recipes.add_recipe(EncodingRecipeBuilder::new("safepoint", f_multiary, 0).emit(
-        r#"
-            sink.add_stackmap(args, func, isa);
-        "#,
-    ));
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("safepoint", f_multiary, 0)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.add_stackmap(args, func, isa);
+                "#,
+            ),
+    );
 
     recipes
 }
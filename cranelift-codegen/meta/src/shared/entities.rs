@@ -33,6 +33,11 @@ pub fn define() -> Vec<OperandKind> {
     let func_ref = create("func_ref", "An external function.").build();
     kinds.push(func_ref);
 
+    // A reference to raw machine code declared in the function preamble.
+    // This is used to provide the bytes and the signature in a raw_bytes instruction.
+    let raw_code = create("raw_code", "Raw machine code.").build();
+    kinds.push(raw_code);
+
     // A reference to a jump table declared in the function preamble.
     let jump_table = create("jump_table", "A jump table.")
         .default_member("table")
@@ -22,6 +22,7 @@ pub fn define(immediates: &OperandKinds, entities: &OperandKinds) -> FormatRegis
     let jump_table = entities.by_name("jump_table");
     let func_ref = entities.by_name("func_ref");
     let sig_ref = entities.by_name("sig_ref");
+    let raw_code = entities.by_name("raw_code");
     let stack_slot = entities.by_name("stack_slot");
     let heap = entities.by_name("heap");
     let table = entities.by_name("table");
@@ -34,6 +35,7 @@ pub fn define(immediates: &OperandKinds, entities: &OperandKinds) -> FormatRegis
     registry.insert(Builder::new("UnaryIeee64").imm(ieee64));
     registry.insert(Builder::new("UnaryBool").imm(boolean));
     registry.insert(Builder::new("UnaryGlobalValue").imm(global_value));
+    registry.insert(Builder::new("UnaryUimm32").imm(uimm32));
 
     registry.insert(Builder::new("Binary").value().value());
     registry.insert(Builder::new("BinaryImm").value().imm(imm64));
@@ -109,7 +111,9 @@ pub fn define(immediates: &OperandKinds, entities: &OperandKinds) -> FormatRegis
 
     registry.insert(Builder::new("Call").imm(func_ref).varargs());
     registry.insert(Builder::new("CallIndirect").imm(sig_ref).value().varargs());
+    registry.insert(Builder::new("Invoke").imm(func_ref).imm(ebb).varargs());
     registry.insert(Builder::new("FuncAddr").imm(func_ref));
+    registry.insert(Builder::new("RawBytes").imm(raw_code).varargs());
 
     registry.insert(Builder::new("Load").imm(memflags).value().imm(offset32));
     registry.insert(
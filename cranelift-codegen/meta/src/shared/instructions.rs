@@ -42,6 +42,7 @@ pub fn define(
     let variable_args = entities.by_name("variable_args");
     let func_ref = entities.by_name("func_ref");
     let sig_ref = entities.by_name("sig_ref");
+    let raw_code = entities.by_name("raw_code");
     let stack_slot = entities.by_name("stack_slot");
     let global_value = entities.by_name("global_value");
     let heap = entities.by_name("heap");
@@ -53,6 +54,7 @@ pub fn define(
     let b1: &TypeVar = &ValueType::from(LaneType::from(types::Bool::B1)).into();
     let f32_: &TypeVar = &ValueType::from(LaneType::from(types::Float::F32)).into();
     let f64_: &TypeVar = &ValueType::from(LaneType::from(types::Float::F64)).into();
+    let i64_: &TypeVar = &ValueType::from(LaneType::from(types::Int::I64)).into();
 
     // Starting definitions.
     let Int = &TypeVar::new(
@@ -388,6 +390,25 @@ pub fn define(
         .is_terminator(true),
     );
 
+    let args = &operand_doc("args", variable_args, "Values captured by the exit");
+
+    ig.push(
+        Inst::new(
+            "deopt",
+            r#"
+        Exit to the runtime to deoptimize the function.
+
+        Terminate execution with a trap, and report the locations of ``args``
+        at the exit point along with the source location of the instruction.
+        The runtime's trap handler can use them to rebuild the state of the
+        function and resume it in a less optimized tier.
+        "#,
+        )
+        .operands_in(vec![args])
+        .can_trap(true)
+        .is_terminator(true),
+    );
+
     ig.push(
         Inst::new(
             "trapz",
@@ -530,6 +551,30 @@ pub fn define(
         .is_call(true),
     );
 
+    let EBB = &operand_doc("EBB", ebb, "landing pad entered when the callee unwinds");
+
+    ig.push(
+        Inst::new(
+            "invoke",
+            r#"
+        Direct function call with a landing pad.
+
+        Call a function which has been declared in the preamble, like `call`.
+        When the callee returns, execution continues with the next
+        instruction. When the callee unwinds, control is transferred to the
+        landing pad ``EBB`` instead. The landing pad can't have parameters,
+        and the results of the call are not available in it.
+
+        Cranelift doesn't emit unwind tables yet, so `invoke` is legalized
+        to a `call` and its landing pad is never entered by unwinding.
+        "#,
+        )
+        .operands_in(vec![FN, EBB, args])
+        .operands_out(vec![rvals])
+        .is_call(true)
+        .is_branch(true),
+    );
+
     ig.push(
         Inst::new(
             "func_addr",
@@ -547,6 +592,32 @@ pub fn define(
         .operands_out(vec![addr]),
     );
 
+    let CODE = &operand_doc("CODE", raw_code, "machine code, declared by `raw_code`");
+    let args = &operand_doc("args", variable_args, "code arguments");
+
+    ig.push(
+        Inst::new(
+            "raw_bytes",
+            r#"
+        Emit raw machine code.
+
+        Copy the bytes of the machine code declared in the preamble to the
+        instruction's position. The arguments and the results are passed in
+        the registers given by the code's signature, and the registers
+        declared as clobbered by the code don't hold any values that are live
+        across this instruction.
+
+        The machine code must fall through to the next instruction.
+        "#,
+        )
+        .operands_in(vec![CODE, args])
+        .operands_out(vec![rvals])
+        .is_call(true)
+        .can_load(true)
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
     let SS = &operand("SS", stack_slot);
     let Offset = &operand_doc("Offset", offset32, "Byte offset from base address");
     let x = &operand_doc("x", Mem, "Value to be stored");
@@ -986,6 +1057,9 @@ pub fn define(
            absolute address corresponding to a byte offset of ``p`` from the
            heap's base address.
         2. If ``p + Size`` is greater than the heap bound, generate a trap.
+
+        The bytes of the range that fall in the heap's offset-guard pages are not
+        checked explicitly, since accessing them traps.
         "#,
         )
         .operands_in(vec![H, p, Size])
@@ -1088,6 +1162,30 @@ pub fn define(
         .operands_out(vec![a]),
     );
 
+    let ID = &operand_doc("ID", uimm32, "Identifier of the patch site");
+    let a = &operand_doc("a", i64_, "The current value of the constant");
+
+    ig.push(
+        Inst::new(
+            "patchable_iconst",
+            r#"
+        Late-bound integer constant.
+
+        Load a 64-bit constant from a patch site in the function's read-only
+        data. The code size information returned by the compiler lists the
+        offset of each patch site along with its identifier, so the runtime
+        can bind the constant after compilation, and repatch it atomically
+        while the code is running.
+
+        The patch sites are 8-byte aligned relative to the start of the
+        function, and they are initialized to 0.
+        "#,
+        )
+        .operands_in(vec![ID])
+        .operands_out(vec![a])
+        .can_load(true),
+    );
+
     let a = &operand_doc("a", Ref, "A constant reference null value");
 
     ig.push(
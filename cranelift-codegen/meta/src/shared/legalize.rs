@@ -124,6 +124,7 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
 
     // Custom expansions for calls.
     expand.custom_legalize(insts.by_name("call"), "expand_call");
+    expand.custom_legalize(insts.by_name("invoke"), "expand_invoke");
 
     // Custom expansions that need to change the CFG.
     // TODO: Add sufficient XForm syntax that we don't need to hand-code these.
@@ -26,6 +26,18 @@ pub fn define() -> SettingGroup {
         true,
     );
 
+    settings.add_bool(
+        "enable_fast_verifier",
+        r#"
+        Run a fast subset of the verifier when `enable_verifier` is false.
+
+        Only the block terminators, the branch targets and the dominance of
+        the values used by instructions are checked. This is cheap enough to
+        catch gross bugs in the compiler in production.
+        "#,
+        false,
+    );
+
     // Note that Cranelift doesn't currently need an is_pie flag, because PIE is
     // just PIC where symbols can't be pre-empted, which can be expressed with the
     // `colocated` flag on external functions and global values.
@@ -47,7 +59,7 @@ pub fn define() -> SettingGroup {
         false,
     );
 
-    settings.add_bool(
+    let avoid_div_traps = settings.add_bool(
         "avoid_div_traps",
         r#"
             Generate explicit checks around native division instructions to avoid
@@ -72,7 +84,7 @@ pub fn define() -> SettingGroup {
         true,
     );
 
-    settings.add_bool(
+    let enable_nan_canonicalization = settings.add_bool(
         "enable_nan_canonicalization",
         r#"
             Enable NaN canonicalization
@@ -104,6 +116,32 @@ pub fn define() -> SettingGroup {
         false,
     );
 
+    settings.add_bool(
+        "enable_auto_safepoints",
+        r#"
+            Insert a safepoint before every call and every loop backedge.
+
+            The safepoints are recorded in stackmaps even when no reference values
+            are live, so runtimes can interrupt long-running loops cooperatively.
+            This has no effect unless `enable_safepoints` is set.
+            "#,
+        false,
+    );
+
+    settings.add_bool(
+        "enable_shadow_stack",
+        r#"
+            Protect return addresses with a shadow stack.
+
+            Prologues copy the return address to a separate stack, and epilogues
+            trap if the return address was modified in the meantime. On x86-64,
+            the shadow stack pointer is kept in %r15, which is reserved for this
+            purpose and must be initialized by the embedder. Other targets don't
+            support this setting.
+            "#,
+        false,
+    );
+
     // Settings specific to the `baldrdash` calling convention.
 
     settings.add_enum(
@@ -196,5 +234,14 @@ pub fn define() -> SettingGroup {
         true,
     );
 
+    // Presets.
+
+    // Results must not depend on the host: NaN bit patterns are canonicalized, and integer
+    // division traps are raised by explicit checks rather than by the native instructions.
+    settings.add_preset(
+        "deterministic",
+        preset!(enable_nan_canonicalization && avoid_div_traps),
+    );
+
     settings.build()
 }
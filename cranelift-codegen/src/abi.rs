@@ -3,7 +3,7 @@
 //! This module provides functions and data structures that are useful for implementing the
 //! `TargetIsa::legalize_signature()` method.
 
-use crate::ir::{AbiParam, ArgumentExtension, ArgumentLoc, Type};
+use crate::ir::{AbiParam, ArgumentExtension, ArgumentLoc, Signature, Type};
 use core::cmp::Ordering;
 use std::vec::Vec;
 
@@ -118,6 +118,23 @@ pub fn legalize_args<AA: ArgAssigner>(args: &mut Vec<AbiParam>, aa: &mut AA) {
     }
 }
 
+/// Legalize the parameters of `sig` using the given argument assigner.
+///
+/// This keeps track of where the variadic arguments of a variadic signature start when the fixed
+/// parameters are split or converted.
+pub fn legalize_params<AA: ArgAssigner>(sig: &mut Signature, aa: &mut AA) {
+    match sig.variadic {
+        None => legalize_args(&mut sig.params, aa),
+        Some(fixed) => {
+            let mut variadic = sig.params.split_off(fixed);
+            legalize_args(&mut sig.params, aa);
+            sig.variadic = Some(sig.params.len());
+            legalize_args(&mut variadic, aa);
+            sig.params.append(&mut variadic);
+        }
+    }
+}
+
 /// Determine the right action to take when passing a `have` value type to a call signature where
 /// the next argument is `arg` which has a different value type.
 ///
@@ -0,0 +1,29 @@
+use crate::ir::{Function, Value, ValueLoc};
+use crate::isa::{RegUnit, StackRef};
+use crate::regalloc::RegDiversions;
+use std::vec::Vec;
+
+/// Location of a value captured by a `deopt` instruction when its trap is raised.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeoptLocation {
+    /// The value is in a register.
+    Reg(RegUnit),
+
+    /// The value is in memory, at a byte offset from the stack pointer.
+    Stack(i32),
+}
+
+impl DeoptLocation {
+    /// Get the current locations of the values captured by a `deopt` instruction.
+    pub fn from_values(args: &[Value], func: &Function, divert: &RegDiversions) -> Vec<Self> {
+        args.iter()
+            .map(|&arg| match divert.get(arg, &func.locations) {
+                ValueLoc::Reg(reg) => DeoptLocation::Reg(reg),
+                ValueLoc::Stack(ss) => {
+                    DeoptLocation::Stack(StackRef::sp(ss, &func.stack_slots).offset)
+                }
+                ValueLoc::Unassigned => panic!("{} has no location", arg),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,320 @@
+//! Code sink that streams binary machine code into growable memory.
+//!
+//! The `MemoryCodeSink` requires the embedder to allocate memory for the whole function before
+//! emitting it, using the size returned by `Context::compile()`. The `GrowableCodeSink` instead
+//! asks a `CodeMemory` for more room whenever the machine code doesn't fit, so embedders can
+//! stream functions into memory that grows a page at a time, and don't need to know the size of
+//! the code before emitting it.
+//!
+//! Like the `MemoryCodeSink`, the `GrowableCodeSink` is a type known to `TargetIsa`, so the
+//! machine code generation is specialized for it. Each write is checked against the size of the
+//! memory, but the `CodeMemory` trait object is only called when the memory needs to grow.
+use super::{
+    Addend, CodeInfo, CodeOffset, CodeSink, DeoptLocation, MemoryCodeSink, PatchSite, Reloc,
+    RelocSink, StackmapSink, TrapSink,
+};
+use crate::binemit::stackmap::Stackmap;
+use crate::ir::entities::Value;
+use crate::ir::{ExternalName, Function, JumpTable, SourceLoc, TrapCode};
+use crate::isa::TargetIsa;
+use core::cmp;
+use core::ptr::write_unaligned;
+use std::vec::Vec;
+
+/// Memory that grows as machine code is emitted into it.
+///
+/// The machine code of a function is written from the start of the memory, and the memory may
+/// move when it grows, so it must not be relocated or executed before the emission is complete.
+pub trait CodeMemory {
+    /// Grow the memory to at least `size` bytes, keeping its contents, and return all of it.
+    ///
+    /// Implementations may round `size` up, for example to a whole number of pages, and the
+    /// returned slice is then longer than `size`.
+    fn grow(&mut self, size: usize) -> &mut [u8];
+}
+
+/// A `Vec<u8>` grows to exactly the requested size, so after emitting a function into it, its
+/// length is at least the `total_size` of the function, and the remaining bytes are zero.
+impl CodeMemory for Vec<u8> {
+    fn grow(&mut self, size: usize) -> &mut [u8] {
+        if self.len() < size {
+            self.resize(size, 0);
+        }
+        self
+    }
+}
+
+/// Minimum size requested by a `GrowableCodeSink` when it grows its memory.
+const MIN_GROWTH: usize = 64;
+
+/// A `CodeSink` that writes binary machine code into a `CodeMemory`, growing it as needed.
+///
+/// Any relocations in the function are forwarded to the `RelocSink` trait object.
+///
+/// Note that `GrowableCodeSink` writes multi-byte values in the native byte order of the host.
+/// This is not the right thing to do for cross compilation.
+pub struct GrowableCodeSink<'a> {
+    memory: &'a mut dyn CodeMemory,
+    /// Pointer to the start of the memory returned by the last `CodeMemory::grow` call.
+    data: *mut u8,
+    /// Size of the memory returned by the last `CodeMemory::grow` call.
+    capacity: usize,
+    offset: usize,
+    relocs: &'a mut dyn RelocSink,
+    traps: &'a mut dyn TrapSink,
+    stackmaps: &'a mut dyn StackmapSink,
+    /// Information about the generated code and read-only data.
+    pub info: CodeInfo,
+}
+
+impl<'a> GrowableCodeSink<'a> {
+    /// Create a new code sink that writes a function to the start of `memory`.
+    pub fn new(
+        memory: &'a mut dyn CodeMemory,
+        relocs: &'a mut dyn RelocSink,
+        traps: &'a mut dyn TrapSink,
+        stackmaps: &'a mut dyn StackmapSink,
+    ) -> Self {
+        Self {
+            memory,
+            data: core::ptr::null_mut(),
+            capacity: 0,
+            offset: 0,
+            info: CodeInfo {
+                code_size: 0,
+                jumptables_size: 0,
+                rodata_size: 0,
+                total_size: 0,
+                patch_sites: Vec::new(),
+            },
+            relocs,
+            traps,
+            stackmaps,
+        }
+    }
+
+    /// Emit `size` bytes of machine code with `emit`, which writes them into a `MemoryCodeSink`
+    /// forwarding to the same relocation, trap, and stackmap sinks.
+    ///
+    /// The memory is grown to `size` bytes first, so `emit` must not write more than that.
+    pub fn emit_with_memory_sink<F>(&mut self, size: CodeOffset, emit: F)
+    where
+        F: FnOnce(&mut MemoryCodeSink),
+    {
+        let end = self.offset + size as usize;
+        if end > self.capacity {
+            self.grow(end);
+        }
+        let info = {
+            let mut sink = unsafe {
+                MemoryCodeSink::new(
+                    self.data.add(self.offset),
+                    &mut *self.relocs,
+                    &mut *self.traps,
+                    &mut *self.stackmaps,
+                )
+            };
+            emit(&mut sink);
+            sink.info
+        };
+        assert!(info.total_size <= size, "Emitted more than {} bytes", size);
+        self.offset += info.total_size as usize;
+        self.info = info;
+    }
+
+    fn write<T>(&mut self, x: T) {
+        let size = std::mem::size_of::<T>();
+        if self.offset + size > self.capacity {
+            self.grow(self.offset + size);
+        }
+        unsafe {
+            #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+            write_unaligned(self.data.add(self.offset) as *mut T, x);
+        }
+        self.offset += size;
+    }
+
+    /// Grow the memory to at least `size` bytes, doubling it to amortize the copies.
+    #[cold]
+    fn grow(&mut self, size: usize) {
+        let size = cmp::max(size, cmp::max(2 * self.capacity, MIN_GROWTH));
+        let memory = self.memory.grow(size);
+        assert!(memory.len() >= size, "CodeMemory didn't grow");
+        self.data = memory.as_mut_ptr();
+        self.capacity = memory.len();
+    }
+}
+
+impl<'a> CodeSink for GrowableCodeSink<'a> {
+    fn offset(&self) -> CodeOffset {
+        self.offset as CodeOffset
+    }
+
+    fn put1(&mut self, x: u8) {
+        self.write(x);
+    }
+
+    fn put2(&mut self, x: u16) {
+        self.write(x);
+    }
+
+    fn put4(&mut self, x: u32) {
+        self.write(x);
+    }
+
+    fn put8(&mut self, x: u64) {
+        self.write(x);
+    }
+
+    fn reloc_ebb(&mut self, rel: Reloc, ebb_offset: CodeOffset) {
+        let ofs = self.offset();
+        self.relocs.reloc_ebb(ofs, rel, ebb_offset);
+    }
+
+    fn reloc_external(&mut self, rel: Reloc, name: &ExternalName, addend: Addend) {
+        let ofs = self.offset();
+        self.relocs.reloc_external(ofs, rel, name, addend);
+    }
+
+    fn reloc_jt(&mut self, rel: Reloc, jt: JumpTable) {
+        let ofs = self.offset();
+        self.relocs.reloc_jt(ofs, rel, jt);
+    }
+
+    fn trap(&mut self, code: TrapCode, srcloc: SourceLoc) {
+        let ofs = self.offset();
+        self.traps.trap(ofs, srcloc, code);
+    }
+
+    fn deopt(&mut self, srcloc: SourceLoc, locations: &[DeoptLocation]) {
+        let ofs = self.offset();
+        self.traps.deopt(ofs, srcloc, locations);
+    }
+
+    fn patch_site(&mut self, id: u32) {
+        let offset = self.offset();
+        self.info.patch_sites.push(PatchSite { offset, id });
+    }
+
+    fn begin_jumptables(&mut self) {
+        self.info.code_size = self.offset();
+    }
+
+    fn begin_rodata(&mut self) {
+        self.info.jumptables_size = self.offset() - self.info.code_size;
+    }
+
+    fn end_codegen(&mut self) {
+        self.info.rodata_size = self.offset() - (self.info.jumptables_size + self.info.code_size);
+        self.info.total_size = self.offset();
+    }
+
+    fn add_stackmap(&mut self, val_list: &[Value], func: &Function, isa: &dyn TargetIsa) {
+        let ofs = self.offset();
+        let stackmap = Stackmap::from_values(val_list, func, isa);
+        self.stackmaps.add_stackmap(ofs, stackmap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binemit::{function_size, NullRelocSink, NullStackmapSink, NullTrapSink};
+    use std::vec;
+
+    /// Memory growing a page at a time, checking that the sink always asks for more room.
+    struct Pages(Vec<u8>);
+
+    impl CodeMemory for Pages {
+        fn grow(&mut self, size: usize) -> &mut [u8] {
+            assert!(size > self.0.len());
+            let pages = (size + 4095) / 4096;
+            self.0.resize(pages * 4096, 0);
+            &mut self.0
+        }
+    }
+
+    #[test]
+    fn grows() {
+        let mut memory = Pages(Vec::new());
+        let mut relocs = NullRelocSink {};
+        let mut traps = NullTrapSink {};
+        let mut stackmaps = NullStackmapSink {};
+        let mut sink = GrowableCodeSink::new(&mut memory, &mut relocs, &mut traps, &mut stackmaps);
+        for i in 0..3000u32 {
+            sink.put1(i as u8);
+            sink.put4(i);
+        }
+        sink.begin_jumptables();
+        sink.begin_rodata();
+        sink.end_codegen();
+        assert_eq!(sink.info.total_size, 15000);
+        assert_eq!(sink.info.code_size, 15000);
+
+        assert_eq!(memory.0.len(), 16384);
+        assert_eq!(&memory.0[..5], [0, 0, 0, 0, 0]);
+        assert_eq!(memory.0[14995], 0xb7);
+        assert_eq!(&memory.0[14996..15000], 2999u32.to_ne_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn default_emitter() {
+        use crate::cursor::{Cursor, FuncCursor};
+        use crate::ir::{types, AbiParam, Function, InstBuilder};
+        use crate::isa;
+        use crate::settings;
+        use crate::Context;
+        use core::str::FromStr;
+        use target_lexicon::triple;
+
+        let isa = isa::lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()));
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I32));
+        func.signature.returns.push(AbiParam::new(types::I32));
+        let ebb = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            let sum = pos.ins().iadd_imm(arg, 42);
+            pos.ins().patchable_iconst(7);
+            pos.ins().return_(&[sum]);
+        }
+        let mut ctx = Context::for_function(func);
+        let info = ctx.compile(&*isa).unwrap();
+        assert_eq!(info.patch_sites.len(), 1);
+        assert_eq!(
+            function_size(&ctx.func, &isa.encoding_info()),
+            info.total_size
+        );
+
+        // The ISA's own emitter and the default one, emitting into a `MemoryCodeSink`, must agree.
+        let mut relocs = NullRelocSink {};
+        let mut traps = NullTrapSink {};
+        let mut stackmaps = NullStackmapSink {};
+        let mut expected = Vec::new();
+        let mut sink =
+            GrowableCodeSink::new(&mut expected, &mut relocs, &mut traps, &mut stackmaps);
+        isa.emit_function_to_growable(&ctx.func, &mut sink);
+        assert_eq!(sink.info.total_size, info.total_size);
+
+        let mut memory = Vec::new();
+        let mut sink = GrowableCodeSink::new(&mut memory, &mut relocs, &mut traps, &mut stackmaps);
+        sink.emit_with_memory_sink(info.total_size, |sink| {
+            isa.emit_function_to_memory(&ctx.func, sink)
+        });
+        assert_eq!(sink.info.total_size, info.total_size);
+        assert_eq!(sink.info.patch_sites, info.patch_sites);
+        assert_eq!(memory, expected);
+    }
+
+    #[test]
+    fn vec_memory() {
+        let mut memory = vec![0xff; 2];
+        assert_eq!(memory.grow(1).len(), 2);
+        assert_eq!(memory.grow(4), [0xff, 0xff, 0, 0]);
+    }
+}
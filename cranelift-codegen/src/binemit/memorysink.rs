@@ -13,12 +13,15 @@
 //! that a `MemoryCodeSink` will always write binary machine code to raw memory. It forwards any
 //! relocations to a `RelocSink` trait object. Relocations are less frequent than the
 //! `CodeSink::put*` methods, so the performance impact of the virtual callbacks is less severe.
-use super::{Addend, CodeInfo, CodeOffset, CodeSink, Reloc};
+use super::{
+    Addend, CodeInfo, CodeOffset, CodeSink, DeoptLocation, EncodingAnnotation, PatchSite, Reloc,
+};
 use crate::binemit::stackmap::Stackmap;
 use crate::ir::entities::Value;
-use crate::ir::{ExternalName, Function, JumpTable, SourceLoc, TrapCode};
-use crate::isa::TargetIsa;
+use crate::ir::{ExternalName, Function, Inst, JumpTable, SourceLoc, TrapCode};
+use crate::isa::{Encoding, TargetIsa};
 use core::ptr::write_unaligned;
+use std::vec::Vec;
 
 /// A `CodeSink` that writes binary machine code directly into memory.
 ///
@@ -39,6 +42,7 @@ pub struct MemoryCodeSink<'a> {
     relocs: &'a mut dyn RelocSink,
     traps: &'a mut dyn TrapSink,
     stackmaps: &'a mut dyn StackmapSink,
+    annotations: Option<&'a mut Vec<EncodingAnnotation>>,
     /// Information about the generated code and read-only data.
     pub info: CodeInfo,
 }
@@ -62,12 +66,19 @@ impl<'a> MemoryCodeSink<'a> {
                 jumptables_size: 0,
                 rodata_size: 0,
                 total_size: 0,
+                patch_sites: Vec::new(),
             },
             relocs,
             traps,
             stackmaps,
+            annotations: None,
         }
     }
+
+    /// Record the offset, size, and encoding of each emitted instruction into `annotations`.
+    pub fn annotate(&mut self, annotations: &'a mut Vec<EncodingAnnotation>) {
+        self.annotations = Some(annotations);
+    }
 }
 
 /// A trait for receiving relocations for code that is emitted directly into memory.
@@ -89,6 +100,11 @@ pub trait RelocSink {
 pub trait TrapSink {
     /// Add trap information for a specific offset.
     fn trap(&mut self, _: CodeOffset, _: SourceLoc, _: TrapCode);
+
+    /// Add the locations of the values captured by a deoptimization exit at a specific offset.
+    ///
+    /// Sinks that don't record deoptimization exits can ignore this.
+    fn deopt(&mut self, _offset: CodeOffset, _srcloc: SourceLoc, _locations: &[DeoptLocation]) {}
 }
 
 impl<'a> MemoryCodeSink<'a> {
@@ -142,6 +158,16 @@ impl<'a> CodeSink for MemoryCodeSink<'a> {
         self.traps.trap(ofs, srcloc, code);
     }
 
+    fn deopt(&mut self, srcloc: SourceLoc, locations: &[DeoptLocation]) {
+        let ofs = self.offset();
+        self.traps.deopt(ofs, srcloc, locations);
+    }
+
+    fn patch_site(&mut self, id: u32) {
+        let offset = self.offset();
+        self.info.patch_sites.push(PatchSite { offset, id });
+    }
+
     fn begin_jumptables(&mut self) {
         self.info.code_size = self.offset();
     }
@@ -160,6 +186,18 @@ impl<'a> CodeSink for MemoryCodeSink<'a> {
         let stackmap = Stackmap::from_values(&val_list, func, isa);
         self.stackmaps.add_stackmap(ofs, stackmap);
     }
+
+    fn annotate_inst(&mut self, inst: Inst, enc: Encoding, offset: CodeOffset) {
+        let size = self.offset() - offset;
+        if let Some(ref mut annotations) = self.annotations {
+            annotations.push(EncodingAnnotation {
+                inst,
+                offset,
+                size,
+                encoding: enc,
+            });
+        }
+    }
 }
 
 /// A `RelocSink` implementation that does nothing, which is convenient when
@@ -192,3 +230,69 @@ pub struct NullStackmapSink {}
 impl StackmapSink for NullStackmapSink {
     fn add_stackmap(&mut self, _: CodeOffset, _: Stackmap) {}
 }
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use crate::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{types, AbiParam, Function, InstBuilder};
+    use crate::isa;
+    use crate::settings;
+    use crate::Context;
+    use core::str::FromStr;
+    use std::string::ToString;
+    use std::vec;
+    use std::vec::Vec;
+    use target_lexicon::triple;
+
+    #[test]
+    fn annotations() {
+        let isa = isa::lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()));
+
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I64));
+        func.signature.returns.push(AbiParam::new(types::I64));
+        let ebb0 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I64);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let v1 = pos.ins().iadd_imm(v0, 1);
+            pos.ins().return_(&[v1]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        let info = ctx.compile(&*isa).unwrap();
+        let mut code = vec![0; info.total_size as usize];
+        let mut annotations = Vec::new();
+        unsafe {
+            ctx.emit_to_memory_annotated(
+                &*isa,
+                code.as_mut_ptr(),
+                &mut NullRelocSink {},
+                &mut NullTrapSink {},
+                &mut NullStackmapSink {},
+                &mut annotations,
+            )
+        };
+
+        // Every instruction is annotated, and the annotations cover the whole code.
+        let insts: Vec<_> = ctx.func.layout.ebb_insts(ebb0).collect();
+        assert_eq!(annotations.len(), insts.len());
+        let mut offset = 0;
+        for (annotation, &inst) in annotations.iter().zip(&insts) {
+            assert_eq!(annotation.inst, inst);
+            assert_eq!(annotation.offset, offset);
+            assert_eq!(annotation.encoding, ctx.func.encodings[inst]);
+            offset += annotation.size;
+        }
+        assert_eq!(offset, info.code_size);
+
+        let encinfo = isa.encoding_info();
+        let last = annotations.last().unwrap();
+        assert_eq!(encinfo.display(last.encoding).to_string(), "Op1ret#c3");
+        assert_eq!(last.size, 1);
+    }
+}
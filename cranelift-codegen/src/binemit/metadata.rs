@@ -0,0 +1,502 @@
+//! Consolidated metadata of a compiled function.
+//!
+//! Instead of handling relocations, traps, and stackmaps in three separate sinks, embedders can
+//! collect them into a `CompiledFunctionMetadata`, which has a stable binary encoding so it can be
+//! cached alongside the machine code.
+
+use super::{
+    Addend, CodeOffset, DeoptLocation, Reloc, RelocSink, Stackmap, StackmapSink, TrapSink,
+};
+use crate::entity::EntityRef;
+use crate::ir::{ExternalName, JumpTable, LibCall, SourceLoc, TrapCode};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// Magic bytes at the start of the encoded metadata.
+const MAGIC: &[u8; 4] = b"CLMD";
+
+/// Version of the binary encoding.
+const VERSION: u8 = 1;
+
+/// Encoding of the relocation kinds, by position. New kinds must be appended.
+const RELOCS: [Reloc; 10] = [
+    Reloc::Abs4,
+    Reloc::Abs8,
+    Reloc::X86PCRel4,
+    Reloc::X86PCRelRodata4,
+    Reloc::X86CallPCRel4,
+    Reloc::X86CallPLTRel4,
+    Reloc::X86GOTPCRel4,
+    Reloc::Arm32Call,
+    Reloc::Arm64Call,
+    Reloc::RiscvCall,
+];
+
+/// Encoding of the trap codes, by position. New codes must be appended.
+const TRAP_CODES: [TrapCode; 12] = [
+    TrapCode::StackOverflow,
+    TrapCode::HeapOutOfBounds,
+    TrapCode::TableOutOfBounds,
+    TrapCode::OutOfBounds,
+    TrapCode::IndirectCallToNull,
+    TrapCode::BadSignature,
+    TrapCode::IntegerOverflow,
+    TrapCode::IntegerDivisionByZero,
+    TrapCode::BadConversionToInteger,
+    TrapCode::UnreachableCodeReached,
+    TrapCode::Interrupt,
+    TrapCode::ReturnAddressMismatch,
+];
+
+/// Encoding of `TrapCode::User`.
+const USER_TRAP_CODE: u8 = 0xff;
+
+/// The target of a relocation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RelocTarget {
+    /// An EBB at the given offset in the function.
+    Ebb(CodeOffset),
+
+    /// An external symbol.
+    External(ExternalName),
+
+    /// A jump table of the function.
+    JumpTable(JumpTable),
+}
+
+/// A relocation in the machine code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelocRecord {
+    /// Offset of the relocated bytes in the machine code.
+    pub offset: CodeOffset,
+
+    /// The kind of relocation.
+    pub reloc: Reloc,
+
+    /// The referenced entity.
+    pub target: RelocTarget,
+
+    /// Addend to the address of the target.
+    pub addend: Addend,
+}
+
+/// What happens at a trapping instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrapKind {
+    /// A trap with the given code.
+    Trap(TrapCode),
+
+    /// A deoptimization exit, with the locations of the captured values.
+    Deopt(Vec<DeoptLocation>),
+}
+
+/// A trapping instruction in the machine code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrapRecord {
+    /// Offset of the trapping instruction.
+    pub offset: CodeOffset,
+
+    /// Source location of the trapping instruction.
+    pub srcloc: SourceLoc,
+
+    /// The reason for the trap.
+    pub kind: TrapKind,
+}
+
+/// A stackmap in the machine code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackmapRecord {
+    /// Offset of the instruction described by the stackmap.
+    pub offset: CodeOffset,
+
+    /// The live reference values on the stack.
+    pub stackmap: Stackmap,
+}
+
+/// The relocations, traps, and stackmaps of a compiled function.
+///
+/// Each field implements the corresponding sink trait, so they can be passed directly to
+/// `Context::compile_and_emit`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompiledFunctionMetadata {
+    /// Relocations, in code order.
+    pub relocs: Vec<RelocRecord>,
+
+    /// Traps and deoptimization exits, in code order.
+    pub traps: Vec<TrapRecord>,
+
+    /// Stackmaps, in code order.
+    pub stackmaps: Vec<StackmapRecord>,
+}
+
+impl RelocSink for Vec<RelocRecord> {
+    fn reloc_ebb(&mut self, offset: CodeOffset, reloc: Reloc, ebb_offset: CodeOffset) {
+        self.push(RelocRecord {
+            offset,
+            reloc,
+            target: RelocTarget::Ebb(ebb_offset),
+            addend: 0,
+        });
+    }
+
+    fn reloc_external(
+        &mut self,
+        offset: CodeOffset,
+        reloc: Reloc,
+        name: &ExternalName,
+        addend: Addend,
+    ) {
+        self.push(RelocRecord {
+            offset,
+            reloc,
+            target: RelocTarget::External(name.clone()),
+            addend,
+        });
+    }
+
+    fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable) {
+        self.push(RelocRecord {
+            offset,
+            reloc,
+            target: RelocTarget::JumpTable(jt),
+            addend: 0,
+        });
+    }
+}
+
+impl TrapSink for Vec<TrapRecord> {
+    fn trap(&mut self, offset: CodeOffset, srcloc: SourceLoc, code: TrapCode) {
+        self.push(TrapRecord {
+            offset,
+            srcloc,
+            kind: TrapKind::Trap(code),
+        });
+    }
+
+    fn deopt(&mut self, offset: CodeOffset, srcloc: SourceLoc, locations: &[DeoptLocation]) {
+        self.push(TrapRecord {
+            offset,
+            srcloc,
+            kind: TrapKind::Deopt(locations.to_vec()),
+        });
+    }
+}
+
+impl StackmapSink for Vec<StackmapRecord> {
+    fn add_stackmap(&mut self, offset: CodeOffset, stackmap: Stackmap) {
+        self.push(StackmapRecord { offset, stackmap });
+    }
+}
+
+impl CompiledFunctionMetadata {
+    /// Create empty metadata.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the binary encoding of the metadata to `out`.
+    ///
+    /// All integers are encoded in little-endian byte order, so the encoding doesn't depend on
+    /// the host.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+
+        put_u32(out, self.relocs.len() as u32);
+        for record in &self.relocs {
+            put_u32(out, record.offset);
+            out.push(RELOCS.iter().position(|&r| r == record.reloc).unwrap() as u8);
+            put_u64(out, record.addend as u64);
+            match record.target {
+                RelocTarget::Ebb(offset) => {
+                    out.push(0);
+                    put_u32(out, offset);
+                }
+                RelocTarget::External(ref name) => {
+                    out.push(1);
+                    encode_name(out, name);
+                }
+                RelocTarget::JumpTable(jt) => {
+                    out.push(2);
+                    put_u32(out, jt.index() as u32);
+                }
+            }
+        }
+
+        put_u32(out, self.traps.len() as u32);
+        for record in &self.traps {
+            put_u32(out, record.offset);
+            put_u32(out, record.srcloc.bits());
+            match record.kind {
+                TrapKind::Trap(code) => {
+                    out.push(0);
+                    match code {
+                        TrapCode::User(x) => {
+                            out.push(USER_TRAP_CODE);
+                            put_u16(out, x);
+                        }
+                        _ => out.push(TRAP_CODES.iter().position(|&c| c == code).unwrap() as u8),
+                    }
+                }
+                TrapKind::Deopt(ref locations) => {
+                    out.push(1);
+                    put_u32(out, locations.len() as u32);
+                    for location in locations {
+                        match *location {
+                            DeoptLocation::Reg(reg) => {
+                                out.push(0);
+                                put_u32(out, u32::from(reg));
+                            }
+                            DeoptLocation::Stack(offset) => {
+                                out.push(1);
+                                put_u32(out, offset as u32);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        put_u32(out, self.stackmaps.len() as u32);
+        for record in &self.stackmaps {
+            put_u32(out, record.offset);
+            let words = record.stackmap.as_words();
+            put_u32(out, words.len() as u32);
+            for word in words {
+                put_u32(out, word);
+            }
+        }
+    }
+
+    /// Decode metadata encoded by `encode`.
+    ///
+    /// Returns `None` if `bytes` isn't valid metadata, or if it was encoded by an incompatible
+    /// version of Cranelift.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut r = Reader { bytes };
+        if r.take(MAGIC.len())? != MAGIC || r.u8()? != VERSION {
+            return None;
+        }
+        let mut metadata = Self::new();
+
+        for _ in 0..r.u32()? {
+            let offset = r.u32()?;
+            let reloc = *RELOCS.get(usize::from(r.u8()?))?;
+            let addend = r.u64()? as Addend;
+            let target = match r.u8()? {
+                0 => RelocTarget::Ebb(r.u32()?),
+                1 => RelocTarget::External(decode_name(&mut r)?),
+                2 => RelocTarget::JumpTable(JumpTable::new(r.u32()? as usize)),
+                _ => return None,
+            };
+            metadata.relocs.push(RelocRecord {
+                offset,
+                reloc,
+                target,
+                addend,
+            });
+        }
+
+        for _ in 0..r.u32()? {
+            let offset = r.u32()?;
+            let srcloc = SourceLoc::new(r.u32()?);
+            let kind = match r.u8()? {
+                0 => match r.u8()? {
+                    USER_TRAP_CODE => TrapKind::Trap(TrapCode::User(r.u16()?)),
+                    code => TrapKind::Trap(*TRAP_CODES.get(usize::from(code))?),
+                },
+                1 => {
+                    let mut locations = Vec::new();
+                    for _ in 0..r.u32()? {
+                        locations.push(match r.u8()? {
+                            0 => DeoptLocation::Reg(r.u32()? as u16),
+                            1 => DeoptLocation::Stack(r.u32()? as i32),
+                            _ => return None,
+                        });
+                    }
+                    TrapKind::Deopt(locations)
+                }
+                _ => return None,
+            };
+            metadata.traps.push(TrapRecord {
+                offset,
+                srcloc,
+                kind,
+            });
+        }
+
+        for _ in 0..r.u32()? {
+            let offset = r.u32()?;
+            let mut words = Vec::new();
+            for _ in 0..r.u32()? {
+                words.push(r.u32()?);
+            }
+            metadata.stackmaps.push(StackmapRecord {
+                offset,
+                stackmap: Stackmap::from_words(words),
+            });
+        }
+
+        if r.bytes.is_empty() {
+            Some(metadata)
+        } else {
+            None
+        }
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, x: u16) {
+    out.extend_from_slice(&x.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, x: u32) {
+    out.extend_from_slice(&x.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, x: u64) {
+    out.extend_from_slice(&x.to_le_bytes());
+}
+
+fn encode_name(out: &mut Vec<u8>, name: &ExternalName) {
+    match *name {
+        ExternalName::User { namespace, index } => {
+            out.push(0);
+            put_u32(out, namespace);
+            put_u32(out, index);
+        }
+        ExternalName::TestCase { length, ascii } => {
+            out.push(1);
+            out.push(length);
+            out.extend_from_slice(&ascii[..usize::from(length)]);
+        }
+        ExternalName::LibCall(libcall) => {
+            let name = libcall.to_string();
+            out.push(2);
+            out.push(name.len() as u8);
+            out.extend_from_slice(name.as_bytes());
+        }
+    }
+}
+
+fn decode_name(r: &mut Reader) -> Option<ExternalName> {
+    match r.u8()? {
+        0 => Some(ExternalName::User {
+            namespace: r.u32()?,
+            index: r.u32()?,
+        }),
+        1 => {
+            let length = usize::from(r.u8()?);
+            Some(ExternalName::testcase(r.take(length)?))
+        }
+        2 => {
+            let length = usize::from(r.u8()?);
+            let name = String::from_utf8(r.take(length)?.to_vec()).ok()?;
+            name.parse::<LibCall>().ok().map(ExternalName::LibCall)
+        }
+        _ => None,
+    }
+}
+
+/// Reader of encoded metadata.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < n {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let mut buf = [0; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Some(u16::from_le_bytes(buf))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Some(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    fn sample() -> CompiledFunctionMetadata {
+        let mut metadata = CompiledFunctionMetadata::new();
+        metadata.relocs.reloc_ebb(1, Reloc::X86PCRel4, 32);
+        metadata
+            .relocs
+            .reloc_external(5, Reloc::X86CallPLTRel4, &ExternalName::user(1, 2), -4);
+        metadata.relocs.reloc_external(
+            9,
+            Reloc::Abs8,
+            &ExternalName::LibCall(LibCall::FloorF32),
+            0,
+        );
+        metadata
+            .relocs
+            .reloc_external(17, Reloc::Abs8, &ExternalName::testcase("callee"), 8);
+        metadata
+            .relocs
+            .reloc_jt(25, Reloc::X86PCRelRodata4, JumpTable::new(3));
+        metadata
+            .traps
+            .trap(30, SourceLoc::new(7), TrapCode::HeapOutOfBounds);
+        metadata
+            .traps
+            .trap(32, SourceLoc::new(8), TrapCode::User(3));
+        metadata.traps.deopt(
+            34,
+            SourceLoc::default(),
+            &[DeoptLocation::Reg(7), DeoptLocation::Stack(-8)],
+        );
+        metadata
+            .stackmaps
+            .add_stackmap(40, Stackmap::from_vec(&vec![true, false, true]));
+        metadata
+    }
+
+    #[test]
+    fn round_trip() {
+        let metadata = sample();
+        let mut bytes = Vec::new();
+        metadata.encode(&mut bytes);
+        assert_eq!(&bytes[..5], b"CLMD\x01");
+        assert_eq!(CompiledFunctionMetadata::decode(&bytes), Some(metadata));
+    }
+
+    #[test]
+    fn invalid() {
+        let mut bytes = Vec::new();
+        sample().encode(&mut bytes);
+        for len in 0..bytes.len() {
+            assert_eq!(CompiledFunctionMetadata::decode(&bytes[..len]), None);
+        }
+        bytes.push(0);
+        assert_eq!(CompiledFunctionMetadata::decode(&bytes), None);
+
+        let mut empty = Vec::new();
+        CompiledFunctionMetadata::new().encode(&mut empty);
+        empty[4] = VERSION + 1;
+        assert_eq!(CompiledFunctionMetadata::decode(&empty), None);
+    }
+}
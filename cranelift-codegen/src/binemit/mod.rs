@@ -3,25 +3,39 @@
 //! The `binemit` module contains code for translating Cranelift's intermediate representation into
 //! binary machine code.
 
+mod deopt;
+mod growablesink;
 mod memorysink;
+mod metadata;
 mod relaxation;
+mod resolve;
 mod shrink;
 mod stackmap;
 
+pub use self::deopt::DeoptLocation;
+pub use self::growablesink::{CodeMemory, GrowableCodeSink};
 pub use self::memorysink::{
     MemoryCodeSink, NullRelocSink, NullStackmapSink, NullTrapSink, RelocSink, StackmapSink,
     TrapSink,
 };
+pub use self::metadata::{
+    CompiledFunctionMetadata, RelocRecord, RelocTarget, StackmapRecord, TrapKind, TrapRecord,
+};
 pub use self::relaxation::relax_branches;
+pub(crate) use self::resolve::ResolvingRelocSink;
+pub use self::resolve::SymbolResolver;
 pub use self::shrink::shrink_instructions;
 pub use self::stackmap::Stackmap;
 use crate::ir::entities::Value;
-use crate::ir::{ExternalName, Function, Inst, JumpTable, SourceLoc, TrapCode};
-use crate::isa::TargetIsa;
+use crate::ir::{
+    ExternalName, Function, Inst, InstructionData, JumpTable, Opcode, SourceLoc, TrapCode,
+};
+use crate::isa::{EncInfo, Encoding, TargetIsa};
 pub use crate::regalloc::RegDiversions;
 use core::fmt;
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
+use std::vec::Vec;
 
 /// Offset in bytes from the beginning of the function.
 ///
@@ -93,6 +107,10 @@ pub struct CodeInfo {
 
     /// Number of bytes in total.
     pub total_size: CodeOffset,
+
+    /// Patch sites of the late-bound constants in the read-only data, in order of increasing
+    /// offset.
+    pub patch_sites: Vec<PatchSite>,
 }
 
 impl CodeInfo {
@@ -107,6 +125,37 @@ impl CodeInfo {
     }
 }
 
+/// A 64-bit constant loaded by `patchable_iconst` instructions.
+///
+/// The constant is stored at an 8-byte aligned offset in the read-only data, so the runtime can
+/// update it atomically while the code is running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PatchSite {
+    /// Offset of the constant from the beginning of the function.
+    pub offset: CodeOffset,
+
+    /// The identifier given to the `patchable_iconst` instruction.
+    pub id: u32,
+}
+
+/// The encoding of an emitted instruction.
+///
+/// The name of the recipe is displayed by `EncInfo::display(encoding)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodingAnnotation {
+    /// The emitted instruction.
+    pub inst: Inst,
+
+    /// Offset of the instruction from the beginning of the function.
+    pub offset: CodeOffset,
+
+    /// Number of bytes emitted for the instruction.
+    pub size: CodeOffset,
+
+    /// The encoding of the instruction.
+    pub encoding: Encoding,
+}
+
 /// Abstract interface for adding bytes to the code segment.
 ///
 /// A `CodeSink` will receive all of the machine code for a function. It also accepts relocations
@@ -139,6 +188,17 @@ pub trait CodeSink {
     /// Add trap information for the current offset.
     fn trap(&mut self, _: TrapCode, _: SourceLoc);
 
+    /// Add deoptimization information for the current offset.
+    ///
+    /// Sinks that don't record deoptimization exits can ignore this.
+    fn deopt(&mut self, _srcloc: SourceLoc, _locations: &[DeoptLocation]) {}
+
+    /// Add a patch site for the late-bound constant with the given identifier at the current
+    /// offset.
+    ///
+    /// Sinks that don't patch late-bound constants can ignore this.
+    fn patch_site(&mut self, _id: u32) {}
+
     /// Machine code output is complete, jump table data may follow.
     fn begin_jumptables(&mut self);
 
@@ -150,6 +210,12 @@ pub trait CodeSink {
 
     /// Add a stackmap at the current code offset.
     fn add_stackmap(&mut self, _: &[Value], _: &Function, _: &dyn TargetIsa);
+
+    /// The instruction `inst` with the encoding `enc` was emitted from `offset` up to the current
+    /// offset.
+    ///
+    /// Sinks that don't record the encodings of the instructions can ignore this.
+    fn annotate_inst(&mut self, _inst: Inst, _enc: Encoding, _offset: CodeOffset) {}
 }
 
 /// Report a bad encoding error.
@@ -172,11 +238,17 @@ where
     EI: Fn(&Function, Inst, &mut RegDiversions, &mut CS, &dyn TargetIsa),
 {
     let mut divert = RegDiversions::new();
+    let mut patches = Vec::new();
     for ebb in func.layout.ebbs() {
         divert.clear();
         debug_assert_eq!(func.offsets[ebb], sink.offset());
         for inst in func.layout.ebb_insts(ebb) {
+            let offset = sink.offset();
             emit_inst(func, inst, &mut divert, sink, isa);
+            sink.annotate_inst(inst, func.encodings[inst], offset);
+            if let Some(id) = patch_site_id(func, inst) {
+                patches.push((inst, id));
+            }
         }
     }
 
@@ -191,8 +263,64 @@ where
         }
     }
 
+    // Pad the jump tables so the patch sites are aligned.
+    if !patches.is_empty() {
+        while sink.offset() % 8 != 0 {
+            sink.put1(0);
+        }
+    }
+
     sink.begin_rodata();
-    // TODO: No read-only data (constant pools) at this time.
+
+    // output patch sites
+    for (inst, id) in patches {
+        debug_assert_eq!(func.patch_offsets[inst], sink.offset());
+        sink.patch_site(id);
+        sink.put8(0);
+    }
 
     sink.end_codegen();
 }
+
+/// Get the size in bytes of the code, jump tables, and read-only data emitted for `func`.
+///
+/// The EBB offsets computed by `relax_branches` must be up to date.
+pub fn function_size(func: &Function, encinfo: &EncInfo) -> CodeOffset {
+    let mut offset = 0;
+    if let Some(ebb) = func.layout.last_ebb() {
+        let mut divert = RegDiversions::new();
+        offset = func.offsets[ebb];
+        for inst in func.layout.ebb_insts(ebb) {
+            divert.apply(&func.dfg[inst]);
+            offset += encinfo.byte_size(func.encodings[inst], inst, &divert, func);
+        }
+    }
+
+    for jt_data in func.jump_tables.values() {
+        offset += jt_data.len() as CodeOffset * 4;
+    }
+
+    // The patch sites are 8-byte aligned.
+    let mut patch_sites = func
+        .layout
+        .ebbs()
+        .flat_map(|ebb| func.layout.ebb_insts(ebb))
+        .filter(|&inst| patch_site_id(func, inst).is_some())
+        .peekable();
+    if patch_sites.peek().is_some() {
+        offset = (offset + 7) & !7;
+        offset += patch_sites.count() as CodeOffset * 8;
+    }
+    offset
+}
+
+/// Get the patch site identifier of `inst` if it is a `patchable_iconst` instruction.
+pub fn patch_site_id(func: &Function, inst: Inst) -> Option<u32> {
+    match func.dfg[inst] {
+        InstructionData::UnaryUimm32 {
+            opcode: Opcode::PatchableIconst,
+            imm,
+        } => Some(imm.into()),
+        _ => None,
+    }
+}
@@ -26,29 +26,55 @@
 //!     jump ebb17
 //! ebb23:
 //! ```
+//!
+//! # Veneers
+//!
+//! When no encoding of a branch can reach its destination, which happens in very large functions,
+//! the branch is redirected to a veneer: a new EBB inserted right after the EBB of the branch,
+//! holding an unconditional jump to the destination. Unconditional jumps have the longest range,
+//! so the function is split into regions connected by the veneers:
+//!
+//! ```clif
+//!     brz v1, ebb17
+//!     ...
+//! ```
+//!
+//! becomes:
+//!
+//! ```clif
+//!     brz v1, ebb30
+//!     ...
+//! ebb30:
+//!     jump ebb17
+//! ```
+//!
+//! If the veneer itself is out of the range of the branch, or the destination is out of the
+//! range of the jump, the function is too large to be compiled.
 
-use crate::binemit::{CodeInfo, CodeOffset};
+use crate::binemit::{patch_site_id, CodeInfo, CodeOffset, PatchSite};
 use crate::cursor::{Cursor, FuncCursor};
 use crate::dominator_tree::DominatorTree;
+use crate::entity::EntitySet;
 use crate::flowgraph::ControlFlowGraph;
-use crate::ir::{Function, InstructionData, Opcode};
+use crate::ir::{Ebb, Function, InstBuilder, InstructionData, Opcode, ValueList};
 use crate::isa::{EncInfo, TargetIsa};
 use crate::iterators::IteratorExtras;
 use crate::regalloc::RegDiversions;
 use crate::timing;
-use crate::CodegenResult;
+use crate::{CodegenError, CodegenResult};
 use log::debug;
+use std::vec::Vec;
 
 #[cfg(feature = "basic-blocks")]
-use crate::ir::{Ebb, Inst, Value, ValueList};
+use crate::ir::{Inst, Value};
 
 /// Relax branches and compute the final layout of EBB headers in `func`.
 ///
 /// Fill in the `func.offsets` table so the function is ready for binary emission.
 pub fn relax_branches(
     func: &mut Function,
-    _cfg: &mut ControlFlowGraph,
-    _domtree: &mut DominatorTree,
+    cfg: &mut ControlFlowGraph,
+    domtree: &mut DominatorTree,
     isa: &dyn TargetIsa,
 ) -> CodegenResult<CodeInfo> {
     let _tt = timing::relax_branches();
@@ -61,7 +87,7 @@ pub fn relax_branches(
 
     // Start by removing redundant jumps.
     #[cfg(feature = "basic-blocks")]
-    fold_redundant_jumps(func, _cfg, _domtree);
+    fold_redundant_jumps(func, cfg, domtree);
 
     // Convert jumps to fallthrough instructions where possible.
     fallthroughs(func);
@@ -84,6 +110,7 @@ pub fn relax_branches(
     }
 
     // Then, run the relaxation algorithm until it converges.
+    let mut veneers = EntitySet::new();
     let mut go_again = true;
     while go_again {
         go_again = false;
@@ -110,9 +137,19 @@ pub fn relax_branches(
                     if let Some(dest) = cur.func.dfg[inst].branch_destination() {
                         let dest_offset = cur.func.offsets[dest];
                         if !range.contains(offset, dest_offset) {
-                            offset +=
-                                relax_branch(&mut cur, &divert, offset, dest_offset, &encinfo, isa);
-                            continue;
+                            if let Some(size) =
+                                relax_branch(&mut cur, &divert, offset, dest_offset, &encinfo, isa)
+                            {
+                                offset += size;
+                                continue;
+                            }
+
+                            // A veneer can't help a branch from or to a veneer.
+                            if veneers.contains(ebb) || veneers.contains(dest) {
+                                return Err(CodegenError::CodeTooLarge);
+                            }
+                            veneers.insert(insert_veneer(&mut cur, isa)?);
+                            go_again = true;
                         }
                     }
                 }
@@ -122,6 +159,16 @@ pub fn relax_branches(
         }
     }
 
+    // The veneers changed the control flow graph.
+    if !veneers.is_empty() {
+        if cfg.is_valid() {
+            cfg.compute(func);
+        }
+        if domtree.is_valid() {
+            domtree.compute(func, cfg);
+        }
+    }
+
     let code_size = offset;
     let jumptables = offset;
 
@@ -132,11 +179,24 @@ pub fn relax_branches(
         offset += jt_data.len() as u32 * 4;
     }
 
-    let jumptables_size = offset - jumptables;
-    let rodata = offset;
-
-    // TODO: Once we have constant pools we'll do some processing here to update offset.
+    // The patch sites of late-bound constants are 8-byte aligned so they can be updated
+    // atomically. The padding belongs to the jump tables.
+    let mut patch_sites = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if let Some(id) = patch_site_id(func, inst) {
+                if patch_sites.is_empty() {
+                    offset = (offset + 7) & !7;
+                }
+                func.patch_offsets[inst] = offset;
+                patch_sites.push(PatchSite { offset, id });
+                offset += 8;
+            }
+        }
+    }
 
+    let rodata = patch_sites.first().map_or(offset, |site| site.offset);
+    let jumptables_size = rodata - jumptables;
     let rodata_size = offset - rodata;
 
     Ok(CodeInfo {
@@ -144,6 +204,7 @@ pub fn relax_branches(
         jumptables_size,
         rodata_size,
         total_size: offset,
+        patch_sites,
     })
 }
 
@@ -307,7 +368,7 @@ fn fallthroughs(func: &mut Function) {
 /// Relax the branch instruction at `cur` so it can cover the range `offset - dest_offset`.
 ///
 /// Return the size of the replacement instructions up to and including the location where `cur` is
-/// left.
+/// left, or `None` if no encoding of the branch has enough range.
 fn relax_branch(
     cur: &mut FuncCursor,
     divert: &RegDiversions,
@@ -315,7 +376,7 @@ fn relax_branch(
     dest_offset: CodeOffset,
     encinfo: &EncInfo,
     isa: &dyn TargetIsa,
-) -> CodeOffset {
+) -> Option<CodeOffset> {
     let inst = cur.current_inst().unwrap();
     debug!(
         "Relaxing [{}] {} for {:#x}-{:#x} range",
@@ -354,7 +415,7 @@ fn relax_branch(
     {
         debug_assert!(enc != cur.func.encodings[inst]);
         cur.func.encodings[inst] = enc;
-        return encinfo.byte_size(enc, inst, &divert, &cur.func);
+        return Some(encinfo.byte_size(enc, inst, &divert, &cur.func));
     }
 
     // Note: On some RISC ISAs, conditional branches have shorter range than unconditional
@@ -375,6 +436,135 @@ fn relax_branch(
     // predecessor could contain kill points for some values that are live in this EBB, and
     // diversions are not automatically cancelled when the live range of a value ends.
 
-    // This assumes solution 2. above:
-    panic!("No branch in range for {:#x}-{:#x}", offset, dest_offset);
+    // This assumes solution 2. above. Until then, the caller redirects the branch to a veneer.
+    debug!("No branch in range for {:#x}-{:#x}", offset, dest_offset);
+    None
+}
+
+/// Redirect the branch instruction at `cur` to a veneer jumping to its destination.
+///
+/// The veneer is inserted right after the EBB of the branch, and it receives the EBB arguments of
+/// the branch. The branch keeps its encoding, and `cur` is left at the branch.
+///
+/// Returns the veneer.
+fn insert_veneer(cur: &mut FuncCursor, isa: &dyn TargetIsa) -> CodegenResult<Ebb> {
+    let inst = cur.current_inst().unwrap();
+    let ebb = cur.current_ebb().unwrap();
+    let dest = cur.func.dfg[inst].branch_destination().unwrap();
+    debug!(
+        "Inserting a veneer for {}",
+        cur.func.dfg.display_inst(inst, isa)
+    );
+
+    // The veneer can't be inserted between `ebb` and a fall-through successor.
+    let last = cur.func.layout.last_inst(ebb).unwrap();
+    match cur.func.dfg[last] {
+        InstructionData::Jump { ref mut opcode, .. } if *opcode == Opcode::Fallthrough => {
+            *opcode = Opcode::Jump;
+            cur.func.encodings[last] = cur.func.encode(last, isa).expect("Can't encode a jump");
+        }
+        InstructionData::MultiAry {
+            opcode: Opcode::FallthroughReturn,
+            ..
+        } => return Err(CodegenError::CodeTooLarge),
+        _ => {}
+    }
+
+    let veneer = cur.func.dfg.make_ebb();
+    cur.func.layout.insert_ebb_after(veneer, ebb);
+    let srcloc = cur.func.srclocs[inst];
+    let args = cur.func.dfg.inst_variable_args(inst).to_vec();
+    let jump = {
+        let mut pos = FuncCursor::new(&mut *cur.func).at_bottom(veneer);
+        pos.set_srcloc(srcloc);
+        pos.ins().jump(dest, &args)
+    };
+    cur.func.encodings[jump] = cur.func.encode(jump, isa).expect("Can't encode a jump");
+
+    // The veneer takes over the EBB arguments.
+    let fixed_args = cur.func.dfg.inst_fixed_args(inst).to_vec();
+    let value_list = ValueList::from_slice(&fixed_args, &mut cur.func.dfg.value_lists);
+    cur.func.dfg[inst].take_value_list();
+    cur.func.dfg[inst].put_value_list(value_list);
+    cur.func.change_branch_destination(inst, veneer);
+
+    Ok(veneer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{types, AbiParam, Function, InstBuilder, Opcode};
+    use crate::isa::{self, TargetIsa};
+    use crate::settings;
+    use crate::Context;
+    use core::str::FromStr;
+    use std::boxed::Box;
+    use std::vec::Vec;
+    use target_lexicon::triple;
+
+    // Make a riscv32 `TargetIsa`, if possible.
+    fn riscv32() -> Option<Box<dyn TargetIsa>> {
+        let shared_flags = settings::Flags::new(settings::builder());
+        isa::lookup(triple!("riscv32"))
+            .ok()
+            .map(|b| b.finish(shared_flags))
+    }
+
+    #[test]
+    fn veneer() {
+        let isa = match riscv32() {
+            Some(isa) => isa,
+            None => return,
+        };
+
+        // The conditional branches of RISC-V have a range of 4 KiB.
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I32));
+        func.signature.returns.push(AbiParam::new(types::I32));
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        let v1 = func.dfg.append_ebb_param(ebb2, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().brz(v0, ebb2, &[v0]);
+            pos.ins().jump(ebb1, &[]);
+            pos.insert_ebb(ebb1);
+            let mut v = v0;
+            for _ in 0..1200 {
+                v = pos.ins().iadd(v, v0);
+            }
+            pos.ins().jump(ebb2, &[v]);
+            pos.insert_ebb(ebb2);
+            pos.ins().return_(&[v1]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        let mut code = Vec::new();
+        ctx.compile_and_emit(
+            &*isa,
+            &mut code,
+            &mut NullRelocSink {},
+            &mut NullTrapSink {},
+            &mut NullStackmapSink {},
+        )
+        .unwrap();
+        ctx.verify_if(&*isa).unwrap();
+
+        // The branch goes through a veneer inserted after its EBB, which passes the arguments.
+        let func = &ctx.func;
+        let branch = func.layout.first_inst(ebb0).unwrap();
+        assert_eq!(func.dfg[branch].opcode(), Opcode::Brz);
+        assert!(func.dfg.inst_variable_args(branch).is_empty());
+        let veneer = func.dfg[branch].branch_destination().unwrap();
+        assert_eq!(func.layout.next_ebb(ebb0), Some(veneer));
+        let jump = func.layout.first_inst(veneer).unwrap();
+        assert_eq!(func.dfg[jump].opcode(), Opcode::Jump);
+        assert_eq!(func.dfg[jump].branch_destination(), Some(ebb2));
+        assert_eq!(func.dfg.inst_variable_args(jump).len(), 1);
+    }
 }
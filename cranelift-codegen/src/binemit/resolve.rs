@@ -0,0 +1,256 @@
+//! Emission of relocation-free machine code.
+//!
+//! A JIT compiler that knows the addresses of the symbols referenced by a function before
+//! emitting it doesn't need to collect its relocations and patch them in a separate pass: with a
+//! `SymbolResolver`, `Context::emit_to_memory_resolved` writes the final absolute and PC-relative
+//! values into the machine code, which is ready to run once it returns.
+//!
+//! The references to the EBBs, jump tables, and read-only data of the function itself are already
+//! resolved by the emission, as long as the code and its data aren't split apart.
+
+use super::{Addend, CodeOffset, Reloc, RelocSink};
+use crate::ir::{ExternalName, JumpTable};
+use crate::result::{CodegenError, CodegenResult};
+use core::ptr::write_unaligned;
+use std::vec::Vec;
+
+/// Resolves the addresses of the external symbols referenced by a function.
+///
+/// Closures taking an `&ExternalName` and returning an `Option<*const u8>` are resolvers.
+pub trait SymbolResolver {
+    /// Get the address of the symbol `name`, or `None` if it's unknown.
+    fn resolve(&mut self, name: &ExternalName) -> Option<*const u8>;
+}
+
+impl<F> SymbolResolver for F
+where
+    F: FnMut(&ExternalName) -> Option<*const u8>,
+{
+    fn resolve(&mut self, name: &ExternalName) -> Option<*const u8> {
+        self(name)
+    }
+}
+
+/// A relocation whose target address is known.
+struct Fixup {
+    offset: CodeOffset,
+    reloc: Reloc,
+    name: ExternalName,
+    /// The address of the symbol plus the addend.
+    target: u64,
+}
+
+/// A `RelocSink` resolving the external relocations of a function as they are emitted.
+///
+/// The relocated bytes are only written after the relocation is reported, so the fixups are
+/// applied to the machine code by `apply` once the whole function has been emitted.
+pub(crate) struct ResolvingRelocSink<'a> {
+    resolver: &'a mut dyn SymbolResolver,
+    fixups: Vec<Fixup>,
+    error: Option<CodegenError>,
+}
+
+impl<'a> ResolvingRelocSink<'a> {
+    pub fn new(resolver: &'a mut dyn SymbolResolver) -> Self {
+        Self {
+            resolver,
+            fixups: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Write the resolved values of the relocations into the machine code at `code`.
+    ///
+    /// This is unsafe since `code` must point to the machine code the relocations were collected
+    /// from.
+    pub unsafe fn apply(self, code: *mut u8) -> CodegenResult<()> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        for fixup in self.fixups {
+            let at = code.add(fixup.offset as usize);
+            match fixup.reloc {
+                Reloc::Abs4 => {
+                    if fixup.target > u64::from(u32::max_value()) {
+                        return Err(CodegenError::RelocationOutOfRange(fixup.name));
+                    }
+                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                    write_unaligned(at as *mut u32, fixup.target as u32);
+                }
+                Reloc::Abs8 => {
+                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                    write_unaligned(at as *mut u64, fixup.target);
+                }
+                // The resolved address is the final one, so calls through the PLT can go
+                // directly to their target.
+                Reloc::X86PCRel4 | Reloc::X86CallPCRel4 | Reloc::X86CallPLTRel4 => {
+                    let pcrel = fixup.target.wrapping_sub(at as u64) as i64;
+                    if pcrel != i64::from(pcrel as i32) {
+                        return Err(CodegenError::RelocationOutOfRange(fixup.name));
+                    }
+                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                    write_unaligned(at as *mut i32, pcrel as i32);
+                }
+                _ => {
+                    return Err(CodegenError::Unsupported(
+                        "relocation kind in resolved emission",
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> RelocSink for ResolvingRelocSink<'a> {
+    fn reloc_ebb(&mut self, _: CodeOffset, _: Reloc, _: CodeOffset) {}
+
+    fn reloc_external(
+        &mut self,
+        offset: CodeOffset,
+        reloc: Reloc,
+        name: &ExternalName,
+        addend: Addend,
+    ) {
+        match self.resolver.resolve(name) {
+            Some(address) => self.fixups.push(Fixup {
+                offset,
+                reloc,
+                name: name.clone(),
+                target: (address as u64).wrapping_add(addend as u64),
+            }),
+            None => {
+                if self.error.is_none() {
+                    self.error = Some(CodegenError::UnresolvedSymbol(name.clone()));
+                }
+            }
+        }
+    }
+
+    fn reloc_jt(&mut self, _: CodeOffset, _: Reloc, _: JumpTable) {}
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use crate::binemit::{NullStackmapSink, NullTrapSink, RelocRecord, RelocTarget};
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{
+        types, AbiParam, ExtFuncData, ExternalName, Function, GlobalValueData, InstBuilder,
+        Signature,
+    };
+    use crate::isa::{self, CallConv};
+    use crate::result::CodegenError;
+    use crate::settings;
+    use crate::Context;
+    use core::str::FromStr;
+    use std::string::ToString;
+    use std::vec;
+    use std::vec::Vec;
+    use target_lexicon::triple;
+
+    fn function() -> Function {
+        let mut func = Function::new();
+        func.signature.returns.push(AbiParam::new(types::I64));
+        let signature = func.import_signature(Signature::new(CallConv::SystemV));
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("callee"),
+            signature,
+            colocated: true,
+        });
+        let data = func.create_global_value(GlobalValueData::Symbol {
+            name: ExternalName::testcase("data"),
+            offset: 8.into(),
+            colocated: false,
+        });
+
+        let ebb0 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        pos.ins().call(callee, &[]);
+        let v0 = pos.ins().symbol_value(types::I64, data);
+        pos.ins().return_(&[v0]);
+        func
+    }
+
+    #[test]
+    fn resolved_emission() {
+        let isa = isa::lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()));
+        let mut ctx = Context::for_function(function());
+        let mut code = Vec::new();
+        let mut relocs: Vec<RelocRecord> = Vec::new();
+        let info = ctx
+            .compile_and_emit(
+                &*isa,
+                &mut code,
+                &mut relocs,
+                &mut NullTrapSink {},
+                &mut NullStackmapSink {},
+            )
+            .unwrap();
+
+        let mut resolved = vec![0; info.total_size as usize];
+        let code_address = resolved.as_ptr() as u64;
+        let mut resolver = |name: &ExternalName| match name.to_string().as_str() {
+            "%callee" => Some((code_address + 0x1000) as *const u8),
+            "%data" => Some(0x1234_5678 as *const u8),
+            _ => None,
+        };
+        let resolved_info = unsafe {
+            ctx.emit_to_memory_resolved(
+                &*isa,
+                resolved.as_mut_ptr(),
+                &mut resolver,
+                &mut NullTrapSink {},
+                &mut NullStackmapSink {},
+            )
+        }
+        .unwrap();
+        assert!(resolved_info == info);
+
+        // Only the relocated bytes differ from the code emitted with relocations.
+        assert_eq!(relocs.len(), 2);
+        for record in &relocs {
+            let at = record.offset as usize;
+            let target = match record.target {
+                RelocTarget::External(ref name) => name.to_string(),
+                _ => panic!("unexpected relocation {:?}", record),
+            };
+            if target == "%callee" {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(&resolved[at..at + 4]);
+                let pcrel = 0x1000 + record.addend - at as i64;
+                assert_eq!(i64::from(i32::from_ne_bytes(bytes)), pcrel);
+                code[at..at + 4].copy_from_slice(&bytes);
+            } else {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&resolved[at..at + 8]);
+                assert_eq!(
+                    u64::from_ne_bytes(bytes),
+                    0x1234_5678 + record.addend as u64
+                );
+                code[at..at + 8].copy_from_slice(&bytes);
+            }
+        }
+        assert_eq!(resolved, code);
+
+        let mut unresolved = |_: &ExternalName| None;
+        let error = unsafe {
+            ctx.emit_to_memory_resolved(
+                &*isa,
+                resolved.as_mut_ptr(),
+                &mut unresolved,
+                &mut NullTrapSink {},
+                &mut NullStackmapSink {},
+            )
+        }
+        .err();
+        assert_eq!(
+            error,
+            Some(CodegenError::UnresolvedSymbol(ExternalName::testcase(
+                "callee"
+            )))
+        );
+    }
+}
@@ -4,7 +4,7 @@ use crate::isa::TargetIsa;
 use std::vec::Vec;
 
 /// Wrapper class for longer bit vectors that cannot be represented by a single BitSet.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Stackmap {
     bitmap: Vec<BitSet<u32>>,
 }
@@ -75,6 +75,19 @@ impl Stackmap {
         Self { bitmap }
     }
 
+    /// Create a stackmap from 32-bit words, as returned by `as_words`.
+    pub fn from_words(words: Vec<u32>) -> Self {
+        Self {
+            bitmap: words.into_iter().map(BitSet::<u32>).collect(),
+        }
+    }
+
+    /// Returns the bitmap as 32-bit words. Bit `i` of the stackmap is bit `i % 32` of word
+    /// `i / 32`.
+    pub fn as_words(&self) -> Vec<u32> {
+        self.bitmap.iter().map(|word| word.0).collect()
+    }
+
     /// Returns a specified bit.
     pub fn get_bit(&self, bit_index: usize) -> bool {
         assert!(bit_index < 32 * self.bitmap.len());
@@ -0,0 +1,196 @@
+//! Block placement driven by branch probability hints.
+//!
+//! EBBs that can only be reached through unlikely control flow edges are moved to the end of the
+//! function, so the likely paths are laid out as straight-line code and the rarely executed
+//! EBBs, typically error handling, don't take up room in the instruction cache. So are the EBBs
+//! calling a `cold` function, and the ones only reachable through them.
+
+use crate::entity::SecondaryMap;
+use crate::flowgraph::{BasicBlock, ControlFlowGraph};
+use crate::ir::instructions::CallInfo;
+use crate::ir::{BranchHint, Ebb, Function, Inst, Opcode};
+use crate::timing;
+use std::vec::Vec;
+
+/// Is the control flow edge leaving through `inst` unlikely to be taken?
+///
+/// This is the case for the edge of an unlikely conditional branch, and for the edge of a jump
+/// following a likely conditional branch.
+fn is_unlikely_edge(func: &Function, inst: Inst) -> bool {
+    match func.branch_hints[inst] {
+        Some(hint) => hint == BranchHint::Unlikely,
+        None => {
+            func.dfg[inst].opcode() == Opcode::Jump
+                && func.layout.prev_inst(inst).map_or(false, |prev| {
+                    func.branch_hints[prev] == Some(BranchHint::Likely)
+                })
+        }
+    }
+}
+
+/// Does `ebb` call a function with the `cold` attribute?
+fn calls_cold_function(func: &Function, ebb: Ebb) -> bool {
+    func.layout.ebb_insts(ebb).any(|inst| {
+        match func.dfg[inst].analyze_call(&func.dfg.value_lists) {
+            CallInfo::Direct(func_ref, _) => func.dfg.ext_func_attributes[func_ref].cold,
+            _ => false,
+        }
+    })
+}
+
+/// Move the cold EBBs of `func` to the end of the layout, keeping their relative order.
+///
+/// An EBB is cold when it calls a `cold` function, or when all its predecessors reach it through
+/// an unlikely edge or are cold themselves. The entry block is never moved.
+pub fn do_block_placement(func: &mut Function, cfg: &ControlFlowGraph) {
+    let _tt = timing::block_placement();
+    let entry = func.layout.entry_block();
+
+    let mut cold = SecondaryMap::<Ebb, bool>::new();
+    if func
+        .dfg
+        .ext_func_attributes
+        .values()
+        .any(|attrs| attrs.cold)
+    {
+        for ebb in func.layout.ebbs() {
+            cold[ebb] = Some(ebb) != entry && calls_cold_function(func, ebb);
+        }
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for ebb in func.layout.ebbs() {
+            if cold[ebb] || Some(ebb) == entry || cfg.pred_iter(ebb).next().is_none() {
+                continue;
+            }
+            if cfg
+                .pred_iter(ebb)
+                .all(|BasicBlock { ebb: pred, inst }| cold[pred] || is_unlikely_edge(func, inst))
+            {
+                cold[ebb] = true;
+                changed = true;
+            }
+        }
+    }
+
+    let cold_ebbs: Vec<Ebb> = func.layout.ebbs().filter(|&ebb| cold[ebb]).collect();
+    for ebb in cold_ebbs {
+        func.layout.move_ebb_to_end(ebb);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::do_block_placement;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::flowgraph::ControlFlowGraph;
+    use crate::ir::{
+        types, BranchHint, ExtFuncData, ExternalName, Function, InstBuilder, Signature, TrapCode,
+    };
+    use crate::isa::CallConv;
+    use std::vec::Vec;
+
+    #[test]
+    fn cold_chain() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+        let ebb4 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let brnz = pos.ins().brnz(v0, ebb1, &[]);
+            pos.func.branch_hints[brnz] = Some(BranchHint::Unlikely);
+            pos.ins().jump(ebb3, &[]);
+
+            // Only reachable from the unlikely branch.
+            pos.insert_ebb(ebb1);
+            pos.ins().jump(ebb2, &[]);
+            pos.insert_ebb(ebb2);
+            pos.ins().trap(TrapCode::User(0));
+
+            pos.insert_ebb(ebb3);
+            let brz = pos.ins().brz(v0, ebb4, &[]);
+            pos.func.branch_hints[brz] = Some(BranchHint::Likely);
+            pos.ins().jump(ebb2, &[]);
+
+            pos.insert_ebb(ebb4);
+            pos.ins().return_(&[]);
+        }
+
+        let cfg = ControlFlowGraph::with_function(&func);
+        do_block_placement(&mut func, &cfg);
+        let order: Vec<_> = func.layout.ebbs().collect();
+        assert_eq!(order, [ebb0, ebb3, ebb4, ebb1, ebb2]);
+    }
+
+    #[test]
+    fn shared_successor() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let brnz = pos.ins().brnz(v0, ebb1, &[]);
+            pos.func.branch_hints[brnz] = Some(BranchHint::Unlikely);
+            pos.ins().jump(ebb2, &[]);
+
+            // Also reached from the likely path through `ebb2`.
+            pos.insert_ebb(ebb1);
+            pos.ins().return_(&[]);
+
+            pos.insert_ebb(ebb2);
+            pos.ins().jump(ebb1, &[]);
+        }
+
+        let cfg = ControlFlowGraph::with_function(&func);
+        do_block_placement(&mut func, &cfg);
+        let order: Vec<_> = func.layout.ebbs().collect();
+        assert_eq!(order, [ebb0, ebb1, ebb2]);
+    }
+
+    #[test]
+    fn cold_callee() {
+        let mut func = Function::new();
+        let sig = func.import_signature(Signature::new(CallConv::SystemV));
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("log_error"),
+            signature: sig,
+            colocated: false,
+        });
+        func.dfg.ext_func_attributes[callee].cold = true;
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().brnz(v0, ebb1, &[]);
+            pos.ins().jump(ebb3, &[]);
+
+            // Calls the cold function, and leads to `ebb2`, which is only reachable from here.
+            pos.insert_ebb(ebb1);
+            pos.ins().call(callee, &[]);
+            pos.ins().jump(ebb2, &[]);
+            pos.insert_ebb(ebb2);
+            pos.ins().trap(TrapCode::User(0));
+
+            pos.insert_ebb(ebb3);
+            pos.ins().return_(&[]);
+        }
+
+        let cfg = ControlFlowGraph::with_function(&func);
+        do_block_placement(&mut func, &cfg);
+        let order: Vec<_> = func.layout.ebbs().collect();
+        assert_eq!(order, [ebb0, ebb3, ebb1, ebb2]);
+    }
+}
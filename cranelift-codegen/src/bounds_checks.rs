@@ -0,0 +1,268 @@
+//! Bounds check coalescing.
+//!
+//! The accesses to the fields of a structure stored in a heap typically use the same index with
+//! different offsets, and each of them gets its own `heap_addr` instruction, so its own bounds
+//! check. This pass removes the `heap_addr` instructions whose bounds check is covered by an
+//! earlier `heap_addr` of the same heap and index, growing the access size of the earlier
+//! instruction to the largest one of the group when that doesn't change the behavior of the
+//! function. The `table_addr` instructions are handled the same way, except that their bounds
+//! check doesn't depend on their offset, so it never needs to be grown.
+//!
+//! Growing the access size of a `heap_addr` makes it trap where a later one would have, so it's
+//! only done when nothing observable happens between both instructions: no branch, trap, store or
+//! call. Reusing the address computed by an earlier instruction requires the base and bound of the
+//! heap or table to be unchanged, so stores and calls end the coverage of all the bounds checks.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::fx::FxHashMap;
+use crate::ir::{
+    Function, Heap, Inst, InstBuilder, InstructionData, Opcode, Table, Value, ValueDef,
+};
+use crate::timing;
+
+/// A bounds check that can cover the later ones.
+struct Check {
+    /// The `heap_addr` instruction.
+    inst: Inst,
+    /// Can the access size of `inst` be grown?
+    growable: bool,
+}
+
+/// Coalesce the bounds checks of the `heap_addr` and `table_addr` instructions of `func`.
+pub fn do_bounds_check_coalescing(func: &mut Function) {
+    let _tt = timing::bounds_checks();
+
+    let mut checks: FxHashMap<(Heap, Value), Check> = FxHashMap::default();
+    let mut table_checks: FxHashMap<(Table, Value), Inst> = FxHashMap::default();
+    let mut pos = FuncCursor::new(func);
+    while pos.next_ebb().is_some() {
+        checks.clear();
+        table_checks.clear();
+        while let Some(inst) = pos.next_inst() {
+            let (heap, arg, size) = match pos.func.dfg[inst] {
+                InstructionData::HeapAddr {
+                    opcode: Opcode::HeapAddr,
+                    heap,
+                    arg,
+                    imm,
+                } => (heap, arg, imm.into()),
+                InstructionData::TableAddr {
+                    opcode: Opcode::TableAddr,
+                    table,
+                    arg,
+                    offset,
+                } => {
+                    let key = (table, index_source(pos.func, arg));
+                    let offset: i64 = offset.into();
+                    match table_checks.get(&key) {
+                        Some(&covering)
+                            if pos.func.dfg.ctrl_typevar(covering)
+                                == pos.func.dfg.ctrl_typevar(inst) =>
+                        {
+                            let covering_offset = table_addr_offset(pos.func, covering);
+                            if offset == covering_offset {
+                                pos.func.dfg.replace_with_aliases(inst, covering);
+                                pos.remove_inst_and_step_back();
+                            } else {
+                                let covering = pos.func.dfg.first_result(covering);
+                                pos.func
+                                    .dfg
+                                    .replace(inst)
+                                    .iadd_imm(covering, offset - covering_offset);
+                            }
+                        }
+                        _ => {
+                            table_checks.insert(key, inst);
+                        }
+                    }
+                    continue;
+                }
+                ref data => {
+                    let opcode = data.opcode();
+                    if opcode.is_call() || opcode.can_store() || opcode.other_side_effects() {
+                        checks.clear();
+                        table_checks.clear();
+                    } else if opcode.is_branch() || opcode.can_trap() {
+                        for check in checks.values_mut() {
+                            check.growable = false;
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            let key = (heap, index_source(pos.func, arg));
+            let covering = match checks.get(&key) {
+                Some(check) if heap_addr_size(pos.func, check.inst) >= size => Some(check.inst),
+                Some(check) if check.growable => {
+                    set_heap_addr_size(pos.func, check.inst, size);
+                    Some(check.inst)
+                }
+                _ => None,
+            };
+
+            match covering {
+                Some(covering) => {
+                    pos.func.dfg.replace_with_aliases(inst, covering);
+                    pos.remove_inst_and_step_back();
+                }
+                None => {
+                    checks.insert(
+                        key,
+                        Check {
+                            inst,
+                            growable: true,
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Get the value an index is computed from.
+///
+/// The indices narrower than the index type of their heap are zero extended before each access,
+/// so look through the `uextend` instructions to identify them.
+fn index_source(func: &Function, index: Value) -> Value {
+    let index = func.dfg.resolve_aliases(index);
+    if let ValueDef::Result(def, _) = func.dfg.value_def(index) {
+        if let InstructionData::Unary {
+            opcode: Opcode::Uextend,
+            arg,
+        } = func.dfg[def]
+        {
+            return func.dfg.resolve_aliases(arg);
+        }
+    }
+    index
+}
+
+/// Get the access size of the `heap_addr` instruction `inst`.
+fn heap_addr_size(func: &Function, inst: Inst) -> u32 {
+    match func.dfg[inst] {
+        InstructionData::HeapAddr { imm, .. } => imm.into(),
+        _ => panic!("Wanted heap_addr: {}", func.dfg.display_inst(inst, None)),
+    }
+}
+
+/// Get the offset of the `table_addr` instruction `inst`.
+fn table_addr_offset(func: &Function, inst: Inst) -> i64 {
+    match func.dfg[inst] {
+        InstructionData::TableAddr { offset, .. } => offset.into(),
+        _ => panic!("Wanted table_addr: {}", func.dfg.display_inst(inst, None)),
+    }
+}
+
+/// Set the access size of the `heap_addr` instruction `inst`.
+fn set_heap_addr_size(func: &mut Function, inst: Inst, size: u32) {
+    if let InstructionData::HeapAddr { ref mut imm, .. } = func.dfg[inst] {
+        *imm = size.into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{
+        types, ExtFuncData, ExternalName, GlobalValueData, HeapData, HeapStyle, Signature,
+        TableData, TableStyle,
+    };
+    use crate::isa::CallConv;
+    use std::vec::Vec;
+
+    #[test]
+    fn coalesce() {
+        let mut func = Function::new();
+        let vmctx = func.create_global_value(GlobalValueData::VMContext);
+        let heap = func.create_heap(HeapData {
+            base: vmctx,
+            min_size: 0.into(),
+            offset_guard_size: 0.into(),
+            style: HeapStyle::Dynamic { bound_gv: vmctx },
+            index_type: types::I64,
+        });
+        let signature = func.import_signature(Signature::new(CallConv::SystemV));
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("callee"),
+            signature,
+            colocated: false,
+        });
+
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        let (v2, v3, v4, v5);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let v1 = pos.ins().uextend(types::I64, v0);
+            v2 = pos.ins().heap_addr(types::I64, heap, v1, 4);
+            let v1 = pos.ins().uextend(types::I64, v0);
+            v3 = pos.ins().heap_addr(types::I64, heap, v1, 12);
+            pos.ins().brz(v0, ebb1, &[]);
+            v4 = pos.ins().heap_addr(types::I64, heap, v1, 16);
+            v5 = pos.ins().heap_addr(types::I64, heap, v1, 8);
+            pos.ins().call(callee, &[]);
+            pos.ins().heap_addr(types::I64, heap, v1, 8);
+            pos.ins().return_(&[]);
+            pos.insert_ebb(ebb1);
+            pos.ins().return_(&[]);
+        }
+
+        do_bounds_check_coalescing(&mut func);
+
+        // The first check is grown to cover the second one, the one after the branch covers the
+        // next one, and the call ends their coverage.
+        let sizes: Vec<u32> = func
+            .layout
+            .ebb_insts(ebb0)
+            .filter(|&inst| func.dfg[inst].opcode() == Opcode::HeapAddr)
+            .map(|inst| heap_addr_size(&func, inst))
+            .collect();
+        assert_eq!(sizes, [12, 16, 8]);
+        assert_eq!(func.dfg.resolve_aliases(v3), v2);
+        assert_eq!(func.dfg.resolve_aliases(v5), v4);
+    }
+
+    #[test]
+    fn coalesce_tables() {
+        let mut func = Function::new();
+        let vmctx = func.create_global_value(GlobalValueData::VMContext);
+        let table = func.create_table(TableData {
+            base_gv: vmctx,
+            min_size: 0.into(),
+            style: TableStyle::Static { bound: 16.into() },
+            element_size: 16.into(),
+            index_type: types::I32,
+        });
+
+        let ebb0 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        let (v1, v2, v3);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            v1 = pos.ins().table_addr(types::I64, table, v0, 0);
+            v2 = pos.ins().table_addr(types::I64, table, v0, 8);
+            v3 = pos.ins().table_addr(types::I64, table, v0, 0);
+            pos.ins().return_(&[]);
+        }
+
+        do_bounds_check_coalescing(&mut func);
+
+        // The second access is computed from the first one, and the third one reuses it.
+        let opcodes: Vec<_> = func
+            .layout
+            .ebb_insts(ebb0)
+            .map(|inst| func.dfg[inst].opcode())
+            .collect();
+        assert_eq!(
+            opcodes,
+            [Opcode::TableAddr, Opcode::IaddImm, Opcode::Return]
+        );
+        let inst = func.dfg.value_def(v2).unwrap_inst();
+        assert_eq!(func.dfg.inst_args(inst), [v1]);
+        assert_eq!(func.dfg.resolve_aliases(v3), v1);
+    }
+}
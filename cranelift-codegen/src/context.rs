@@ -10,28 +10,41 @@
 //! single ISA instance.
 
 use crate::binemit::{
-    relax_branches, shrink_instructions, CodeInfo, MemoryCodeSink, RelocSink, StackmapSink,
-    TrapSink,
+    relax_branches, shrink_instructions, CodeInfo, CodeMemory, CompiledFunctionMetadata,
+    EncodingAnnotation, GrowableCodeSink, MemoryCodeSink, RelocSink, ResolvingRelocSink,
+    StackmapSink, SymbolResolver, TrapSink,
 };
+use crate::block_placement::do_block_placement;
+use crate::bounds_checks::do_bounds_check_coalescing;
 use crate::dce::do_dce;
 use crate::dominator_tree::DominatorTree;
 use crate::flowgraph::ControlFlowGraph;
-use crate::ir::Function;
+use crate::instrument::{do_counter_instrumentation, CounterKind, CounterSite};
+use crate::ir::{ExternalName, Function, GlobalValue, ValueLabel};
 use crate::isa::TargetIsa;
 use crate::legalize_function;
 use crate::licm::do_licm;
 use crate::loop_analysis::LoopAnalysis;
 use crate::nan_canonicalization::do_nan_canonicalization;
+use crate::osr::{check_osr_entry, do_osr_entry};
 use crate::postopt::do_postopt;
 use crate::regalloc;
 use crate::result::CodegenResult;
+use crate::scheduling::do_post_ra_scheduling;
 use crate::settings::{FlagsOrIsa, OptLevel};
 use crate::simple_gvn::do_simple_gvn;
 use crate::simple_preopt::do_preopt;
+use crate::stack_usage::StackUsage;
 use crate::timing;
-use crate::unreachable_code::eliminate_unreachable_code;
+use crate::unreachable_code::{eliminate_unreachable_code, trap_after_noreturn_calls};
 use crate::value_label::{build_value_labels_ranges, ComparableSourceLoc, ValueLabelsRanges};
-use crate::verifier::{verify_context, verify_locations, VerifierErrors, VerifierResult};
+use crate::value_trace::do_value_tracing;
+#[cfg(feature = "testing_hooks")]
+use crate::verifier::verify_encoding_sizes;
+use crate::verifier::{
+    verify_abi, verify_context, verify_function_fast, verify_locations, VerifierErrors,
+    VerifierResult,
+};
 use std::vec::Vec;
 
 /// Persistent data structures and compilation pipeline.
@@ -76,6 +89,10 @@ impl Context {
     }
 
     /// Clear all data structures in this context.
+    ///
+    /// The memory allocated for the function and the analyses is kept for the next function, so a
+    /// context that is reused for compiling many functions stops allocating once it has seen the
+    /// largest one.
     pub fn clear(&mut self) {
         self.func.clear();
         self.cfg.clear();
@@ -113,6 +130,26 @@ impl Context {
         Ok(info)
     }
 
+    /// Compile the function, and emit machine code into a `Vec<u8>`, collecting the relocations,
+    /// traps, and stackmaps into a single `CompiledFunctionMetadata`.
+    ///
+    /// The metadata has a stable binary encoding, so it can be cached alongside the machine code.
+    pub fn compile_and_emit_with_metadata(
+        &mut self,
+        isa: &dyn TargetIsa,
+        mem: &mut Vec<u8>,
+    ) -> CodegenResult<(CodeInfo, CompiledFunctionMetadata)> {
+        let mut metadata = CompiledFunctionMetadata::new();
+        let info = self.compile_and_emit(
+            isa,
+            mem,
+            &mut metadata.relocs,
+            &mut metadata.traps,
+            &mut metadata.stackmaps,
+        )?;
+        Ok((info, metadata))
+    }
+
     /// Compile the function.
     ///
     /// Run the function through all the passes necessary to generate code for the target ISA
@@ -121,39 +158,90 @@ impl Context {
     ///
     /// Returns information about the function's code and read-only data.
     pub fn compile(&mut self, isa: &dyn TargetIsa) -> CodegenResult<CodeInfo> {
+        self.compile_at(isa, isa.flags().opt_level())
+    }
+
+    /// Compile the function as fast as possible, and emit its machine code into `mem`.
+    ///
+    /// This is meant for baseline JIT tiers, which care more about the number of functions
+    /// compiled per second than about the quality of the generated code. The function goes through
+    /// the passes `compile` runs with `opt_level = "fastest"`, whatever the `opt_level` setting of
+    /// `isa` is, so it is legalized, register allocated and relaxed, but none of the optional
+    /// optimizations run: no preopt, GVN, LICM, DCE, block placement, scheduling or instruction
+    /// shrinking. The register allocator is the same as for the other optimization levels.
+    ///
+    /// The machine code is then emitted in the same call, growing `mem` as needed like
+    /// `emit_to_code_memory`, so the size of the code doesn't have to be known up front.
+    ///
+    /// Returns information about the emitted code and data.
+    pub fn compile_fast(
+        &mut self,
+        isa: &dyn TargetIsa,
+        mem: &mut dyn CodeMemory,
+        relocs: &mut dyn RelocSink,
+        traps: &mut dyn TrapSink,
+        stackmaps: &mut dyn StackmapSink,
+    ) -> CodegenResult<CodeInfo> {
+        self.compile_at(isa, OptLevel::Fastest)?;
+        Ok(self.emit_to_code_memory(isa, mem, relocs, traps, stackmaps))
+    }
+
+    /// Compile the function, running the passes enabled by `opt_level`.
+    fn compile_at(&mut self, isa: &dyn TargetIsa, opt_level: OptLevel) -> CodegenResult<CodeInfo> {
         let _tt = timing::compile();
         self.verify_if(isa)?;
 
+        if self.func.osr_entry.is_some() {
+            self.osr_entry(isa)?;
+        }
         self.compute_cfg();
-        if isa.flags().opt_level() != OptLevel::Fastest {
+        self.trap_after_noreturn_calls(isa)?;
+        if opt_level != OptLevel::Fastest {
             self.preopt(isa)?;
         }
         if isa.flags().enable_nan_canonicalization() {
             self.canonicalize_nans(isa)?;
         }
+        if opt_level != OptLevel::Fastest {
+            self.coalesce_bounds_checks(isa)?;
+        }
         self.legalize(isa)?;
-        if isa.flags().opt_level() != OptLevel::Fastest {
+        if opt_level != OptLevel::Fastest {
             self.postopt(isa)?;
         }
-        if isa.flags().opt_level() == OptLevel::Best {
+        if opt_level == OptLevel::Best {
             self.compute_domtree();
             self.compute_loop_analysis();
             self.licm(isa)?;
             self.simple_gvn(isa)?;
+        } else {
+            self.compute_domtree();
         }
-        self.compute_domtree();
         self.eliminate_unreachable_code(isa)?;
-        if isa.flags().opt_level() != OptLevel::Fastest {
+        if opt_level != OptLevel::Fastest {
             self.dce(isa)?;
+            self.block_placement(isa)?;
+        }
+        if isa.flags().enable_safepoints() && isa.flags().enable_auto_safepoints() {
+            self.insert_safepoints(isa)?;
+            self.eliminate_redundant_safepoints(isa)?;
         }
         self.regalloc(isa)?;
         self.prologue_epilogue(isa)?;
-        if isa.flags().opt_level() == OptLevel::Best {
+        if opt_level == OptLevel::Best {
+            self.post_ra_scheduling(isa)?;
             self.shrink_instructions(isa)?;
         }
         self.relax_branches(isa)
     }
 
+    /// Compute the stack usage of the compiled function.
+    ///
+    /// Returns `None` if the function hasn't been compiled yet.
+    pub fn stack_usage(&self) -> Option<StackUsage> {
+        StackUsage::from_function(&self.func)
+    }
+
     /// Emit machine code directly into raw memory.
     ///
     /// Write all of the function's machine code to the memory at `mem`. The size of the machine
@@ -179,6 +267,98 @@ impl Context {
         sink.info
     }
 
+    /// Emit machine code directly into raw memory, recording the encoding of each instruction.
+    ///
+    /// This is like `emit_to_memory`, except that the offset, size, and encoding of each emitted
+    /// instruction are appended to `annotations`, in layout order. This is useful for checking
+    /// the machine code, analyzing its size, and debugging the selection of encoding recipes.
+    ///
+    /// This function is unsafe for the same reasons as `emit_to_memory`.
+    ///
+    /// Returns information about the emitted code and data.
+    pub unsafe fn emit_to_memory_annotated(
+        &self,
+        isa: &dyn TargetIsa,
+        mem: *mut u8,
+        relocs: &mut dyn RelocSink,
+        traps: &mut dyn TrapSink,
+        stackmaps: &mut dyn StackmapSink,
+        annotations: &mut Vec<EncodingAnnotation>,
+    ) -> CodeInfo {
+        let _tt = timing::binemit();
+        let mut sink = MemoryCodeSink::new(mem, relocs, traps, stackmaps);
+        sink.annotate(annotations);
+        isa.emit_function_to_memory(&self.func, &mut sink);
+        sink.info
+    }
+
+    /// Emit relocation-free machine code directly into raw memory.
+    ///
+    /// This is like `emit_to_memory`, except that the addresses of the external symbols are
+    /// taken from `resolver`, and the final absolute and PC-relative values are written into the
+    /// machine code instead of being reported as relocations. The code at `mem` is ready to run
+    /// once this function returns, so `mem` must be its final location.
+    ///
+    /// This function is unsafe for the same reasons as `emit_to_memory`.
+    ///
+    /// Returns information about the emitted code and data, or an error if a symbol can't be
+    /// resolved, or if it is out of the range of a PC-relative reference.
+    pub unsafe fn emit_to_memory_resolved(
+        &self,
+        isa: &dyn TargetIsa,
+        mem: *mut u8,
+        resolver: &mut dyn SymbolResolver,
+        traps: &mut dyn TrapSink,
+        stackmaps: &mut dyn StackmapSink,
+    ) -> CodegenResult<CodeInfo> {
+        let mut relocs = ResolvingRelocSink::new(resolver);
+        let info = self.emit_to_memory(isa, mem, &mut relocs, traps, stackmaps);
+        relocs.apply(mem)?;
+        Ok(info)
+    }
+
+    /// Emit machine code into memory that grows as needed.
+    ///
+    /// This is the second phase of the compilation after `compile`, for embedders which don't
+    /// want to allocate memory for the whole function up front: `mem` is asked for more room
+    /// whenever the machine code doesn't fit, so the size returned by `compile` isn't needed.
+    ///
+    /// The machine code is written from the start of `mem`, and it is not relocated. Instead, any
+    /// relocations are emitted into `relocs`.
+    ///
+    /// Returns information about the emitted code and data.
+    pub fn emit_to_code_memory(
+        &self,
+        isa: &dyn TargetIsa,
+        mem: &mut dyn CodeMemory,
+        relocs: &mut dyn RelocSink,
+        traps: &mut dyn TrapSink,
+        stackmaps: &mut dyn StackmapSink,
+    ) -> CodeInfo {
+        let _tt = timing::binemit();
+        let mut sink = GrowableCodeSink::new(mem, relocs, traps, stackmaps);
+        isa.emit_function_to_growable(&self.func, &mut sink);
+        sink.info
+    }
+
+    /// Verify that the sizes of the encodings match the layout of `code`, the machine code emitted
+    /// by `emit_to_memory` for the function.
+    ///
+    /// The layout of the code is recomputed from the instruction sizes given by the encoding
+    /// tables, which catches inconsistencies between the encoding recipes, branch relaxation, and
+    /// the code emitters. The emitted instructions themselves are not decoded.
+    #[cfg(feature = "testing_hooks")]
+    pub fn verify_encoding_sizes(&self, isa: &dyn TargetIsa, code: &[u8]) -> VerifierResult<()> {
+        let mut errors = VerifierErrors::default();
+        let _ = verify_encoding_sizes(isa, &self.func, code, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     /// Run the verifier on the function.
     ///
     /// Also check that the dominator tree and control flow graph are consistent with the function.
@@ -193,11 +373,21 @@ impl Context {
         }
     }
 
-    /// Run the verifier only if the `enable_verifier` setting is true.
+    /// Run the fast verifier on the function.
+    ///
+    /// This only checks the control flow and the SSA form of the function.
+    pub fn verify_fast<'a, FOI: Into<FlagsOrIsa<'a>>>(&self, fisa: FOI) -> VerifierResult<()> {
+        verify_function_fast(&self.func, fisa)
+    }
+
+    /// Run the verifier only if the `enable_verifier` setting is true, or the fast verifier if the
+    /// `enable_fast_verifier` setting is true.
     pub fn verify_if<'a, FOI: Into<FlagsOrIsa<'a>>>(&self, fisa: FOI) -> CodegenResult<()> {
         let fisa = fisa.into();
         if fisa.flags.enable_verifier() {
             self.verify(fisa)?;
+        } else if fisa.flags.enable_fast_verifier() {
+            self.verify_fast(fisa)?;
         }
         Ok(())
     }
@@ -222,6 +412,35 @@ impl Context {
         Ok(())
     }
 
+    /// Run the ABI verifier on the calls in the function.
+    pub fn verify_abi(&self, isa: &dyn TargetIsa) -> VerifierResult<()> {
+        let mut errors = VerifierErrors::default();
+        let _ = verify_abi(isa, &self.func, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Run the ABI verifier only if the `enable_verifier` setting is true.
+    pub fn verify_abi_if(&self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        if isa.flags().enable_verifier() {
+            self.verify_abi(isa)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the bounds checks of heap accesses covered by an earlier one.
+    pub fn coalesce_bounds_checks<'a, FOI: Into<FlagsOrIsa<'a>>>(
+        &mut self,
+        fisa: FOI,
+    ) -> CodegenResult<()> {
+        do_bounds_check_coalescing(&mut self.func);
+        self.verify_if(fisa)
+    }
+
     /// Perform dead-code elimination on the function.
     pub fn dce<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
         do_dce(&mut self.func, &mut self.domtree);
@@ -229,6 +448,29 @@ impl Context {
         Ok(())
     }
 
+    /// Move the EBBs only reachable through unlikely branches to the end of the function.
+    pub fn block_placement<'a, FOI: Into<FlagsOrIsa<'a>>>(
+        &mut self,
+        fisa: FOI,
+    ) -> CodegenResult<()> {
+        do_block_placement(&mut self.func, &self.cfg);
+        self.verify_if(fisa)?;
+        Ok(())
+    }
+
+    /// Insert safepoints before the calls and the loop backedges of the function.
+    pub fn insert_safepoints(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        self.compute_loop_analysis();
+        regalloc::insert_safepoints(&mut self.func, &self.cfg, &self.loop_analysis, isa);
+        self.verify_if(isa)
+    }
+
+    /// Remove the safepoints made redundant by an earlier safepoint.
+    pub fn eliminate_redundant_safepoints(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        regalloc::eliminate_redundant_safepoints(&mut self.func);
+        self.verify_if(isa)
+    }
+
     /// Perform pre-legalization rewrites on the function.
     pub fn preopt(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
         do_preopt(&mut self.func, &mut self.cfg);
@@ -248,8 +490,10 @@ impl Context {
         // TODO: Avoid doing this when legalization doesn't actually mutate the CFG.
         self.domtree.clear();
         self.loop_analysis.clear();
+        isa.check_call_convs(&self.func)?;
         legalize_function(&mut self.func, &mut self.cfg, isa);
-        self.verify_if(isa)
+        self.verify_if(isa)?;
+        self.verify_abi_if(isa)
     }
 
     /// Perform post-legalization rewrites on the function.
@@ -308,10 +552,73 @@ impl Context {
         self.verify_if(fisa)
     }
 
+    /// Insert increments of the counters stored in the memory pointed to by `base`.
+    ///
+    /// Returns the sites of the counters, indexed by counter number.
+    pub fn instrument_counters(
+        &mut self,
+        isa: &dyn TargetIsa,
+        kind: CounterKind,
+        base: GlobalValue,
+    ) -> CodegenResult<Vec<CounterSite>> {
+        let sites = do_counter_instrumentation(&mut self.func, kind, base, isa.pointer_type());
+        self.verify_if(isa)?;
+        Ok(sites)
+    }
+
+    /// Insert calls to the hook `hook_name` after the definitions of the values labeled with one
+    /// of `labels`.
+    ///
+    /// The hook is called with the default calling convention of `isa`, and takes the label as an
+    /// `i32` and the bits of the value as an `i64`.
+    pub fn trace_values(
+        &mut self,
+        isa: &dyn TargetIsa,
+        labels: &[ValueLabel],
+        hook_name: ExternalName,
+    ) -> CodegenResult<()> {
+        do_value_tracing(&mut self.func, labels, hook_name, isa.default_call_conv());
+        self.verify_if(isa)
+    }
+
+    /// Replace the entry block with one entering the function at its OSR entry block, and remove
+    /// the code which becomes unreachable.
+    ///
+    /// Fails if the code reachable from the OSR entry block uses values defined before it.
+    pub fn osr_entry(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        do_osr_entry(&mut self.func, isa);
+        self.compute_cfg();
+        self.compute_domtree();
+        check_osr_entry(&self.func, &self.domtree)?;
+        self.eliminate_unreachable_code(isa)
+    }
+
+    /// Replace the code following calls to `noreturn` functions with traps, and delete the code
+    /// which becomes unreachable.
+    pub fn trap_after_noreturn_calls<'a, FOI>(&mut self, fisa: FOI) -> CodegenResult<()>
+    where
+        FOI: Into<FlagsOrIsa<'a>>,
+    {
+        if trap_after_noreturn_calls(&mut self.func, &mut self.cfg) {
+            self.compute_domtree();
+            self.eliminate_unreachable_code(fisa)?;
+        }
+        Ok(())
+    }
+
     /// Run the register allocator.
     pub fn regalloc(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
-        self.regalloc
-            .run(isa, &mut self.func, &self.cfg, &mut self.domtree)
+        // The spilling pass ranks its candidates by loop depth.
+        if !self.loop_analysis.is_valid() {
+            self.compute_loop_analysis();
+        }
+        self.regalloc.run(
+            isa,
+            &mut self.func,
+            &self.cfg,
+            &mut self.domtree,
+            &self.loop_analysis,
+        )
     }
 
     /// Insert prologue and epilogues after computing the stack frame layout.
@@ -322,6 +629,14 @@ impl Context {
         Ok(())
     }
 
+    /// Reorder the instructions after register allocation to hide their latencies.
+    pub fn post_ra_scheduling(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        do_post_ra_scheduling(&mut self.func, isa);
+        self.verify_if(isa)?;
+        self.verify_locations_if(isa)?;
+        Ok(())
+    }
+
     /// Run the instruction shrinking pass.
     pub fn shrink_instructions(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
         shrink_instructions(&mut self.func, isa);
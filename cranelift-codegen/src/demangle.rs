@@ -0,0 +1,85 @@
+//! Demangling of symbol names.
+//!
+//! The external names of functions compiled from Rust or C++ are usually mangled symbols, which
+//! are hard to read in diagnostics. With the `demangle-rust` and `demangle-cpp` features, the
+//! verifier errors annotate the definitions of such names with their demangled form.
+
+#[cfg(feature = "demangle-rust")]
+use core::fmt::Write;
+use std::string::String;
+
+/// Demangle the Rust or C++ symbol `symbol`.
+///
+/// Returns `None` if `symbol` isn't mangled, or if the demangler for its language isn't enabled.
+pub fn demangle(symbol: &str) -> Option<String> {
+    demangle_rust(symbol).or_else(|| demangle_cpp(symbol))
+}
+
+#[cfg(feature = "demangle-rust")]
+fn demangle_rust(symbol: &str) -> Option<String> {
+    // The alternate format omits the hash suffix of the legacy mangling.
+    let demangled = rustc_demangle::try_demangle(symbol).ok()?;
+    let mut s = String::new();
+    write!(s, "{:#}", demangled).ok()?;
+    Some(s)
+}
+
+#[cfg(not(feature = "demangle-rust"))]
+fn demangle_rust(_symbol: &str) -> Option<String> {
+    None
+}
+
+#[cfg(feature = "demangle-cpp")]
+fn demangle_cpp(symbol: &str) -> Option<String> {
+    let symbol = cpp_demangle::Symbol::new(symbol).ok()?;
+    symbol.demangle(&Default::default()).ok()
+}
+
+#[cfg(not(feature = "demangle-cpp"))]
+fn demangle_cpp(_symbol: &str) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::demangle;
+    #[cfg(feature = "demangle-rust")]
+    use crate::ir::{ExternalName, ExternalNameTable};
+    #[cfg(any(feature = "demangle-rust", feature = "demangle-cpp"))]
+    use std::string::String;
+
+    #[test]
+    fn plain_names() {
+        assert_eq!(demangle("memcpy"), None);
+        assert_eq!(demangle(""), None);
+    }
+
+    #[test]
+    #[cfg(feature = "demangle-rust")]
+    fn rust_names() {
+        assert_eq!(
+            demangle("_ZN4core3ptr13drop_in_place17h0123456789abcdefE"),
+            Some(String::from("core::ptr::drop_in_place"))
+        );
+        let mut names = ExternalNameTable::new(0);
+        assert_eq!(
+            ExternalName::testcase("_ZN3foo3barE").demangled(&names),
+            Some(String::from("foo::bar"))
+        );
+        let name = names.intern("_ZN4core3ptr13drop_in_place17h0123456789abcdefE");
+        assert_eq!(
+            name.demangled(&names),
+            Some(String::from("core::ptr::drop_in_place"))
+        );
+        assert_eq!(ExternalName::user(0, 1).demangled(&names), None);
+    }
+
+    #[test]
+    #[cfg(feature = "demangle-cpp")]
+    fn cpp_names() {
+        assert_eq!(
+            demangle("_ZN5space3fooEibc"),
+            Some(String::from("space::foo(int, bool, char)"))
+        );
+    }
+}
@@ -236,7 +236,7 @@ impl DominatorTree {
         let _tt = timing::domtree();
         debug_assert!(cfg.is_valid());
         self.compute_postorder(func);
-        self.compute_domtree(func, cfg);
+        self.compute_domtree(func, cfg, &SecondaryMap::new());
         self.valid = true;
     }
 
@@ -369,7 +369,14 @@ impl DominatorTree {
 
     /// Build a dominator tree from a control flow graph using Keith D. Cooper's
     /// "Simple, Fast Dominator Algorithm."
-    fn compute_domtree(&mut self, func: &Function, cfg: &ControlFlowGraph) {
+    ///
+    /// The immediate dominators in `known` are used as is instead of being computed.
+    fn compute_domtree(
+        &mut self,
+        func: &Function,
+        cfg: &ControlFlowGraph,
+        known: &SecondaryMap<Ebb, PackedOption<Inst>>,
+    ) {
         // During this algorithm, `rpo_number` has the following values:
         //
         // 0: EBB is not reachable.
@@ -396,8 +403,12 @@ impl DominatorTree {
             //
             // Due to the nature of the post-order traversal, every node we visit will have at
             // least one predecessor that has previously been visited during this RPO.
+            let idom = match known[ebb].expand() {
+                Some(idom) => idom,
+                None => self.compute_idom(ebb, cfg, &func.layout),
+            };
             self.nodes[ebb] = DomNode {
-                idom: self.compute_idom(ebb, cfg, &func.layout).into(),
+                idom: idom.into(),
                 rpo_number: (rpo_idx as u32 + 3) * STRIDE,
             }
         }
@@ -409,7 +420,7 @@ impl DominatorTree {
         let mut changed = true;
         while changed {
             changed = false;
-            for &ebb in postorder.iter().rev() {
+            for &ebb in postorder.iter().rev().filter(|&&ebb| known[ebb].is_none()) {
                 let idom = self.compute_idom(ebb, cfg, &func.layout).into();
                 if self.nodes[ebb].idom != idom {
                     self.nodes[ebb].idom = idom;
@@ -497,6 +508,74 @@ impl DominatorTree {
     }
 }
 
+impl DominatorTree {
+    /// Update the dominator tree after adding an edge from `from` to `to` in the control flow
+    /// graph.
+    ///
+    /// `cfg` must already contain the new edge.
+    pub fn insert_edge(&mut self, func: &Function, cfg: &ControlFlowGraph, from: Ebb, to: Ebb) {
+        // An unreachable EBB stays unreachable when its successors change, and it doesn't
+        // contribute to the dominators of other EBBs.
+        if self.is_reachable(from) {
+            self.update_edges_to(func, cfg, &[to]);
+        }
+    }
+
+    /// Update the dominator tree after removing an edge from `from` to `to` in the control flow
+    /// graph.
+    ///
+    /// `cfg` must already be updated to not contain the edge any more. Other branches from `from`
+    /// to `to` may remain.
+    pub fn remove_edge(&mut self, func: &Function, cfg: &ControlFlowGraph, from: Ebb, to: Ebb) {
+        if self.is_reachable(from) {
+            self.update_edges_to(func, cfg, &[to]);
+        }
+    }
+
+    /// Update the dominator tree after adding or removing any number of edges to the EBBs in
+    /// `targets`, which may be new EBBs.
+    ///
+    /// `cfg` must already be updated. Only the EBBs reachable from `targets` can have their
+    /// dominators changed by these edges, since any path to another EBB doesn't go through them.
+    /// The CFG post-order is computed again, which is a single traversal of the function, but the
+    /// immediate dominators of the other EBBs are kept, which avoids iterating over the whole
+    /// function until the dominators converge.
+    pub fn update_edges_to(&mut self, func: &Function, cfg: &ControlFlowGraph, targets: &[Ebb]) {
+        let _tt = timing::domtree();
+        debug_assert!(self.is_valid());
+        debug_assert!(cfg.is_valid());
+
+        // Find the EBBs reachable from `targets`.
+        let mut affected = SecondaryMap::<Ebb, bool>::new();
+        for &ebb in targets {
+            if !affected[ebb] {
+                affected[ebb] = true;
+                self.stack.push(ebb);
+            }
+        }
+        while let Some(ebb) = self.stack.pop() {
+            for succ in cfg.succ_iter(ebb) {
+                if !affected[succ] {
+                    affected[succ] = true;
+                    self.stack.push(succ);
+                }
+            }
+        }
+
+        // Keep the immediate dominators of the other reachable EBBs.
+        let mut known = SecondaryMap::new();
+        for &ebb in &self.postorder {
+            if !affected[ebb] {
+                known[ebb] = self.nodes[ebb].idom;
+            }
+        }
+
+        self.compute_postorder(func);
+        self.compute_domtree(func, cfg, &known);
+        self.valid = true;
+    }
+}
+
 /// Optional pre-order information that can be computed for a dominator tree.
 ///
 /// This data structure is computed from a `DominatorTree` and provides:
@@ -940,4 +1019,73 @@ mod tests {
 
         assert!(errors.0.is_empty());
     }
+
+    #[test]
+    fn incremental_edges() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+        let ebb4 = func.dfg.make_ebb();
+
+        let mut cur = FuncCursor::new(&mut func);
+
+        cur.insert_ebb(ebb0);
+        let cond = cur.ins().iconst(I32, 0);
+        let br_ebb0_ebb2 = cur.ins().brnz(cond, ebb2, &[]);
+        cur.ins().jump(ebb1, &[]);
+
+        cur.insert_ebb(ebb1);
+        let jmp_ebb1_ebb3 = cur.ins().jump(ebb3, &[]);
+
+        cur.insert_ebb(ebb2);
+        let jmp_ebb2_ebb3 = cur.ins().jump(ebb3, &[]);
+
+        cur.insert_ebb(ebb3);
+        cur.ins().brnz(cond, ebb1, &[]);
+        cur.ins().return_(&[]);
+
+        cur.insert_ebb(ebb4);
+        cur.ins().return_(&[]);
+
+        let mut cfg = ControlFlowGraph::with_function(cur.func);
+        let mut dt = DominatorTree::with_function(cur.func, &cfg);
+        let flags = settings::Flags::new(settings::builder());
+        assert!(!dt.is_reachable(ebb4));
+        assert_eq!(dt.idom(ebb1), Some(br_ebb0_ebb2));
+
+        // Make `ebb4` reachable from `ebb1`.
+        cur.goto_inst(jmp_ebb1_ebb3);
+        let br_ebb1_ebb4 = cur.ins().brnz(cond, ebb4, &[]);
+        cfg.recompute_ebb(cur.func, ebb1);
+        dt.insert_edge(cur.func, &cfg, ebb1, ebb4);
+        verify_context(cur.func, &cfg, &dt, &flags, &mut VerifierErrors::default()).unwrap();
+        assert_eq!(dt.idom(ebb4), Some(br_ebb1_ebb4));
+
+        // Remove the `ebb2 -> ebb3` edge, so `ebb1` dominates `ebb3`.
+        cur.goto_inst(jmp_ebb2_ebb3);
+        cur.remove_inst();
+        cur.ins().return_(&[]);
+        cfg.recompute_ebb(cur.func, ebb2);
+        dt.remove_edge(cur.func, &cfg, ebb2, ebb3);
+        verify_context(cur.func, &cfg, &dt, &flags, &mut VerifierErrors::default()).unwrap();
+        assert_eq!(dt.idom(ebb3), Some(jmp_ebb1_ebb3));
+
+        // Remove the `ebb0 -> ebb2` edge, so `ebb2` becomes unreachable.
+        cur.goto_inst(br_ebb0_ebb2);
+        cur.remove_inst();
+        cfg.recompute_ebb(cur.func, ebb0);
+        dt.remove_edge(cur.func, &cfg, ebb0, ebb2);
+        verify_context(cur.func, &cfg, &dt, &flags, &mut VerifierErrors::default()).unwrap();
+        assert!(!dt.is_reachable(ebb2));
+
+        // Branching from the unreachable `ebb2` changes nothing.
+        cur.goto_first_insertion_point(ebb2);
+        cur.ins().brnz(cond, ebb3, &[]);
+        cfg.recompute_ebb(cur.func, ebb2);
+        dt.insert_edge(cur.func, &cfg, ebb2, ebb3);
+        verify_context(cur.func, &cfg, &dt, &flags, &mut VerifierErrors::default()).unwrap();
+        assert_eq!(dt.idom(ebb3), Some(jmp_ebb1_ebb3));
+    }
 }
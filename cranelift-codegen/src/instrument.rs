@@ -0,0 +1,284 @@
+//! Counter instrumentation.
+//!
+//! This pass inserts code incrementing a 64-bit counter every time an EBB or a control flow edge
+//! is executed. The counters live in an array provided by the embedder, and the pass returns a
+//! table describing what each counter counts, so the collected profile can be mapped back to the
+//! source program for coverage reports or profile-guided block placement.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{
+    types, Ebb, Function, GlobalValue, Inst, InstBuilder, InstructionData, MemFlags, SourceLoc,
+    Type, Value,
+};
+use crate::timing;
+use std::vec::Vec;
+
+/// The kind of events counted by the instrumentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterKind {
+    /// Count the executions of each EBB.
+    Ebb,
+
+    /// Count the traversals of each control flow edge.
+    Edge,
+}
+
+/// Description of the event counted by one counter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterSite {
+    /// The counted EBB, or the original destination of the counted edge.
+    pub ebb: Ebb,
+
+    /// The branch instruction leaving through the counted edge, or `None` for an EBB counter.
+    pub branch: Option<Inst>,
+
+    /// Source location of the first instruction of the EBB, or of the branch.
+    pub srcloc: SourceLoc,
+}
+
+/// Insert counter increments into `func`.
+///
+/// The counters are consecutive `i64` values in the memory pointed to by `base`, which must be
+/// a global value of type `addr_type`. Returns the sites of the counters, indexed by counter
+/// number.
+///
+/// Edges leaving an EBB through its final jump are counted before the jump. Other edges are split
+/// by a new EBB, appended to the layout, which increments the counter before jumping to the
+/// original destination.
+pub fn do_counter_instrumentation(
+    func: &mut Function,
+    kind: CounterKind,
+    base: GlobalValue,
+    addr_type: Type,
+) -> Vec<CounterSite> {
+    let _tt = timing::instrument();
+    let mut sites = Vec::new();
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    for ebb in ebbs {
+        match kind {
+            CounterKind::Ebb => {
+                let mut pos = FuncCursor::new(func).at_first_insertion_point(ebb);
+                let srcloc = pos
+                    .current_inst()
+                    .map_or_else(SourceLoc::default, |inst| pos.func.srclocs[inst]);
+                pos.set_srcloc(srcloc);
+                increment_counter(&mut pos, base, addr_type, sites.len());
+                sites.push(CounterSite {
+                    ebb,
+                    branch: None,
+                    srcloc,
+                });
+            }
+            CounterKind::Edge => instrument_edges(func, ebb, base, addr_type, &mut sites),
+        }
+    }
+    sites
+}
+
+/// Count the edges leaving `ebb`.
+fn instrument_edges(
+    func: &mut Function,
+    ebb: Ebb,
+    base: GlobalValue,
+    addr_type: Type,
+    sites: &mut Vec<CounterSite>,
+) {
+    let branches: Vec<Inst> = func
+        .layout
+        .ebb_insts(ebb)
+        .filter(|&inst| func.dfg[inst].opcode().is_branch())
+        .collect();
+
+    for inst in branches {
+        let srcloc = func.srclocs[inst];
+        match func.dfg.analyze_branch(inst) {
+            BranchInfo::NotABranch => {}
+            BranchInfo::SingleDest(dest, _) => {
+                if func.dfg[inst].opcode().is_terminator() {
+                    let mut pos = FuncCursor::new(func).at_inst(inst).with_srcloc(srcloc);
+                    increment_counter(&mut pos, base, addr_type, sites.len());
+                } else {
+                    let edge = make_edge_ebb(func, dest, base, addr_type, sites.len(), srcloc);
+                    func.change_branch_destination(inst, edge);
+                }
+                sites.push(CounterSite {
+                    ebb: dest,
+                    branch: Some(inst),
+                    srcloc,
+                });
+            }
+            BranchInfo::Table(jt, default) => {
+                // Jump tables can be shared between branches, so each branch gets its own copy
+                // pointing to its edge EBBs. Entries with the same destination share a counter.
+                let mut table = func.jump_tables[jt].clone();
+                let mut edges: Vec<(Ebb, Ebb)> = Vec::new();
+                for dest in default.into_iter().chain(table.iter().cloned()) {
+                    if edges.iter().all(|&(d, _)| d != dest) {
+                        let edge = make_edge_ebb(func, dest, base, addr_type, sites.len(), srcloc);
+                        edges.push((dest, edge));
+                        sites.push(CounterSite {
+                            ebb: dest,
+                            branch: Some(inst),
+                            srcloc,
+                        });
+                    }
+                }
+                let edge_for = |dest: Ebb| edges.iter().find(|&&(d, _)| d == dest).unwrap().1;
+                for entry in table.iter_mut() {
+                    *entry = edge_for(*entry);
+                }
+                let new_jt = func.create_jump_table(table);
+                match func.dfg[inst] {
+                    InstructionData::BranchTable {
+                        ref mut destination,
+                        ref mut table,
+                        ..
+                    } => {
+                        *destination = edge_for(*destination);
+                        *table = new_jt;
+                    }
+                    InstructionData::IndirectJump { ref mut table, .. } => *table = new_jt,
+                    _ => panic!("unexpected jump table branch"),
+                }
+            }
+        }
+    }
+}
+
+/// Create an EBB that increments counter `index` and jumps to `dest`, forwarding its parameters.
+fn make_edge_ebb(
+    func: &mut Function,
+    dest: Ebb,
+    base: GlobalValue,
+    addr_type: Type,
+    index: usize,
+    srcloc: SourceLoc,
+) -> Ebb {
+    let edge = func.dfg.make_ebb();
+    func.layout.append_ebb(edge);
+    let types: Vec<Type> = func
+        .dfg
+        .ebb_params(dest)
+        .iter()
+        .map(|&param| func.dfg.value_type(param))
+        .collect();
+    let args: Vec<Value> = types
+        .into_iter()
+        .map(|ty| func.dfg.append_ebb_param(edge, ty))
+        .collect();
+
+    let mut pos = FuncCursor::new(func).at_bottom(edge).with_srcloc(srcloc);
+    increment_counter(&mut pos, base, addr_type, index);
+    pos.ins().jump(dest, &args);
+    edge
+}
+
+/// Insert an increment of counter `index` at `pos`.
+fn increment_counter(pos: &mut FuncCursor, base: GlobalValue, addr_type: Type, index: usize) {
+    let flags = MemFlags::trusted();
+    let offset = (index * 8) as i32;
+    let addr = pos.ins().global_value(addr_type, base);
+    let count = pos.ins().load(types::I64, flags, addr, offset);
+    let count = pos.ins().iadd_imm(count, 1);
+    pos.ins().store(flags, count, addr, offset);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{ExternalName, GlobalValueData, JumpTableData, Opcode};
+
+    fn counter_base(func: &mut Function) -> GlobalValue {
+        func.create_global_value(GlobalValueData::Symbol {
+            name: ExternalName::testcase("counters"),
+            offset: 0.into(),
+            colocated: false,
+        })
+    }
+
+    #[test]
+    fn ebb_counters() {
+        let mut func = Function::new();
+        let base = counter_base(&mut func);
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.set_srcloc(SourceLoc::new(7));
+            pos.ins().brnz(v0, ebb1, &[]);
+            pos.ins().return_(&[]);
+            pos.insert_ebb(ebb1);
+            pos.ins().return_(&[]);
+        }
+
+        let sites = do_counter_instrumentation(&mut func, CounterKind::Ebb, base, types::I64);
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[0].ebb, ebb0);
+        assert_eq!(sites[0].srcloc, SourceLoc::new(7));
+        assert_eq!(sites[1].ebb, ebb1);
+        assert_eq!(sites[1].branch, None);
+
+        // The increment of the second counter comes first in `ebb1`.
+        let store = func
+            .layout
+            .prev_inst(func.layout.last_inst(ebb1).unwrap())
+            .unwrap();
+        assert_eq!(func.dfg[store].opcode(), Opcode::Store);
+        assert_eq!(func.layout.ebbs().count(), 2);
+    }
+
+    #[test]
+    fn edge_counters() {
+        let mut func = Function::new();
+        let base = counter_base(&mut func);
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        func.dfg.append_ebb_param(ebb1, types::I32);
+        let mut jt_data = JumpTableData::new();
+        jt_data.push_entry(ebb2);
+        jt_data.push_entry(ebb2);
+        let jt = func.create_jump_table(jt_data);
+        let (brnz, jump, br_table);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            brnz = pos.ins().brnz(v0, ebb1, &[v0]);
+            jump = pos.ins().jump(ebb2, &[]);
+            pos.insert_ebb(ebb1);
+            br_table = pos.ins().br_table(v0, ebb2, jt);
+            pos.insert_ebb(ebb2);
+            pos.ins().return_(&[]);
+        }
+
+        let sites = do_counter_instrumentation(&mut func, CounterKind::Edge, base, types::I64);
+        let summary: Vec<_> = sites.iter().map(|s| (s.ebb, s.branch)).collect();
+        assert_eq!(
+            summary,
+            [
+                (ebb1, Some(brnz)),
+                (ebb2, Some(jump)),
+                (ebb2, Some(br_table))
+            ]
+        );
+
+        // The conditional branch and the jump table branch go through new edge EBBs.
+        assert_eq!(func.layout.ebbs().count(), 5);
+        let edge = func.dfg[brnz].branch_destination().unwrap();
+        assert_ne!(edge, ebb1);
+        assert_eq!(func.dfg.num_ebb_params(edge), 1);
+        assert_eq!(func.dfg[jump].branch_destination(), Some(ebb2));
+        match func.dfg.analyze_branch(br_table) {
+            BranchInfo::Table(new_jt, Some(default)) => {
+                assert_ne!(new_jt, jt);
+                assert_ne!(default, ebb2);
+                assert_eq!(func.jump_tables[new_jt].as_slice(), [default, default]);
+            }
+            _ => panic!("expected a jump table branch"),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Branch probability hints.
+
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
+
+/// A hint telling how likely a conditional branch is to be taken.
+///
+/// Frontends usually know which paths handle errors or other rare conditions. The hints are not
+/// required for correctness, but they are used to place the rarely executed EBBs out of the way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum BranchHint {
+    /// The branch is usually taken.
+    Likely,
+    /// The branch is rarely taken.
+    Unlikely,
+}
+
+impl BranchHint {
+    /// Get the hint for the branch with the inverted condition.
+    pub fn inverse(self) -> Self {
+        match self {
+            BranchHint::Likely => BranchHint::Unlikely,
+            BranchHint::Unlikely => BranchHint::Likely,
+        }
+    }
+}
+
+impl Display for BranchHint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            BranchHint::Likely => "likely",
+            BranchHint::Unlikely => "unlikely",
+        })
+    }
+}
+
+impl FromStr for BranchHint {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "likely" => Ok(BranchHint::Likely),
+            "unlikely" => Ok(BranchHint::Unlikely),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn display() {
+        for &hint in &[BranchHint::Likely, BranchHint::Unlikely] {
+            assert_eq!(hint.to_string().parse(), Ok(hint));
+            assert_eq!(hint.inverse().inverse(), hint);
+            assert_ne!(hint.inverse(), hint);
+        }
+        assert_eq!("probably".parse::<BranchHint>(), Err(()));
+    }
+}
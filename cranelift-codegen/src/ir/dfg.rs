@@ -7,8 +7,8 @@ use crate::ir::extfunc::ExtFuncData;
 use crate::ir::instructions::{BranchInfo, CallInfo, InstructionData};
 use crate::ir::types;
 use crate::ir::{
-    Ebb, FuncRef, Inst, SigRef, Signature, Type, Value, ValueLabelAssignments, ValueList,
-    ValueListPool,
+    Ebb, FuncAttributes, FuncRef, Inst, RawCode, RawCodeData, SigRef, Signature, Type, Value,
+    ValueLabelAssignments, ValueList, ValueListPool,
 };
 use crate::isa::TargetIsa;
 use crate::packed_option::ReservedValue;
@@ -62,9 +62,19 @@ pub struct DataFlowGraph {
     /// well as the external function references.
     pub signatures: PrimaryMap<SigRef, Signature>,
 
+    /// The pre-legalization signature for each entry in `signatures`, kept by the legalizer for
+    /// the ABI verifier when the `enable_verifier` setting is true.
+    pub old_signatures: SecondaryMap<SigRef, Option<Signature>>,
+
     /// External function references. These are functions that can be called directly.
     pub ext_funcs: PrimaryMap<FuncRef, ExtFuncData>,
 
+    /// Attributes of the external functions in `ext_funcs`.
+    pub ext_func_attributes: SecondaryMap<FuncRef, FuncAttributes>,
+
+    /// Raw machine code emitted by `raw_bytes` instructions.
+    pub raw_code: PrimaryMap<RawCode, RawCodeData>,
+
     /// Saves Value labels.
     pub values_labels: Option<HashMap<Value, ValueLabelAssignments>>,
 }
@@ -79,7 +89,10 @@ impl DataFlowGraph {
             value_lists: ValueListPool::new(),
             values: PrimaryMap::new(),
             signatures: PrimaryMap::new(),
+            old_signatures: SecondaryMap::new(),
             ext_funcs: PrimaryMap::new(),
+            ext_func_attributes: SecondaryMap::new(),
+            raw_code: PrimaryMap::new(),
             values_labels: None,
         }
     }
@@ -92,7 +105,10 @@ impl DataFlowGraph {
         self.value_lists.clear();
         self.values.clear();
         self.signatures.clear();
+        self.old_signatures.clear();
         self.ext_funcs.clear();
+        self.ext_func_attributes.clear();
+        self.raw_code.clear();
         self.values_labels = None;
     }
 
@@ -664,13 +680,15 @@ impl DataFlowGraph {
         self.results[inst].as_slice(&self.value_lists)
     }
 
-    /// Get the call signature of a direct or indirect call instruction.
+    /// Get the call signature of a direct or indirect call instruction, or the signature of the
+    /// raw code emitted by a `raw_bytes` instruction.
     /// Returns `None` if `inst` is not a call instruction.
     pub fn call_signature(&self, inst: Inst) -> Option<SigRef> {
         match self.insts[inst].analyze_call(&self.value_lists) {
             CallInfo::NotACall => None,
             CallInfo::Direct(f, _) => Some(self.ext_funcs[f].signature),
             CallInfo::Indirect(s, _) => Some(s),
+            CallInfo::Raw(code, _) => Some(self.raw_code[code].signature),
         }
     }
 
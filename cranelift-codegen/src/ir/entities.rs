@@ -159,6 +159,24 @@ impl SigRef {
     }
 }
 
+/// A reference to a sequence of raw machine code bytes.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RawCode(u32);
+entity_impl!(RawCode, "code");
+
+impl RawCode {
+    /// Create a new raw code reference from its number.
+    ///
+    /// This method is for use by the parser.
+    pub fn with_number(n: u32) -> Option<Self> {
+        if n < u32::MAX {
+            Some(RawCode(n))
+        } else {
+            None
+        }
+    }
+}
+
 /// A reference to a heap.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Heap(u32);
@@ -216,6 +234,8 @@ pub enum AnyEntity {
     FuncRef(FuncRef),
     /// A function call signature.
     SigRef(SigRef),
+    /// Raw machine code.
+    RawCode(RawCode),
     /// A heap.
     Heap(Heap),
     /// A table.
@@ -234,6 +254,7 @@ impl fmt::Display for AnyEntity {
             AnyEntity::JumpTable(r) => r.fmt(f),
             AnyEntity::FuncRef(r) => r.fmt(f),
             AnyEntity::SigRef(r) => r.fmt(f),
+            AnyEntity::RawCode(r) => r.fmt(f),
             AnyEntity::Heap(r) => r.fmt(f),
             AnyEntity::Table(r) => r.fmt(f),
         }
@@ -294,6 +315,12 @@ impl From<SigRef> for AnyEntity {
     }
 }
 
+impl From<RawCode> for AnyEntity {
+    fn from(r: RawCode) -> Self {
+        AnyEntity::RawCode(r)
+    }
+}
+
 impl From<Heap> for AnyEntity {
     fn from(r: Heap) -> Self {
         AnyEntity::Heap(r)
@@ -18,6 +18,20 @@ use std::vec::Vec;
 ///
 /// A signature can optionally include ISA-specific ABI information which specifies exactly how
 /// arguments and return values are passed.
+///
+/// Fields may be added to signatures, so struct literals should fill in the fields they don't set
+/// from `Signature::new()` or `Signature::default()`:
+///
+/// ```
+/// use cranelift_codegen::ir::{types, AbiParam, Signature};
+/// use cranelift_codegen::isa::CallConv;
+///
+/// let sig = Signature {
+///     params: vec![AbiParam::new(types::I64)],
+///     ..Signature::new(CallConv::SystemV)
+/// };
+/// assert_eq!(sig.variadic, None);
+/// ```
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Signature {
     /// The arguments passed to the function.
@@ -27,6 +41,11 @@ pub struct Signature {
 
     /// Calling convention.
     pub call_conv: CallConv,
+
+    /// The number of fixed parameters, if this is the signature of a call to a variadic function.
+    ///
+    /// The remaining parameters are the variadic arguments passed by the call.
+    pub variadic: Option<usize>,
 }
 
 impl Signature {
@@ -36,6 +55,7 @@ impl Signature {
             params: Vec::new(),
             returns: Vec::new(),
             call_conv,
+            variadic: None,
         }
     }
 
@@ -44,6 +64,7 @@ impl Signature {
         self.params.clear();
         self.returns.clear();
         self.call_conv = call_conv;
+        self.variadic = None;
     }
 
     /// Return an object that can display `self` with correct register names.
@@ -57,6 +78,13 @@ impl Signature {
     }
 }
 
+impl Default for Signature {
+    /// Create a new blank signature using the `fast` calling convention.
+    fn default() -> Self {
+        Self::new(CallConv::Fast)
+    }
+}
+
 /// Wrapper type capable of displaying a `Signature` with correct register names.
 pub struct DisplaySignature<'a>(&'a Signature, Option<&'a RegInfo>);
 
@@ -76,7 +104,20 @@ fn write_list(f: &mut fmt::Formatter, args: &[AbiParam], regs: Option<&RegInfo>)
 impl<'a> fmt::Display for DisplaySignature<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "(")?;
-        write_list(f, &self.0.params, self.1)?;
+        match self.0.variadic {
+            None => write_list(f, &self.0.params, self.1)?,
+            Some(fixed) => {
+                let (fixed, variadic) = self.0.params.split_at(fixed);
+                write_list(f, fixed, self.1)?;
+                if !fixed.is_empty() {
+                    write!(f, ", ")?;
+                }
+                write!(f, "...")?;
+                for arg in variadic {
+                    write!(f, ", {}", arg.display(self.1))?;
+                }
+            }
+        }
         write!(f, ")")?;
         if !self.0.returns.is_empty() {
             write!(f, " -> ")?;
@@ -270,23 +311,97 @@ pub enum ArgumentPurpose {
     /// This is a pointer to a stack limit. It is used to check the current stack pointer
     /// against. Can only appear once in a signature.
     StackLimit,
-}
 
-/// Text format names of the `ArgumentPurpose` variants.
-static PURPOSE_NAMES: [&str; 8] = [
-    "normal",
-    "sret",
-    "link",
-    "fp",
-    "csr",
-    "vmctx",
-    "sigid",
-    "stack_limit",
-];
+    /// The number of vector registers holding the arguments of a variadic call.
+    ///
+    /// The x86-64 System V ABI passes this in `%al`, so the callee only needs to save the vector
+    /// registers that are in use. The legalizer computes the argument value.
+    VarargCount,
+
+    /// A struct passed by value.
+    ///
+    /// The argument is a pointer to the struct. Signature legalization either splits the struct
+    /// into `StructArgumentPart` arguments, or keeps this argument as a pointer to a copy of the
+    /// struct made by the caller, depending on the ABI.
+    StructArgument(StructLayout),
+
+    /// A part of a struct passed by value.
+    ///
+    /// These only appear in legalized signatures. The consecutive parts of a struct cover all its
+    /// bytes, in order, and are passed in registers or on the stack.
+    StructArgumentPart(StructLayout),
+}
 
 impl fmt::Display for ArgumentPurpose {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(PURPOSE_NAMES[*self as usize])
+        f.write_str(match *self {
+            ArgumentPurpose::Normal => "normal",
+            ArgumentPurpose::StructReturn => "sret",
+            ArgumentPurpose::Link => "link",
+            ArgumentPurpose::FramePointer => "fp",
+            ArgumentPurpose::CalleeSaved => "csr",
+            ArgumentPurpose::VMContext => "vmctx",
+            ArgumentPurpose::SignatureId => "sigid",
+            ArgumentPurpose::StackLimit => "stack_limit",
+            ArgumentPurpose::VarargCount => "vararg_count",
+            ArgumentPurpose::StructArgument(layout) => return write!(f, "sarg({})", layout),
+            ArgumentPurpose::StructArgumentPart(layout) => {
+                return write!(f, "sarg_part({})", layout)
+            }
+        })
+    }
+}
+
+/// The layout of a struct passed by value, as far as calling conventions are concerned.
+///
+/// The layout is written as the size of the struct in bytes, followed by the classes of its
+/// eightbytes when some of them are floating point: `16, fi` is a struct whose first eightbyte
+/// contains floating point fields, and whose second eightbyte contains integer fields.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct StructLayout {
+    /// Size of the struct in bytes.
+    pub size: u32,
+    /// Bit mask of the eightbytes of the struct which only contain floating point fields.
+    ///
+    /// The x86-64 System V ABI passes these eightbytes in vector registers when the struct is
+    /// passed in registers.
+    pub float_eightbytes: u8,
+}
+
+impl StructLayout {
+    /// Create the layout of a struct of `size` bytes which only contains integer fields.
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            float_eightbytes: 0,
+        }
+    }
+
+    /// Get the number of eightbytes of the struct.
+    pub fn eightbytes(self) -> u32 {
+        (self.size + 7) / 8
+    }
+
+    /// Does the eightbyte at `index` only contain floating point fields?
+    pub fn is_float_eightbyte(self, index: u32) -> bool {
+        index < 8 && self.float_eightbytes & (1 << index) != 0
+    }
+}
+
+impl fmt::Display for StructLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.size)?;
+        if self.float_eightbytes != 0 {
+            write!(f, ", ")?;
+            for index in 0..self.eightbytes().min(8) {
+                f.write_str(if self.is_float_eightbyte(index) {
+                    "f"
+                } else {
+                    "i"
+                })?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -302,6 +417,7 @@ impl FromStr for ArgumentPurpose {
             "vmctx" => Ok(ArgumentPurpose::VMContext),
             "sigid" => Ok(ArgumentPurpose::SignatureId),
             "stack_limit" => Ok(ArgumentPurpose::StackLimit),
+            "vararg_count" => Ok(ArgumentPurpose::VarargCount),
             _ => Err(()),
         }
     }
@@ -322,19 +438,113 @@ pub struct ExtFuncData {
     pub colocated: bool,
 }
 
+impl ExtFuncData {
+    /// Return an object that can display this external function with its `attributes`, which are
+    /// kept in `DataFlowGraph::ext_func_attributes`.
+    pub fn display<'a>(&'a self, attributes: &'a FuncAttributes) -> DisplayExtFuncData<'a> {
+        DisplayExtFuncData {
+            data: self,
+            attributes,
+        }
+    }
+}
+
 impl fmt::Display for ExtFuncData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.colocated {
+        self.display(&FuncAttributes::default()).fmt(f)
+    }
+}
+
+/// A display wrapper for an `ExtFuncData` with its attributes.
+pub struct DisplayExtFuncData<'a> {
+    data: &'a ExtFuncData,
+    attributes: &'a FuncAttributes,
+}
+
+impl<'a> fmt::Display for DisplayExtFuncData<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.data.colocated {
             write!(f, "colocated ")?;
         }
-        write!(f, "{} {}", self.name, self.signature)
+        if !self.attributes.is_empty() {
+            write!(f, "{} ", self.attributes)?;
+        }
+        write!(f, "{} {}", self.data.name, self.data.signature)
+    }
+}
+
+/// Attributes of a function, or of an external function called by it.
+///
+/// The attributes are written before the function name in the textual IR:
+///
+/// ```clif
+/// function cold align(16) %f() {
+///     fn0 = noreturn %abort()
+///     ...
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FuncAttributes {
+    /// Required alignment of the function entry in bytes, which must be a power of two.
+    ///
+    /// Cranelift doesn't place functions in memory, so this is a request to the embedder, which
+    /// must align the code at least this much.
+    pub align: Option<u8>,
+
+    /// The function is rarely executed, so it can be placed away from the hot code.
+    ///
+    /// The EBBs calling a cold function are placed after the other ones, like the targets of
+    /// unlikely branches. Cranelift doesn't place functions in memory, so for the function itself,
+    /// this is a hint to the embedder.
+    pub cold: bool,
+
+    /// The function never returns to its caller.
+    ///
+    /// The instructions following a call to a `noreturn` function are unreachable, and they are
+    /// replaced by a trap. A `noreturn` function can't contain `return` instructions.
+    pub noreturn: bool,
+
+    /// The function has no prologue or epilogue.
+    ///
+    /// A `naked` function runs on its caller's stack frame, so it can't have stack slots or make
+    /// calls, and the register allocator leaves the callee-saved registers alone. This is meant
+    /// for trampolines and other entry points that manage the machine state themselves.
+    pub naked: bool,
+}
+
+impl FuncAttributes {
+    /// Are all the attributes unset?
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for FuncAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut sep = "";
+        if self.cold {
+            write!(f, "{}cold", sep)?;
+            sep = " ";
+        }
+        if self.noreturn {
+            write!(f, "{}noreturn", sep)?;
+            sep = " ";
+        }
+        if self.naked {
+            write!(f, "{}naked", sep)?;
+            sep = " ";
+        }
+        if let Some(align) = self.align {
+            write!(f, "{}align({})", sep, align)?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ir::types::{B8, F32, I32};
+    use crate::ir::types::{B8, F32, F64, I32, I64};
     use std::string::ToString;
 
     #[test]
@@ -348,24 +558,57 @@ mod tests {
         assert_eq!(t.to_string(), "i32 uext sret");
     }
 
+    #[test]
+    fn func_attributes() {
+        let mut attrs = FuncAttributes::default();
+        assert!(attrs.is_empty());
+        assert_eq!(attrs.to_string(), "");
+        attrs.noreturn = true;
+        assert_eq!(attrs.to_string(), "noreturn");
+        attrs.cold = true;
+        attrs.align = Some(32);
+        assert!(!attrs.is_empty());
+        assert_eq!(attrs.to_string(), "cold noreturn align(32)");
+        attrs.naked = true;
+        assert_eq!(attrs.to_string(), "cold noreturn naked align(32)");
+    }
+
     #[test]
     fn argument_purpose() {
         let all_purpose = [
-            ArgumentPurpose::Normal,
-            ArgumentPurpose::StructReturn,
-            ArgumentPurpose::Link,
-            ArgumentPurpose::FramePointer,
-            ArgumentPurpose::CalleeSaved,
-            ArgumentPurpose::VMContext,
-            ArgumentPurpose::SignatureId,
-            ArgumentPurpose::StackLimit,
+            (ArgumentPurpose::Normal, "normal"),
+            (ArgumentPurpose::StructReturn, "sret"),
+            (ArgumentPurpose::Link, "link"),
+            (ArgumentPurpose::FramePointer, "fp"),
+            (ArgumentPurpose::CalleeSaved, "csr"),
+            (ArgumentPurpose::VMContext, "vmctx"),
+            (ArgumentPurpose::SignatureId, "sigid"),
+            (ArgumentPurpose::StackLimit, "stack_limit"),
+            (ArgumentPurpose::VarargCount, "vararg_count"),
         ];
-        for (&e, &n) in all_purpose.iter().zip(PURPOSE_NAMES.iter()) {
+        for &(e, n) in all_purpose.iter() {
             assert_eq!(e.to_string(), n);
             assert_eq!(Ok(e), n.parse());
         }
     }
 
+    #[test]
+    fn struct_arguments() {
+        let mut layout = StructLayout::new(12);
+        assert_eq!(layout.eightbytes(), 2);
+        assert_eq!(
+            ArgumentPurpose::StructArgument(layout).to_string(),
+            "sarg(12)"
+        );
+        layout.float_eightbytes = 0b01;
+        assert!(layout.is_float_eightbyte(0));
+        assert!(!layout.is_float_eightbyte(1));
+        assert_eq!(
+            ArgumentPurpose::StructArgumentPart(layout).to_string(),
+            "sarg_part(12, fi)"
+        );
+    }
+
     #[test]
     fn call_conv() {
         for &cc in &[
@@ -406,4 +649,18 @@ mod tests {
             "(i32 [24], i32x4 [8]) -> f32, b8 baldrdash_system_v"
         );
     }
+
+    #[test]
+    fn variadic_signatures() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.variadic = Some(0);
+        assert_eq!(sig.to_string(), "(...) system_v");
+        sig.params.push(AbiParam::new(I64));
+        sig.params.push(AbiParam::new(F64));
+        assert_eq!(sig.to_string(), "(..., i64, f64) system_v");
+        sig.variadic = Some(1);
+        assert_eq!(sig.to_string(), "(i64, ..., f64) system_v");
+        sig.variadic = Some(2);
+        assert_eq!(sig.to_string(), "(i64, f64, ...) system_v");
+    }
 }
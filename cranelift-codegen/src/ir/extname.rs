@@ -4,10 +4,14 @@
 //! function. The name of an external declaration doesn't have any meaning to
 //! Cranelift, which compiles functions independently.
 
+use crate::demangle::demangle;
+use crate::fx::FxHashMap;
 use crate::ir::LibCall;
 use core::cmp;
 use core::fmt::{self, Write};
-use core::str::FromStr;
+use core::str::{self, FromStr};
+use std::string::String;
+use std::vec::Vec;
 
 const TESTCASE_NAME_LENGTH: usize = 16;
 
@@ -22,7 +26,7 @@ const TESTCASE_NAME_LENGTH: usize = 16;
 /// External names can also serve as a primitive testing and debugging tool.
 /// In particular, many `.clif` test files use function names to identify
 /// functions.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ExternalName {
     /// A name in a user-defined symbol table. Cranelift does not interpret
     /// these numbers in any way.
@@ -80,6 +84,23 @@ impl ExternalName {
     pub fn user(namespace: u32, index: u32) -> Self {
         ExternalName::User { namespace, index }
     }
+
+    /// Demangle the symbol of this name.
+    ///
+    /// The symbol of a test case name is the name itself, and the symbols of user-defined names
+    /// are looked up in `names`. Returns `None` for the symbols that aren't mangled or aren't in
+    /// `names`, and for the libcalls.
+    pub fn demangled(&self, names: &ExternalNameTable) -> Option<String> {
+        match *self {
+            ExternalName::User { .. } => names.name(self).and_then(demangle),
+            ExternalName::TestCase { length, ascii } => {
+                str::from_utf8(&ascii[..usize::from(length)])
+                    .ok()
+                    .and_then(demangle)
+            }
+            ExternalName::LibCall(_) => None,
+        }
+    }
 }
 
 impl Default for ExternalName {
@@ -116,12 +137,103 @@ impl FromStr for ExternalName {
     }
 }
 
+/// A table interning symbol names as user-defined external names.
+///
+/// Symbol strings are slow to compare and hash, so embedders that refer to the same symbols from
+/// many functions can intern them once and use the resulting `ExternalName::User` names, which are
+/// pairs of small integers. All the names of one table share the same namespace, and their
+/// indices are assigned in interning order.
+///
+/// The symbols used by a function can be kept in its `names` table, which the textual IR prints as
+/// `symbol` declarations in the function preamble, so they round-trip through the parser.
+#[derive(Clone, Debug)]
+pub struct ExternalNameTable {
+    namespace: u32,
+    names: Vec<String>,
+    indices: FxHashMap<String, u32>,
+}
+
+impl ExternalNameTable {
+    /// Create an empty table assigning names in `namespace`.
+    pub fn new(namespace: u32) -> Self {
+        Self {
+            namespace,
+            names: Vec::new(),
+            indices: FxHashMap(),
+        }
+    }
+
+    /// Clear all the interned names, keeping the namespace.
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.indices.clear();
+    }
+
+    /// Get the namespace of the names in this table.
+    pub fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    /// Get the number of interned names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Is this table empty?
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Get the external name for `symbol`, interning it if needed.
+    pub fn intern(&mut self, symbol: &str) -> ExternalName {
+        let index = match self.indices.get(symbol) {
+            Some(&index) => index,
+            None => {
+                let index = self.names.len() as u32;
+                self.names.push(symbol.into());
+                self.indices.insert(symbol.into(), index);
+                index
+            }
+        };
+        ExternalName::user(self.namespace, index)
+    }
+
+    /// Get the external name for `symbol` if it has been interned.
+    pub fn get(&self, symbol: &str) -> Option<ExternalName> {
+        self.indices
+            .get(symbol)
+            .map(|&index| ExternalName::user(self.namespace, index))
+    }
+
+    /// Get the symbol interned as `name`.
+    ///
+    /// Returns `None` if `name` doesn't come from this table.
+    pub fn name(&self, name: &ExternalName) -> Option<&str> {
+        match *name {
+            ExternalName::User { namespace, index } if namespace == self.namespace => {
+                self.names.get(index as usize).map(String::as_str)
+            }
+            _ => None,
+        }
+    }
+
+    /// Iterate over the interned names and their symbols, in interning order.
+    pub fn iter(&self) -> impl Iterator<Item = (ExternalName, &str)> {
+        let namespace = self.namespace;
+        self.names
+            .iter()
+            .enumerate()
+            .map(move |(index, symbol)| (ExternalName::user(namespace, index as u32), &**symbol))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::ExternalName;
+    use super::{ExternalName, ExternalNameTable};
     use crate::ir::LibCall;
     use core::u32;
     use std::string::ToString;
+    use std::vec::Vec;
 
     #[test]
     fn display_testcase() {
@@ -160,4 +272,48 @@ mod tests {
             "%FloorF32"
         );
     }
+
+    #[test]
+    fn interning() {
+        let mut table = ExternalNameTable::new(2);
+        assert!(table.is_empty());
+        let foo = table.intern("foo");
+        let bar = table.intern("bar");
+        assert_eq!(foo, ExternalName::user(2, 0));
+        assert_eq!(bar, ExternalName::user(2, 1));
+        assert_eq!(table.intern("foo"), foo);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get("bar"), Some(bar.clone()));
+        assert_eq!(table.get("baz"), None);
+
+        // Interned names round-trip through the text format.
+        assert_eq!(bar.to_string(), "u2:1");
+        assert_eq!(table.name(&bar), Some("bar"));
+        assert_eq!(table.name(&ExternalName::user(2, 2)), None);
+        assert_eq!(table.name(&ExternalName::user(0, 0)), None);
+        assert_eq!(table.name(&ExternalName::testcase("foo")), None);
+
+        let names: Vec<_> = table.iter().collect();
+        assert_eq!(names, [(foo, "foo"), (bar, "bar")]);
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(table.get("foo"), None);
+    }
+
+    #[test]
+    fn interning_many() {
+        let mut table = ExternalNameTable::new(0);
+        let symbols: Vec<_> = (0..100).map(|i| format!("sym{}", i)).collect();
+        for (i, symbol) in symbols.iter().enumerate() {
+            assert_eq!(table.intern(symbol), ExternalName::user(0, i as u32));
+        }
+        for (i, symbol) in symbols.iter().enumerate() {
+            assert_eq!(table.get(symbol), Some(ExternalName::user(0, i as u32)));
+            assert_eq!(
+                table.name(&ExternalName::user(0, i as u32)),
+                Some(&symbol[..])
+            );
+        }
+        assert_eq!(table.get("sym100"), None);
+    }
 }
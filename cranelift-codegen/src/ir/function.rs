@@ -6,13 +6,15 @@
 use crate::binemit::CodeOffset;
 use crate::entity::{PrimaryMap, SecondaryMap};
 use crate::ir;
-use crate::ir::{DataFlowGraph, ExternalName, Layout, Signature};
+use crate::ir::{BranchHints, EbbOffsets, InstEncodings, SourceLocs, StackSlots, ValueLocations};
+use crate::ir::{
+    DataFlowGraph, ExternalName, ExternalNameTable, FuncAttributes, Layout, Signature,
+};
 use crate::ir::{
     Ebb, ExtFuncData, FuncRef, GlobalValue, GlobalValueData, Heap, HeapData, Inst, JumpTable,
     JumpTableData, SigRef, StackSlot, StackSlotData, Table, TableData,
 };
-use crate::ir::{EbbOffsets, InstEncodings, SourceLocs, StackSlots, ValueLocations};
-use crate::ir::{JumpTableOffsets, JumpTables};
+use crate::ir::{JumpTableOffsets, JumpTables, PatchOffsets};
 use crate::isa::{CallConv, EncInfo, Encoding, Legalize, TargetIsa};
 use crate::regalloc::RegDiversions;
 use crate::value_label::ValueLabelsRanges;
@@ -34,6 +36,9 @@ pub struct Function {
     /// Signature of this function.
     pub signature: Signature,
 
+    /// Attributes of this function.
+    pub attributes: FuncAttributes,
+
     /// Stack slots allocated in this function.
     pub stack_slots: StackSlots,
 
@@ -72,11 +77,29 @@ pub struct Function {
     /// Code offsets of Jump Table headers.
     pub jt_offsets: JumpTableOffsets,
 
+    /// Code offsets of the patch sites loaded by `patchable_iconst` instructions.
+    pub patch_offsets: PatchOffsets,
+
     /// Source locations.
     ///
     /// Track the original source location for each instruction. The source locations are not
     /// interpreted by Cranelift, only preserved.
     pub srclocs: SourceLocs,
+
+    /// Probability hints for the conditional branches. Only conditional branches may have a hint.
+    pub branch_hints: BranchHints,
+
+    /// Alternate entry block for on-stack replacement.
+    ///
+    /// When set, the function is compiled to start executing at this EBB, with its parameters
+    /// loaded from a state buffer passed as an additional pointer argument.
+    pub osr_entry: Option<Ebb>,
+
+    /// Symbols of the user-defined external names used by this function.
+    ///
+    /// This table is only used for printing the function and in diagnostics. It is empty unless
+    /// the embedder or the parser fills it.
+    pub names: ExternalNameTable,
 }
 
 impl Function {
@@ -85,6 +108,7 @@ impl Function {
         Self {
             name,
             signature: sig,
+            attributes: FuncAttributes::default(),
             stack_slots: StackSlots::new(),
             global_values: PrimaryMap::new(),
             heaps: PrimaryMap::new(),
@@ -96,13 +120,21 @@ impl Function {
             locations: SecondaryMap::new(),
             offsets: SecondaryMap::new(),
             jt_offsets: SecondaryMap::new(),
+            patch_offsets: SecondaryMap::new(),
             srclocs: SecondaryMap::new(),
+            branch_hints: SecondaryMap::new(),
+            osr_entry: None,
+            names: ExternalNameTable::new(0),
         }
     }
 
     /// Clear all data structures in this function.
+    ///
+    /// The tables keep their allocated memory, so building another function of a similar size
+    /// doesn't need to allocate again.
     pub fn clear(&mut self) {
         self.signature.clear(CallConv::Fast);
+        self.attributes = FuncAttributes::default();
         self.stack_slots.clear();
         self.global_values.clear();
         self.heaps.clear();
@@ -113,7 +145,12 @@ impl Function {
         self.encodings.clear();
         self.locations.clear();
         self.offsets.clear();
+        self.jt_offsets.clear();
+        self.patch_offsets.clear();
         self.srclocs.clear();
+        self.branch_hints.clear();
+        self.osr_entry = None;
+        self.names.clear();
     }
 
     /// Create a new empty, anonymous function with a Fast calling convention.
@@ -183,6 +220,15 @@ impl Function {
             .map(|i| self.dfg.ebb_params(entry)[i])
     }
 
+    /// Get the layout of the state buffer expected by the on-stack replacement entry point.
+    ///
+    /// Returns `None` if the function has no `osr_entry`. Compiling the function replaces the
+    /// OSR entry with a regular entry block, so this must be called before compilation.
+    pub fn osr_state_layout(&self) -> Option<ir::OsrStateLayout> {
+        self.osr_entry
+            .map(|ebb| ir::OsrStateLayout::for_ebb(&self.dfg, ebb))
+    }
+
     /// Get an iterator over the instructions in `ebb`, including offsets and encoded instruction
     /// sizes.
     ///
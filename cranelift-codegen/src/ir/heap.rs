@@ -15,6 +15,9 @@ pub struct HeapData {
     pub min_size: Uimm64,
 
     /// Size in bytes of the offset-guard pages following the heap.
+    ///
+    /// The `heap_addr` legalization doesn't check the bytes of an access that land in the
+    /// offset-guard pages, since accessing them traps.
     pub offset_guard_size: Uimm64,
 
     /// Heap style, with additional style-specific info.
@@ -30,6 +33,8 @@ pub enum HeapStyle {
     /// A dynamic heap can be relocated to a different base address when it is grown.
     Dynamic {
         /// Global value providing the current bound of the heap in bytes.
+        ///
+        /// It has the heap's index type, or the pointer type when the index type is wider.
         bound_gv: GlobalValue,
     },
 
@@ -13,7 +13,7 @@ use std::vec::Vec;
 
 use crate::ir;
 use crate::ir::types;
-use crate::ir::{Ebb, FuncRef, JumpTable, SigRef, Type, Value};
+use crate::ir::{Ebb, FuncRef, JumpTable, RawCode, SigRef, Type, Value};
 use crate::isa;
 
 use crate::bitset::BitSet;
@@ -198,6 +198,9 @@ impl InstructionData {
                 table, destination, ..
             } => BranchInfo::Table(table, Some(destination)),
             InstructionData::IndirectJump { table, .. } => BranchInfo::Table(table, None),
+            // The arguments of an `invoke` go to the callee, and its landing pad has no
+            // parameters.
+            InstructionData::Invoke { destination, .. } => BranchInfo::SingleDest(destination, &[]),
             _ => {
                 debug_assert!(!self.opcode().is_branch());
                 BranchInfo::NotABranch
@@ -215,7 +218,8 @@ impl InstructionData {
             | InstructionData::Branch { destination, .. }
             | InstructionData::BranchInt { destination, .. }
             | InstructionData::BranchFloat { destination, .. }
-            | InstructionData::BranchIcmp { destination, .. } => Some(destination),
+            | InstructionData::BranchIcmp { destination, .. }
+            | InstructionData::Invoke { destination, .. } => Some(destination),
             InstructionData::BranchTable { .. } | InstructionData::IndirectJump { .. } => None,
             _ => {
                 debug_assert!(!self.opcode().is_branch());
@@ -249,6 +253,10 @@ impl InstructionData {
             | InstructionData::BranchIcmp {
                 ref mut destination,
                 ..
+            }
+            | InstructionData::Invoke {
+                ref mut destination,
+                ..
             } => Some(destination),
             InstructionData::BranchTable { .. } => None,
             _ => {
@@ -265,10 +273,16 @@ impl InstructionData {
         match *self {
             InstructionData::Call {
                 func_ref, ref args, ..
+            }
+            | InstructionData::Invoke {
+                func_ref, ref args, ..
             } => CallInfo::Direct(func_ref, args.as_slice(pool)),
             InstructionData::CallIndirect {
                 sig_ref, ref args, ..
             } => CallInfo::Indirect(sig_ref, &args.as_slice(pool)[1..]),
+            InstructionData::RawBytes {
+                raw_code, ref args, ..
+            } => CallInfo::Raw(raw_code, args.as_slice(pool)),
             _ => {
                 debug_assert!(!self.opcode().is_call());
                 CallInfo::NotACall
@@ -302,6 +316,10 @@ pub enum CallInfo<'a> {
 
     /// This is an indirect call with the specified signature. See `DataFlowGraph.signatures`.
     Indirect(SigRef, &'a [Value]),
+
+    /// This is raw machine code declared in the preamble, which uses its arguments like a call.
+    /// See `DataFlowGraph.raw_code`.
+    Raw(RawCode, &'a [Value]),
 }
 
 /// Value type constraints for a given opcode.
@@ -431,6 +431,40 @@ impl Layout {
         }
     }
 
+    /// Move `ebb` and all its instructions to the end of the layout.
+    pub fn move_ebb_to_end(&mut self, ebb: Ebb) {
+        debug_assert!(self.is_ebb_inserted(ebb), "EBB not in the layout");
+        let last = self.last_ebb.expect("EBB not in the layout");
+        if last == ebb {
+            return;
+        }
+
+        // Unlink `ebb`, which has a successor since it is not the last EBB.
+        let prev = self.ebbs[ebb].prev;
+        let next = self.ebbs[ebb].next;
+        match prev.expand() {
+            None => self.first_ebb = next.expand(),
+            Some(p) => self.ebbs[p].next = next,
+        }
+        self.ebbs[next.unwrap()].prev = prev;
+
+        // Link it after `last`.
+        self.ebbs[last].next = ebb.into();
+        self.ebbs[ebb].prev = last.into();
+        self.ebbs[ebb].next = None.into();
+        self.last_ebb = Some(ebb);
+
+        // Nothing follows `ebb`, so it can be numbered with major strides.
+        let mut seq = self.last_ebb_seq(last) + MAJOR_STRIDE;
+        self.ebbs[ebb].seq = seq;
+        let mut next_inst = self.ebbs[ebb].first_inst.expand();
+        while let Some(inst) = next_inst {
+            seq += MAJOR_STRIDE;
+            self.insts[inst].seq = seq;
+            next_inst = self.insts[inst].next.expand();
+        }
+    }
+
     /// Return an iterator over all EBBs in layout order.
     pub fn ebbs(&self) -> Ebbs {
         Ebbs {
@@ -1091,6 +1125,33 @@ mod tests {
         assert_eq!(v1, [i2, i3]);
     }
 
+    #[test]
+    fn move_ebb_to_end() {
+        let mut layout = Layout::new();
+
+        let e0 = Ebb::new(0);
+        let e1 = Ebb::new(1);
+        let e2 = Ebb::new(2);
+        let i0 = Inst::new(0);
+        let i1 = Inst::new(1);
+        let i2 = Inst::new(2);
+
+        layout.append_ebb(e0);
+        layout.append_ebb(e1);
+        layout.append_ebb(e2);
+        layout.append_inst(i0, e0);
+        layout.append_inst(i1, e1);
+        layout.append_inst(i2, e1);
+
+        layout.move_ebb_to_end(e1);
+        verify(&mut layout, &[(e0, &[i0]), (e2, &[]), (e1, &[i1, i2])]);
+        layout.move_ebb_to_end(e1);
+        verify(&mut layout, &[(e0, &[i0]), (e2, &[]), (e1, &[i1, i2])]);
+        layout.move_ebb_to_end(e0);
+        verify(&mut layout, &[(e2, &[]), (e1, &[i1, i2]), (e0, &[i0])]);
+        assert_eq!(layout.entry_block(), Some(e2));
+    }
+
     #[test]
     fn split_ebb() {
         let mut layout = Layout::new();
@@ -1,5 +1,6 @@
 //! Representation of Cranelift IR functions.
 
+mod branchhint;
 mod builder;
 pub mod condcodes;
 pub mod dfg;
@@ -15,7 +16,9 @@ pub mod jumptable;
 pub mod layout;
 mod libcall;
 mod memflags;
+mod osr;
 mod progpoint;
+mod rawcode;
 mod sourceloc;
 pub mod stackslot;
 mod table;
@@ -26,15 +29,17 @@ mod valueloc;
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
 
+pub use crate::ir::branchhint::BranchHint;
 pub use crate::ir::builder::{InsertBuilder, InstBuilder, InstBuilderBase, InstInserterBase};
 pub use crate::ir::dfg::{DataFlowGraph, ValueDef};
 pub use crate::ir::entities::{
-    Ebb, FuncRef, GlobalValue, Heap, Inst, JumpTable, SigRef, StackSlot, Table, Value,
+    Ebb, FuncRef, GlobalValue, Heap, Inst, JumpTable, RawCode, SigRef, StackSlot, Table, Value,
 };
 pub use crate::ir::extfunc::{
-    AbiParam, ArgumentExtension, ArgumentPurpose, ExtFuncData, Signature,
+    AbiParam, ArgumentExtension, ArgumentPurpose, ExtFuncData, FuncAttributes, Signature,
+    StructLayout,
 };
-pub use crate::ir::extname::ExternalName;
+pub use crate::ir::extname::{ExternalName, ExternalNameTable};
 pub use crate::ir::function::{DisplayFunctionAnnotations, Function};
 pub use crate::ir::globalvalue::GlobalValueData;
 pub use crate::ir::heap::{HeapData, HeapStyle};
@@ -45,10 +50,12 @@ pub use crate::ir::jumptable::JumpTableData;
 pub use crate::ir::layout::Layout;
 pub use crate::ir::libcall::{get_libcall_funcref, get_probestack_funcref, LibCall};
 pub use crate::ir::memflags::MemFlags;
+pub use crate::ir::osr::{is_valid_osr_state_type, OsrStateLayout};
 pub use crate::ir::progpoint::{ExpandedProgramPoint, ProgramOrder, ProgramPoint};
+pub use crate::ir::rawcode::{DisplayRawCodeData, RawCodeData};
 pub use crate::ir::sourceloc::SourceLoc;
-pub use crate::ir::stackslot::{StackSlotData, StackSlotKind, StackSlots};
-pub use crate::ir::table::TableData;
+pub use crate::ir::stackslot::{StackSlotData, StackSlotKind, StackSlotLifetime, StackSlots};
+pub use crate::ir::table::{TableData, TableStyle};
 pub use crate::ir::trapcode::TrapCode;
 pub use crate::ir::types::Type;
 pub use crate::ir::valueloc::{ArgumentLoc, ValueLoc};
@@ -72,9 +79,15 @@ pub type EbbOffsets = SecondaryMap<Ebb, binemit::CodeOffset>;
 /// Code offsets for Jump Tables.
 pub type JumpTableOffsets = SecondaryMap<JumpTable, binemit::CodeOffset>;
 
+/// Code offsets of the patch sites loaded by `patchable_iconst` instructions.
+pub type PatchOffsets = SecondaryMap<Inst, binemit::CodeOffset>;
+
 /// Source locations for instructions.
 pub type SourceLocs = SecondaryMap<Inst, SourceLoc>;
 
+/// Probability hints for conditional branches.
+pub type BranchHints = SecondaryMap<Inst, Option<BranchHint>>;
+
 /// Marked with a label value.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
@@ -0,0 +1,74 @@
+//! On-stack replacement state.
+//!
+//! A tiering runtime can transfer the execution of a hot interpreter loop into optimized code by
+//! compiling the function with an alternate entry block, declared in the preamble:
+//!
+//! ```clif
+//! function %f(i64 vmctx) -> i32 system_v {
+//!     osr_entry ebb3
+//!     ...
+//! }
+//! ```
+//!
+//! The compiled function takes an additional pointer argument to a buffer holding the live state
+//! of the interpreter, and it starts executing at the OSR entry block with the parameters loaded
+//! from the buffer. The `OsrStateLayout` describes where each parameter is expected.
+
+use crate::ir::{DataFlowGraph, Ebb, Type};
+use std::vec::Vec;
+
+/// The layout of the state buffer passed to an on-stack replacement entry point.
+///
+/// The parameters of the OSR entry block are stored in order, each one aligned to its own size.
+/// The buffer itself must be aligned to the largest parameter size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OsrStateLayout {
+    /// Byte offset of each entry block parameter in the buffer.
+    pub offsets: Vec<u32>,
+
+    /// Total size of the buffer in bytes.
+    pub size: u32,
+}
+
+impl OsrStateLayout {
+    /// Compute the layout of a buffer holding values of the given types.
+    pub fn new<I: IntoIterator<Item = Type>>(types: I) -> Self {
+        let mut offsets = Vec::new();
+        let mut size = 0;
+        for ty in types {
+            let bytes = ty.bytes().max(1);
+            size = (size + bytes - 1) & !(bytes - 1);
+            offsets.push(size);
+            size += bytes;
+        }
+        Self { offsets, size }
+    }
+
+    /// Compute the layout of the state for the parameters of `ebb`.
+    pub fn for_ebb(dfg: &DataFlowGraph, ebb: Ebb) -> Self {
+        Self::new(dfg.ebb_params(ebb).iter().map(|&v| dfg.value_type(v)))
+    }
+}
+
+/// Can a value of type `ty` be part of the state passed to an OSR entry point?
+pub fn is_valid_osr_state_type(ty: Type) -> bool {
+    (ty.is_int() || ty.is_float() || ty.is_ref() || ty.is_vector()) && !ty.lane_type().is_bool()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::types;
+    use std::vec;
+
+    #[test]
+    fn layout() {
+        let layout = OsrStateLayout::new(vec![types::I8, types::I64, types::F32, types::I32X4]);
+        assert_eq!(layout.offsets, [0, 8, 16, 32]);
+        assert_eq!(layout.size, 48);
+
+        let empty = OsrStateLayout::new(vec![]);
+        assert!(empty.offsets.is_empty());
+        assert_eq!(empty.size, 0);
+    }
+}
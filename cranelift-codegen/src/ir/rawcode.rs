@@ -0,0 +1,98 @@
+//! Raw machine code.
+//!
+//! Embedders can splice literal machine code into a function with the `raw_bytes` instruction,
+//! for example CPU-specific fences or patch sites that Cranelift has no instructions for. The
+//! bytes are declared in the function preamble along with the registers they use:
+//!
+//! ```clif
+//! function %f(i64) -> i64 system_v {
+//!     sig0 = (i64 [%rdi]) -> i64 [%rax] system_v
+//!     code0 = raw_code [0x48, 0x89, 0xf8], sig0, clobbers [%rcx]
+//!     ...
+//! }
+//! ```
+
+use crate::ir::SigRef;
+use crate::isa::{RegInfo, RegUnit};
+use core::fmt;
+use std::vec::Vec;
+
+/// A sequence of machine code bytes emitted verbatim by `raw_bytes` instructions.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RawCodeData {
+    /// The machine code, which is copied to the code buffer without any relocations.
+    pub bytes: Vec<u8>,
+
+    /// The signature describing the operands of the code.
+    ///
+    /// The parameters and return values of the signature must be pinned to registers, which hold
+    /// the arguments and the results of the `raw_bytes` instructions.
+    pub signature: SigRef,
+
+    /// Registers modified by the code, besides the ones holding the results.
+    ///
+    /// Values that are live across a `raw_bytes` instruction are kept out of these registers.
+    pub clobbers: Vec<RegUnit>,
+}
+
+impl RawCodeData {
+    /// Return an object that can display this raw code with correct register names.
+    pub fn display<'a, R: Into<Option<&'a RegInfo>>>(&'a self, regs: R) -> DisplayRawCodeData<'a> {
+        DisplayRawCodeData(self, regs.into())
+    }
+}
+
+/// Wrapper type capable of displaying a `RawCodeData` with correct register names.
+pub struct DisplayRawCodeData<'a>(&'a RawCodeData, Option<&'a RegInfo>);
+
+impl<'a> fmt::Display for DisplayRawCodeData<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "raw_code [")?;
+        for (i, byte) in self.0.bytes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:#04x}", byte)?;
+        }
+        write!(f, "], {}", self.0.signature)?;
+        if !self.0.clobbers.is_empty() {
+            write!(f, ", clobbers [")?;
+            for (i, &reg) in self.0.clobbers.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                match self.1 {
+                    Some(regs) => write!(f, "{}", regs.display_regunit(reg))?,
+                    None => write!(f, "%{}", reg)?,
+                }
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+    use std::vec;
+
+    #[test]
+    fn display() {
+        let mut code = RawCodeData {
+            bytes: vec![0x0f, 0xae, 0xf0],
+            signature: SigRef::with_number(1).unwrap(),
+            clobbers: vec![],
+        };
+        assert_eq!(
+            code.display(None).to_string(),
+            "raw_code [0x0f, 0xae, 0xf0], sig1"
+        );
+        code.clobbers.push(3);
+        assert_eq!(
+            code.display(None).to_string(),
+            "raw_code [0x0f, 0xae, 0xf0], sig1, clobbers [%3]"
+        );
+    }
+}
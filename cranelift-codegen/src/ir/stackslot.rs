@@ -100,6 +100,33 @@ impl fmt::Display for StackSlotKind {
     }
 }
 
+/// The lifetime of an explicit stack slot.
+///
+/// A lifetime is an interval of abstract positions, typically the boundaries of the lexical scopes
+/// of a source program numbered in order. The contents of a stack slot are only accessed while it
+/// is alive, so stack slots with disjoint lifetimes can share the same memory in the stack frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct StackSlotLifetime {
+    /// Position where the stack slot becomes alive.
+    pub begin: u32,
+    /// Position where the stack slot dies, inclusive.
+    pub end: u32,
+}
+
+impl StackSlotLifetime {
+    /// Create a lifetime from `begin` to `end`, inclusive.
+    pub fn new(begin: u32, end: u32) -> Self {
+        debug_assert!(begin <= end);
+        Self { begin, end }
+    }
+
+    /// Are the two stack slots with these lifetimes ever alive at the same time?
+    pub fn overlaps(self, other: Self) -> bool {
+        self.begin <= other.end && other.begin <= self.end
+    }
+}
+
 /// Contents of a stack slot.
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
@@ -118,6 +145,9 @@ pub struct StackSlotData {
     /// For `OutgoingArg` stack slots, the offset is relative to the current function's stack
     /// pointer immediately before the call.
     pub offset: Option<StackOffset>,
+
+    /// Lifetime of an `ExplicitSlot` stack slot, or `None` if it is alive in the whole function.
+    pub lifetime: Option<StackSlotLifetime>,
 }
 
 impl StackSlotData {
@@ -127,6 +157,7 @@ impl StackSlotData {
             kind,
             size,
             offset: None,
+            lifetime: None,
         }
     }
 
@@ -147,6 +178,9 @@ impl fmt::Display for StackSlotData {
         if let Some(offset) = self.offset {
             write!(f, ", offset {}", offset)?;
         }
+        if let Some(lifetime) = self.lifetime {
+            write!(f, ", lifetime {} {}", lifetime.begin, lifetime.end)?;
+        }
         Ok(())
     }
 }
@@ -14,8 +14,8 @@ pub struct TableData {
     /// bounds checking.
     pub min_size: Uimm64,
 
-    /// Global value giving the current bound of the table, in elements.
-    pub bound_gv: GlobalValue,
+    /// Table style, with additional style-specific info.
+    pub style: TableStyle,
 
     /// The size of a table element, in bytes.
     pub element_size: Uimm64,
@@ -24,13 +24,38 @@ pub struct TableData {
     pub index_type: Type,
 }
 
+/// Style of table including style-specific information.
+#[derive(Clone)]
+pub enum TableStyle {
+    /// A dynamic table can be grown, and its bound is only known at runtime.
+    Dynamic {
+        /// Global value giving the current bound of the table, in elements.
+        bound_gv: GlobalValue,
+    },
+
+    /// A static table has a fixed number of elements.
+    Static {
+        /// Table bound in elements.
+        bound: Uimm64,
+    },
+}
+
 impl fmt::Display for TableData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str("dynamic")?;
+        f.write_str(match self.style {
+            TableStyle::Dynamic { .. } => "dynamic",
+            TableStyle::Static { .. } => "static",
+        })?;
+
+        write!(f, " {}, min {}", self.base_gv, self.min_size)?;
+        match self.style {
+            TableStyle::Dynamic { bound_gv } => write!(f, ", bound {}", bound_gv)?,
+            TableStyle::Static { bound } => write!(f, ", bound {}", bound)?,
+        }
         write!(
             f,
-            " {}, min {}, bound {}, element_size {}, index_type {}",
-            self.base_gv, self.min_size, self.bound_gv, self.element_size, self.index_type
+            ", element_size {}, index_type {}",
+            self.element_size, self.index_type
         )
     }
 }
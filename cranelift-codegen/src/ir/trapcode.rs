@@ -49,6 +49,10 @@ pub enum TrapCode {
     /// This trap is resumable.
     Interrupt,
 
+    /// A function was about to return to an address that doesn't match the one saved on the
+    /// shadow stack.
+    ReturnAddressMismatch,
+
     /// A user-defined trap code.
     User(u16),
 }
@@ -68,6 +72,7 @@ impl Display for TrapCode {
             BadConversionToInteger => "bad_toint",
             UnreachableCodeReached => "unreachable",
             Interrupt => "interrupt",
+            ReturnAddressMismatch => "bad_ret",
             User(x) => return write!(f, "user{}", x),
         };
         f.write_str(identifier)
@@ -91,6 +96,7 @@ impl FromStr for TrapCode {
             "bad_toint" => Ok(BadConversionToInteger),
             "unreachable" => Ok(UnreachableCodeReached),
             "interrupt" => Ok(Interrupt),
+            "bad_ret" => Ok(ReturnAddressMismatch),
             _ if s.starts_with("user") => s[4..].parse().map(User).map_err(|_| ()),
             _ => Err(()),
         }
@@ -103,7 +109,7 @@ mod tests {
     use std::string::ToString;
 
     // Everything but user-defined codes.
-    const CODES: [TrapCode; 11] = [
+    const CODES: [TrapCode; 12] = [
         TrapCode::StackOverflow,
         TrapCode::HeapOutOfBounds,
         TrapCode::TableOutOfBounds,
@@ -115,6 +121,7 @@ mod tests {
         TrapCode::BadConversionToInteger,
         TrapCode::UnreachableCodeReached,
         TrapCode::Interrupt,
+        TrapCode::ReturnAddressMismatch,
     ];
 
     #[test]
@@ -2,7 +2,7 @@
 //! This is from the RISC-V target and will need to be updated for ARM32.
 
 use super::registers::{D, GPR, Q, S};
-use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
+use crate::abi::{legalize_params, ArgAction, ArgAssigner, ValueConversion};
 use crate::ir::{self, AbiParam, ArgumentExtension, ArgumentLoc, Type};
 use crate::isa::RegClass;
 use crate::regalloc::RegisterSet;
@@ -82,7 +82,7 @@ pub fn legalize_signature(sig: &mut ir::Signature, triple: &Triple, _current: bo
     let bits = triple.pointer_width().unwrap().bits();
 
     let mut args = Args::new(bits);
-    legalize_args(&mut sig.params, &mut args);
+    legalize_params(sig, &mut args);
 }
 
 /// Get register class for a type appearing in a legalized signature.
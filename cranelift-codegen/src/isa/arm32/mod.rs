@@ -9,14 +9,15 @@ pub mod settings;
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
 use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::{emit_function, GrowableCodeSink, MemoryCodeSink};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{CustomCallConv, EncInfo, RegClass, RegInfo, TargetIsa};
 use crate::regalloc;
 use core::fmt;
 use std::boxed::Box;
+use std::vec::Vec;
 use target_lexicon::{Architecture, Triple};
 
 #[allow(dead_code)]
@@ -32,6 +33,7 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        call_convs: Vec::new(),
         constructor: isa_constructor,
     }
 }
@@ -40,6 +42,7 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    _call_convs: Vec<CustomCallConv>,
 ) -> Box<dyn TargetIsa> {
     let level1 = match triple.architecture {
         Architecture::Thumbv6m | Architecture::Thumbv7em | Architecture::Thumbv7m => {
@@ -73,6 +76,10 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn isa_flags(&self) -> Vec<shared_settings::Value> {
+        self.isa_flags.iter().collect()
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -127,6 +134,10 @@ impl TargetIsa for Isa {
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
         emit_function(func, binemit::emit_inst, sink, self)
     }
+
+    fn emit_function_to_growable(&self, func: &ir::Function, sink: &mut GrowableCodeSink) {
+        emit_function(func, binemit::emit_inst, sink, self)
+    }
 }
 
 impl fmt::Display for Isa {
@@ -9,14 +9,15 @@ pub mod settings;
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
 use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::{emit_function, GrowableCodeSink, MemoryCodeSink};
 use crate::ir;
 use crate::isa::enc_tables::{lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{CustomCallConv, EncInfo, RegClass, RegInfo, TargetIsa};
 use crate::regalloc;
 use core::fmt;
 use std::boxed::Box;
+use std::vec::Vec;
 use target_lexicon::Triple;
 
 #[allow(dead_code)]
@@ -31,6 +32,7 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        call_convs: Vec::new(),
         constructor: isa_constructor,
     }
 }
@@ -39,6 +41,7 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    _call_convs: Vec<CustomCallConv>,
 ) -> Box<dyn TargetIsa> {
     Box::new(Isa {
         triple,
@@ -60,6 +63,10 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn isa_flags(&self) -> Vec<shared_settings::Value> {
+        self.isa_flags.iter().collect()
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -114,6 +121,10 @@ impl TargetIsa for Isa {
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
         emit_function(func, binemit::emit_inst, sink, self)
     }
+
+    fn emit_function_to_growable(&self, func: &ir::Function, sink: &mut GrowableCodeSink) {
+        emit_function(func, binemit::emit_inst, sink, self)
+    }
 }
 
 impl fmt::Display for Isa {
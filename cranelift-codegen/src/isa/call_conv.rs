@@ -9,7 +9,7 @@ use target_lexicon::{CallingConvention, Triple};
 pub enum CallConv {
     /// Best performance, not ABI-stable
     Fast,
-    /// Smallest caller code size, not ABI-stable
+    /// Smallest caller code size, not ABI-stable. The callee preserves most registers.
     Cold,
     /// System V-style convention used on many platforms
     SystemV,
@@ -21,6 +21,28 @@ pub enum CallConv {
     BaldrdashWindows,
     /// Specialized convention for the probestack function
     Probestack,
+    /// User-defined convention registered with `isa::Builder::register_call_conv`
+    Custom(u8),
+}
+
+/// Description of a user-defined calling convention.
+///
+/// Registers are named like in the textual IR, without the `%` prefix. The arguments and return
+/// values which don't fit in the listed registers are passed on the stack.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CustomCallConv {
+    /// Registers used for the integer arguments, in order.
+    pub int_args: &'static [&'static str],
+    /// Registers used for the floating point arguments, in order.
+    pub float_args: &'static [&'static str],
+    /// Registers used for the integer return values, in order.
+    pub int_returns: &'static [&'static str],
+    /// Registers used for the floating point return values, in order.
+    pub float_returns: &'static [&'static str],
+    /// Registers preserved by the callee, besides the stack and frame pointers.
+    pub callee_saved: &'static [&'static str],
+    /// Alignment of the stack pointer at calls, in bytes.
+    pub stack_align: u32,
 }
 
 impl CallConv {
@@ -75,6 +97,7 @@ impl fmt::Display for CallConv {
             CallConv::BaldrdashSystemV => "baldrdash_system_v",
             CallConv::BaldrdashWindows => "baldrdash_windows",
             CallConv::Probestack => "probestack",
+            CallConv::Custom(index) => return write!(f, "custom{}", index),
         })
     }
 }
@@ -90,7 +113,30 @@ impl str::FromStr for CallConv {
             "baldrdash_system_v" => Ok(CallConv::BaldrdashSystemV),
             "baldrdash_windows" => Ok(CallConv::BaldrdashWindows),
             "probestack" => Ok(CallConv::Probestack),
+            _ if s.starts_with("custom") => {
+                let index = &s["custom".len()..];
+                if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(());
+                }
+                index.parse().map(CallConv::Custom).map_err(|_| ())
+            }
             _ => Err(()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::CallConv;
+    use core::str::FromStr;
+    use std::string::ToString;
+
+    #[test]
+    fn custom() {
+        assert_eq!(CallConv::Custom(3).to_string(), "custom3");
+        assert_eq!(CallConv::from_str("custom3"), Ok(CallConv::Custom(3)));
+        assert_eq!(CallConv::from_str("custom"), Err(()));
+        assert_eq!(CallConv::from_str("custom+3"), Err(()));
+        assert_eq!(CallConv::from_str("custom256"), Err(()));
+    }
+}
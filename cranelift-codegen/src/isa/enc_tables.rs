@@ -42,6 +42,9 @@ pub type LegalizeCode = u8;
 /// Empty entries are encoded with a `!0` value for `log2len` which will always be out of range.
 /// Entries that have a `legalize` value but no level 2 table have an `offset` field that is out of
 /// bounds.
+///
+/// With the `compact-tables` feature, the level 2 tables are arrays sorted by opcode instead of
+/// hash tables, so they don't need any empty entries, and `log2len` holds their length instead.
 pub struct Level1Entry<OffT: Into<u32> + Copy> {
     pub ty: Type,
     pub log2len: u8,
@@ -53,7 +56,11 @@ impl<OffT: Into<u32> + Copy> Level1Entry<OffT> {
     /// Get the level 2 table range indicated by this entry.
     fn range(&self) -> Range<usize> {
         let b = self.offset.into() as usize;
-        b..b + (1 << self.log2len)
+        if cfg!(feature = "compact-tables") {
+            b..b + self.log2len as usize
+        } else {
+            b..b + (1 << self.log2len)
+        }
     }
 }
 
@@ -97,6 +104,25 @@ impl<OffT: Into<u32> + Copy> Table<Opcode> for [Level2Entry<OffT>] {
     }
 }
 
+/// Find the entry for `opcode` in the level 2 table `l2tab`.
+fn find_level2<OffT: Into<u32> + Copy>(
+    l2tab: &[Level2Entry<OffT>],
+    opcode: Opcode,
+) -> Option<&Level2Entry<OffT>> {
+    if cfg!(feature = "compact-tables") {
+        l2tab
+            .binary_search_by_key(&(opcode as usize), |l2ent| {
+                l2ent.opcode.map_or(0, |op| op as usize)
+            })
+            .ok()
+            .map(|l2idx| &l2tab[l2idx])
+    } else {
+        probe(l2tab, opcode, opcode as usize)
+            .ok()
+            .map(|l2idx| &l2tab[l2idx])
+    }
+}
+
 /// Two-level hash table lookup and iterator construction.
 ///
 /// Given the controlling type variable and instruction opcode, find the corresponding encoding
@@ -131,9 +157,9 @@ where
             let offset = match level2_table.get(l1ent.range()) {
                 Some(l2tab) => {
                     let opcode = inst.opcode();
-                    match probe(l2tab, opcode, opcode as usize) {
-                        Ok(l2idx) => l2tab[l2idx].offset.into() as usize,
-                        Err(_) => !0,
+                    match find_level2(l2tab, opcode) {
+                        Some(l2ent) => l2ent.offset.into() as usize,
+                        None => !0,
                     }
                 }
                 // The l1ent range is invalid. This means that we just have a customized
@@ -46,7 +46,7 @@
 //! The configured target ISA trait object is a `Box<TargetIsa>` which can be used for multiple
 //! concurrent function compilations.
 
-pub use crate::isa::call_conv::CallConv;
+pub use crate::isa::call_conv::{CallConv, CustomCallConv};
 pub use crate::isa::constraints::{
     BranchRange, ConstraintKind, OperandConstraint, RecipeConstraints,
 };
@@ -59,13 +59,14 @@ use crate::flowgraph;
 use crate::ir;
 use crate::isa::enc_tables::Encodings;
 use crate::regalloc;
-use crate::result::CodegenResult;
+use crate::result::{CodegenError, CodegenResult};
 use crate::settings;
 use crate::settings::SetResult;
 use crate::timing;
 use core::fmt;
 use failure_derive::Fail;
 use std::boxed::Box;
+use std::vec::Vec;
 use target_lexicon::{Architecture, PointerWidth, Triple};
 
 #[cfg(feature = "riscv")]
@@ -87,6 +88,10 @@ mod encoding;
 pub mod registers;
 mod stack;
 
+/// Function computing the number of cycles between the start of an instruction and the
+/// availability of its results.
+pub type LatencyFn = fn(&ir::Function, ir::Inst) -> u32;
+
 /// Returns a builder that can create a corresponding `TargetIsa`
 /// or `Err(LookupError::Unsupported)` if not enabled.
 macro_rules! isa_builder {
@@ -141,14 +146,55 @@ pub enum LookupError {
 pub struct Builder {
     triple: Triple,
     setup: settings::Builder,
-    constructor: fn(Triple, settings::Flags, settings::Builder) -> Box<dyn TargetIsa>,
+    call_convs: Vec<CustomCallConv>,
+    constructor:
+        fn(Triple, settings::Flags, settings::Builder, Vec<CustomCallConv>) -> Box<dyn TargetIsa>,
 }
 
 impl Builder {
     /// Combine the ISA-specific settings with the provided ISA-independent settings and allocate a
     /// fully configured `TargetIsa` trait object.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a registered calling convention uses registers which don't exist in the ISA or
+    /// can't be used the way they are listed.
     pub fn finish(self, shared_flags: settings::Flags) -> Box<dyn TargetIsa> {
-        (self.constructor)(self.triple, shared_flags, self.setup)
+        (self.constructor)(self.triple, shared_flags, self.setup, self.call_convs)
+    }
+
+    /// Register a user-defined calling convention, and return the `CallConv` to use in the
+    /// signatures following it.
+    ///
+    /// Custom calling conventions are currently only implemented by the x86 ISA. The other ISAs
+    /// refuse to compile functions using them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stack alignment is not a power of two, or if too many calling conventions
+    /// are registered.
+    pub fn register_call_conv(&mut self, call_conv: CustomCallConv) -> CallConv {
+        assert!(
+            call_conv.stack_align.is_power_of_two(),
+            "stack alignment must be a power of two"
+        );
+        let index = self.call_convs.len();
+        assert!(
+            index <= usize::from(u8::MAX),
+            "too many calling conventions"
+        );
+        self.call_convs.push(call_conv);
+        CallConv::Custom(index as u8)
+    }
+
+    /// Get the name of the ISA-specific settings group.
+    pub fn name(&self) -> &'static str {
+        self.setup.name()
+    }
+
+    /// Iterate over the ISA-specific settings and presets.
+    pub fn iter(&self) -> impl Iterator<Item = settings::Setting> {
+        self.setup.iter()
     }
 }
 
@@ -209,6 +255,13 @@ pub trait TargetIsa: fmt::Display + Sync {
     /// Get the ISA-independent flags that were used to make this trait object.
     fn flags(&self) -> &settings::Flags;
 
+    /// Get the values of the ISA-specific flags that were used to make this trait object.
+    ///
+    /// ISAs without specific settings don't have to implement this.
+    fn isa_flags(&self) -> Vec<settings::Value> {
+        Vec::new()
+    }
+
     /// Get the default calling convention of this target.
     fn default_call_conv(&self) -> CallConv {
         CallConv::triple_default(self.triple())
@@ -317,6 +370,25 @@ pub trait TargetIsa: fmt::Display + Sync {
     /// allocation.
     fn legalize_signature(&self, sig: &mut ir::Signature, current: bool);
 
+    /// Check that this ISA supports the calling conventions used by `func` with its settings.
+    ///
+    /// This is called before the signatures of `func` are legalized, so ISAs can reject the
+    /// conventions they can't generate code for instead of panicking later. The default
+    /// implementation rejects the custom calling conventions.
+    fn check_call_convs(&self, func: &ir::Function) -> CodegenResult<()> {
+        let sigs = Some(&func.signature)
+            .into_iter()
+            .chain(func.dfg.signatures.values());
+        for sig in sigs {
+            if let CallConv::Custom(_) = sig.call_conv {
+                return Err(CodegenError::Unsupported(
+                    "custom calling conventions on this ISA",
+                ));
+            }
+        }
+        Ok(())
+    }
+
     /// Get the register class that should be used to represent an ABI argument or return value of
     /// type `ty`. This should be the top-level register class that contains the argument
     /// registers.
@@ -331,19 +403,44 @@ pub trait TargetIsa: fmt::Display + Sync {
     /// registers.
     fn allocatable_registers(&self, func: &ir::Function) -> regalloc::RegisterSet;
 
+    /// Get the set of allocatable registers that are preserved by calls from `func` to functions
+    /// using the `call_conv` calling convention.
+    ///
+    /// The register allocator keeps the values that are live across such calls in the preserved
+    /// registers instead of spilling them. Return `None` if the calls may clobber all the
+    /// allocatable registers, which is what the default implementation does.
+    fn call_preserved_registers(
+        &self,
+        _func: &ir::Function,
+        _call_conv: CallConv,
+    ) -> Option<regalloc::RegisterSet> {
+        None
+    }
+
+    /// Get the latency model used to schedule the instructions after register allocation.
+    ///
+    /// Return `None` if the ISA has no latency model, which is what the default implementation
+    /// does. The instructions are then left in their order.
+    fn latency_model(&self) -> Option<LatencyFn> {
+        None
+    }
+
     /// Compute the stack layout and insert prologue and epilogue code into `func`.
     ///
     /// Return an error if the stack frame is too large.
     fn prologue_epilogue(&self, func: &mut ir::Function) -> CodegenResult<()> {
         let _tt = timing::prologue_epilogue();
+        if self.flags().enable_shadow_stack() {
+            return Err(CodegenError::Unsupported("shadow stack"));
+        }
         // This default implementation is unlikely to be good enough.
         use crate::ir::stackslot::{StackOffset, StackSize};
         use crate::stack_layout::layout_stack;
 
         let word_size = StackSize::from(self.pointer_bytes());
 
-        // Account for the SpiderMonkey standard prologue pushes.
-        if func.signature.call_conv.extends_baldrdash() {
+        // Account for the SpiderMonkey standard prologue pushes, unless there is no prologue.
+        if func.signature.call_conv.extends_baldrdash() && !func.attributes.naked {
             let bytes = StackSize::from(self.flags().baldrdash_prologue_words()) * word_size;
             let mut ss = ir::StackSlotData::new(ir::StackSlotKind::IncomingArg, bytes);
             ss.offset = Some(-(bytes as StackOffset));
@@ -372,4 +469,13 @@ pub trait TargetIsa: fmt::Display + Sync {
 
     /// Emit a whole function into memory.
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut binemit::MemoryCodeSink);
+
+    /// Emit a whole function into growable memory.
+    ///
+    /// The default implementation grows the memory to the size of the function and emits it with
+    /// `emit_function_to_memory`. ISAs can override it to avoid computing the size first.
+    fn emit_function_to_growable(&self, func: &ir::Function, sink: &mut binemit::GrowableCodeSink) {
+        let size = binemit::function_size(func, &self.encoding_info());
+        sink.emit_with_memory_sink(size, |sink| self.emit_function_to_memory(func, sink))
+    }
 }
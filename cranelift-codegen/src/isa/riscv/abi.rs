@@ -7,7 +7,7 @@
 
 use super::registers::{FPR, GPR};
 use super::settings;
-use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
+use crate::abi::{legalize_args, legalize_params, ArgAction, ArgAssigner, ValueConversion};
 use crate::ir::{self, AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, Type};
 use crate::isa::RegClass;
 use crate::regalloc::RegisterSet;
@@ -96,7 +96,7 @@ pub fn legalize_signature(
     let bits = triple.pointer_width().unwrap().bits();
 
     let mut args = Args::new(bits, isa_flags.enable_e());
-    legalize_args(&mut sig.params, &mut args);
+    legalize_params(sig, &mut args);
 
     let mut rets = Args::new(bits, isa_flags.enable_e());
     legalize_args(&mut sig.returns, &mut rets);
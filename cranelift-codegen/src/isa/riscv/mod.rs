@@ -9,14 +9,15 @@ pub mod settings;
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
 use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::{emit_function, GrowableCodeSink, MemoryCodeSink};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{CustomCallConv, EncInfo, RegClass, RegInfo, TargetIsa};
 use crate::regalloc;
 use core::fmt;
 use std::boxed::Box;
+use std::vec::Vec;
 use target_lexicon::{PointerWidth, Triple};
 
 #[allow(dead_code)]
@@ -32,6 +33,7 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        call_convs: Vec::new(),
         constructor: isa_constructor,
     }
 }
@@ -40,6 +42,7 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    _call_convs: Vec<CustomCallConv>,
 ) -> Box<dyn TargetIsa> {
     let level1 = match triple.pointer_width().unwrap() {
         PointerWidth::U16 => panic!("16-bit RISC-V unrecognized"),
@@ -67,6 +70,10 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn isa_flags(&self) -> Vec<shared_settings::Value> {
+        self.isa_flags.iter().collect()
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -121,13 +128,19 @@ impl TargetIsa for Isa {
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
         emit_function(func, binemit::emit_inst, sink, self)
     }
+
+    fn emit_function_to_growable(&self, func: &ir::Function, sink: &mut GrowableCodeSink) {
+        emit_function(func, binemit::emit_inst, sink, self)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::cursor::{Cursor, FuncCursor};
     use crate::ir::{immediates, types};
-    use crate::ir::{Function, InstructionData, Opcode};
-    use crate::isa;
+    use crate::ir::{Function, InstBuilder, InstructionData, Opcode};
+    use crate::isa::{self, CustomCallConv};
+    use crate::result::CodegenError;
     use crate::settings::{self, Configurable};
     use core::str::FromStr;
     use std::string::{String, ToString};
@@ -272,6 +285,36 @@ mod tests {
             "R#10c"
         );
     }
+
+    #[test]
+    fn custom_call_conv() {
+        let mut isa_builder = isa::lookup(triple!("riscv32")).unwrap();
+        let call_conv = isa_builder.register_call_conv(CustomCallConv {
+            int_args: &["x10"],
+            float_args: &[],
+            int_returns: &["x10"],
+            float_returns: &[],
+            callee_saved: &[],
+            stack_align: 16,
+        });
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+
+        let mut func = Function::new();
+        func.signature.call_conv = call_conv;
+        let ebb = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            pos.ins().return_(&[]);
+        }
+
+        assert_eq!(
+            isa.check_call_convs(&func),
+            Err(CodegenError::Unsupported(
+                "custom calling conventions on this ISA"
+            ))
+        );
+    }
 }
 
 impl fmt::Display for Isa {
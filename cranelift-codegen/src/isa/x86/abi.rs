@@ -1,56 +1,147 @@
 //! x86 ABI implementation.
 
 use super::super::settings as shared_settings;
-use super::registers::{FPR, GPR, RU};
+use super::registers::{FPR, GPR, INFO, RU};
 use super::settings as isa_settings;
-use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
+use crate::abi::{legalize_args, legalize_params, ArgAction, ArgAssigner, ValueConversion};
 use crate::cursor::{Cursor, CursorPosition, EncCursor};
 use crate::ir;
 use crate::ir::immediates::Imm64;
 use crate::ir::stackslot::{StackOffset, StackSize};
 use crate::ir::{
     get_probestack_funcref, AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder,
-    ValueLoc,
+    StructLayout, ValueLoc,
 };
-use crate::isa::{CallConv, RegClass, RegUnit, TargetIsa};
+use crate::isa::{CallConv, CustomCallConv, RegClass, RegUnit, TargetIsa};
 use crate::regalloc::RegisterSet;
-use crate::result::CodegenResult;
+use crate::result::{CodegenError, CodegenResult};
 use crate::stack_layout::layout_stack;
 use core::i32;
+use std::vec::Vec;
 use target_lexicon::{PointerWidth, Triple};
 
 /// Argument registers for x86-64
-static ARG_GPRS: [RU; 6] = [RU::rdi, RU::rsi, RU::rdx, RU::rcx, RU::r8, RU::r9];
+static ARG_GPRS: [RegUnit; 6] = [
+    RU::rdi as RegUnit,
+    RU::rsi as RegUnit,
+    RU::rdx as RegUnit,
+    RU::rcx as RegUnit,
+    RU::r8 as RegUnit,
+    RU::r9 as RegUnit,
+];
+
+/// Floating point argument registers for x86-64
+static ARG_FPRS: [RegUnit; 8] = [
+    RU::xmm0 as RegUnit,
+    RU::xmm1 as RegUnit,
+    RU::xmm2 as RegUnit,
+    RU::xmm3 as RegUnit,
+    RU::xmm4 as RegUnit,
+    RU::xmm5 as RegUnit,
+    RU::xmm6 as RegUnit,
+    RU::xmm7 as RegUnit,
+];
 
 /// Return value registers.
-static RET_GPRS: [RU; 3] = [RU::rax, RU::rdx, RU::rcx];
+static RET_GPRS: [RegUnit; 3] = [RU::rax as RegUnit, RU::rdx as RegUnit, RU::rcx as RegUnit];
+
+/// Floating point return value registers.
+static RET_FPRS: [RegUnit; 2] = [RU::xmm0 as RegUnit, RU::xmm1 as RegUnit];
 
 /// Argument registers for x86-64, when using windows fastcall
-static ARG_GPRS_WIN_FASTCALL_X64: [RU; 4] = [RU::rcx, RU::rdx, RU::r8, RU::r9];
+static ARG_GPRS_WIN_FASTCALL_X64: [RegUnit; 4] = [
+    RU::rcx as RegUnit,
+    RU::rdx as RegUnit,
+    RU::r8 as RegUnit,
+    RU::r9 as RegUnit,
+];
+
+/// Floating point argument registers for x86-64, when using windows fastcall
+static ARG_FPRS_WIN_FASTCALL_X64: [RegUnit; 4] = [
+    RU::xmm0 as RegUnit,
+    RU::xmm1 as RegUnit,
+    RU::xmm2 as RegUnit,
+    RU::xmm3 as RegUnit,
+];
 
 /// Return value registers for x86-64, when using windows fastcall
-static RET_GPRS_WIN_FASTCALL_X64: [RU; 1] = [RU::rax];
+static RET_GPRS_WIN_FASTCALL_X64: [RegUnit; 1] = [RU::rax as RegUnit];
+
+/// Floating point return value registers for x86-64, when using windows fastcall
+static RET_FPRS_WIN_FASTCALL_X64: [RegUnit; 1] = [RU::xmm0 as RegUnit];
+
+/// The registers of a user-defined calling convention, resolved for x86.
+pub struct CustomRegs {
+    int_args: Vec<RegUnit>,
+    float_args: Vec<RegUnit>,
+    int_returns: Vec<RegUnit>,
+    float_returns: Vec<RegUnit>,
+    callee_saved: Vec<RegUnit>,
+    stack_align: u32,
+}
+
+impl CustomRegs {
+    /// Resolve the register names of `call_conv`.
+    ///
+    /// Panics if a register doesn't exist or doesn't belong to the class it is used for.
+    pub fn new(call_conv: &CustomCallConv) -> Self {
+        let resolve = |names: &[&str], rc: RegClass| -> Vec<RegUnit> {
+            names
+                .iter()
+                .map(|name| match INFO.parse_regunit(name) {
+                    Some(reg) if rc.contains(reg) => reg,
+                    Some(_) => panic!("register {} can't be used as a {} register", name, rc),
+                    None => panic!("unknown register {}", name),
+                })
+                .collect()
+        };
+        Self {
+            int_args: resolve(call_conv.int_args, GPR),
+            float_args: resolve(call_conv.float_args, FPR),
+            int_returns: resolve(call_conv.int_returns, GPR),
+            float_returns: resolve(call_conv.float_returns, FPR),
+            callee_saved: resolve(call_conv.callee_saved, GPR),
+            stack_align: call_conv.stack_align,
+        }
+    }
+}
+
+/// Get the registers of the custom calling convention `call_conv`, if it is one.
+///
+/// `check_call_convs` rejects the functions using conventions that weren't registered.
+fn custom_regs(custom: &[CustomRegs], call_conv: CallConv) -> Option<&CustomRegs> {
+    match call_conv {
+        CallConv::Custom(index) => Some(
+            custom
+                .get(usize::from(index))
+                .expect("calling convention not registered with the ISA builder"),
+        ),
+        _ => None,
+    }
+}
 
-struct Args {
+struct Args<'a> {
     pointer_bytes: u8,
     pointer_bits: u8,
     pointer_type: ir::Type,
-    gpr: &'static [RU],
+    gpr: &'a [RegUnit],
     gpr_used: usize,
-    fpr_limit: usize,
+    fpr: &'a [RegUnit],
     fpr_used: usize,
     offset: u32,
+    struct_parts: u32,
+    struct_on_stack: bool,
     call_conv: CallConv,
     shared_flags: shared_settings::Flags,
     #[allow(dead_code)]
     isa_flags: isa_settings::Flags,
 }
 
-impl Args {
+impl<'a> Args<'a> {
     fn new(
         bits: u8,
-        gpr: &'static [RU],
-        fpr_limit: usize,
+        gpr: &'a [RegUnit],
+        fpr: &'a [RegUnit],
         call_conv: CallConv,
         shared_flags: &shared_settings::Flags,
         isa_flags: &isa_settings::Flags,
@@ -69,24 +160,74 @@ impl Args {
             pointer_type: ir::Type::int(u16::from(bits)).unwrap(),
             gpr,
             gpr_used: 0,
-            fpr_limit,
+            fpr,
             fpr_used: 0,
             offset,
+            struct_parts: 0,
+            struct_on_stack: false,
             call_conv,
             shared_flags: shared_flags.clone(),
             isa_flags: isa_flags.clone(),
         }
     }
+
+    /// Is the struct with the given layout passed on the stack?
+    ///
+    /// The System V ABI passes structs of up to 16 bytes in registers, unless there aren't
+    /// enough registers left for all their eightbytes.
+    fn struct_in_memory(&self, layout: StructLayout, parts: u32) -> bool {
+        if self.call_conv.extends_windows_fastcall() {
+            return false;
+        }
+        if layout.size > 16 {
+            return true;
+        }
+        let floats = (0..parts)
+            .filter(|&index| layout.is_float_eightbyte(index))
+            .count();
+        let ints = parts as usize - floats;
+        self.gpr_used + ints > self.gpr.len() || self.fpr_used + floats > self.fpr.len()
+    }
+
+    /// Assign the next stack location to a value of type `ty`.
+    ///
+    /// Each value takes a whole number of pointer-sized stack words, so an `f64` takes two words
+    /// on 32-bit x86.
+    fn assign_stack(&mut self, ty: ir::Type) -> ArgAction {
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        let word = u32::from(self.pointer_bytes);
+        self.offset += (ty.bytes() + word - 1) / word * word;
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
+    }
 }
 
-impl ArgAssigner for Args {
+impl<'a> ArgAssigner for Args<'a> {
     fn assign(&mut self, arg: &AbiParam) -> ArgAction {
         let ty = arg.value_type;
 
+        // The parts of a struct are either all passed in registers, or all on the stack.
+        if let ArgumentPurpose::StructArgumentPart(layout) = arg.purpose {
+            if self.struct_parts == 0 {
+                let part_bytes = u32::from(self.pointer_bytes);
+                let parts = (layout.size + part_bytes - 1) / part_bytes;
+                self.struct_on_stack = self.struct_in_memory(layout, parts);
+                self.struct_parts = parts;
+            }
+            self.struct_parts -= 1;
+            if self.struct_on_stack {
+                return self.assign_stack(ty);
+            }
+        }
+
         // Vectors should stay in vector registers unless SIMD is not enabled--then they are split
         if ty.is_vector() {
             if self.shared_flags.enable_simd() {
-                let reg = FPR.unit(self.fpr_used);
+                let reg = self
+                    .fpr
+                    .get(self.fpr_used)
+                    .cloned()
+                    .unwrap_or_else(|| FPR.unit(self.fpr_used));
                 self.fpr_used += 1;
                 return ArgumentLoc::Reg(reg).into();
             } else {
@@ -128,7 +269,7 @@ impl ArgAssigner for Args {
 
         // Try to use a GPR.
         if !ty.is_float() && self.gpr_used < self.gpr.len() {
-            let reg = self.gpr[self.gpr_used] as RegUnit;
+            let reg = self.gpr[self.gpr_used];
             self.gpr_used += 1;
             return ArgumentLoc::Reg(reg).into();
         }
@@ -138,80 +279,153 @@ impl ArgAssigner for Args {
             // Float and general registers on windows share the same parameter index.
             // The used register depends entirely on the parameter index: Even if XMM0
             // is not used for the first parameter, it cannot be used for the second parameter.
-            debug_assert_eq!(self.fpr_limit, self.gpr.len());
+            debug_assert_eq!(self.fpr.len(), self.gpr.len());
             &mut self.gpr_used
         } else {
             &mut self.fpr_used
         };
 
-        if ty.is_float() && *fpr_offset < self.fpr_limit {
-            let reg = FPR.unit(*fpr_offset);
+        if ty.is_float() && *fpr_offset < self.fpr.len() {
+            let reg = self.fpr[*fpr_offset];
             *fpr_offset += 1;
             return ArgumentLoc::Reg(reg).into();
         }
 
         // Assign a stack location.
-        let loc = ArgumentLoc::Stack(self.offset as i32);
-        self.offset += u32::from(self.pointer_bytes);
-        debug_assert!(self.offset <= i32::MAX as u32);
-        loc.into()
+        self.assign_stack(ty)
     }
 }
 
+/// Split the struct arguments of `sig` into the parts passed in registers or on the stack.
+///
+/// Each part holds an eightbyte of the struct on x86-64, and 4 bytes on 32-bit x86. Windows x64
+/// passes structs of 1, 2, 4 or 8 bytes like integers, and the other structs as pointers to
+/// copies made by the caller, so these are left alone.
+fn split_struct_arguments(sig: &mut ir::Signature, bits: u8) {
+    if !sig.params.iter().any(|param| match param.purpose {
+        ArgumentPurpose::StructArgument(_) => true,
+        _ => false,
+    }) {
+        return;
+    }
+
+    let windows = bits == 64 && sig.call_conv.extends_windows_fastcall();
+    let part_bytes = u32::from(bits / 8);
+    let int_type = ir::Type::int(u16::from(bits)).unwrap();
+    let mut params = Vec::with_capacity(sig.params.len());
+    let mut variadic = None;
+    for (index, &param) in sig.params.iter().enumerate() {
+        if sig.variadic == Some(index) {
+            variadic = Some(params.len());
+        }
+        let layout = match param.purpose {
+            ArgumentPurpose::StructArgument(layout) => layout,
+            _ => {
+                params.push(param);
+                continue;
+            }
+        };
+        let purpose = ArgumentPurpose::StructArgumentPart(layout);
+        if windows {
+            match layout.size {
+                1 | 2 | 4 | 8 => params.push(AbiParam::special(int_type, purpose)),
+                _ => params.push(param),
+            }
+            continue;
+        }
+        for part in 0..(layout.size + part_bytes - 1) / part_bytes {
+            let value_type = if bits == 64 && layout.is_float_eightbyte(part) {
+                ir::types::F64
+            } else {
+                int_type
+            };
+            params.push(AbiParam::special(value_type, purpose));
+        }
+    }
+    if sig.variadic == Some(sig.params.len()) {
+        variadic = Some(params.len());
+    }
+
+    sig.params = params;
+    sig.variadic = variadic;
+}
+
 /// Legalize `sig`.
 pub fn legalize_signature(
     sig: &mut ir::Signature,
     triple: &Triple,
-    _current: bool,
+    current: bool,
     shared_flags: &shared_settings::Flags,
     isa_flags: &isa_settings::Flags,
+    custom: &[CustomRegs],
 ) {
-    let bits;
-    let mut args;
-
-    match triple.pointer_width().unwrap() {
+    let bits = match triple.pointer_width().unwrap() {
         PointerWidth::U16 => panic!(),
-        PointerWidth::U32 => {
-            bits = 32;
-            args = Args::new(bits, &[], 0, sig.call_conv, shared_flags, isa_flags);
-        }
-        PointerWidth::U64 => {
-            bits = 64;
-            args = if sig.call_conv.extends_windows_fastcall() {
-                Args::new(
-                    bits,
-                    &ARG_GPRS_WIN_FASTCALL_X64[..],
-                    4,
-                    sig.call_conv,
-                    shared_flags,
-                    isa_flags,
+        PointerWidth::U32 => 32,
+        PointerWidth::U64 => 64,
+    };
+
+    let (arg_gprs, arg_fprs, ret_gprs, ret_fprs): (&[RegUnit], &[RegUnit], &[RegUnit], &[RegUnit]) =
+        if let Some(regs) = custom_regs(custom, sig.call_conv) {
+            (
+                &regs.int_args,
+                &regs.float_args,
+                &regs.int_returns,
+                &regs.float_returns,
+            )
+        } else if sig.call_conv.extends_windows_fastcall() {
+            // windows-x64 calling convention only uses XMM0 or RAX for return values
+            if bits == 32 {
+                (
+                    &[],
+                    &[],
+                    &RET_GPRS_WIN_FASTCALL_X64,
+                    &RET_FPRS_WIN_FASTCALL_X64,
                 )
             } else {
-                Args::new(
-                    bits,
-                    &ARG_GPRS[..],
-                    8,
-                    sig.call_conv,
-                    shared_flags,
-                    isa_flags,
+                (
+                    &ARG_GPRS_WIN_FASTCALL_X64,
+                    &ARG_FPRS_WIN_FASTCALL_X64,
+                    &RET_GPRS_WIN_FASTCALL_X64,
+                    &RET_FPRS_WIN_FASTCALL_X64,
                 )
-            };
-        }
-    }
-
-    legalize_args(&mut sig.params, &mut args);
+            }
+        } else if bits == 32 {
+            (&[], &[], &RET_GPRS, &RET_FPRS)
+        } else {
+            (&ARG_GPRS, &ARG_FPRS, &RET_GPRS, &RET_FPRS)
+        };
 
-    let (regs, fpr_limit) = if sig.call_conv.extends_windows_fastcall() {
-        // windows-x64 calling convention only uses XMM0 or RAX for return values
-        (&RET_GPRS_WIN_FASTCALL_X64[..], 1)
-    } else {
-        (&RET_GPRS[..], 2)
-    };
+    split_struct_arguments(sig, bits);
+    let mut args = Args::new(
+        bits,
+        arg_gprs,
+        arg_fprs,
+        sig.call_conv,
+        shared_flags,
+        isa_flags,
+    );
+    legalize_params(sig, &mut args);
+
+    // Variadic calls using the System V ABI pass the number of vector registers holding arguments
+    // in `%al`.
+    if !current
+        && sig.variadic.is_some()
+        && bits == 64
+        && custom_regs(custom, sig.call_conv).is_none()
+        && !sig.call_conv.extends_windows_fastcall()
+    {
+        sig.params.push(AbiParam::special_reg(
+            ir::types::I32,
+            ArgumentPurpose::VarargCount,
+            RU::rax as RegUnit,
+        ));
+    }
 
     let mut rets = Args::new(
         bits,
-        regs,
-        fpr_limit,
+        ret_gprs,
+        ret_fprs,
         sig.call_conv,
         shared_flags,
         isa_flags,
@@ -221,7 +435,7 @@ pub fn legalize_signature(
 
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() || ty.is_bool() {
+    if ty.is_int() || ty.is_bool() || ty.is_ref() {
         GPR
     } else {
         FPR
@@ -229,11 +443,31 @@ pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
 }
 
 /// Get the set of allocatable registers for `func`.
-pub fn allocatable_registers(_func: &ir::Function, triple: &Triple) -> RegisterSet {
+pub fn allocatable_registers(
+    func: &ir::Function,
+    triple: &Triple,
+    custom: &[CustomRegs],
+) -> RegisterSet {
     let mut regs = RegisterSet::new();
     regs.take(GPR, RU::rsp as RegUnit);
     regs.take(GPR, RU::rbp as RegUnit);
 
+    // Naked functions have no prologue to save the callee-saved registers. The registers holding
+    // the arguments and return values must stay available to the register allocator though.
+    if func.attributes.naked {
+        let sig = &func.signature;
+        for reg in callee_saved_gprs(triple, sig.call_conv, custom).iter(GPR) {
+            let is_abi_reg = sig
+                .params
+                .iter()
+                .chain(&sig.returns)
+                .any(|param| param.location == ArgumentLoc::Reg(reg));
+            if !is_abi_reg {
+                regs.take(GPR, reg);
+            }
+        }
+    }
+
     // 32-bit arch only has 8 registers.
     if triple.pointer_width().unwrap() != PointerWidth::U64 {
         for i in 8..16 {
@@ -245,39 +479,108 @@ pub fn allocatable_registers(_func: &ir::Function, triple: &Triple) -> RegisterS
     regs
 }
 
+/// Get the set of registers preserved by calls to functions using `call_conv`.
+///
+/// On x86-64, a function using the cold calling convention preserves all the general purpose
+/// registers except `%rax`, `%r11` and the registers holding its return values, so the caller can
+/// keep its values in registers across the call. The XMM registers are not preserved.
+///
+/// A function using a custom calling convention preserves the registers listed as callee-saved
+/// when the convention was registered.
+pub fn call_preserved_registers(
+    func: &ir::Function,
+    triple: &Triple,
+    call_conv: CallConv,
+    custom: &[CustomRegs],
+) -> Option<RegisterSet> {
+    match call_conv {
+        CallConv::Custom(_) => {}
+        CallConv::Cold if triple.pointer_width().unwrap() == PointerWidth::U64 => {}
+        _ => return None,
+    }
+
+    let mut regs = callee_saved_gprs(triple, call_conv, custom);
+    regs.intersect(&allocatable_registers(func, triple, custom));
+    Some(regs)
+}
+
 /// Get the set of callee-saved registers.
-fn callee_saved_gprs(isa: &dyn TargetIsa, call_conv: CallConv) -> &'static [RU] {
-    match isa.triple().pointer_width().unwrap() {
-        PointerWidth::U16 => panic!(),
-        PointerWidth::U32 => &[RU::rbx, RU::rsi, RU::rdi],
-        PointerWidth::U64 => {
-            if call_conv.extends_windows_fastcall() {
-                // "registers RBX, RBP, RDI, RSI, RSP, R12, R13, R14, R15 are considered nonvolatile
-                //  and must be saved and restored by a function that uses them."
-                // as per https://msdn.microsoft.com/en-us/library/6t169e9c.aspx
-                // RSP & RSB are not listed below, since they are restored automatically during
-                // a function call. If that wasn't the case, function calls (RET) would not work.
-                &[
-                    RU::rbx,
-                    RU::rdi,
-                    RU::rsi,
-                    RU::r12,
-                    RU::r13,
-                    RU::r14,
-                    RU::r15,
-                ]
-            } else {
-                &[RU::rbx, RU::r12, RU::r13, RU::r14, RU::r15]
+fn callee_saved_gprs(triple: &Triple, call_conv: CallConv, custom: &[CustomRegs]) -> RegisterSet {
+    let regs: &[RegUnit] = if let Some(custom) = custom_regs(custom, call_conv) {
+        &custom.callee_saved
+    } else {
+        match triple.pointer_width().unwrap() {
+            PointerWidth::U16 => panic!(),
+            PointerWidth::U32 => &[RU::rbx as RegUnit, RU::rsi as RegUnit, RU::rdi as RegUnit],
+            PointerWidth::U64 => {
+                if call_conv == CallConv::Cold {
+                    // The return value registers are excluded by the callee and the caller.
+                    &[
+                        RU::rbx as RegUnit,
+                        RU::rcx as RegUnit,
+                        RU::rdx as RegUnit,
+                        RU::rsi as RegUnit,
+                        RU::rdi as RegUnit,
+                        RU::r8 as RegUnit,
+                        RU::r9 as RegUnit,
+                        RU::r10 as RegUnit,
+                        RU::r12 as RegUnit,
+                        RU::r13 as RegUnit,
+                        RU::r14 as RegUnit,
+                        RU::r15 as RegUnit,
+                    ]
+                } else if call_conv.extends_windows_fastcall() {
+                    // "registers RBX, RBP, RDI, RSI, RSP, R12, R13, R14, R15 are considered nonvolatile
+                    //  and must be saved and restored by a function that uses them."
+                    // as per https://msdn.microsoft.com/en-us/library/6t169e9c.aspx
+                    // RSP & RSB are not listed below, since they are restored automatically during
+                    // a function call. If that wasn't the case, function calls (RET) would not work.
+                    &[
+                        RU::rbx as RegUnit,
+                        RU::rdi as RegUnit,
+                        RU::rsi as RegUnit,
+                        RU::r12 as RegUnit,
+                        RU::r13 as RegUnit,
+                        RU::r14 as RegUnit,
+                        RU::r15 as RegUnit,
+                    ]
+                } else {
+                    &[
+                        RU::rbx as RegUnit,
+                        RU::r12 as RegUnit,
+                        RU::r13 as RegUnit,
+                        RU::r14 as RegUnit,
+                        RU::r15 as RegUnit,
+                    ]
+                }
             }
         }
+    };
+
+    let mut set = RegisterSet::empty();
+    for &reg in regs {
+        if !set.is_avail(GPR, reg) {
+            set.free(GPR, reg);
+        }
     }
+    set
 }
 
 /// Get the set of callee-saved registers that are used.
-fn callee_saved_gprs_used(isa: &dyn TargetIsa, func: &ir::Function) -> RegisterSet {
-    let mut all_callee_saved = RegisterSet::empty();
-    for reg in callee_saved_gprs(isa, func.signature.call_conv) {
-        all_callee_saved.free(GPR, *reg as RegUnit);
+fn callee_saved_gprs_used(
+    isa: &dyn TargetIsa,
+    func: &ir::Function,
+    custom: &[CustomRegs],
+) -> RegisterSet {
+    let mut all_callee_saved = callee_saved_gprs(isa.triple(), func.signature.call_conv, custom);
+
+    // The registers holding the return values can't be restored by the epilogue.
+    for ret in &func.signature.returns {
+        if let ArgumentLoc::Reg(reg) = ret.location {
+            if all_callee_saved.is_avail(GPR, reg) {
+                all_callee_saved.take(GPR, reg);
+            }
+        }
     }
 
     let mut used = RegisterSet::empty();
@@ -298,6 +601,9 @@ fn callee_saved_gprs_used(isa: &dyn TargetIsa, func: &ir::Function) -> RegisterS
     //
     // TODO: Consider re-evaluating how regmove/regfill/regspill work and whether it's possible
     // to avoid this step.
+    //
+    // Calls to functions preserving fewer registers than `func` clobber the difference, which
+    // must then be saved as well, and so do the clobbers declared by raw machine code.
     for ebb in &func.layout {
         for inst in func.layout.ebb_insts(ebb) {
             match func.dfg[inst] {
@@ -307,8 +613,26 @@ fn callee_saved_gprs_used(isa: &dyn TargetIsa, func: &ir::Function) -> RegisterS
                         used.free(GPR, dst);
                     }
                 }
+                ir::instructions::InstructionData::RawBytes { raw_code, .. } => {
+                    for &reg in &func.dfg.raw_code[raw_code].clobbers {
+                        if GPR.contains(reg) && !used.is_avail(GPR, reg) {
+                            used.free(GPR, reg);
+                        }
+                    }
+                    continue;
+                }
                 _ => (),
             }
+
+            if let Some(sig) = func.dfg.call_signature(inst) {
+                let call_conv = func.dfg.signatures[sig].call_conv;
+                let preserved = callee_saved_gprs(isa.triple(), call_conv, custom);
+                for reg in all_callee_saved.iter(GPR) {
+                    if !preserved.is_avail(GPR, reg) && !used.is_avail(GPR, reg) {
+                        used.free(GPR, reg);
+                    }
+                }
+            }
         }
     }
 
@@ -316,13 +640,21 @@ fn callee_saved_gprs_used(isa: &dyn TargetIsa, func: &ir::Function) -> RegisterS
     used
 }
 
-pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+pub fn prologue_epilogue(
+    func: &mut ir::Function,
+    isa: &dyn TargetIsa,
+    custom: &[CustomRegs],
+) -> CodegenResult<()> {
+    if func.attributes.naked {
+        return naked_prologue_epilogue(func, isa);
+    }
     match func.signature.call_conv {
-        // For now, just translate fast and cold as system_v.
-        CallConv::Fast | CallConv::Cold | CallConv::SystemV => {
-            system_v_prologue_epilogue(func, isa)
+        // For now, just translate fast as system_v. The cold and custom calling conventions only
+        // differ by their sets of callee-saved registers and their stack alignment.
+        CallConv::Fast | CallConv::Cold | CallConv::SystemV | CallConv::Custom(_) => {
+            system_v_prologue_epilogue(func, isa, custom)
         }
-        CallConv::WindowsFastcall => fastcall_prologue_epilogue(func, isa),
+        CallConv::WindowsFastcall => fastcall_prologue_epilogue(func, isa, custom),
         CallConv::BaldrdashSystemV | CallConv::BaldrdashWindows => {
             baldrdash_prologue_epilogue(func, isa)
         }
@@ -330,6 +662,97 @@ pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> Codege
     }
 }
 
+/// Check that the calling conventions used by `func` are supported with the settings of `isa`.
+pub fn check_call_convs(
+    func: &ir::Function,
+    isa: &dyn TargetIsa,
+    custom: &[CustomRegs],
+) -> CodegenResult<()> {
+    let sigs = Some(&func.signature)
+        .into_iter()
+        .chain(func.dfg.signatures.values());
+    for sig in sigs {
+        if let CallConv::Custom(index) = sig.call_conv {
+            if usize::from(index) >= custom.len() {
+                return Err(CodegenError::Unsupported(
+                    "calling convention not registered with the ISA builder",
+                ));
+            }
+        }
+    }
+
+    if isa.flags().enable_shadow_stack() {
+        if isa.pointer_bits() != 64 {
+            return Err(CodegenError::Unsupported("shadow stack on 32-bit x86"));
+        }
+        if func.signature.call_conv.extends_baldrdash() {
+            return Err(CodegenError::Unsupported(
+                "shadow stack with the baldrdash calling conventions",
+            ));
+        }
+        check_shadow_stack_registers(func, custom)?;
+    }
+    Ok(())
+}
+
+/// Check that the custom calling conventions used by `func` leave the shadow stack registers alone.
+///
+/// The shadow stack pointer lives in `%r15`, and the prologue and epilogue use `%r11` as a scratch
+/// register, which no other calling convention uses for arguments, return values, or callee-saved
+/// registers. A custom calling convention could pass values in either register, or expect `%r11`
+/// to be preserved, so those are rejected.
+fn check_shadow_stack_registers(func: &ir::Function, custom: &[CustomRegs]) -> CodegenResult<()> {
+    let reserved = [RU::r11 as RegUnit, RU::r15 as RegUnit];
+    let sigs = Some(&func.signature)
+        .into_iter()
+        .chain(func.dfg.signatures.values());
+    for sig in sigs {
+        if let Some(regs) = custom_regs(custom, sig.call_conv) {
+            let conflicts = regs
+                .int_args
+                .iter()
+                .chain(&regs.int_returns)
+                .any(|reg| reserved.contains(reg));
+            if conflicts {
+                return Err(CodegenError::Unsupported(
+                    "shadow stack with a calling convention passing values in %r11 or %r15",
+                ));
+            }
+        }
+    }
+    if let Some(regs) = custom_regs(custom, func.signature.call_conv) {
+        if regs.callee_saved.contains(&(RU::r11 as RegUnit)) {
+            return Err(CodegenError::Unsupported(
+                "shadow stack with a calling convention preserving %r11",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Lay out the stack of a naked function, which has no frame besides the return address.
+///
+/// Nothing adjusts the stack pointer in a naked function, so it can only have the stack slots of
+/// its incoming arguments. Any other stack slot, including the spill slots created by the register
+/// allocator, would overlap the return address or the caller's frame.
+fn naked_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    if func
+        .stack_slots
+        .values()
+        .any(|ss| ss.kind != ir::StackSlotKind::IncomingArg)
+    {
+        return Err(CodegenError::Unsupported("stack frame in a naked function"));
+    }
+
+    let word_size = StackSize::from(isa.pointer_bytes());
+    let mut ss = ir::StackSlotData::new(ir::StackSlotKind::IncomingArg, word_size);
+    ss.offset = Some(-(word_size as StackOffset));
+    func.stack_slots.push(ss);
+
+    layout_stack(&mut func.stack_slots, word_size)?;
+    Ok(())
+}
+
 fn baldrdash_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
     debug_assert!(
         !isa.flags().probestack_enabled(),
@@ -358,7 +781,11 @@ fn baldrdash_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) ->
 
 /// Implementation of the fastcall-based Win64 calling convention described at [1]
 /// [1] https://msdn.microsoft.com/en-us/library/ms235286.aspx
-fn fastcall_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+fn fastcall_prologue_epilogue(
+    func: &mut ir::Function,
+    isa: &dyn TargetIsa,
+    custom: &[CustomRegs],
+) -> CodegenResult<()> {
     if isa.triple().pointer_width().unwrap() != PointerWidth::U64 {
         panic!("TODO: windows-fastcall: x86-32 not implemented yet");
     }
@@ -370,7 +797,7 @@ fn fastcall_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
     let word_size = isa.pointer_bytes() as usize;
     let reg_type = isa.pointer_type();
 
-    let csrs = callee_saved_gprs_used(isa, func);
+    let csrs = callee_saved_gprs_used(isa, func, custom);
 
     // [1] "Space is allocated on the call stack as a shadow store for callees to save"
     // This shadow store contains the parameters which are passed through registers (ARG_GPRS)
@@ -397,6 +824,7 @@ fn fastcall_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
         kind: ir::StackSlotKind::IncomingArg,
         size: csr_stack_size as u32,
         offset: Some(-(SHADOW_STORE_SIZE + csr_stack_size)),
+        lifetime: None,
     });
 
     let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
@@ -430,15 +858,20 @@ fn fastcall_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
 }
 
 /// Insert a System V-compatible prologue and epilogue.
-fn system_v_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+fn system_v_prologue_epilogue(
+    func: &mut ir::Function,
+    isa: &dyn TargetIsa,
+    custom: &[CustomRegs],
+) -> CodegenResult<()> {
     // The original 32-bit x86 ELF ABI had a 4-byte aligned stack pointer, but
     // newer versions use a 16-byte aligned stack pointer.
-    let stack_align = 16;
+    let stack_align =
+        custom_regs(custom, func.signature.call_conv).map_or(16, |regs| regs.stack_align);
     let pointer_width = isa.triple().pointer_width().unwrap();
     let word_size = pointer_width.bytes() as usize;
     let reg_type = ir::Type::int(u16::from(pointer_width.bits())).unwrap();
 
-    let csrs = callee_saved_gprs_used(isa, func);
+    let csrs = callee_saved_gprs_used(isa, func, custom);
 
     // The reserved stack area is composed of:
     //   return address + frame pointer + all callee-saved registers
@@ -452,6 +885,7 @@ fn system_v_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
         kind: ir::StackSlotKind::IncomingArg,
         size: csr_stack_size as u32,
         offset: Some(-csr_stack_size),
+        lifetime: None,
     });
 
     let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
@@ -493,6 +927,10 @@ fn insert_common_prologue(
     csrs: &RegisterSet,
     isa: &dyn TargetIsa,
 ) {
+    if isa.flags().enable_shadow_stack() {
+        pos.ins().x86_shadow_stack_push();
+    }
+
     if stack_size > 0 {
         // Check if there is a special stack limit parameter. If so insert stack check.
         if let Some(stack_limit_arg) = pos.func.special_param(ArgumentPurpose::StackLimit) {
@@ -623,6 +1061,13 @@ fn insert_common_epilogue(
     reg_type: ir::types::Type,
     csrs: &RegisterSet,
 ) {
+    // The shadow stack check goes right before the return, once the return address is at the top
+    // of the stack again.
+    if pos.isa.flags().enable_shadow_stack() {
+        pos.ins().x86_shadow_stack_pop();
+        pos.prev_inst();
+    }
+
     if stack_size > 0 {
         pos.ins().adjust_sp_up_imm(Imm64::new(stack_size));
     }
@@ -643,3 +1088,212 @@ fn insert_common_epilogue(
         pos.func.dfg.append_inst_arg(inst, csr_ret);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{
+        types, AbiParam, ArgumentLoc, ArgumentPurpose, ExtFuncData, ExternalName, Function,
+        InstBuilder, MemFlags, Signature,
+    };
+    use crate::isa::{self, CallConv, CustomCallConv, RegUnit, TargetIsa};
+    use crate::result::CodegenError;
+    use crate::settings::{self, Configurable};
+    use crate::Context;
+    use core::str::FromStr;
+    use std::boxed::Box;
+    use std::vec::Vec;
+    use target_lexicon::triple;
+
+    fn isa_with_custom_call_conv() -> (Box<dyn TargetIsa>, CallConv) {
+        let mut builder = isa::lookup(triple!("x86_64")).unwrap();
+        let call_conv = builder.register_call_conv(CustomCallConv {
+            int_args: &["rax", "r10"],
+            float_args: &["xmm3"],
+            int_returns: &["rdx"],
+            float_returns: &["xmm1"],
+            callee_saved: &["rbx", "r12", "rdi"],
+            stack_align: 32,
+        });
+        let isa = builder.finish(settings::Flags::new(settings::builder()));
+        (isa, call_conv)
+    }
+
+    fn reg(isa: &dyn TargetIsa, name: &str) -> ArgumentLoc {
+        ArgumentLoc::Reg(isa.register_info().parse_regunit(name).unwrap())
+    }
+
+    #[test]
+    fn custom_signature() {
+        let (isa, call_conv) = isa_with_custom_call_conv();
+        assert_eq!(call_conv, CallConv::Custom(0));
+
+        let mut sig = Signature::new(call_conv);
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::F64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::F64));
+        sig.returns.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::F64));
+        isa.legalize_signature(&mut sig, false);
+
+        let params: Vec<_> = sig.params.iter().map(|param| param.location).collect();
+        assert_eq!(
+            params,
+            [
+                reg(&*isa, "rax"),
+                reg(&*isa, "xmm3"),
+                reg(&*isa, "r10"),
+                ArgumentLoc::Stack(0),
+                ArgumentLoc::Stack(8),
+            ]
+        );
+        let returns: Vec<_> = sig.returns.iter().map(|ret| ret.location).collect();
+        assert_eq!(returns, [reg(&*isa, "rdx"), reg(&*isa, "xmm1")]);
+    }
+
+    #[test]
+    fn custom_callee_saved() {
+        let (isa, call_conv) = isa_with_custom_call_conv();
+
+        // The System V callee doesn't preserve `%rdi`, which the custom convention does.
+        let mut func =
+            Function::with_name_signature(ExternalName::testcase("f"), Signature::new(call_conv));
+        let sig = func.import_signature(Signature::new(CallConv::SystemV));
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("g"),
+            signature: sig,
+            colocated: true,
+        });
+        let ebb = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            pos.ins().call(callee, &[]);
+            pos.ins().return_(&[]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        ctx.compile(&*isa).unwrap();
+        let csrs: Vec<RegUnit> = ctx
+            .func
+            .signature
+            .params
+            .iter()
+            .filter(|param| param.purpose == ArgumentPurpose::CalleeSaved)
+            .map(|param| match param.location {
+                ArgumentLoc::Reg(reg) => reg,
+                _ => panic!("callee-saved register on the stack"),
+            })
+            .collect();
+        let rdi = isa.register_info().parse_regunit("rdi").unwrap();
+        assert_eq!(csrs, [rdi]);
+    }
+
+    #[test]
+    fn shadow_stack_custom_registers() {
+        let mut builder = isa::lookup(triple!("x86_64")).unwrap();
+        let call_conv = builder.register_call_conv(CustomCallConv {
+            int_args: &["rdi", "r15"],
+            float_args: &[],
+            int_returns: &["rax"],
+            float_returns: &[],
+            callee_saved: &["rbx"],
+            stack_align: 16,
+        });
+        let mut flag_builder = settings::builder();
+        flag_builder.enable("enable_shadow_stack").unwrap();
+        let isa = builder.finish(settings::Flags::new(flag_builder));
+
+        // A System V function calling a function which takes an argument in `%r15`.
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I64));
+        let mut sig = Signature::new(call_conv);
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I64));
+        let sig = func.import_signature(sig);
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("g"),
+            signature: sig,
+            colocated: true,
+        });
+        let ebb = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb, types::I64);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            pos.ins().call(callee, &[arg, arg]);
+            pos.ins().return_(&[]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        match ctx.compile(&*isa) {
+            Err(CodegenError::Unsupported(_)) => {}
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("passed an argument in the shadow stack pointer"),
+        }
+    }
+
+    #[test]
+    fn unregistered_custom_call_conv() {
+        let isa = isa::lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()));
+
+        let mut func = Function::new();
+        func.signature.call_conv = CallConv::Custom(0);
+        let ebb = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            pos.ins().return_(&[]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        match ctx.compile(&*isa) {
+            Err(CodegenError::Unsupported(_)) => {}
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("compiled a function with an unregistered calling convention"),
+        }
+    }
+
+    #[test]
+    fn naked_spills() {
+        // The spill slots must be rejected even when the verifier doesn't run.
+        let mut flag_builder = settings::builder();
+        flag_builder.set("enable_verifier", "false").unwrap();
+        let isa = isa::lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(settings::Flags::new(flag_builder));
+
+        // More values are live at once than there are registers left to a naked function.
+        let mut func = Function::with_name_signature(
+            ExternalName::testcase("f"),
+            Signature::new(CallConv::SystemV),
+        );
+        func.attributes.naked = true;
+        func.signature.params.push(AbiParam::new(types::I64));
+        func.signature.returns.push(AbiParam::new(types::I64));
+        let ebb = func.dfg.make_ebb();
+        let addr = func.dfg.append_ebb_param(ebb, types::I64);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            let values: Vec<_> = (0..16)
+                .map(|i| pos.ins().load(types::I64, MemFlags::new(), addr, i * 8))
+                .collect();
+            let sum = values[1..]
+                .iter()
+                .fold(values[0], |sum, &value| pos.ins().iadd(sum, value));
+            pos.ins().return_(&[sum]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        match ctx.compile(&*isa) {
+            Err(CodegenError::Unsupported(_)) => {}
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("naked function compiled with spill slots"),
+        }
+    }
+}
@@ -2,7 +2,7 @@
 
 use super::enc_tables::{needs_offset, needs_sib_byte};
 use super::registers::RU;
-use crate::binemit::{bad_encoding, CodeSink, Reloc};
+use crate::binemit::{bad_encoding, CodeSink, DeoptLocation, Reloc};
 use crate::ir::condcodes::{CondCode, FloatCC, IntCC};
 use crate::ir::{Ebb, Function, Inst, InstructionData, JumpTable, Opcode, TrapCode};
 use crate::isa::{RegUnit, StackBase, StackBaseMask, StackRef, TargetIsa};
@@ -335,6 +335,12 @@ fn disp4<CS: CodeSink + ?Sized>(destination: Ebb, func: &Function, sink: &mut CS
     sink.put4(delta);
 }
 
+/// Emit a four-byte displacement to the patch site of `inst`.
+fn patch_disp4<CS: CodeSink + ?Sized>(inst: Inst, func: &Function, sink: &mut CS) {
+    let delta = func.patch_offsets[inst].wrapping_sub(sink.offset() + 4);
+    sink.put4(delta);
+}
+
 /// Emit a four-byte displacement to jump table `jt`.
 fn jt_disp4<CS: CodeSink + ?Sized>(jt: JumpTable, func: &Function, sink: &mut CS) {
     let delta = func.jt_offsets[jt].wrapping_sub(sink.offset() + 4);
@@ -14,6 +14,7 @@ use crate::isa::RegUnit;
 use crate::isa::{self, TargetIsa};
 use crate::predicates;
 use crate::regalloc::RegDiversions;
+use core::convert::TryFrom;
 
 include!(concat!(env!("OUT_DIR"), "/encoding-x86.rs"));
 include!(concat!(env!("OUT_DIR"), "/legalize-x86.rs"));
@@ -92,6 +93,18 @@ fn size_plus_maybe_sib_or_offset_for_in_reg_1(
     sizing.base_size + additional_size_if(1, inst, divert, func, needs_sib_byte_or_offset)
 }
 
+/// The size of the raw machine code emitted by a `raw_bytes` instruction.
+fn size_of_raw_code(_: &RecipeSizing, inst: Inst, _: &RegDiversions, func: &Function) -> u8 {
+    match func.dfg[inst] {
+        ir::InstructionData::RawBytes { raw_code, .. } => {
+            // The parser and the verifier reject raw code that doesn't fit.
+            let len = func.dfg.raw_code[raw_code].bytes.len();
+            u8::try_from(len).expect("Raw code longer than 255 bytes")
+        }
+        _ => panic!("Expected raw_bytes: {}", func.dfg.display_inst(inst, None)),
+    }
+}
+
 /// If the value's definition is a constant immediate, returns its unpacked value, or None
 /// otherwise.
 fn maybe_iconst_imm(pos: &FuncCursor, value: ir::Value) -> Option<i64> {
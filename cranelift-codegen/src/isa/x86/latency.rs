@@ -0,0 +1,37 @@
+//! x86 instruction latencies.
+//!
+//! The latencies are rough figures for recent out-of-order cores, where most simple integer
+//! instructions take a single cycle. They only need to rank the instructions well enough for the
+//! post-RA scheduler to start the slow ones early.
+
+use crate::ir::{self, Opcode};
+
+/// Get the number of cycles before the results of `inst` are available.
+pub fn inst_latency(func: &ir::Function, inst: ir::Inst) -> u32 {
+    let opcode = func.dfg[inst].opcode();
+    let latency = match opcode {
+        Opcode::Imul | Opcode::X86Umulx | Opcode::X86Smulx => 3,
+        Opcode::X86Udivmodx | Opcode::X86Sdivmodx => 25,
+        Opcode::Fadd | Opcode::Fsub | Opcode::Fmul => 4,
+        Opcode::Fdiv => 14,
+        Opcode::Sqrt => 18,
+        Opcode::Fpromote | Opcode::Fdemote | Opcode::FcvtFromSint | Opcode::FcvtFromUint => 5,
+        Opcode::X86Cvtt2si => 6,
+        Opcode::Popcnt | Opcode::X86Bsr | Opcode::X86Bsf => 3,
+        _ if opcode.can_load() => 4,
+        _ => 1,
+    };
+
+    // Writing an 8-bit or 16-bit register only replaces part of it, so the instructions reading
+    // the whole register stall until the partial write is merged.
+    let partial = func
+        .dfg
+        .inst_results(inst)
+        .iter()
+        .any(|&v| func.dfg.value_type(v).is_int() && func.dfg.value_type(v).bits() < 32);
+    if partial {
+        latency + 2
+    } else {
+        latency
+    }
+}
@@ -3,22 +3,26 @@
 mod abi;
 mod binemit;
 mod enc_tables;
+mod latency;
 mod registers;
 pub mod settings;
 
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
 use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::{emit_function, GrowableCodeSink, MemoryCodeSink};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{
+    CallConv, CustomCallConv, EncInfo, LatencyFn, RegClass, RegInfo, RegUnit, TargetIsa,
+};
 use crate::regalloc;
 use crate::result::CodegenResult;
 use crate::timing;
 use core::fmt;
 use std::boxed::Box;
+use std::vec::Vec;
 use target_lexicon::{PointerWidth, Triple};
 
 #[allow(dead_code)]
@@ -27,6 +31,7 @@ struct Isa {
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
+    call_convs: Vec<abi::CustomRegs>,
 }
 
 /// Get an ISA builder for creating x86 targets.
@@ -34,6 +39,7 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        call_convs: Vec::new(),
         constructor: isa_constructor,
     }
 }
@@ -42,6 +48,7 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    call_convs: Vec<CustomCallConv>,
 ) -> Box<dyn TargetIsa> {
     let level1 = match triple.pointer_width().unwrap() {
         PointerWidth::U16 => unimplemented!("x86-16"),
@@ -53,6 +60,7 @@ fn isa_constructor(
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
         cpumode: level1,
+        call_convs: call_convs.iter().map(abi::CustomRegs::new).collect(),
     })
 }
 
@@ -69,6 +77,10 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn isa_flags(&self) -> Vec<shared_settings::Value> {
+        self.isa_flags.iter().collect()
+    }
+
     fn uses_cpu_flags(&self) -> bool {
         true
     }
@@ -112,6 +124,7 @@ impl TargetIsa for Isa {
             current,
             &self.shared_flags,
             &self.isa_flags,
+            &self.call_convs,
         )
     }
 
@@ -120,7 +133,20 @@ impl TargetIsa for Isa {
     }
 
     fn allocatable_registers(&self, func: &ir::Function) -> regalloc::RegisterSet {
-        abi::allocatable_registers(func, &self.triple)
+        let mut regs = abi::allocatable_registers(func, &self.triple, &self.call_convs);
+        if self.shared_flags.enable_shadow_stack() && self.pointer_bits() == 64 {
+            // The shadow stack pointer.
+            regs.take(registers::GPR, registers::RU::r15 as RegUnit);
+        }
+        regs
+    }
+
+    fn call_preserved_registers(
+        &self,
+        func: &ir::Function,
+        call_conv: CallConv,
+    ) -> Option<regalloc::RegisterSet> {
+        abi::call_preserved_registers(func, &self.triple, call_conv, &self.call_convs)
     }
 
     #[cfg(feature = "testing_hooks")]
@@ -138,9 +164,21 @@ impl TargetIsa for Isa {
         emit_function(func, binemit::emit_inst, sink, self)
     }
 
+    fn emit_function_to_growable(&self, func: &ir::Function, sink: &mut GrowableCodeSink) {
+        emit_function(func, binemit::emit_inst, sink, self)
+    }
+
+    fn latency_model(&self) -> Option<LatencyFn> {
+        Some(latency::inst_latency)
+    }
+
+    fn check_call_convs(&self, func: &ir::Function) -> CodegenResult<()> {
+        abi::check_call_convs(func, self, &self.call_convs)
+    }
+
     fn prologue_epilogue(&self, func: &mut ir::Function) -> CodegenResult<()> {
         let _tt = timing::prologue_epilogue();
-        abi::prologue_epilogue(func, self)
+        abi::prologue_epilogue(func, self, &self.call_convs)
     }
 }
 
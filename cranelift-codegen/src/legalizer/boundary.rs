@@ -10,7 +10,8 @@
 //! The ABI boundary legalization happens in two phases:
 //!
 //! 1. The `legalize_signatures` function rewrites all the preamble signatures with ABI information
-//!    and possibly new argument types. It also rewrites the entry block arguments to match.
+//!    and possibly new argument types. It also rewrites the entry block arguments and the struct
+//!    arguments of calls to match.
 //! 2. The `handle_call_abi` and `handle_return_abi` functions rewrite call and return instructions
 //!    to match the new ABI signatures.
 //!
@@ -19,11 +20,14 @@
 
 use crate::abi::{legalize_abi_value, ValueConversion};
 use crate::cursor::{Cursor, FuncCursor};
+use crate::entity::SecondaryMap;
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir::instructions::CallInfo;
+use crate::ir::types;
 use crate::ir::{
     AbiParam, ArgumentLoc, ArgumentPurpose, DataFlowGraph, Ebb, Function, Inst, InstBuilder,
-    SigRef, Signature, Type, Value, ValueLoc,
+    MemFlags, SigRef, Signature, StackSlot, StackSlotData, StackSlotKind, StructLayout, Type,
+    Value, ValueLoc,
 };
 use crate::isa::TargetIsa;
 use crate::legalizer::split::{isplit, vsplit};
@@ -37,7 +41,14 @@ use std::vec::Vec;
 /// in a state with type discrepancies.
 pub fn legalize_signatures(func: &mut Function, isa: &dyn TargetIsa) {
     legalize_signature(&mut func.signature, true, isa);
-    for sig_data in func.dfg.signatures.values_mut() {
+    // The ABI verifier checks the legalized signatures against the original ones.
+    let keep_old_signatures = isa.flags().enable_verifier();
+    let mut struct_args = SecondaryMap::new();
+    for (sig_ref, sig_data) in func.dfg.signatures.iter_mut() {
+        if keep_old_signatures {
+            func.dfg.old_signatures[sig_ref] = Some(sig_data.clone());
+        }
+        struct_args[sig_ref] = struct_arguments(sig_data);
         legalize_signature(sig_data, false, isa);
     }
 
@@ -45,6 +56,7 @@ pub fn legalize_signatures(func: &mut Function, isa: &dyn TargetIsa) {
         legalize_entry_params(func, entry);
         spill_entry_params(func, entry);
     }
+    legalize_struct_arguments(func, &struct_args);
 }
 
 /// Legalize the libcall signature, which we may generate on the fly after
@@ -92,7 +104,26 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
 
         let abi_type = pos.func.signature.params[abi_arg];
         let arg_type = pos.func.dfg.value_type(arg);
-        if arg_type == abi_type.value_type {
+        if let ArgumentPurpose::StructArgumentPart(layout) = abi_type.purpose {
+            // Store the parts of the struct in a stack slot, and point `arg` to it.
+            let mut parts = Vec::new();
+            let mut size = 0;
+            while size < layout.size {
+                let part_type = pos.func.signature.params[abi_arg].value_type;
+                parts.push((pos.func.dfg.append_ebb_param(entry, part_type), size));
+                size += part_type.bytes();
+                abi_arg += 1;
+            }
+            let slot = pos
+                .func
+                .create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+            for (part, offset) in parts {
+                pos.ins().stack_store(part, slot, offset as i32);
+            }
+            pos.ins()
+                .with_results([Some(arg)])
+                .stack_addr(arg_type, slot, 0);
+        } else if arg_type == abi_type.value_type {
             // No value translation is necessary, this argument matches the ABI type.
             // Just use the original EBB argument value. This is the most common case.
             pos.func.dfg.attach_ebb_param(entry, arg);
@@ -116,6 +147,8 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
                     debug_assert!(!has_stack_limit, "Multiple stack_limit arguments found");
                     has_stack_limit = true;
                 }
+                // A pointer to a copy of the struct made by the caller.
+                ArgumentPurpose::StructArgument(_) => {}
                 _ => panic!("Unexpected special-purpose arg {}", abi_type),
             }
             abi_arg += 1;
@@ -176,6 +209,14 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
                 debug_assert!(!has_stack_limit, "Multiple stack_limit parameters found");
                 has_stack_limit = true;
             }
+            // Variadic functions can be called, but not defined.
+            ArgumentPurpose::VarargCount => {
+                panic!("Unexpected vararg_count parameter {}", arg);
+            }
+            // Struct arguments replace the entry block parameters pointing to the structs.
+            ArgumentPurpose::StructArgument(_) | ArgumentPurpose::StructArgumentPart(_) => {
+                panic!("Leftover struct argument {}", arg);
+            }
         }
 
         // Just create entry block values to match here. We will use them in `handle_return_abi()`
@@ -184,6 +225,152 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
     }
 }
 
+/// The way a struct argument is passed according to a legalized signature.
+enum StructPassing {
+    /// As a pointer to a copy of the struct.
+    Copy,
+    /// As parts of the given types.
+    Parts(Vec<Type>),
+}
+
+/// Get the index and layout of each struct argument in the signature `sig`, before legalization.
+fn struct_arguments(sig: &Signature) -> Vec<(usize, StructLayout)> {
+    sig.params
+        .iter()
+        .enumerate()
+        .filter_map(|(index, param)| match param.purpose {
+            ArgumentPurpose::StructArgument(layout) => Some((index, layout)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rewrite the struct arguments of all the calls in `func` to match their legalized signatures.
+///
+/// The calls pass pointers to the structs until then. The structs are copied to stack slots, and
+/// the calls pass either pointers to the copies, or the parts loaded from the copies.
+/// `all_struct_args` holds the struct arguments of each signature before legalization.
+fn legalize_struct_arguments(
+    func: &mut Function,
+    all_struct_args: &SecondaryMap<SigRef, Vec<(usize, StructLayout)>>,
+) {
+    let mut pos = FuncCursor::new(func);
+    while let Some(_ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            let sig_ref = match pos.func.dfg.call_signature(inst) {
+                Some(sig_ref) => sig_ref,
+                None => continue,
+            };
+            let struct_args = &all_struct_args[sig_ref];
+            if struct_args.is_empty() {
+                continue;
+            }
+
+            // Find how each struct is passed, in order.
+            let params = &pos.func.dfg.signatures[sig_ref].params;
+            let mut passing = Vec::with_capacity(struct_args.len());
+            let mut abi_arg = 0;
+            while abi_arg < params.len() {
+                match params[abi_arg].purpose {
+                    ArgumentPurpose::StructArgument(_) => {
+                        passing.push(StructPassing::Copy);
+                        abi_arg += 1;
+                    }
+                    ArgumentPurpose::StructArgumentPart(layout) => {
+                        let mut types = Vec::new();
+                        let mut size = 0;
+                        while size < layout.size {
+                            let part_type = params[abi_arg].value_type;
+                            types.push(part_type);
+                            size += part_type.bytes();
+                            abi_arg += 1;
+                        }
+                        passing.push(StructPassing::Parts(types));
+                    }
+                    _ => abi_arg += 1,
+                }
+            }
+            debug_assert_eq!(passing.len(), struct_args.len());
+
+            let mut args = pos.func.dfg.inst_variable_args(inst).to_vec();
+            for (&(index, layout), passing) in struct_args.iter().zip(passing).rev() {
+                let ptr = args[index];
+                let values = match passing {
+                    StructPassing::Copy => {
+                        // Windows x64 expects the copies to be 16-byte aligned.
+                        let slot =
+                            copy_struct(&mut pos, ptr, layout.size, (layout.size + 15) & !15);
+                        let ptr_type = pos.func.dfg.value_type(ptr);
+                        vec![pos.ins().stack_addr(ptr_type, slot, 0)]
+                    }
+                    StructPassing::Parts(types) => {
+                        let size = types.iter().map(|ty| ty.bytes()).sum();
+                        let slot = copy_struct(&mut pos, ptr, layout.size, size);
+                        let mut offset = 0;
+                        let mut values = Vec::with_capacity(types.len());
+                        for ty in types {
+                            values.push(pos.ins().stack_load(ty, slot, offset));
+                            offset += ty.bytes() as i32;
+                        }
+                        values
+                    }
+                };
+                args.splice(index..=index, values);
+            }
+
+            let num_fixed_values = pos.func.dfg[inst]
+                .opcode()
+                .constraints()
+                .num_fixed_value_arguments();
+            let mut vlist = pos.func.dfg[inst]
+                .take_value_list()
+                .expect("Call must have a value list");
+            let pool = &mut pos.func.dfg.value_lists;
+            let fixed_values = vlist.as_slice(pool)[..num_fixed_values].to_vec();
+            vlist.clear(pool);
+            vlist.extend(fixed_values.into_iter().chain(args), pool);
+            pos.func.dfg[inst].put_value_list(vlist);
+        }
+    }
+}
+
+/// Copy the `size` bytes of the struct pointed to by `ptr` to a new stack slot of `slot_size`
+/// bytes, inserting instructions at `pos`.
+fn copy_struct(pos: &mut FuncCursor, ptr: Value, size: u32, slot_size: u32) -> StackSlot {
+    let slot = pos
+        .func
+        .create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, slot_size));
+    let ptr_type = pos.func.dfg.value_type(ptr);
+    let dst = pos.ins().stack_addr(ptr_type, slot, 0);
+
+    let mut offset = 0;
+    while offset < size {
+        let chunk = [8, 4, 2, 1]
+            .iter()
+            .cloned()
+            .find(|&chunk| chunk <= ptr_type.bytes() && chunk <= size - offset)
+            .unwrap();
+        let off = offset as i32;
+        match chunk {
+            1 => {
+                let value = pos.ins().uload8(types::I32, MemFlags::new(), ptr, off);
+                pos.ins().istore8(MemFlags::trusted(), value, dst, off);
+            }
+            2 => {
+                let value = pos.ins().uload16(types::I32, MemFlags::new(), ptr, off);
+                pos.ins().istore16(MemFlags::trusted(), value, dst, off);
+            }
+            _ => {
+                let ty = Type::int(chunk as u16 * 8).unwrap();
+                let value = pos.ins().load(ty, MemFlags::new(), ptr, off);
+                pos.ins().store(MemFlags::trusted(), value, dst, off);
+            }
+        }
+        offset += chunk;
+    }
+    slot
+}
+
 /// Legalize the results returned from a call instruction to match the ABI signature.
 ///
 /// The cursor `pos` points to a call instruction with at least one return value. The cursor will
@@ -401,6 +588,7 @@ fn check_call_signature(dfg: &DataFlowGraph, inst: Inst) -> Result<(), SigRef> {
     let (sig_ref, args) = match dfg[inst].analyze_call(&dfg.value_lists) {
         CallInfo::Direct(func, args) => (dfg.ext_funcs[func].signature, args),
         CallInfo::Indirect(sig_ref, args) => (sig_ref, args),
+        CallInfo::Raw(code, args) => (dfg.raw_code[code].signature, args),
         CallInfo::NotACall => panic!("Expected call, got {:?}", dfg[inst]),
     };
     let sig = &dfg.signatures[sig_ref];
@@ -534,6 +722,27 @@ pub fn handle_call_abi(mut inst: Inst, func: &mut Function, cfg: &ControlFlowGra
         func.dfg.signatures[sig_ref].params[abi_arg]
     });
 
+    // A variadic call may need to pass the number of vector registers holding its arguments,
+    // including the parts of structs passed in them.
+    let sig = &pos.func.dfg.signatures[sig_ref];
+    if let Some(abi_arg) = sig.special_param_index(ArgumentPurpose::VarargCount) {
+        let ty = sig.params[abi_arg].value_type;
+        let count = sig
+            .params
+            .iter()
+            .filter(|arg| {
+                arg.location.is_reg() && (arg.value_type.is_float() || arg.value_type.is_vector())
+            })
+            .count();
+        pos.goto_inst(inst);
+        let value = pos.ins().iconst(ty, count as i64);
+        let num_fixed_values = pos.func.dfg[inst]
+            .opcode()
+            .constraints()
+            .num_fixed_value_arguments();
+        pos.func.dfg.inst_args_mut(inst)[num_fixed_values + abi_arg] = value;
+    }
+
     if !pos.func.dfg.signatures[sig_ref].returns.is_empty() {
         inst = legalize_inst_results(pos, |func, abi_res| {
             func.dfg.signatures[sig_ref].returns[abi_res]
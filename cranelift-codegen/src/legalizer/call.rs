@@ -1,7 +1,9 @@
 //! Legalization of calls.
 //!
 //! This module exports the `expand_call` function which transforms a `call`
-//! instruction into `func_addr` and `call_indirect` instructions.
+//! instruction into `func_addr` and `call_indirect` instructions, and the
+//! `expand_invoke` function which transforms an `invoke` instruction into a
+//! `call`.
 
 use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
@@ -52,3 +54,35 @@ pub fn expand_call(
         .replace(inst)
         .CallIndirect(ir::Opcode::CallIndirect, ptr_ty, sig, new_args);
 }
+
+/// Expand an `invoke` instruction into a `call` with the same results.
+///
+/// No unwind tables are emitted yet, so the landing pad can't be entered by unwinding, and the
+/// edge to it is removed from the CFG.
+pub fn expand_invoke(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    // Unpack the instruction.
+    let (func_ref, args) = match func.dfg[inst] {
+        ir::InstructionData::Invoke {
+            opcode,
+            ref args,
+            func_ref,
+            ..
+        } => {
+            debug_assert_eq!(opcode, ir::Opcode::Invoke);
+            (func_ref, args.clone())
+        }
+        _ => panic!("Wanted invoke: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    func.dfg
+        .replace(inst)
+        .Call(ir::Opcode::Call, ir::types::INVALID, func_ref, args);
+
+    let ebb = func.layout.inst_ebb(inst).unwrap();
+    cfg.recompute_ebb(func, ebb);
+}
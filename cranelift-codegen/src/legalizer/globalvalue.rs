@@ -1,13 +1,118 @@
 //! Legalization of global values.
 //!
 //! This module exports the `expand_global_value` function which transforms a `global_value`
-//! instruction into code that depends on the kind of global value referenced.
+//! instruction into code that depends on the kind of global value referenced, and the
+//! `GlobalValueCache` which lets the legalizer reuse the value computed by an earlier
+//! `global_value` instruction instead of computing it again.
 
 use crate::cursor::{Cursor, FuncCursor};
-use crate::flowgraph::ControlFlowGraph;
+use crate::flowgraph::{BasicBlock, ControlFlowGraph};
+use crate::fx::FxHashMap;
 use crate::ir::{self, InstBuilder};
 use crate::isa::TargetIsa;
 
+/// A cached global value.
+#[derive(Clone, Copy)]
+struct CachedValue {
+    /// The value computed for the global value.
+    value: ir::Value,
+    /// Is the global value the same during the whole function?
+    invariant: bool,
+}
+
+/// The global values computed by the instructions legalized so far, which are available at the
+/// current position of the legalizer.
+///
+/// The cached values are dropped at the start of every EBB, except for the EBBs with a single
+/// predecessor, which keep the values available at the branch to them. The global values loaded
+/// from memory without the `readonly` flag are dropped at the stores and the calls.
+pub struct GlobalValueCache {
+    /// The cached values available at the current position.
+    values: FxHashMap<ir::GlobalValue, CachedValue>,
+    /// The cached values available at the branch to an EBB with a single predecessor.
+    branches: FxHashMap<ir::Ebb, (ir::Inst, FxHashMap<ir::GlobalValue, CachedValue>)>,
+}
+
+impl GlobalValueCache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self {
+            values: FxHashMap::default(),
+            branches: FxHashMap::default(),
+        }
+    }
+
+    /// Start legalizing `ebb`.
+    pub fn enter_ebb(&mut self, ebb: ir::Ebb, cfg: &ControlFlowGraph) {
+        self.values.clear();
+        if let Some((branch, values)) = self.branches.remove(&ebb) {
+            let mut preds = cfg.pred_iter(ebb);
+            if let (Some(BasicBlock { inst, .. }), None) = (preds.next(), preds.next()) {
+                if inst == branch {
+                    self.values = values;
+                }
+            }
+        }
+    }
+
+    /// Replace the `global_value` instruction `inst` with the cached value if there is one.
+    ///
+    /// Otherwise, add the result of `inst` to the cache and return false.
+    pub fn reuse(&mut self, inst: ir::Inst, func: &mut ir::Function) -> bool {
+        let gv = match func.dfg[inst] {
+            ir::InstructionData::UnaryGlobalValue { global_value, .. } => global_value,
+            _ => panic!("Wanted global_value: {}", func.dfg.display_inst(inst, None)),
+        };
+        let result = func.dfg.first_result(inst);
+        match self.values.get(&gv) {
+            Some(cached) => {
+                func.dfg.clear_results(inst);
+                func.dfg.change_to_alias(result, cached.value);
+                func.layout.remove_inst(inst);
+                true
+            }
+            None => {
+                let invariant = is_invariant(func, gv);
+                self.values.insert(
+                    gv,
+                    CachedValue {
+                        value: result,
+                        invariant,
+                    },
+                );
+                false
+            }
+        }
+    }
+
+    /// Update the cache after the legal instruction `inst`.
+    pub fn process_inst(&mut self, inst: ir::Inst, func: &ir::Function, cfg: &ControlFlowGraph) {
+        let opcode = func.dfg[inst].opcode();
+        if opcode.can_store() || opcode.is_call() || opcode.other_side_effects() {
+            self.values.retain(|_, cached| cached.invariant);
+        }
+        if self.values.is_empty() {
+            return;
+        }
+        if let ir::instructions::BranchInfo::SingleDest(dest, _) = func.dfg.analyze_branch(inst) {
+            if cfg.pred_iter(dest).nth(1).is_none() {
+                self.branches.insert(dest, (inst, self.values.clone()));
+            }
+        }
+    }
+}
+
+/// Is `gv` the same during the whole function?
+///
+/// This is the case unless it's loaded from memory without the `readonly` flag.
+fn is_invariant(func: &ir::Function, gv: ir::GlobalValue) -> bool {
+    match func.global_values[gv] {
+        ir::GlobalValueData::VMContext | ir::GlobalValueData::Symbol { .. } => true,
+        ir::GlobalValueData::IAddImm { base, .. } => is_invariant(func, base),
+        ir::GlobalValueData::Load { base, readonly, .. } => readonly && is_invariant(func, base),
+    }
+}
+
 /// Expand a `global_value` instruction according to the definition of the global value.
 pub fn expand_global_value(
     inst: ir::Inst,
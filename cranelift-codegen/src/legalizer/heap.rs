@@ -2,6 +2,14 @@
 //!
 //! This module exports the `expand_heap_addr` function which transforms a `heap_addr`
 //! instruction into code that depends on the kind of heap referenced.
+//!
+//! The bounds check of a heap access uses one of three strategies, depending on the heap:
+//!
+//! - Static heaps whose bound covers the whole 32-bit index space don't need any check for 32-bit
+//!   indexes, as long as the offset-guard pages cover the rest of the access.
+//! - Heaps without offset-guard pages check every byte of the access against the bound.
+//! - Heaps with offset-guard pages only check the part of the access that the offset-guard pages
+//!   don't cover, rounded so accesses close to each other share the same check.
 
 use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
@@ -14,7 +22,7 @@ pub fn expand_heap_addr(
     inst: ir::Inst,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
-    _isa: &dyn TargetIsa,
+    isa: &dyn TargetIsa,
 ) {
     // Unpack the instruction.
     let (heap, offset, access_size) = match func.dfg[inst] {
@@ -30,9 +38,12 @@ pub fn expand_heap_addr(
         _ => panic!("Wanted heap_addr: {}", func.dfg.display_inst(inst, None)),
     };
 
+    let offset_guard_size = func.heaps[heap].offset_guard_size.into();
+    let access_size = checked_size(access_size, offset_guard_size);
+
     match func.heaps[heap].style {
         ir::HeapStyle::Dynamic { bound_gv } => {
-            dynamic_addr(inst, heap, offset, access_size, bound_gv, func)
+            dynamic_addr(isa, inst, heap, offset, access_size, bound_gv, func)
         }
         ir::HeapStyle::Static { bound } => {
             static_addr(inst, heap, offset, access_size, bound.into(), func, cfg)
@@ -40,16 +51,34 @@ pub fn expand_heap_addr(
     }
 }
 
+/// Get the number of bytes of an `access_size` bytes access that must be checked against the bound
+/// of a heap followed by `offset_guard_size` bytes of offset-guard pages.
+///
+/// Accessing the offset-guard pages traps, so the last `offset_guard_size` bytes of the access
+/// don't need to be checked, provided the first byte is. The result is rounded up to one plus a
+/// multiple of the offset-guard size, making the checks of nearby accesses identical.
+fn checked_size(access_size: u32, offset_guard_size: u64) -> u64 {
+    let access_size = u64::from(access_size);
+    if offset_guard_size == 0 {
+        access_size
+    } else if access_size <= offset_guard_size {
+        access_size.min(1)
+    } else {
+        let unguarded = access_size - offset_guard_size;
+        1 + (unguarded - 1 + offset_guard_size - 1) / offset_guard_size * offset_guard_size
+    }
+}
+
 /// Expand a `heap_addr` for a dynamic heap.
 fn dynamic_addr(
+    isa: &dyn TargetIsa,
     inst: ir::Inst,
     heap: ir::Heap,
     offset: ir::Value,
-    access_size: u32,
+    access_size: u64,
     bound_gv: ir::GlobalValue,
     func: &mut ir::Function,
 ) {
-    let access_size = u64::from(access_size);
     let offset_ty = func.dfg.value_type(offset);
     let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
     let min_size = func.heaps[heap].min_size.into();
@@ -57,7 +86,15 @@ fn dynamic_addr(
     pos.use_srcloc(inst);
 
     // Start with the bounds check. Trap if `offset + access_size > bound`.
-    let bound = pos.ins().global_value(offset_ty, bound_gv);
+    //
+    // A heap with 64-bit indexes may have a pointer-sized bound on 32-bit targets.
+    let bound_ty = pos.func.global_values[bound_gv].global_type(isa);
+    let bound = if bound_ty.bits() < offset_ty.bits() {
+        let bound = pos.ins().global_value(bound_ty, bound_gv);
+        pos.ins().uextend(offset_ty, bound)
+    } else {
+        pos.ins().global_value(offset_ty, bound_gv)
+    };
     let oob;
     if access_size == 1 {
         // `offset > bound - 1` is the same as `offset >= bound`.
@@ -90,12 +127,11 @@ fn static_addr(
     inst: ir::Inst,
     heap: ir::Heap,
     offset: ir::Value,
-    access_size: u32,
+    access_size: u64,
     bound: u64,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
 ) {
-    let access_size = u64::from(access_size);
     let offset_ty = func.dfg.value_type(offset);
     let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
     let mut pos = FuncCursor::new(func).at_inst(inst);
@@ -123,10 +159,10 @@ fn static_addr(
     // more.
     if offset_ty != ir::types::I32 || limit < 0xffff_ffff {
         let oob = if limit & 1 == 1 {
-            // Prefer testing `offset >= limit - 1` when limit is odd because an even number is
+            // Prefer testing `offset >= limit + 1` when limit is odd because an even number is
             // likely to be a convenient constant on ARM and other RISC architectures.
             pos.ins()
-                .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, offset, limit as i64 - 1)
+                .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, offset, limit as i64 + 1)
         } else {
             pos.ins()
                 .icmp_imm(IntCC::UnsignedGreaterThan, offset, limit as i64)
@@ -149,10 +185,15 @@ fn compute_addr(
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
-    // Convert `offset` to `addr_ty`.
+    // Convert `offset` to `addr_ty`. The bounds check has already been performed, so a 64-bit
+    // offset used with 32-bit addresses is known to fit.
     if offset_ty != addr_ty {
         let labels_value = offset;
-        offset = pos.ins().uextend(addr_ty, offset);
+        offset = if offset_ty.bits() > addr_ty.bits() {
+            pos.ins().ireduce(addr_ty, offset)
+        } else {
+            pos.ins().uextend(addr_ty, offset)
+        };
         if let Some(values_labels) = pos.func.dfg.values_labels.as_mut() {
             values_labels.insert(
                 offset,
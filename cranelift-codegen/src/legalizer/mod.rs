@@ -30,8 +30,8 @@ mod libcall;
 mod split;
 mod table;
 
-use self::call::expand_call;
-use self::globalvalue::expand_global_value;
+use self::call::{expand_call, expand_invoke};
+use self::globalvalue::{expand_global_value, GlobalValueCache};
 use self::heap::expand_heap_addr;
 use self::libcall::expand_as_libcall;
 use self::table::expand_table_addr;
@@ -83,6 +83,7 @@ fn legalize_inst(
 ///
 /// - Transform any instructions that don't have a legal representation in `isa`.
 /// - Fill out `func.encodings`.
+/// - Reuse the values of the `global_value` instructions computed earlier on the same path.
 ///
 pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, isa: &dyn TargetIsa) {
     let _tt = timing::legalize();
@@ -93,19 +94,28 @@ pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, is
     func.encodings.resize(func.dfg.num_insts());
 
     let mut pos = FuncCursor::new(func);
+    let mut global_values = GlobalValueCache::new();
 
     // Process EBBs in layout order. Some legalization actions may split the current EBB or append
     // new ones to the end. We need to make sure we visit those new EBBs too.
-    while let Some(_ebb) = pos.next_ebb() {
+    while let Some(ebb) = pos.next_ebb() {
+        global_values.enter_ebb(ebb, cfg);
+
         // Keep track of the cursor position before the instruction being processed, so we can
         // double back when replacing instructions.
         let mut prev_pos = pos.position();
 
         while let Some(inst) = pos.next_inst() {
-            if legalize_inst(inst, &mut pos, cfg, isa) {
+            if pos.func.dfg[inst].opcode() == ir::Opcode::GlobalValue
+                && global_values.reuse(inst, pos.func)
+            {
+                // The instruction was removed, so there's nothing left to legalize.
+                pos.set_position(prev_pos);
+            } else if legalize_inst(inst, &mut pos, cfg, isa) {
                 // Go back and legalize the inserted return value conversion instructions.
                 pos.set_position(prev_pos);
             } else {
+                global_values.process_inst(inst, pos.func, cfg);
                 // Remember this position in case we need to double back.
                 prev_pos = pos.position();
             }
@@ -31,7 +31,46 @@ pub fn expand_table_addr(
         _ => panic!("Wanted table_addr: {}", func.dfg.display_inst(inst, None)),
     };
 
-    dynamic_addr(inst, table, index, element_offset, func);
+    // Elide the bounds check of constant indices below the minimum size of the table.
+    let min_size: u64 = func.tables[table].min_size.into();
+    if const_index(func, index).map_or(false, |index| index < min_size) {
+        let index_ty = func.dfg.value_type(index);
+        let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+        compute_addr(inst, table, addr_ty, index, index_ty, element_offset, func);
+        return;
+    }
+
+    match func.tables[table].style {
+        ir::TableStyle::Dynamic { bound_gv } => {
+            dynamic_addr(inst, table, index, element_offset, bound_gv, func)
+        }
+        ir::TableStyle::Static { bound } => {
+            static_addr(inst, table, index, element_offset, bound.into(), func)
+        }
+    }
+}
+
+/// Get the value of `index` if it's a constant.
+fn const_index(func: &ir::Function, index: ir::Value) -> Option<u64> {
+    let inst = match func.dfg.value_def(index) {
+        ir::ValueDef::Result(inst, _) => inst,
+        ir::ValueDef::Param(_, _) => return None,
+    };
+    match func.dfg[inst] {
+        ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } => {
+            let bits = func.dfg.value_type(index).bits();
+            let imm: i64 = imm.into();
+            Some(if bits < 64 {
+                imm as u64 & ((1 << bits) - 1)
+            } else {
+                imm as u64
+            })
+        }
+        _ => None,
+    }
 }
 
 /// Expand a `table_addr` for a dynamic table.
@@ -40,9 +79,9 @@ fn dynamic_addr(
     table: ir::Table,
     index: ir::Value,
     element_offset: Offset32,
+    bound_gv: ir::GlobalValue,
     func: &mut ir::Function,
 ) {
-    let bound_gv = func.tables[table].bound_gv;
     let index_ty = func.dfg.value_type(index);
     let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
     let mut pos = FuncCursor::new(func).at_inst(inst);
@@ -68,6 +107,39 @@ fn dynamic_addr(
     );
 }
 
+/// Expand a `table_addr` for a static table.
+fn static_addr(
+    inst: ir::Inst,
+    table: ir::Table,
+    index: ir::Value,
+    element_offset: Offset32,
+    bound: u64,
+    func: &mut ir::Function,
+) {
+    let index_ty = func.dfg.value_type(index);
+    let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // Trap if `index >= bound`, unless the bound covers all the values of the index type.
+    if index_ty.bits() >= 64 || bound < 1 << index_ty.bits() {
+        let oob = pos
+            .ins()
+            .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, index, bound as i64);
+        pos.ins().trapnz(oob, ir::TrapCode::TableOutOfBounds);
+    }
+
+    compute_addr(
+        inst,
+        table,
+        addr_ty,
+        index,
+        index_ty,
+        element_offset,
+        pos.func,
+    );
+}
+
 /// Emit code for the base address computation of a `table_addr` instruction.
 fn compute_addr(
     inst: ir::Inst,
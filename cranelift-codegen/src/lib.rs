@@ -56,7 +56,10 @@ use hashmap_core::{map as hash_map, HashMap, HashSet};
 use std::collections::{hash_map, HashMap, HashSet};
 
 pub use crate::context::Context;
+pub use crate::demangle::demangle;
+pub use crate::instrument::{CounterKind, CounterSite};
 pub use crate::legalizer::legalize_function;
+pub use crate::stack_usage::StackUsage;
 pub use crate::value_label::{ValueLabelsRanges, ValueLocRange};
 pub use crate::verifier::verify_function;
 pub use crate::write::write_function;
@@ -83,28 +86,36 @@ pub use crate::entity::packed_option;
 
 mod abi;
 mod bitset;
+mod block_placement;
+mod bounds_checks;
 mod constant_hash;
 mod context;
 mod dce;
+mod demangle;
 mod divconst_magic_numbers;
 mod fx;
+mod instrument;
 mod iterators;
 mod legalizer;
 mod licm;
 mod nan_canonicalization;
+mod osr;
 mod partition_slice;
 mod postopt;
 mod predicates;
 mod ref_slice;
 mod regalloc;
 mod result;
+mod scheduling;
 mod scoped_hash_map;
 mod simple_gvn;
 mod simple_preopt;
 mod stack_layout;
+mod stack_usage;
 mod topo_order;
 mod unreachable_code;
 mod value_label;
+mod value_trace;
 
 pub use crate::result::{CodegenError, CodegenResult};
 
@@ -56,19 +56,16 @@ pub fn do_licm(
             }
         }
     }
-    // We have to recompute the domtree to account for the changes
-    cfg.compute(func);
-    domtree.compute(func, cfg);
 }
 
-// Insert a pre-header before the header, modifying the function layout and CFG to reflect it.
-// A jump instruction to the header is placed at the end of the pre-header.
+// Insert a pre-header before the header, modifying the function layout, CFG and domtree to
+// reflect it. A jump instruction to the header is placed at the end of the pre-header.
 fn create_pre_header(
     isa: &dyn TargetIsa,
     header: Ebb,
     func: &mut Function,
     cfg: &mut ControlFlowGraph,
-    domtree: &DominatorTree,
+    domtree: &mut DominatorTree,
 ) -> Ebb {
     let pool = &mut ListPool::<Value>::new();
     let header_args_values: Vec<Value> = func.dfg.ebb_params(header).into_iter().cloned().collect();
@@ -82,13 +79,16 @@ fn create_pre_header(
     for typ in header_args_types {
         pre_header_args_value.push(func.dfg.append_ebb_param(pre_header, typ), pool);
     }
+    let mut preds = Vec::new();
     for BasicBlock {
-        inst: last_inst, ..
+        ebb: pred,
+        inst: last_inst,
     } in cfg.pred_iter(header)
     {
         // We only follow normal edges (not the back edges)
         if !domtree.dominates(header, last_inst, &func.layout) {
             func.change_branch_destination(last_inst, pre_header);
+            preds.push(pred);
         }
     }
     {
@@ -98,6 +98,13 @@ fn create_pre_header(
         pos.next_inst();
         pos.ins().jump(header, pre_header_args_value.as_slice(pool));
     }
+
+    // Only the edges to the header and the pre-header changed.
+    for pred in preds {
+        cfg.recompute_ebb(func, pred);
+    }
+    cfg.recompute_ebb(func, pre_header);
+    domtree.update_edges_to(func, cfg, &[pre_header, header]);
     pre_header
 }
 
@@ -82,6 +82,17 @@ impl LoopAnalysis {
         }
     }
 
+    /// Returns the number of loops containing `ebb`, which is zero if it isn't in a loop.
+    pub fn loop_depth(&self, ebb: Ebb) -> usize {
+        let mut depth = 0;
+        let mut finger = self.ebb_loop_map[ebb].expand();
+        while let Some(finger_loop) = finger {
+            depth += 1;
+            finger = self.loop_parent(finger_loop);
+        }
+        depth
+    }
+
     /// Determines if a loop is contained in another loop.
     ///
     /// `is_child_loop(child,parent)` returns `true` if and only if `child` is a child loop of
@@ -286,6 +297,9 @@ mod tests {
         assert_eq!(loop_analysis.is_in_loop(ebb2, loops[0]), true);
         assert_eq!(loop_analysis.is_in_loop(ebb3, loops[0]), true);
         assert_eq!(loop_analysis.is_in_loop(ebb0, loops[1]), false);
+        assert_eq!(loop_analysis.loop_depth(ebb0), 1);
+        assert_eq!(loop_analysis.loop_depth(ebb2), 2);
+        assert_eq!(loop_analysis.loop_depth(ebb3), 1);
     }
 
     #[test]
@@ -34,7 +34,9 @@ fn is_fp_arith(pos: &mut FuncCursor, inst: Inst) -> bool {
     match pos.func.dfg[inst] {
         InstructionData::Unary { opcode, .. } => {
             opcode == Opcode::Ceil
+                || opcode == Opcode::Fdemote
                 || opcode == Opcode::Floor
+                || opcode == Opcode::Fpromote
                 || opcode == Opcode::Nearest
                 || opcode == Opcode::Sqrt
                 || opcode == Opcode::Trunc
@@ -65,15 +67,30 @@ fn add_nan_canon_seq(pos: &mut FuncCursor, inst: Inst) {
     // the canonical NaN value if `val` is NaN, assign the result to `inst`.
     let is_nan = pos.ins().fcmp(FloatCC::NotEqual, new_res, new_res);
     let canon_nan = insert_nan_const(pos, val_type);
-    pos.ins()
-        .with_result(val)
-        .select(is_nan, canon_nan, new_res);
+    if val_type.is_vector() {
+        // The comparison produces a mask with all the bits of the NaN lanes
+        // set. Select the lanes from the canonical NaN vector with the mask.
+        let mask = pos.ins().raw_bitcast(val_type, is_nan);
+        let nan_lanes = pos.ins().band(mask, canon_nan);
+        let other_lanes = pos.ins().band_not(new_res, mask);
+        pos.ins().with_result(val).bor(nan_lanes, other_lanes);
+    } else {
+        pos.ins()
+            .with_result(val)
+            .select(is_nan, canon_nan, new_res);
+    }
 
     pos.prev_inst(); // Step backwards so the pass does not skip instructions.
 }
 
 /// Insert a canonical 32-bit or 64-bit NaN constant at the current position.
+///
+/// For vector types, all the lanes of the constant are canonical NaNs.
 fn insert_nan_const(pos: &mut FuncCursor, nan_type: Type) -> Value {
+    if nan_type.is_vector() {
+        let lane_nan = insert_nan_const(pos, nan_type.lane_type());
+        return pos.ins().splat(nan_type, lane_nan);
+    }
     match nan_type {
         types::F32 => pos.ins().f32const(Ieee32::with_bits(CANON_32BIT_NAN)),
         types::F64 => pos.ins().f64const(Ieee64::with_bits(CANON_64BIT_NAN)),
@@ -83,3 +100,55 @@ fn insert_nan_const(pos: &mut FuncCursor, nan_type: Type) -> Value {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{AbiParam, Opcode};
+    use crate::settings;
+    use crate::verifier::verify_function;
+    use std::vec::Vec;
+
+    #[test]
+    fn canonicalize_vector() {
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::F32X4));
+        func.signature.returns.push(AbiParam::new(types::F32X4));
+        let ebb0 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::F32X4);
+        let v1;
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            v1 = pos.ins().fadd(v0, v0);
+            pos.ins().return_(&[v1]);
+        }
+
+        do_nan_canonicalization(&mut func);
+        let flags = settings::Flags::new(settings::builder());
+        verify_function(&func, &flags).unwrap();
+
+        let opcodes: Vec<_> = func
+            .layout
+            .ebb_insts(ebb0)
+            .map(|inst| func.dfg[inst].opcode())
+            .collect();
+        assert_eq!(
+            opcodes,
+            [
+                Opcode::Fadd,
+                Opcode::Fcmp,
+                Opcode::F32const,
+                Opcode::Splat,
+                Opcode::RawBitcast,
+                Opcode::Band,
+                Opcode::BandNot,
+                Opcode::Bor,
+                Opcode::Return,
+            ]
+        );
+        // The canonicalized value keeps its number, so its uses are unchanged.
+        let bor = func.layout.prev_inst(func.layout.last_inst(ebb0).unwrap());
+        assert_eq!(func.dfg.first_result(bor.unwrap()), v1);
+    }
+}
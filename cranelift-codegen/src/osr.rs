@@ -0,0 +1,133 @@
+//! On-stack replacement entry points.
+//!
+//! A function with an `osr_entry` block is compiled as a variant that starts executing in the
+//! middle of the function, typically at the header of a hot loop. The variant takes the same
+//! arguments as the original function, followed by a pointer to a buffer holding the parameters
+//! of the OSR entry block, laid out as described by `ir::OsrStateLayout`.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::ir::{AbiParam, Function, InstBuilder, MemFlags, OsrStateLayout};
+use crate::isa::TargetIsa;
+use crate::result::{CodegenError, CodegenResult};
+use crate::timing;
+use std::vec::Vec;
+
+/// Replace the entry block of `func` with one that loads the OSR state and jumps to the OSR entry
+/// block.
+///
+/// The new entry block takes over the parameters of the original one, so values derived from the
+/// function arguments remain available. The code that was only reachable from the original entry
+/// block becomes unreachable and should be removed with `eliminate_unreachable_code`, after
+/// checking that the remaining code doesn't use it with `check_osr_entry`.
+pub fn do_osr_entry(func: &mut Function, isa: &dyn TargetIsa) {
+    let _tt = timing::osr_entry();
+    let osr_ebb = match func.osr_entry.take() {
+        Some(ebb) => ebb,
+        None => return,
+    };
+    let old_entry = func.layout.entry_block().expect("Function is empty");
+    let layout = OsrStateLayout::for_ebb(&func.dfg, osr_ebb);
+    let pointer_type = isa.pointer_type();
+
+    let entry = func.dfg.make_ebb();
+    func.layout.insert_ebb(entry, old_entry);
+    let params = func.dfg.detach_ebb_params(old_entry);
+    for i in 0..params.len(&func.dfg.value_lists) {
+        let param = params.get(i, &func.dfg.value_lists).unwrap();
+        func.dfg.attach_ebb_param(entry, param);
+    }
+    func.signature.params.push(AbiParam::new(pointer_type));
+    let state = func.dfg.append_ebb_param(entry, pointer_type);
+
+    let types: Vec<_> = func
+        .dfg
+        .ebb_params(osr_ebb)
+        .iter()
+        .map(|&v| func.dfg.value_type(v))
+        .collect();
+    let mut pos = FuncCursor::new(func).at_bottom(entry);
+    let args: Vec<_> = types
+        .into_iter()
+        .zip(layout.offsets)
+        .map(|(ty, offset)| {
+            pos.ins()
+                .load(ty, MemFlags::trusted(), state, offset as i32)
+        })
+        .collect();
+    pos.ins().jump(osr_ebb, &args);
+}
+
+/// Check that the code reachable from the OSR entry of `func` only uses values it defines, the
+/// parameters of the OSR entry block, or the function arguments.
+///
+/// The values computed before the OSR entry block, like loop invariants in the entry block or in a
+/// loop preheader, aren't part of the OSR state, so they are undefined when entering through it.
+/// `domtree` must be computed after `do_osr_entry`.
+pub fn check_osr_entry(func: &Function, domtree: &DominatorTree) -> CodegenResult<()> {
+    for ebb in func.layout.ebbs() {
+        if !domtree.is_reachable(ebb) {
+            continue;
+        }
+        for inst in func.layout.ebb_insts(ebb) {
+            for &arg in func.dfg.inst_args(inst) {
+                let def = func.dfg.value_def(func.dfg.resolve_aliases(arg));
+                if !domtree.dominates(def, inst, &func.layout) {
+                    return Err(CodegenError::Unsupported(
+                        "values defined before the OSR entry block and used after it",
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{types, AbiParam, Function, InstBuilder};
+    use crate::isa;
+    use crate::result::CodegenError;
+    use crate::settings;
+    use crate::Context;
+    use core::str::FromStr;
+    use target_lexicon::triple;
+
+    #[test]
+    fn live_into_loop() {
+        let isa = isa::lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()));
+
+        // The loop adds `v1`, computed in the entry block, which isn't part of the OSR state.
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I32));
+        func.signature.returns.push(AbiParam::new(types::I32));
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        let v2 = func.dfg.append_ebb_param(ebb1, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let v1 = pos.ins().imul(v0, v0);
+            pos.ins().jump(ebb1, &[v0]);
+            pos.insert_ebb(ebb1);
+            let v3 = pos.ins().iadd(v2, v1);
+            pos.ins().brnz(v3, ebb1, &[v3]);
+            pos.ins().jump(ebb2, &[]);
+            pos.insert_ebb(ebb2);
+            pos.ins().return_(&[v3]);
+        }
+        func.osr_entry = Some(ebb1);
+
+        let mut ctx = Context::for_function(func);
+        match ctx.compile(&*isa) {
+            Err(CodegenError::Unsupported(_)) => {}
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("compiled an OSR entry using an undefined value"),
+        }
+    }
+}
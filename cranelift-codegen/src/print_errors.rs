@@ -21,17 +21,10 @@ pub fn pretty_verifier_error<'a>(
     func_w: Option<Box<dyn FuncWriter + 'a>>,
     errors: VerifierErrors,
 ) -> String {
-    let mut errors = errors.0;
+    let num_errors = errors.0.len();
     let mut w = String::new();
-    let num_errors = errors.len();
 
-    decorate_function(
-        &mut PrettyVerifierError(func_w.unwrap_or_else(|| Box::new(PlainWriter)), &mut errors),
-        &mut w,
-        func,
-        &isa.into(),
-    )
-    .unwrap();
+    write_verifier_errors(&mut w, func, isa, func_w, errors).unwrap();
 
     writeln!(
         w,
@@ -44,6 +37,31 @@ pub fn pretty_verifier_error<'a>(
     w
 }
 
+/// Write `func` annotated with `errors`.
+///
+/// Errors which don't refer to an entity that is printed are written after the function.
+pub(crate) fn write_verifier_errors<'a>(
+    w: &mut dyn Write,
+    func: &ir::Function,
+    isa: Option<&dyn TargetIsa>,
+    func_w: Option<Box<dyn FuncWriter + 'a>>,
+    errors: VerifierErrors,
+) -> fmt::Result {
+    let mut errors = errors.0;
+
+    decorate_function(
+        &mut PrettyVerifierError(func_w.unwrap_or_else(|| Box::new(PlainWriter)), &mut errors),
+        w,
+        func,
+        &isa.into(),
+    )?;
+
+    for err in errors {
+        print_error(w, err)?;
+    }
+    Ok(())
+}
+
 struct PrettyVerifierError<'a>(Box<dyn FuncWriter + 'a>, &'a mut Vec<VerifierError>);
 
 impl<'a> FuncWriter for PrettyVerifierError<'a> {
@@ -168,7 +186,10 @@ fn pretty_preamble_error(
 ) -> fmt::Result {
     let mut s = String::new();
     func_w.write_entity_definition(&mut s, func, entity, value)?;
-    write!(w, "{}", s)?;
+    match demangled_name(func, entity) {
+        Some(name) => writeln!(w, "{} ; {}", s.trim_end(), name)?,
+        None => write!(w, "{}", s)?,
+    }
 
     // TODO: Use drain_filter here when it gets stabilized
     let mut i = 0;
@@ -193,6 +214,18 @@ fn pretty_preamble_error(
     Ok(())
 }
 
+/// Get the demangled symbol of the external name defined by `entity`, if any.
+fn demangled_name(func: &Function, entity: AnyEntity) -> Option<String> {
+    match entity {
+        AnyEntity::FuncRef(fref) => func.dfg.ext_funcs[fref].name.demangled(&func.names),
+        AnyEntity::GlobalValue(gv) => match func.global_values[gv] {
+            ir::GlobalValueData::Symbol { ref name, .. } => name.demangled(&func.names),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 /// Prints:
 ///    ;   ^~~~~~
 fn print_arrow(w: &mut dyn Write, entity: &str) -> fmt::Result {
@@ -211,9 +244,12 @@ fn print_arrow(w: &mut dyn Write, entity: &str) -> fmt::Result {
 }
 
 /// Prints:
-///    ; error: [ERROR BODY]
+///    ; error: [ERROR BODY] [RULE]
 fn print_error(w: &mut dyn Write, err: VerifierError) -> fmt::Result {
-    writeln!(w, "; error: {}", err.to_string())?;
+    match err.rule() {
+        "" => writeln!(w, "; error: {}", err.to_string())?,
+        rule => writeln!(w, "; error: {} [{}]", err.to_string(), rule)?,
+    }
     Ok(())
 }
 
@@ -46,6 +46,7 @@ use crate::cursor::{Cursor, EncCursor};
 use crate::dominator_tree::DominatorTree;
 use crate::ir::{AbiParam, ArgumentLoc, InstBuilder, ValueDef};
 use crate::ir::{Ebb, Function, Inst, Layout, SigRef, Value, ValueLoc};
+use crate::isa::registers::RegClassMask;
 use crate::isa::{regs_overlap, RegClass, RegInfo, RegUnit};
 use crate::isa::{ConstraintKind, EncInfo, OperandConstraint, RecipeConstraints, TargetIsa};
 use crate::packed_option::PackedOption;
@@ -55,6 +56,7 @@ use crate::regalloc::liveness::Liveness;
 use crate::regalloc::liverange::{LiveRange, LiveRangeContext};
 use crate::regalloc::register_set::RegisterSet;
 use crate::regalloc::solver::{Solver, SolverError};
+use crate::regalloc::spilling::call_preserved_registers;
 use crate::regalloc::RegDiversions;
 use crate::timing;
 use core::mem;
@@ -407,6 +409,7 @@ impl<'a> Context<'a> {
                 );
             }
         }
+        let mut clobbers = RegisterSet::empty();
         if let Some(sig) = call_sig {
             self.program_output_abi(
                 sig,
@@ -415,6 +418,7 @@ impl<'a> Context<'a> {
                 &mut replace_global_defines,
                 &regs.global,
             );
+            clobbers = self.program_call_clobbers(inst, sig, throughs);
         }
         if let Some(constraints) = constraints {
             self.program_output_constraints(
@@ -464,6 +468,13 @@ impl<'a> Context<'a> {
 
         // Update `regs` for the next instruction.
         regs.input = output_regs;
+        for &rc in self.reginfo.classes {
+            if rc.index == rc.toprc {
+                for reg in clobbers.iter(rc) {
+                    regs.input.free(rc, reg);
+                }
+            }
+        }
         for lv in defs {
             let loc = self.cur.func.locations[lv.value];
             debug!(
@@ -763,6 +774,48 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Program the registers clobbered by the call `inst` with signature `sig` into the constraint
+    /// solver, so the values that are live across the call are moved to the registers preserved
+    /// by the callee.
+    ///
+    /// Returns the clobbered registers, which are free again after the call.
+    fn program_call_clobbers(
+        &mut self,
+        inst: Inst,
+        sig: SigRef,
+        throughs: &[LiveValue],
+    ) -> RegisterSet {
+        let mut clobbers = RegisterSet::empty();
+        let preserved = match call_preserved_registers(self.cur.isa, &self.cur.func, inst, sig) {
+            Some(preserved) => preserved,
+            // The spiller has already moved all the live-through values to the stack.
+            None => return clobbers,
+        };
+
+        let mut done: RegClassMask = 0;
+        for lv in throughs {
+            if let Affinity::Reg(rci) = lv.affinity {
+                let toprc = self.reginfo.toprc(rci);
+                if done & (1 << toprc.index) != 0 {
+                    continue;
+                }
+                done |= 1 << toprc.index;
+                for reg in self.usable_regs.iter(toprc) {
+                    // The return values have already been programmed as fixed outputs.
+                    let is_return = self.cur.func.dfg.signatures[sig]
+                        .returns
+                        .iter()
+                        .any(|ret| ret.location == ArgumentLoc::Reg(reg));
+                    if !preserved.is_avail(toprc, reg) && !is_return {
+                        self.reserve_output(toprc, reg, throughs);
+                        clobbers.free(toprc, reg);
+                    }
+                }
+            }
+        }
+        clobbers
+    }
+
     /// Add a single fixed output value to the solver.
     fn add_fixed_output(
         &mut self,
@@ -771,6 +824,13 @@ impl<'a> Context<'a> {
         reg: RegUnit,
         throughs: &[LiveValue],
     ) {
+        self.reserve_output(rc, reg, throughs);
+        self.cur.func.locations[value] = ValueLoc::Reg(reg);
+    }
+
+    /// Reserve `reg` on the output side of the solver, moving any interfering live-through values
+    /// out of the way.
+    fn reserve_output(&mut self, rc: RegClass, reg: RegUnit, throughs: &[LiveValue]) {
         if !self.solver.add_fixed_output(rc, reg) {
             // The fixed output conflicts with some of the live-through registers.
             for lv in throughs {
@@ -786,9 +846,12 @@ impl<'a> Context<'a> {
             }
 
             let ok = self.solver.add_fixed_output(rc, reg);
-            debug_assert!(ok, "Couldn't clear fixed output interference for {}", value);
+            debug_assert!(
+                ok,
+                "Couldn't clear fixed output interference for {}",
+                self.reginfo.display_regunit(reg)
+            );
         }
-        self.cur.func.locations[value] = ValueLoc::Reg(reg);
     }
 
     /// Program the output-side constraints for `inst` into the constraint solver.
@@ -8,6 +8,7 @@ use crate::dominator_tree::DominatorTree;
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir::Function;
 use crate::isa::TargetIsa;
+use crate::loop_analysis::LoopAnalysis;
 use crate::regalloc::coalescing::Coalescing;
 use crate::regalloc::coloring::Coloring;
 use crate::regalloc::live_value_tracker::LiveValueTracker;
@@ -20,7 +21,8 @@ use crate::result::CodegenResult;
 use crate::timing;
 use crate::topo_order::TopoOrder;
 use crate::verifier::{
-    verify_context, verify_cssa, verify_liveness, verify_locations, VerifierErrors,
+    verify_context, verify_cssa, verify_liveness, verify_locations, verify_safepoints,
+    VerifierErrors,
 };
 
 /// Persistent memory allocations for register allocation.
@@ -74,15 +76,19 @@ impl Context {
     ///
     /// After register allocation, all values in `func` have been assigned to a register or stack
     /// location that is consistent with instruction encoding constraints.
+    ///
+    /// The loop analysis is used to keep the spill code out of loops.
     pub fn run(
         &mut self,
         isa: &dyn TargetIsa,
         func: &mut Function,
         cfg: &ControlFlowGraph,
         domtree: &mut DominatorTree,
+        loop_analysis: &LoopAnalysis,
     ) -> CodegenResult<()> {
         let _tt = timing::regalloc();
         debug_assert!(domtree.is_valid());
+        debug_assert!(loop_analysis.is_valid());
 
         let mut errors = VerifierErrors::default();
 
@@ -137,6 +143,7 @@ impl Context {
             isa,
             func,
             domtree,
+            loop_analysis,
             &mut self.liveness,
             &self.virtregs,
             &mut self.topo,
@@ -211,6 +218,8 @@ impl Context {
             let ok = verify_context(func, cfg, domtree, isa, &mut errors).is_ok()
                 && verify_liveness(isa, func, cfg, &self.liveness, &mut errors).is_ok()
                 && verify_locations(isa, func, Some(&self.liveness), &mut errors).is_ok()
+                && (!isa.flags().enable_safepoints()
+                    || verify_safepoints(isa, func, &self.liveness, &mut errors).is_ok())
                 && verify_cssa(
                     func,
                     cfg,
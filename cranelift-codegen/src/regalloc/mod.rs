@@ -22,4 +22,4 @@ mod spilling;
 pub use self::context::Context;
 pub use self::diversion::RegDiversions;
 pub use self::register_set::RegisterSet;
-pub use self::safepoint::emit_stackmaps;
+pub use self::safepoint::{eliminate_redundant_safepoints, emit_stackmaps, insert_safepoints};
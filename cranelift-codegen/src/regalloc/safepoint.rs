@@ -1,23 +1,31 @@
 use crate::cursor::{Cursor, FuncCursor};
 use crate::dominator_tree::DominatorTree;
-use crate::ir::{Function, InstBuilder, InstructionData, Opcode, TrapCode};
+use crate::flowgraph::{BasicBlock, ControlFlowGraph};
+use crate::ir::{Function, Inst, InstBuilder, InstructionData, Opcode, TrapCode, Value};
 use crate::isa::TargetIsa;
+use crate::loop_analysis::LoopAnalysis;
 use crate::regalloc::live_value_tracker::LiveValueTracker;
 use crate::regalloc::liveness::Liveness;
+use crate::timing;
 use std::vec::Vec;
 
+/// Collect the reference values among the live values of `tracker`.
+fn live_ref_values(func: &Function, tracker: &LiveValueTracker) -> Vec<Value> {
+    tracker
+        .live()
+        .iter()
+        .filter(|live_value| func.dfg.value_type(live_value.value).is_ref())
+        .map(|live_val| live_val.value)
+        .collect()
+}
+
 fn insert_and_encode_safepoint<'f>(
     pos: &mut FuncCursor<'f>,
     tracker: &LiveValueTracker,
     isa: &dyn TargetIsa,
 ) {
     // Iterate through all live values, collect only the references.
-    let live_ref_values = tracker
-        .live()
-        .iter()
-        .filter(|live_value| pos.func.dfg.value_type(live_value.value).is_ref())
-        .map(|live_val| live_val.value)
-        .collect::<Vec<_>>();
+    let live_ref_values = live_ref_values(pos.func, tracker);
 
     if !live_ref_values.is_empty() {
         pos.ins().safepoint(&live_ref_values);
@@ -31,6 +39,106 @@ fn insert_and_encode_safepoint<'f>(
     }
 }
 
+/// Is `inst` preceded by a safepoint?
+fn has_safepoint(func: &Function, inst: Inst) -> bool {
+    func.layout
+        .prev_inst(inst)
+        .map_or(false, |prev| func.dfg[prev].opcode() == Opcode::Safepoint)
+}
+
+/// Does `inst` need a safepoint in front of it?
+fn needs_safepoint(func: &Function, inst: Inst) -> bool {
+    match func.dfg[inst] {
+        InstructionData::Trap {
+            code: TrapCode::Interrupt,
+            ..
+        } => true,
+        ref data => data.opcode().is_call(),
+    }
+}
+
+/// Does `inst` define a reference value?
+fn defines_ref(func: &Function, inst: Inst) -> bool {
+    func.dfg
+        .inst_results(inst)
+        .iter()
+        .any(|&value| func.dfg.value_type(value).is_ref())
+}
+
+/// Insert a safepoint before every call and every loop backedge in `func`.
+///
+/// The safepoints are inserted before register allocation, so the reference values that are live
+/// across them get spilled. Their arguments are filled in by `emit_stackmaps()`.
+pub fn insert_safepoints(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    loop_analysis: &LoopAnalysis,
+    isa: &dyn TargetIsa,
+) {
+    let _tt = timing::insert_safepoints();
+
+    let mut points = Vec::new();
+    for lp in loop_analysis.loops() {
+        let header = loop_analysis.loop_header(lp);
+        for BasicBlock { ebb, inst } in cfg.pred_iter(header) {
+            if loop_analysis.is_in_loop(ebb, lp) {
+                points.push(inst);
+            }
+        }
+    }
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if func.dfg[inst].opcode().is_call() {
+                points.push(inst);
+            }
+        }
+    }
+    points.sort();
+    points.dedup();
+
+    let mut pos = FuncCursor::new(func);
+    for inst in points {
+        if has_safepoint(pos.func, inst) {
+            continue;
+        }
+        pos.goto_inst(inst);
+        let safepoint = pos.ins().safepoint(&[]);
+        let ok = pos.func.update_encoding(safepoint, isa).is_ok();
+        debug_assert!(ok);
+    }
+}
+
+/// Remove the safepoints made redundant by an earlier safepoint of the same EBB.
+///
+/// A safepoint that doesn't stand in front of a call or an interrupt trap is only a polling
+/// point, and it's redundant when it's reached from another safepoint without crossing a call or
+/// the definition of a reference value. This typically happens to the safepoints inserted before
+/// the several backedges branching from the same EBB. Removing them also removes their stackmaps.
+pub fn eliminate_redundant_safepoints(func: &mut Function) {
+    let _tt = timing::eliminate_safepoints();
+
+    let mut pos = FuncCursor::new(func);
+    while pos.next_ebb().is_some() {
+        // Is the current position covered by a safepoint?
+        let mut covered = false;
+        while let Some(inst) = pos.next_inst() {
+            if pos.func.dfg[inst].opcode() == Opcode::Safepoint {
+                let guards_inst = pos
+                    .func
+                    .layout
+                    .next_inst(inst)
+                    .map_or(false, |next| needs_safepoint(pos.func, next));
+                if covered && !guards_inst {
+                    pos.remove_inst_and_step_back();
+                }
+                covered = true;
+            } else if pos.func.dfg[inst].opcode().is_call() || defines_ref(pos.func, inst) {
+                covered = false;
+            }
+        }
+    }
+}
+
 // The emit_stackmaps() function analyzes each instruction to retrieve the liveness of
 // the defs and operands by traversing a function's ebbs in layout order.
 pub fn emit_stackmaps(
@@ -51,16 +159,15 @@ pub fn emit_stackmaps(
         pos.goto_top(ebb);
 
         while let Some(inst) = pos.next_inst() {
-            if let InstructionData::Trap {
-                code: TrapCode::Interrupt,
-                ..
-            } = &pos.func.dfg[inst]
-            {
-                insert_and_encode_safepoint(&mut pos, tracker, isa);
-            } else if pos.func.dfg[inst].opcode().is_call() {
+            if pos.func.dfg[inst].opcode() == Opcode::Safepoint {
+                // This safepoint was inserted before register allocation, record the live
+                // references even if there are none.
+                let live_ref_values = live_ref_values(pos.func, tracker);
+                pos.func.dfg.replace(inst).safepoint(&live_ref_values);
+            } else if has_safepoint(pos.func, inst) {
+                // The safepoint in front of this instruction covers it.
+            } else if needs_safepoint(pos.func, inst) {
                 insert_and_encode_safepoint(&mut pos, tracker, isa);
-            } else if pos.func.dfg[inst].opcode() == Opcode::Safepoint {
-                panic!("safepoint instruction can only be used by the compiler!");
             }
 
             // Process the instruction and get rid of dead values.
@@ -14,23 +14,53 @@
 //! 2. When the same value is used more than once by an instruction, the operand constraints must
 //!    be compatible. Otherwise, the value must be copied into a new register for some of the
 //!    operands.
+//!
+//! A spilled value is reloaded before each of its register uses and stored after its definition,
+//! so the spilling pass avoids spilling the values used or defined in loops, where the reloads and
+//! spills would run on every iteration. The spill candidates are ranked by the number of their
+//! uses and defs in loops, weighted by the loop depth, and the ones without any are spilled first.
 
 use crate::cursor::{Cursor, EncCursor};
 use crate::dominator_tree::DominatorTree;
-use crate::ir::{ArgumentLoc, Ebb, Function, Inst, InstBuilder, SigRef, Value, ValueLoc};
+use crate::entity::SecondaryMap;
+use crate::ir::{
+    ArgumentLoc, Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, SigRef, TrapCode,
+    Value, ValueLoc,
+};
 use crate::isa::registers::{RegClass, RegClassIndex, RegClassMask, RegUnit};
 use crate::isa::{ConstraintKind, EncInfo, RecipeConstraints, RegInfo, TargetIsa};
+use crate::loop_analysis::LoopAnalysis;
 use crate::regalloc::affinity::Affinity;
 use crate::regalloc::live_value_tracker::{LiveValue, LiveValueTracker};
 use crate::regalloc::liveness::Liveness;
 use crate::regalloc::pressure::Pressure;
+use crate::regalloc::register_set::RegisterSet;
 use crate::regalloc::virtregs::VirtRegs;
 use crate::timing;
 use crate::topo_order::TopoOrder;
+use core::cmp;
 use core::fmt;
 use log::debug;
 use std::vec::Vec;
 
+/// Is `data` a point other than a call where the garbage collector can run?
+fn is_gc_point(data: &InstructionData) -> bool {
+    match *data {
+        InstructionData::Trap {
+            code: TrapCode::Interrupt,
+            ..
+        } => true,
+        ref data => data.opcode() == Opcode::Safepoint,
+    }
+}
+
+/// Take any register of class `rc` out of `regs`.
+fn take_any(regs: &mut RegisterSet, rc: RegClass) -> Option<RegUnit> {
+    let reg = regs.iter(rc).next()?;
+    regs.take(rc, reg);
+    Some(reg)
+}
+
 /// Return a top-level register class which contains `unit`.
 fn toprc_containing_regunit(unit: RegUnit, reginfo: &RegInfo) -> RegClass {
     let bank = reginfo.bank_containing_regunit(unit).unwrap();
@@ -40,10 +70,41 @@ fn toprc_containing_regunit(unit: RegUnit, reginfo: &RegInfo) -> RegClass {
         .expect("reg unit should be in a toprc")
 }
 
+/// Get the registers preserved by the call `inst` with signature `sig`, or `None` if the callee
+/// may clobber all the registers.
+///
+/// A `raw_bytes` instruction preserves all the allocatable registers except its declared
+/// clobbers.
+pub(super) fn call_preserved_registers(
+    isa: &dyn TargetIsa,
+    func: &Function,
+    inst: Inst,
+    sig: SigRef,
+) -> Option<RegisterSet> {
+    match func.dfg[inst] {
+        InstructionData::RawBytes { raw_code, .. } => {
+            let reginfo = isa.register_info();
+            let mut preserved = isa.allocatable_registers(func);
+            for &reg in &func.dfg.raw_code[raw_code].clobbers {
+                let rc = toprc_containing_regunit(reg, &reginfo);
+                if preserved.is_avail(rc, reg) {
+                    preserved.take(rc, reg);
+                }
+            }
+            Some(preserved)
+        }
+        _ => isa.call_preserved_registers(func, func.dfg.signatures[sig].call_conv),
+    }
+}
+
+/// Maximum loop depth distinguished by the spill costs, keeping them from overflowing.
+const MAX_LOOP_DEPTH: usize = 10;
+
 /// Persistent data structures for the spilling pass.
 pub struct Spilling {
     spills: Vec<Value>,
     reg_uses: Vec<RegUse>,
+    spill_costs: SecondaryMap<Value, u32>,
 }
 
 /// Context data structure that gets instantiated once per pass.
@@ -71,6 +132,9 @@ struct Context<'a> {
 
     // Uses of register values in the current instruction.
     reg_uses: &'a mut Vec<RegUse>,
+
+    // Cost of the reloads and spills in loops of each value's virtual register if it were spilled.
+    spill_costs: &'a SecondaryMap<Value, u32>,
 }
 
 impl Spilling {
@@ -79,6 +143,7 @@ impl Spilling {
         Self {
             spills: Vec::new(),
             reg_uses: Vec::new(),
+            spill_costs: SecondaryMap::new(),
         }
     }
 
@@ -86,6 +151,7 @@ impl Spilling {
     pub fn clear(&mut self) {
         self.spills.clear();
         self.reg_uses.clear();
+        self.spill_costs.clear();
     }
 
     /// Run the spilling algorithm over `func`.
@@ -94,6 +160,7 @@ impl Spilling {
         isa: &dyn TargetIsa,
         func: &mut Function,
         domtree: &DominatorTree,
+        loop_analysis: &LoopAnalysis,
         liveness: &mut Liveness,
         virtregs: &VirtRegs,
         topo: &mut TopoOrder,
@@ -101,6 +168,7 @@ impl Spilling {
     ) {
         let _tt = timing::ra_spilling();
         debug!("Spilling for:\n{}", func.display(isa));
+        self.compute_spill_costs(func, loop_analysis, virtregs);
         let reginfo = isa.register_info();
         let usable_regs = isa.allocatable_registers(func);
         let mut ctx = Context {
@@ -114,9 +182,56 @@ impl Spilling {
             pressure: Pressure::new(&reginfo, &usable_regs),
             spills: &mut self.spills,
             reg_uses: &mut self.reg_uses,
+            spill_costs: &self.spill_costs,
         };
         ctx.run(tracker)
     }
+
+    /// Compute the cost of spilling each value of `func`.
+    ///
+    /// Each use and def in a loop costs 1, multiplied by 8 for each enclosing loop, approximating
+    /// the number of times the reload or spill would run. The uses as branch arguments are free,
+    /// since the EBB parameters are in the same virtual register, so they would be spilled too.
+    ///
+    /// Spilling a value spills its whole virtual register, so every value gets the total cost of
+    /// its virtual register.
+    fn compute_spill_costs(
+        &mut self,
+        func: &Function,
+        loop_analysis: &LoopAnalysis,
+        virtregs: &VirtRegs,
+    ) {
+        self.spill_costs.clear();
+        self.spill_costs.resize(func.dfg.num_values());
+        for ebb in func.layout.ebbs() {
+            let depth = cmp::min(loop_analysis.loop_depth(ebb), MAX_LOOP_DEPTH);
+            if depth == 0 {
+                continue;
+            }
+            let cost = 1 << (3 * (depth - 1));
+            for inst in func.layout.ebb_insts(ebb) {
+                let args = if func.dfg[inst].opcode().is_branch() {
+                    func.dfg.inst_fixed_args(inst)
+                } else {
+                    func.dfg.inst_args(inst)
+                };
+                for &value in args.iter().chain(func.dfg.inst_results(inst)) {
+                    let value = func.dfg.resolve_aliases(value);
+                    self.spill_costs[value] = self.spill_costs[value].saturating_add(cost);
+                }
+            }
+        }
+
+        for vreg in virtregs.all_virtregs() {
+            let values = virtregs.values(vreg);
+            let cost = values
+                .iter()
+                .fold(0, |cost: u32, &v| cost.saturating_add(self.spill_costs[v]));
+            for &value in values {
+                self.spill_costs[value] = cost;
+            }
+        }
+    }
 }
 
 impl<'a> Context<'a> {
@@ -264,12 +379,33 @@ impl<'a> Context<'a> {
         // Remove kills from the pressure tracker.
         self.free_regs(kills);
 
-        // If inst is a call, spill all register values that are live across the call.
-        // This means that we don't currently take advantage of callee-saved registers.
-        // TODO: Be more sophisticated.
-        if call_sig.is_some() {
+        // If inst is a call, spill all register values that are live across the call, except the
+        // ones fitting in the registers that the callee's calling convention preserves.
+        if let Some(sig) = call_sig {
+            let mut preserved = self.call_preserved_regs(inst, sig);
             for lv in throughs {
-                if lv.affinity.is_reg() && !self.spills.contains(&lv.value) {
+                if let Affinity::Reg(rci) = lv.affinity {
+                    let rc = self.reginfo.rc(rci);
+                    if !self.spills.contains(&lv.value)
+                        && preserved
+                            .as_mut()
+                            .and_then(|regs| take_any(regs, rc))
+                            .is_none()
+                    {
+                        self.spill_reg(lv.value);
+                    }
+                }
+            }
+        }
+
+        // The stackmap of a safepoint only records references in stack slots, so spill the
+        // reference values that are live across an interrupt trap or a safepoint too.
+        if is_gc_point(&self.cur.func.dfg[inst]) {
+            for lv in throughs {
+                if lv.affinity.is_reg()
+                    && self.cur.func.dfg.value_type(lv.value).lane_type().is_ref()
+                    && !self.spills.contains(&lv.value)
+                {
                     self.spill_reg(lv.value);
                 }
             }
@@ -304,6 +440,21 @@ impl<'a> Context<'a> {
         self.take_live_regs(defs);
     }
 
+    // Get the registers preserved by the call `inst` with signature `sig` that are still
+    // available for the values live across the call once the return values are defined.
+    fn call_preserved_regs(&self, inst: Inst, sig: SigRef) -> Option<RegisterSet> {
+        let mut preserved = call_preserved_registers(self.cur.isa, &self.cur.func, inst, sig)?;
+        for ret in &self.cur.func.dfg.signatures[sig].returns {
+            if let ArgumentLoc::Reg(reg) = ret.location {
+                let rc = toprc_containing_regunit(reg, &self.reginfo);
+                if preserved.is_avail(rc, reg) {
+                    preserved.take(rc, reg);
+                }
+            }
+        }
+        Some(preserved)
+    }
+
     // Collect register uses that are noteworthy in one of the following ways:
     //
     // 1. It's a fixed register constraint.
@@ -487,9 +638,10 @@ impl<'a> Context<'a> {
     {
         // Find the best viable spill candidate.
         //
-        // The very simple strategy implemented here is to spill the value with the earliest def in
-        // the reverse post-order. This strategy depends on a good reload pass to generate good
-        // code.
+        // The very simple strategy implemented here is to spill the value whose virtual register
+        // has the lowest spill cost, keeping the reloads and spills out of the loops, and then
+        // the value with the earliest def in the reverse post-order. This strategy depends on a
+        // good reload pass to generate good code.
         //
         // We know that all candidate defs dominate the current instruction, so one of them will
         // dominate the others. That is the earliest def.
@@ -508,12 +660,15 @@ impl<'a> Context<'a> {
                 None
             })
             .min_by(|&a, &b| {
-                // Find the minimum candidate according to the RPO of their defs.
-                self.domtree.rpo_cmp(
-                    self.cur.func.dfg.value_def(a),
-                    self.cur.func.dfg.value_def(b),
-                    &self.cur.func.layout,
-                )
+                // Find the minimum candidate according to their spill costs, and then the RPO of
+                // their defs.
+                self.spill_costs[a].cmp(&self.spill_costs[b]).then_with(|| {
+                    self.domtree.rpo_cmp(
+                        self.cur.func.dfg.value_def(a),
+                        self.cur.func.dfg.value_def(b),
+                        &self.cur.func.layout,
+                    )
+                })
             })
     }
 
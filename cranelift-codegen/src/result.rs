@@ -1,5 +1,6 @@
 //! Result and error types representing the outcome of compiling a function.
 
+use crate::ir::ExternalName;
 use crate::verifier::VerifierErrors;
 use failure_derive::Fail;
 
@@ -30,6 +31,21 @@ pub enum CodegenError {
     /// is exceeded, compilation fails.
     #[fail(display = "Code for function is too large")]
     CodeTooLarge,
+
+    /// A feature enabled by the settings is not supported by the target ISA.
+    #[fail(display = "Unsupported feature: {}", _0)]
+    Unsupported(&'static str),
+
+    /// The address of a symbol referenced by the function couldn't be resolved.
+    ///
+    /// This is only reported when emitting relocation-free machine code.
+    #[fail(display = "Unresolved symbol: {}", _0)]
+    UnresolvedSymbol(ExternalName),
+
+    /// A symbol referenced by the function is too far from the code to be reached by the
+    /// relocation referencing it.
+    #[fail(display = "Symbol out of the range of its relocation: {}", _0)]
+    RelocationOutOfRange(ExternalName),
 }
 
 /// A convenient alias for a `Result` that uses `CodegenError` as the error type.
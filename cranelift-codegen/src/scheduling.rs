@@ -0,0 +1,381 @@
+//! Post-register allocation instruction scheduling.
+//!
+//! This pass reorders the independent instructions of each EBB after register allocation, so the
+//! instructions with long latencies, like loads, start early and their results aren't needed right
+//! away. It uses the latency model of the ISA, and it leaves the function unchanged if the ISA
+//! doesn't have one.
+//!
+//! The instructions are scheduled in regions delimited by the instructions that can't move:
+//! branches, calls, instructions with other side effects, register diversions, and instructions
+//! that can trap, like `trapif` or `udiv`. The trapping instructions are barriers because they
+//! guard the instructions after them: a bounds check must stay before the `notrap` heap accesses
+//! it protects. Within a region, an instruction stays after the instructions it depends on
+//! through:
+//!
+//! - The registers it reads and writes, taking the diversions into account.
+//! - The CPU flags, which are also clobbered by many instructions that don't use them.
+//! - Memory, since stores stay in order with all the other memory accesses.
+//! - Traps, since the memory accesses that can trap stay in order, so the same one traps first.
+//!
+//! Each region is scheduled for a single-issue machine by a list scheduler: among the
+//! instructions whose dependencies are scheduled, it picks the one whose operands are available
+//! the earliest, preferring the one on the longest path to the end of the region, and then the
+//! original order.
+
+use crate::fx::FxHashMap;
+use crate::ir::{Function, Inst, InstructionData, MemFlags, ValueLoc};
+use crate::isa::{EncInfo, LatencyFn, RegUnit, TargetIsa};
+use crate::regalloc::RegDiversions;
+use crate::timing;
+use core::cmp;
+use std::vec;
+use std::vec::Vec;
+
+/// Maximum number of instructions in a region, bounding the quadratic scheduling time.
+const MAX_REGION_SIZE: usize = 64;
+
+/// Reorder the instructions of `func` to hide their latencies, using the latency model of `isa`.
+///
+/// The function must have been register allocated.
+pub fn do_post_ra_scheduling(func: &mut Function, isa: &dyn TargetIsa) {
+    let _tt = timing::post_ra_scheduling();
+    let latency = match isa.latency_model() {
+        Some(latency) => latency,
+        None => return,
+    };
+    let encinfo = isa.encoding_info();
+    let mut divert = RegDiversions::new();
+    let mut region = Vec::new();
+
+    let mut next_ebb = func.layout.entry_block();
+    while let Some(ebb) = next_ebb {
+        next_ebb = func.layout.next_ebb(ebb);
+        divert.clear();
+        let mut next_inst = func.layout.first_inst(ebb);
+        while let Some(inst) = next_inst {
+            next_inst = func.layout.next_inst(inst);
+            if is_barrier(func, inst) {
+                schedule_region(func, &region, Some(inst), &divert, &encinfo, latency);
+                region.clear();
+                divert.apply(&func.dfg[inst]);
+                continue;
+            }
+            region.push(inst);
+            if region.len() == MAX_REGION_SIZE {
+                schedule_region(func, &region, next_inst, &divert, &encinfo, latency);
+                region.clear();
+            }
+        }
+        if !region.is_empty() {
+            schedule_region(func, &region, None, &divert, &encinfo, latency);
+            region.clear();
+        }
+    }
+}
+
+/// Is `inst` an instruction that can't be moved, and that no instruction can move across?
+fn is_barrier(func: &Function, inst: Inst) -> bool {
+    let data = &func.dfg[inst];
+    match *data {
+        InstructionData::RegMove { .. }
+        | InstructionData::RegSpill { .. }
+        | InstructionData::RegFill { .. }
+        | InstructionData::CopySpecial { .. } => return true,
+        _ => {}
+    }
+    let opcode = data.opcode();
+    opcode.is_branch()
+        || opcode.is_terminator()
+        || opcode.is_call()
+        || opcode.can_trap()
+        || opcode.other_side_effects()
+}
+
+/// Get the memory flags of `inst`, if it has some.
+fn memflags(func: &Function, inst: Inst) -> Option<MemFlags> {
+    match func.dfg[inst] {
+        InstructionData::Load { flags, .. }
+        | InstructionData::LoadComplex { flags, .. }
+        | InstructionData::Store { flags, .. }
+        | InstructionData::StoreComplex { flags, .. } => Some(flags),
+        _ => None,
+    }
+}
+
+/// The dependencies of the instructions of a region.
+#[derive(Default)]
+struct Dependencies {
+    /// The successors of each instruction, with the minimum number of cycles between them.
+    succs: Vec<Vec<(usize, u32)>>,
+    /// The number of predecessors of each instruction.
+    num_preds: Vec<usize>,
+
+    /// The last instruction writing each register.
+    reg_def: FxHashMap<RegUnit, usize>,
+    /// The instructions reading each register since it was last written.
+    reg_uses: FxHashMap<RegUnit, Vec<usize>>,
+    /// The last instruction writing the CPU flags.
+    flags_def: Option<usize>,
+    /// The instructions reading the CPU flags since they were last written.
+    flags_uses: Vec<usize>,
+    /// The last instruction writing memory.
+    store: Option<usize>,
+    /// The instructions reading memory since it was last written.
+    loads: Vec<usize>,
+    /// The last memory access that can trap.
+    trap: Option<usize>,
+}
+
+impl Dependencies {
+    /// Add instruction `i`, which accesses `accesses`.
+    fn add_inst(&mut self, i: usize, latencies: &[u32], accesses: &Accesses) {
+        let mut preds = Vec::new();
+
+        // Reads follow the writes, once their results are available.
+        for reg in &accesses.reg_reads {
+            if let Some(&def) = self.reg_def.get(reg) {
+                preds.push((def, latencies[def]));
+            }
+        }
+        if accesses.flags_read {
+            if let Some(def) = self.flags_def {
+                preds.push((def, latencies[def]));
+            }
+        }
+
+        // Writes follow the reads and the writes.
+        for reg in &accesses.reg_writes {
+            if let Some(&def) = self.reg_def.get(reg) {
+                preds.push((def, 0));
+            }
+            if let Some(uses) = self.reg_uses.get(reg) {
+                preds.extend(uses.iter().map(|&user| (user, 0)));
+            }
+        }
+        if accesses.flags_write {
+            if let Some(def) = self.flags_def {
+                preds.push((def, 0));
+            }
+            preds.extend(self.flags_uses.iter().map(|&user| (user, 0)));
+        }
+
+        // Stores stay in order with all the memory accesses.
+        if accesses.mem_read || accesses.mem_write {
+            if let Some(store) = self.store {
+                preds.push((store, 1));
+            }
+        }
+        if accesses.mem_write {
+            preds.extend(self.loads.iter().map(|&load| (load, 0)));
+        }
+        if accesses.may_trap {
+            if let Some(trap) = self.trap {
+                preds.push((trap, 0));
+            }
+        }
+
+        self.succs.push(Vec::new());
+        self.num_preds.push(preds.len());
+        for (pred, cycles) in preds {
+            self.succs[pred].push((i, cycles));
+        }
+
+        for &reg in &accesses.reg_reads {
+            self.reg_uses.entry(reg).or_insert_with(Vec::new).push(i);
+        }
+        for &reg in &accesses.reg_writes {
+            self.reg_def.insert(reg, i);
+            if let Some(uses) = self.reg_uses.get_mut(&reg) {
+                uses.clear();
+            }
+        }
+        if accesses.flags_read {
+            self.flags_uses.push(i);
+        }
+        if accesses.flags_write {
+            self.flags_def = Some(i);
+            self.flags_uses.clear();
+        }
+        if accesses.mem_read {
+            self.loads.push(i);
+        }
+        if accesses.mem_write {
+            self.store = Some(i);
+            self.loads.clear();
+        }
+        if accesses.may_trap {
+            self.trap = Some(i);
+        }
+    }
+}
+
+/// The resources accessed by an instruction.
+///
+/// Registers are identified by their first register unit, which is enough for the ISAs with a
+/// latency model, whose registers all have a single unit.
+#[derive(Default)]
+struct Accesses {
+    reg_reads: Vec<RegUnit>,
+    reg_writes: Vec<RegUnit>,
+    flags_read: bool,
+    flags_write: bool,
+    mem_read: bool,
+    mem_write: bool,
+    may_trap: bool,
+}
+
+impl Accesses {
+    fn new(func: &Function, inst: Inst, divert: &RegDiversions, encinfo: &EncInfo) -> Self {
+        let mut accesses = Self::default();
+        for &arg in func.dfg.inst_args(inst) {
+            if func.dfg.value_type(arg).is_flags() {
+                accesses.flags_read = true;
+                continue;
+            }
+            match divert.get(arg, &func.locations) {
+                ValueLoc::Reg(reg) => accesses.reg_reads.push(reg),
+                ValueLoc::Stack(_) => accesses.mem_read = true,
+                ValueLoc::Unassigned => {}
+            }
+        }
+        for &result in func.dfg.inst_results(inst) {
+            if func.dfg.value_type(result).is_flags() {
+                accesses.flags_write = true;
+                continue;
+            }
+            match divert.get(result, &func.locations) {
+                ValueLoc::Reg(reg) => accesses.reg_writes.push(reg),
+                ValueLoc::Stack(_) => accesses.mem_write = true,
+                ValueLoc::Unassigned => {}
+            }
+        }
+        if let Some(constraints) = encinfo.operand_constraints(func.encodings[inst]) {
+            accesses.flags_write |= constraints.clobbers_flags;
+        }
+
+        let opcode = func.dfg[inst].opcode();
+        accesses.mem_read |= opcode.can_load();
+        accesses.mem_write |= opcode.can_store();
+        accesses.may_trap = memflags(func, inst).map_or(false, |flags| !flags.notrap());
+        accesses
+    }
+}
+
+/// Schedule the instructions of `region`, and move them before `before`, or to the end of their
+/// EBB.
+fn schedule_region(
+    func: &mut Function,
+    region: &[Inst],
+    before: Option<Inst>,
+    divert: &RegDiversions,
+    encinfo: &EncInfo,
+    latency: LatencyFn,
+) {
+    if region.len() < 2 {
+        return;
+    }
+
+    let latencies: Vec<u32> = region.iter().map(|&inst| latency(func, inst)).collect();
+    let mut deps = Dependencies::default();
+    for (i, &inst) in region.iter().enumerate() {
+        deps.add_inst(i, &latencies, &Accesses::new(func, inst, divert, encinfo));
+    }
+
+    // The number of cycles from the start of each instruction to the end of the region.
+    let mut heights = latencies.clone();
+    for i in (0..region.len()).rev() {
+        for &(succ, cycles) in &deps.succs[i] {
+            heights[i] = cmp::max(heights[i], cycles + heights[succ]);
+        }
+    }
+
+    let mut order = Vec::with_capacity(region.len());
+    let mut scheduled = vec![false; region.len()];
+    let mut available = vec![0; region.len()];
+    let mut cycle = 0;
+    while order.len() < region.len() {
+        let i = (0..region.len())
+            .filter(|&i| !scheduled[i] && deps.num_preds[i] == 0)
+            .min_by_key(|&i| (cmp::max(available[i], cycle), cmp::Reverse(heights[i]), i))
+            .expect("Cyclic dependencies");
+        let start = cmp::max(available[i], cycle);
+        cycle = start + 1;
+        scheduled[i] = true;
+        order.push(region[i]);
+        for &(succ, cycles) in &deps.succs[i] {
+            available[succ] = cmp::max(available[succ], start + cycles);
+            deps.num_preds[succ] -= 1;
+        }
+    }
+
+    if order == region {
+        return;
+    }
+    let ebb = func.layout.inst_ebb(region[0]).unwrap();
+    for &inst in region {
+        func.layout.remove_inst(inst);
+    }
+    for inst in order {
+        match before {
+            Some(before) => func.layout.insert_inst(inst, before),
+            None => func.layout.append_inst(inst, ebb),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{types, AbiParam, InstBuilder, Opcode};
+    use crate::isa;
+    use crate::settings;
+    use crate::Context;
+    use core::str::FromStr;
+    use target_lexicon::triple;
+
+    #[test]
+    fn hoist_load() {
+        let isa = isa::lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()));
+
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I64));
+        func.signature.params.push(AbiParam::new(types::I64));
+        func.signature.returns.push(AbiParam::new(types::I64));
+        let ebb0 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I64);
+        let v1 = func.dfg.append_ebb_param(ebb0, types::I64);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let v2 = pos.ins().iadd(v1, v1);
+            let v3 = pos.ins().load(types::I64, MemFlags::trusted(), v0, 0);
+            let v4 = pos.ins().iadd(v2, v3);
+            pos.ins().return_(&[v4]);
+        }
+
+        let mut ctx = Context::for_function(func);
+        ctx.compute_cfg();
+        ctx.legalize(&*isa).unwrap();
+        ctx.compute_domtree();
+        ctx.regalloc(&*isa).unwrap();
+        do_post_ra_scheduling(&mut ctx.func, &*isa);
+        ctx.verify_if(&*isa).unwrap();
+        ctx.verify_locations_if(&*isa).unwrap();
+
+        // The load starts before the independent addition, which runs while it completes.
+        let opcodes: Vec<_> = ctx
+            .func
+            .layout
+            .ebb_insts(ebb0)
+            .map(|inst| ctx.func.dfg[inst].opcode())
+            .filter(|&opcode| opcode != Opcode::Copy && opcode != Opcode::Regmove)
+            .collect();
+        assert_eq!(
+            opcodes,
+            [Opcode::Load, Opcode::Iadd, Opcode::Iadd, Opcode::Return]
+        );
+    }
+}
@@ -60,6 +60,20 @@ impl Builder {
         }
     }
 
+    /// Get the name of the settings group configured by this builder.
+    pub fn name(&self) -> &'static str {
+        self.template.name
+    }
+
+    /// Iterate over the settings and presets of this group, in definition order.
+    pub fn iter(&self) -> impl Iterator<Item = Setting> {
+        let template = self.template;
+        template
+            .descriptors
+            .iter()
+            .map(move |d| template.setting(d))
+    }
+
     /// Extract contents of builder once everything is configured.
     pub fn state_for(self, name: &str) -> Box<[u8]> {
         assert_eq!(name, self.template.name);
@@ -183,6 +197,98 @@ pub enum SetError {
 /// A result returned when changing a setting.
 pub type SetResult<T> = Result<T, SetError>;
 
+/// The kind of a setting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingKind {
+    /// A boolean setting.
+    Bool,
+    /// A numerical setting.
+    Num,
+    /// A setting taking one of a fixed set of named values.
+    Enum,
+    /// A preset, which is not a setting itself but enables a collection of boolean settings.
+    Preset,
+}
+
+/// Description of a setting or a preset, as listed by `Builder::iter`.
+#[derive(Clone, Copy, Debug)]
+pub struct Setting {
+    /// Name of the setting, as used by `Configurable::set`.
+    pub name: &'static str,
+    /// Documentation of the setting. Empty for presets.
+    pub description: &'static str,
+    /// Kind of the setting.
+    pub kind: SettingKind,
+    /// The possible values of an `Enum` setting.
+    pub values: Option<&'static [&'static str]>,
+}
+
+/// The value of a setting in a `Flags` object, as listed by its `iter` method.
+#[derive(Clone, Copy, Debug)]
+pub struct Value {
+    /// Description of the setting.
+    pub setting: Setting,
+    detail: detail::Detail,
+    byte: u8,
+    default_byte: u8,
+}
+
+impl Value {
+    /// Get the current value of an `Enum` setting.
+    pub fn as_enum(&self) -> Option<&'static str> {
+        self.setting
+            .values
+            .and_then(|values| values.get(usize::from(self.byte)))
+            .cloned()
+    }
+
+    /// Get the current value of a `Num` setting.
+    pub fn as_num(&self) -> Option<u8> {
+        match self.detail {
+            detail::Detail::Num => Some(self.byte),
+            _ => None,
+        }
+    }
+
+    /// Get the current value of a `Bool` setting.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.detail {
+            detail::Detail::Bool { bit } => Some(self.byte & (1 << bit) != 0),
+            _ => None,
+        }
+    }
+
+    /// Get the default value of this setting.
+    pub fn default_value(&self) -> Self {
+        Self {
+            byte: self.default_byte,
+            ..*self
+        }
+    }
+
+    /// Does this setting have its default value?
+    pub fn is_default(&self) -> bool {
+        let mask = match self.detail {
+            detail::Detail::Bool { bit } => 1 << bit,
+            _ => 0xff,
+        };
+        (self.byte ^ self.default_byte) & mask == 0
+    }
+}
+
+/// Display the value in the format accepted by `Configurable::set`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(value) = self.as_bool() {
+            write!(f, "{}", value)
+        } else if let Some(value) = self.as_enum() {
+            f.write_str(value)
+        } else {
+            write!(f, "{}", self.byte)
+        }
+    }
+}
+
 /// A reference to just the boolean predicates of a settings object.
 ///
 /// The settings objects themselves are generated and appear in the `isa/*/settings.rs` modules.
@@ -210,6 +316,7 @@ impl<'a> PredicateView<'a> {
 /// This module holds definitions that need to be public so the can be instantiated by generated
 /// code in other modules.
 pub mod detail {
+    use super::{Setting, SettingKind, Value};
     use crate::constant_hash;
     use core::fmt;
 
@@ -230,11 +337,42 @@ pub mod detail {
     }
 
     impl Template {
+        /// Get the public description of the setting `d`.
+        pub fn setting(&self, d: &Descriptor) -> Setting {
+            let (kind, values) = match d.detail {
+                Detail::Bool { .. } => (SettingKind::Bool, None),
+                Detail::Num => (SettingKind::Num, None),
+                Detail::Enum { last, enumerators } => {
+                    (SettingKind::Enum, Some(self.enums(last, enumerators)))
+                }
+                Detail::Preset => (SettingKind::Preset, None),
+            };
+            Setting {
+                name: d.name,
+                description: d.description,
+                kind,
+                values,
+            }
+        }
+
+        /// Get the value of the setting `d` in the settings `bytes`.
+        pub fn value(&self, d: &Descriptor, bytes: &[u8]) -> Value {
+            debug_assert!(!d.detail.is_preset());
+            let offset = d.offset as usize;
+            Value {
+                setting: self.setting(d),
+                detail: d.detail,
+                byte: bytes[offset],
+                default_byte: self.defaults[offset],
+            }
+        }
+
         /// Get enumerators corresponding to a `Details::Enum`.
-        pub fn enums(&self, last: u8, enumerators: u16) -> &[&'static str] {
+        pub fn enums(&self, last: u8, enumerators: u16) -> &'static [&'static str] {
             let from = enumerators as usize;
             let len = usize::from(last) + 1;
-            &self.enumerators[from..from + len]
+            let all = self.enumerators;
+            &all[from..from + len]
         }
 
         /// Format a setting value as a TOML string. This is mostly for use by the generated
@@ -288,12 +426,15 @@ pub mod detail {
         /// Offset of byte containing this setting.
         pub offset: u32,
 
+        /// Documentation of the setting, as defined in meta.
+        pub description: &'static str,
+
         /// Additional details, depending on the kind of setting.
         pub detail: Detail,
     }
 
     /// The different kind of settings along with descriptor bits that depend on the kind.
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Debug)]
     pub enum Detail {
         /// A boolean setting only uses one bit, numbered from LSB.
         Bool {
@@ -368,8 +509,9 @@ impl<'a> From<&'a dyn TargetIsa> for FlagsOrIsa<'a> {
 mod tests {
     use super::Configurable;
     use super::SetError::*;
-    use super::{builder, Flags};
+    use super::{builder, Flags, SettingKind};
     use std::string::ToString;
+    use std::vec::Vec;
 
     #[test]
     fn display_default() {
@@ -383,6 +525,7 @@ mod tests {
              baldrdash_prologue_words = 0\n\
              probestack_size_log2 = 12\n\
              enable_verifier = true\n\
+             enable_fast_verifier = false\n\
              is_pic = false\n\
              colocated_libcalls = false\n\
              avoid_div_traps = false\n\
@@ -391,6 +534,8 @@ mod tests {
              enable_simd = false\n\
              enable_atomics = true\n\
              enable_safepoints = false\n\
+             enable_auto_safepoints = false\n\
+             enable_shadow_stack = false\n\
              allones_funcaddrs = false\n\
              probestack_enabled = true\n\
              probestack_func_adjusts_sp = false\n\
@@ -435,4 +580,68 @@ mod tests {
         assert_eq!(f.enable_simd(), false);
         assert_eq!(f.opt_level(), super::OptLevel::Best);
     }
+
+    #[test]
+    fn apply_preset() {
+        let mut b = builder();
+        assert_eq!(
+            b.set("deterministic", "true"),
+            Err(BadName("deterministic".to_string()))
+        );
+        assert_eq!(b.enable("deterministic"), Ok(()));
+
+        let f = Flags::new(b);
+        assert_eq!(f.enable_nan_canonicalization(), true);
+        assert_eq!(f.avoid_div_traps(), true);
+        assert_eq!(f.enable_simd(), false);
+    }
+
+    #[test]
+    fn iterate() {
+        let mut b = builder();
+        let opt_level = b.iter().find(|s| s.name == "opt_level").unwrap();
+        assert_eq!(opt_level.kind, SettingKind::Enum);
+        assert_eq!(opt_level.values, Some(&["default", "best", "fastest"][..]));
+        assert!(opt_level.description.starts_with("Optimization level:"));
+        let presets: Vec<_> = b
+            .iter()
+            .filter(|s| s.kind == SettingKind::Preset)
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(presets, ["deterministic"]);
+
+        b.set("opt_level", "best").unwrap();
+        b.set("probestack_size_log2", "16").unwrap();
+        let f = Flags::new(b);
+        let values: Vec<_> = f.iter().collect();
+        assert_eq!(values.len(), builder().iter().count() - presets.len());
+
+        let opt_level = values[0];
+        assert_eq!(opt_level.setting.name, "opt_level");
+        assert_eq!(opt_level.as_enum(), Some("best"));
+        assert_eq!(opt_level.as_bool(), None);
+        assert!(!opt_level.is_default());
+        assert_eq!(opt_level.default_value().to_string(), "default");
+
+        let changed: Vec<_> = values
+            .iter()
+            .filter(|v| !v.is_default())
+            .map(|v| (v.setting.name, v.to_string()))
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                ("opt_level", "best".to_string()),
+                ("probestack_size_log2", "16".to_string())
+            ]
+        );
+
+        let enable_verifier = values
+            .iter()
+            .find(|v| v.setting.name == "enable_verifier")
+            .unwrap();
+        assert_eq!(enable_verifier.setting.kind, SettingKind::Bool);
+        assert_eq!(enable_verifier.as_bool(), Some(true));
+        assert_eq!(enable_verifier.as_num(), None);
+    }
 }
@@ -933,6 +933,8 @@ fn branch_order(pos: &mut FuncCursor, cfg: &mut ControlFlowGraph, ebb: Ebb, inst
         }
     }
 
+    // The conditional branch now goes to the other destination.
+    pos.func.branch_hints[cond_inst] = pos.func.branch_hints[cond_inst].map(|h| h.inverse());
     cfg.recompute_ebb(pos.func, ebb);
 }
 
@@ -1,14 +1,37 @@
 //! Computing stack layout.
 
-use crate::ir::stackslot::{StackOffset, StackSize, StackSlotKind};
+use crate::ir::stackslot::{StackOffset, StackSize, StackSlotKind, StackSlotLifetime};
 use crate::ir::StackSlots;
 use crate::result::{CodegenError, CodegenResult};
 use core::cmp::{max, min};
+use std::vec::Vec;
+
+/// A stack slot that has been assigned an offset by `layout_stack`.
+struct PlacedSlot {
+    offset: StackOffset,
+    size: StackSize,
+    lifetime: Option<StackSlotLifetime>,
+}
+
+impl PlacedSlot {
+    /// Can a slot with `lifetime` occupy `size` bytes at `offset` without clobbering this one?
+    fn is_compatible(
+        &self,
+        offset: StackOffset,
+        size: StackSize,
+        lifetime: StackSlotLifetime,
+    ) -> bool {
+        let disjoint_memory = offset + size as StackOffset <= self.offset
+            || self.offset + self.size as StackOffset <= offset;
+        disjoint_memory || self.lifetime.map_or(false, |l| !l.overlaps(lifetime))
+    }
+}
 
 /// Compute the stack frame layout.
 ///
 /// Determine the total size of this stack frame and assign offsets to all `Spill` and
-/// `Explicit` stack slots.
+/// `Explicit` stack slots. Explicit slots with disjoint lifetimes may be assigned overlapping
+/// memory.
 ///
 /// The total frame size will be a multiple of `alignment` which must be a power of two.
 ///
@@ -69,6 +92,7 @@ pub fn layout_stack(frame: &mut StackSlots, alignment: StackSize) -> CodegenResu
     // The offset is negative, growing downwards.
     // Start with the smallest alignments for better packing.
     let mut offset = incoming_min;
+    let mut placed = Vec::new();
     debug_assert!(min_align.is_power_of_two());
     while min_align <= alignment {
         for slot in frame.values_mut() {
@@ -84,6 +108,26 @@ pub fn layout_stack(frame: &mut StackSlots, alignment: StackSize) -> CodegenResu
                 StackSlotKind::IncomingArg | StackSlotKind::OutgoingArg => continue,
             }
 
+            // Try to share the memory of a slot that is never alive at the same time.
+            if let Some(lifetime) = slot.lifetime {
+                let shared = placed.iter().map(|p: &PlacedSlot| p.offset).find(|&o| {
+                    o & (min_align as StackOffset - 1) == 0
+                        && o + slot.size as StackOffset <= incoming_min
+                        && placed
+                            .iter()
+                            .all(|p| p.is_compatible(o, slot.size, lifetime))
+                });
+                if let Some(o) = shared {
+                    slot.offset = Some(o);
+                    placed.push(PlacedSlot {
+                        offset: o,
+                        size: slot.size,
+                        lifetime: Some(lifetime),
+                    });
+                    continue;
+                }
+            }
+
             offset = offset
                 .checked_sub(slot.size as StackOffset)
                 .ok_or(CodegenError::ImplLimitExceeded)?;
@@ -91,6 +135,11 @@ pub fn layout_stack(frame: &mut StackSlots, alignment: StackSize) -> CodegenResu
             // Aligning the negative offset can never cause overflow. We're only clearing bits.
             offset &= -(min_align as StackOffset);
             slot.offset = Some(offset);
+            placed.push(PlacedSlot {
+                offset,
+                size: slot.size,
+                lifetime: slot.lifetime,
+            });
         }
 
         // Move on to the next higher alignment.
@@ -113,7 +162,7 @@ mod tests {
     use super::layout_stack;
     use crate::ir::stackslot::StackOffset;
     use crate::ir::types;
-    use crate::ir::{StackSlotData, StackSlotKind, StackSlots};
+    use crate::ir::{StackSlotData, StackSlotKind, StackSlotLifetime, StackSlots};
     use crate::result::CodegenError;
 
     #[test]
@@ -208,4 +257,36 @@ mod tests {
         assert_eq!(sss[ss1].offset, Some(-8));
         assert_eq!(sss[ss2].offset, Some(-12));
     }
+
+    #[test]
+    fn lifetimes() {
+        let sss = &mut StackSlots::new();
+        let mut explicit = |size, lifetime| {
+            let mut data = StackSlotData::new(StackSlotKind::ExplicitSlot, size);
+            data.lifetime = lifetime;
+            sss.push(data)
+        };
+
+        // Two sibling scopes nested in an outer one, followed by a slot alive everywhere.
+        let outer = explicit(8, Some(StackSlotLifetime::new(0, 5)));
+        let first = explicit(8, Some(StackSlotLifetime::new(1, 2)));
+        let second = explicit(4, Some(StackSlotLifetime::new(3, 4)));
+        let third = explicit(8, Some(StackSlotLifetime::new(3, 4)));
+        let global = explicit(4, None);
+
+        assert_eq!(layout_stack(sss, 8), Ok(24));
+        assert_eq!(sss[second].offset, Some(-4));
+        assert_eq!(sss[global].offset, Some(-8));
+        assert_eq!(sss[outer].offset, Some(-16));
+        assert_eq!(sss[first].offset, Some(-24));
+        assert_eq!(sss[third].offset, Some(-24));
+
+        // A slot that doesn't fit in the memory it would share is placed below it.
+        let big = sss.push(StackSlotData {
+            lifetime: Some(StackSlotLifetime::new(6, 7)),
+            ..StackSlotData::new(StackSlotKind::ExplicitSlot, 32)
+        });
+        assert_eq!(layout_stack(sss, 8), Ok(56));
+        assert_eq!(sss[big].offset, Some(-56));
+    }
 }
@@ -0,0 +1,103 @@
+//! Static stack usage analysis.
+//!
+//! After compilation, the size of a function's stack frame is fixed. Combined with the functions
+//! it calls, embedders can compute an upper bound of the stack used by a whole program, as long as
+//! the call graph has no cycles.
+
+use crate::ir::instructions::CallInfo;
+use crate::ir::stackslot::StackSize;
+use crate::ir::{ExternalName, Function, Signature};
+use std::vec::Vec;
+
+/// Stack usage of a compiled function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackUsage {
+    /// The size of the stack frame in bytes.
+    ///
+    /// This is the distance from the stack pointer in the function to the stack pointer in its
+    /// caller, so it includes the return address, the saved registers, and the space for outgoing
+    /// call arguments. The stack used by a call is the frame size of the caller plus the stack
+    /// used by the callee.
+    pub frame_size: StackSize,
+
+    /// The functions called directly, including library calls inserted by the legalizer.
+    pub direct_calls: Vec<ExternalName>,
+
+    /// The signatures of the functions called indirectly, as legalized for the target ISA.
+    ///
+    /// An indirect call may reach any function with a matching signature.
+    pub indirect_calls: Vec<Signature>,
+}
+
+impl StackUsage {
+    /// Compute the stack usage of `func`.
+    ///
+    /// Returns `None` if the stack frame of the function hasn't been laid out yet.
+    pub fn from_function(func: &Function) -> Option<Self> {
+        let frame_size = func.stack_slots.frame_size?;
+        let mut direct_calls = Vec::new();
+        let mut indirect_calls = Vec::new();
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                match func.dfg[inst].analyze_call(&func.dfg.value_lists) {
+                    CallInfo::Direct(func_ref, _) => {
+                        let name = &func.dfg.ext_funcs[func_ref].name;
+                        if !direct_calls.contains(name) {
+                            direct_calls.push(name.clone());
+                        }
+                    }
+                    CallInfo::Indirect(sig_ref, _) => {
+                        let sig = &func.dfg.signatures[sig_ref];
+                        if !indirect_calls.contains(sig) {
+                            indirect_calls.push(sig.clone());
+                        }
+                    }
+                    CallInfo::Raw(..) | CallInfo::NotACall => {}
+                }
+            }
+        }
+        Some(Self {
+            frame_size,
+            direct_calls,
+            indirect_calls,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StackUsage;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{types, AbiParam, ExtFuncData, ExternalName, Function, InstBuilder, Signature};
+    use crate::isa::CallConv;
+
+    #[test]
+    fn calls() {
+        let mut func = Function::new();
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I64));
+        let sig_ref = func.import_signature(sig.clone());
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("callee"),
+            signature: sig_ref,
+            colocated: false,
+        });
+        let ebb0 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I64);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().call(callee, &[v0]);
+            pos.ins().call(callee, &[v0]);
+            pos.ins().call_indirect(sig_ref, v0, &[v0]);
+            pos.ins().return_(&[]);
+        }
+        assert_eq!(StackUsage::from_function(&func), None);
+
+        func.stack_slots.frame_size = Some(32);
+        let usage = StackUsage::from_function(&func).unwrap();
+        assert_eq!(usage.frame_size, 32);
+        assert_eq!(usage.direct_calls, [ExternalName::testcase("callee")]);
+        assert_eq!(usage.indirect_calls, [sig]);
+    }
+}
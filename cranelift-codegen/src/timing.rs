@@ -46,22 +46,33 @@ define_passes! {
     wasm_translate_function: "Translate WASM function",
 
     verifier: "Verify Cranelift IR",
+    verifier_fast: "Verify Cranelift IR control flow",
     verify_cssa: "Verify CSSA",
     verify_liveness: "Verify live ranges",
     verify_locations: "Verify value locations",
+    verify_safepoints: "Verify safepoints",
+    verify_abi: "Verify ABI conformance",
+    verify_encoding_sizes: "Verify encoding sizes",
     verify_flags: "Verify CPU flags",
 
     compile: "Compilation passes",
     flowgraph: "Control flow graph",
     domtree: "Dominator tree",
     loop_analysis: "Loop analysis",
+    osr_entry: "On-stack replacement entry",
+    instrument: "Counter instrumentation",
+    value_trace: "Value tracing",
     postopt: "Post-legalization rewriting",
     preopt: "Pre-legalization rewriting",
     dce: "Dead code elimination",
+    bounds_checks: "Bounds check coalescing",
     legalize: "Legalization",
     gvn: "Global value numbering",
     licm: "Loop invariant code motion",
     unreachable_code: "Remove unreachable blocks",
+    block_placement: "Block placement",
+    insert_safepoints: "Safepoint insertion",
+    eliminate_safepoints: "Redundant safepoint elimination",
 
     regalloc: "Register allocation",
     ra_liveness: "RA liveness analysis",
@@ -71,6 +82,7 @@ define_passes! {
     ra_coloring: "RA coloring",
 
     prologue_epilogue: "Prologue/epilogue insertion",
+    post_ra_scheduling: "Post-RA instruction scheduling",
     shrink_instructions: "Instruction encoding shrinking",
     relax_branches: "Branch relaxation",
     binemit: "Binary machine code emission",
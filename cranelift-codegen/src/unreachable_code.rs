@@ -4,6 +4,8 @@ use crate::cursor::{Cursor, FuncCursor};
 use crate::dominator_tree::DominatorTree;
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir;
+use crate::ir::instructions::CallInfo;
+use crate::ir::InstBuilder;
 use crate::timing;
 use log::debug;
 
@@ -44,3 +46,58 @@ pub fn eliminate_unreachable_code(
         pos.func.layout.remove_ebb(ebb);
     }
 }
+
+/// Replace the instructions following calls to `noreturn` functions with a trap.
+///
+/// Execution never continues after such a call, so the rest of the EBB is unreachable, and so are
+/// the EBBs that were only reachable through it. The CFG is updated, but the unreachable EBBs
+/// must still be deleted by `eliminate_unreachable_code` with a recomputed dominator tree, since
+/// they may use values defined by the deleted instructions.
+///
+/// Returns `true` if the function was changed.
+pub fn trap_after_noreturn_calls(func: &mut ir::Function, cfg: &mut ControlFlowGraph) -> bool {
+    let _tt = timing::unreachable_code();
+    if !func.dfg.ext_func_attributes.values().any(|a| a.noreturn) {
+        return false;
+    }
+
+    let mut changed = false;
+    let mut pos = FuncCursor::new(func);
+    while let Some(ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            let noreturn = match pos.func.dfg[inst].analyze_call(&pos.func.dfg.value_lists) {
+                CallInfo::Direct(func_ref, _) => {
+                    pos.func.dfg.ext_func_attributes[func_ref].noreturn
+                }
+                _ => false,
+            };
+            if !noreturn || is_trap_terminator(pos.func, inst) {
+                continue;
+            }
+
+            debug!(
+                "Trapping after noreturn call {}",
+                pos.func.dfg.display_inst(inst, None)
+            );
+            while let Some(next) = pos.func.layout.next_inst(inst) {
+                pos.func.layout.remove_inst(next);
+            }
+            pos.goto_after_inst(inst);
+            pos.ins().trap(ir::TrapCode::UnreachableCodeReached);
+            cfg.recompute_ebb(pos.func, ebb);
+            changed = true;
+            break;
+        }
+    }
+    changed
+}
+
+/// Is `inst` followed by a trap ending its EBB?
+fn is_trap_terminator(func: &ir::Function, inst: ir::Inst) -> bool {
+    match func.layout.next_inst(inst) {
+        Some(next) => {
+            func.dfg[next].opcode() == ir::Opcode::Trap && func.layout.next_inst(next).is_none()
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,181 @@
+//! Value tracing.
+//!
+//! This pass inserts a call to a hook function after the definition of every value carrying one
+//! of the selected value labels, so the values computed by a program can be logged at runtime.
+//! Comparing the traces of a function compiled with different settings helps finding the first
+//! value that a miscompilation gets wrong, without a debugger.
+//!
+//! The hook takes the label as an `i32` and the bits of the value as an `i64`. Integers are zero
+//! extended, booleans are converted to 0 or 1, and floating point numbers are passed as their bit
+//! patterns. Values of other types, such as vectors and CPU flags, aren't traced.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::ir::{
+    types, AbiParam, ExtFuncData, ExternalName, FuncRef, Function, InstBuilder, Signature, Type,
+    Value, ValueDef, ValueLabel, ValueLabelAssignments,
+};
+use crate::isa::CallConv;
+use crate::timing;
+use std::vec::Vec;
+
+/// Insert calls to the hook `hook_name` tracing the values labeled with one of `labels`.
+///
+/// The labels of the values are taken from `func.dfg.values_labels`, so the function must have
+/// been built with debug info collection enabled. The hook is called with the `call_conv` calling
+/// convention.
+pub fn do_value_tracing(
+    func: &mut Function,
+    labels: &[ValueLabel],
+    hook_name: ExternalName,
+    call_conv: CallConv,
+) {
+    let _tt = timing::value_trace();
+    let traced = traced_values(func, labels);
+    if traced.is_empty() {
+        return;
+    }
+
+    let mut sig = Signature::new(call_conv);
+    sig.params.push(AbiParam::new(types::I32));
+    sig.params.push(AbiParam::new(types::I64));
+    let signature = func.import_signature(sig);
+    let hook = func.import_function(ExtFuncData {
+        name: hook_name,
+        signature,
+        colocated: false,
+    });
+
+    for (value, label) in traced {
+        let mut pos = FuncCursor::new(func);
+        match pos.func.dfg.value_def(value) {
+            ValueDef::Result(inst, _) => {
+                let srcloc = pos.func.srclocs[inst];
+                pos.goto_after_inst(inst);
+                pos.set_srcloc(srcloc);
+            }
+            ValueDef::Param(ebb, _) => pos.goto_first_insertion_point(ebb),
+        }
+        trace_value(&mut pos, hook, value, label);
+    }
+}
+
+/// Get the values to trace with their labels, in layout order.
+fn traced_values(func: &Function, labels: &[ValueLabel]) -> Vec<(Value, ValueLabel)> {
+    let values_labels = match func.dfg.values_labels {
+        Some(ref values_labels) => values_labels,
+        None => return Vec::new(),
+    };
+
+    let mut traced = Vec::new();
+    let mut add_value = |value: Value| {
+        if !is_traceable_type(func.dfg.value_type(value)) {
+            return;
+        }
+        if let Some(ValueLabelAssignments::Starts(starts)) = values_labels.get(&value) {
+            for start in starts {
+                if labels.contains(&start.label) && !traced.contains(&(value, start.label)) {
+                    traced.push((value, start.label));
+                }
+            }
+        }
+    };
+    for ebb in func.layout.ebbs() {
+        for &param in func.dfg.ebb_params(ebb) {
+            add_value(param);
+        }
+        for inst in func.layout.ebb_insts(ebb) {
+            for &result in func.dfg.inst_results(inst) {
+                add_value(result);
+            }
+        }
+    }
+    traced
+}
+
+/// Can values of type `ty` be passed to the hook?
+fn is_traceable_type(ty: Type) -> bool {
+    !ty.is_vector() && (ty.is_bool() || ty.is_float() || (ty.is_int() && ty.bits() <= 64))
+}
+
+/// Insert a call to `hook` passing `label` and the bits of `value` at `pos`.
+fn trace_value(pos: &mut FuncCursor, hook: FuncRef, value: Value, label: ValueLabel) {
+    let ty = pos.func.dfg.value_type(value);
+    let bits = if ty.is_bool() {
+        pos.ins().bint(types::I64, value)
+    } else if ty == types::F32 {
+        let bits = pos.ins().bitcast(types::I32, value);
+        pos.ins().uextend(types::I64, bits)
+    } else if ty == types::F64 {
+        pos.ins().bitcast(types::I64, value)
+    } else if ty == types::I64 {
+        value
+    } else {
+        pos.ins().uextend(types::I64, value)
+    };
+    let label = pos.ins().iconst(types::I32, i64::from(label.as_u32()));
+    pos.ins().call(hook, &[label, bits]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{Opcode, SourceLoc, ValueLabelStart};
+    use std::vec;
+
+    #[test]
+    fn trace_labeled_values() {
+        let mut func = Function::new();
+        func.dfg.collect_debug_info();
+        let ebb0 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::F32);
+        let (v1, v2);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            v1 = pos.ins().iconst(types::I8, 3);
+            v2 = pos.ins().iconst(types::I64, 4);
+            pos.ins().return_(&[]);
+        }
+        let label = |n| {
+            ValueLabelAssignments::Starts(vec![ValueLabelStart {
+                from: SourceLoc::default(),
+                label: ValueLabel::from_u32(n),
+            }])
+        };
+        let values_labels = func.dfg.values_labels.as_mut().unwrap();
+        values_labels.insert(v0, label(0));
+        values_labels.insert(v1, label(1));
+        values_labels.insert(v2, label(2));
+
+        let labels = [ValueLabel::from_u32(0), ValueLabel::from_u32(1)];
+        do_value_tracing(
+            &mut func,
+            &labels,
+            ExternalName::testcase("trace"),
+            CallConv::SystemV,
+        );
+
+        // `v2` isn't traced since its label wasn't selected.
+        let opcodes: Vec<_> = func
+            .layout
+            .ebb_insts(ebb0)
+            .map(|inst| func.dfg[inst].opcode())
+            .collect();
+        assert_eq!(
+            opcodes,
+            [
+                Opcode::Bitcast,
+                Opcode::Uextend,
+                Opcode::Iconst,
+                Opcode::Call,
+                Opcode::Iconst,
+                Opcode::Uextend,
+                Opcode::Iconst,
+                Opcode::Call,
+                Opcode::Iconst,
+                Opcode::Return,
+            ]
+        );
+        assert_eq!(func.dfg.ext_funcs.len(), 1);
+    }
+}
@@ -0,0 +1,355 @@
+//! Verify the ABI conformance of call sites.
+
+use crate::ir::instructions::CallInfo;
+use crate::ir::{
+    AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, Function, Inst, SigRef,
+};
+use crate::isa::TargetIsa;
+use crate::timing;
+use crate::verifier::{VerifierErrors, VerifierStepResult};
+use std::vec::Vec;
+
+/// Verify the argument and return value locations of the calls in `func`.
+///
+/// After legalization, the signature of every call must have its parameters and return values
+/// assigned to locations that follow the rules shared by the calling conventions of the target
+/// ISA:
+///
+/// - Every parameter and return value has a location.
+/// - The integers and booleans fit in a register of the target.
+/// - A parameter passed in a register uses a register of the class used for its type.
+/// - Two parameters or two return values never share a register or overlapping stack bytes.
+/// - The stack locations are aligned to the size of their parameter, up to the size of a pointer.
+///
+/// When the legalizer saved the signatures before legalization, which it only does when the
+/// verifier is enabled, the legalized signature must also pass the same number of bytes of
+/// normal parameters and return values, so none of them were lost or duplicated when they were
+/// split or extended.
+pub fn verify_abi(
+    isa: &dyn TargetIsa,
+    func: &Function,
+    errors: &mut VerifierErrors,
+) -> VerifierStepResult<()> {
+    let _tt = timing::verify_abi();
+    let verifier = AbiVerifier { isa, func };
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst].analyze_call(&func.dfg.value_lists) {
+                CallInfo::Direct(func_ref, _) => {
+                    let sig_ref = func.dfg.ext_funcs[func_ref].signature;
+                    verifier.check_call(inst, sig_ref, errors)?;
+                }
+                CallInfo::Indirect(sig_ref, _) => {
+                    verifier.check_call(inst, sig_ref, errors)?;
+                }
+                // Raw code pins its operands to registers instead of following a convention.
+                CallInfo::Raw(..) | CallInfo::NotACall => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+struct AbiVerifier<'a> {
+    isa: &'a dyn TargetIsa,
+    func: &'a Function,
+}
+
+impl<'a> AbiVerifier<'a> {
+    /// Check the locations in the signature `sig_ref` of the call `inst`.
+    fn check_call(
+        &self,
+        inst: Inst,
+        sig_ref: SigRef,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        let sig = &self.func.dfg.signatures[sig_ref];
+        let old_sig = self.func.dfg.old_signatures[sig_ref].as_ref();
+        self.check_locations(inst, sig_ref, "argument", &sig.params, errors)?;
+        self.check_locations(inst, sig_ref, "return value", &sig.returns, errors)?;
+        if let Some(old_sig) = old_sig {
+            let lists = [
+                ("argument", &sig.params, &old_sig.params),
+                ("return value", &sig.returns, &old_sig.returns),
+            ];
+            for &(kind, params, old_params) in &lists {
+                self.check_sizes(inst, sig_ref, kind, params, old_params, errors)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the locations in `params` follow the rules of the calling conventions.
+    fn check_locations(
+        &self,
+        inst: Inst,
+        sig_ref: SigRef,
+        kind: &str,
+        params: &[AbiParam],
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        let sig = &self.func.dfg.signatures[sig_ref];
+        let reginfo = self.isa.register_info();
+        let pointer_bytes = u32::from(self.isa.pointer_bytes());
+        // The register or the stack bytes used by each parameter so far.
+        let mut used: Vec<(usize, ArgumentLoc, u32)> = Vec::with_capacity(params.len());
+
+        for (i, param) in params.iter().enumerate() {
+            let ty = param.value_type;
+            let size = ty.bytes();
+            if !ty.is_float() && !ty.is_vector() && ty.bits() > u16::from(self.isa.pointer_bits()) {
+                return fatal!(
+                    errors,
+                    "abi-locations",
+                    inst,
+                    "{} {} of {} {} is {}, which doesn't fit in a register",
+                    kind,
+                    i,
+                    sig_ref,
+                    sig,
+                    ty
+                );
+            }
+
+            match param.location {
+                ArgumentLoc::Unassigned => {
+                    return fatal!(
+                        errors,
+                        "abi-locations",
+                        inst,
+                        "{} {} of {} {} has no location; the signature is not legalized",
+                        kind,
+                        i,
+                        sig_ref,
+                        sig
+                    );
+                }
+                ArgumentLoc::Reg(reg) => {
+                    let rc = self.isa.regclass_for_abi_type(ty);
+                    if !rc.contains(reg) {
+                        return fatal!(
+                            errors,
+                            "abi-locations",
+                            inst,
+                            "{} {} of {} is {} in {}, which is not a {} register",
+                            kind,
+                            i,
+                            sig_ref,
+                            ty,
+                            param.location.display(&reginfo),
+                            rc
+                        );
+                    }
+                }
+                ArgumentLoc::Stack(offset) => {
+                    let align = size.min(pointer_bytes).max(1);
+                    if offset < 0 || offset as u32 % align != 0 {
+                        return fatal!(
+                            errors,
+                            "abi-locations",
+                            inst,
+                            "{} {} of {} is {} at misaligned stack offset {}",
+                            kind,
+                            i,
+                            sig_ref,
+                            ty,
+                            offset
+                        );
+                    }
+                }
+            }
+
+            let overlap = used
+                .iter()
+                .find(|&&(_, loc, other_size)| match (loc, param.location) {
+                    (ArgumentLoc::Reg(a), ArgumentLoc::Reg(b)) => a == b,
+                    (ArgumentLoc::Stack(a), ArgumentLoc::Stack(b)) => {
+                        a < b + size as i32 && b < a + other_size as i32
+                    }
+                    _ => false,
+                });
+            if let Some(&(other, _, _)) = overlap {
+                return fatal!(
+                    errors,
+                    "abi-locations",
+                    inst,
+                    "{} {} of {} in {} overlaps {} {}",
+                    kind,
+                    i,
+                    sig_ref,
+                    param.location.display(&reginfo),
+                    kind,
+                    other
+                );
+            }
+            used.push((i, param.location, size));
+        }
+        Ok(())
+    }
+
+    /// Check that the normal values in `params` have the same size as the ones in `old_params`,
+    /// the same list before legalization.
+    fn check_sizes(
+        &self,
+        inst: Inst,
+        sig_ref: SigRef,
+        kind: &str,
+        params: &[AbiParam],
+        old_params: &[AbiParam],
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        // Extended values get wider, and struct arguments turn into pointers or parts.
+        let unchanged = |param: &AbiParam| param.purpose == ArgumentPurpose::Normal;
+        if old_params
+            .iter()
+            .any(|param| !unchanged(param) || param.extension != ArgumentExtension::None)
+        {
+            return Ok(());
+        }
+
+        let size = |params: &[AbiParam]| -> u32 {
+            params
+                .iter()
+                .filter(|param| unchanged(param))
+                .map(|param| param.value_type.bytes())
+                .sum()
+        };
+        let (have, expected) = (size(params), size(old_params));
+        if have != expected {
+            let reginfo = self.isa.register_info();
+            return fatal!(
+                errors,
+                "abi-sizes",
+                inst,
+                "{} {} passes {} bytes of {}s, but {} declares {}",
+                sig_ref,
+                self.func.dfg.signatures[sig_ref].display(&reginfo),
+                have,
+                kind,
+                self.func.dfg.old_signatures[sig_ref].as_ref().unwrap(),
+                expected
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify_abi;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::flowgraph::ControlFlowGraph;
+    use crate::ir::{types, AbiParam, ArgumentLoc, Function, InstBuilder, Signature};
+    use crate::isa::{self, CallConv, TargetIsa};
+    use crate::legalizer::legalize_function;
+    use crate::settings;
+    use crate::verifier::VerifierErrors;
+    use core::str::FromStr;
+    use std::boxed::Box;
+    use std::string::ToString;
+    use target_lexicon::triple;
+
+    // Make a riscv32 `TargetIsa`, if possible.
+    fn riscv32() -> Option<Box<dyn TargetIsa>> {
+        let shared_flags = settings::Flags::new(settings::builder());
+        isa::lookup(triple!("riscv32"))
+            .ok()
+            .map(|b| b.finish(shared_flags))
+    }
+
+    // Make a function calling a signature with an `i32` and an `i64` argument, and legalize it.
+    fn legalized_call(isa: &dyn TargetIsa) -> Function {
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I32));
+        func.signature.params.push(AbiParam::new(types::I32));
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I32));
+        sig.params.push(AbiParam::new(types::I64));
+        let sig_ref = func.import_signature(sig);
+        let ebb = func.dfg.make_ebb();
+        let callee = func.dfg.append_ebb_param(ebb, types::I32);
+        let arg = func.dfg.append_ebb_param(ebb, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            let wide = pos.ins().uextend(types::I64, arg);
+            pos.ins().call_indirect(sig_ref, callee, &[arg, wide]);
+            pos.ins().return_(&[]);
+        }
+
+        let mut cfg = ControlFlowGraph::with_function(&func);
+        legalize_function(&mut func, &mut cfg, isa);
+        func
+    }
+
+    #[test]
+    fn legalized() {
+        let isa = match riscv32() {
+            Some(isa) => isa,
+            None => return,
+        };
+        let func = legalized_call(&*isa);
+        let mut errors = VerifierErrors::default();
+        verify_abi(&*isa, &func, &mut errors).unwrap();
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn shared_register() {
+        let isa = match riscv32() {
+            Some(isa) => isa,
+            None => return,
+        };
+        let mut func = legalized_call(&*isa);
+        let sig_ref = func.dfg.signatures.keys().next().unwrap();
+        let params = &mut func.dfg.signatures[sig_ref].params;
+        assert_eq!(params.len(), 3);
+        params[2].location = params[0].location;
+
+        let mut errors = VerifierErrors::default();
+        assert!(verify_abi(&*isa, &func, &mut errors).is_err());
+        assert_eq!(
+            errors.to_string(),
+            "- inst1: argument 2 of sig0 in %x10 overlaps argument 0\n"
+        );
+    }
+
+    #[test]
+    fn wrong_register_class() {
+        let isa = match riscv32() {
+            Some(isa) => isa,
+            None => return,
+        };
+        let mut func = legalized_call(&*isa);
+        let sig_ref = func.dfg.signatures.keys().next().unwrap();
+        let reginfo = isa.register_info();
+        func.dfg.signatures[sig_ref].params[1].location =
+            ArgumentLoc::Reg(reginfo.parse_regunit("f11").unwrap());
+
+        let mut errors = VerifierErrors::default();
+        assert!(verify_abi(&*isa, &func, &mut errors).is_err());
+        assert_eq!(
+            errors.to_string(),
+            "- inst1: argument 1 of sig0 is i32 in %f11, which is not a GPR register\n"
+        );
+    }
+
+    #[test]
+    fn lost_argument() {
+        let isa = match riscv32() {
+            Some(isa) => isa,
+            None => return,
+        };
+        let mut func = legalized_call(&*isa);
+        let sig_ref = func.dfg.signatures.keys().next().unwrap();
+        func.dfg.signatures[sig_ref].params.pop();
+
+        let mut errors = VerifierErrors::default();
+        assert!(verify_abi(&*isa, &func, &mut errors).is_err());
+        assert_eq!(
+            errors.to_string(),
+            "- inst1: sig0 (i32 [%x10], i32 [%x12]) system_v passes 8 bytes of arguments, but \
+             (i32, i64) system_v declares 12\n"
+        );
+    }
+}
@@ -65,13 +65,19 @@ impl<'a> CssaVerifier<'a> {
 
             for (idx, &val) in values.iter().enumerate() {
                 if !self.func.dfg.value_is_valid(val) {
-                    return fatal!(errors, val, "Invalid value in {}", vreg);
+                    return fatal!(errors, "cssa-virtregs", val, "Invalid value in {}", vreg);
                 }
                 if !self.func.dfg.value_is_attached(val) {
-                    return fatal!(errors, val, "Detached value in {}", vreg);
+                    return fatal!(errors, "cssa-virtregs", val, "Detached value in {}", vreg);
                 }
                 if self.liveness.get(val).is_none() {
-                    return fatal!(errors, val, "Value in {} has no live range", vreg);
+                    return fatal!(
+                        errors,
+                        "cssa-virtregs",
+                        val,
+                        "Value in {} has no live range",
+                        vreg
+                    );
                 };
 
                 // Check topological ordering with the previous values in the virtual register.
@@ -84,6 +90,7 @@ impl<'a> CssaVerifier<'a> {
                     if prev_def == def {
                         return fatal!(
                             errors,
+                            "cssa-virtregs",
                             val,
                             "Values {} and {} in {} = {} defined at the same program point",
                             prev_val,
@@ -99,6 +106,7 @@ impl<'a> CssaVerifier<'a> {
                     {
                         return fatal!(
                             errors,
+                            "cssa-virtregs",
                             val,
                             "Value in {} = {} def dominates previous {}",
                             vreg,
@@ -122,6 +130,7 @@ impl<'a> CssaVerifier<'a> {
                         if self.liveness[prev_val].overlaps_def(def, def_ebb, ctx) {
                             return fatal!(
                                 errors,
+                                "cssa-virtregs",
                                 val,
                                 "Value def in {} = {} interferes with {}",
                                 vreg,
@@ -155,6 +164,7 @@ impl<'a> CssaVerifier<'a> {
                     if !self.virtregs.same_class(ebb_param, pred_arg) {
                         return fatal!(
                             errors,
+                            "cssa-interference",
                             pred,
                             "{} and {} must be in the same virtual register",
                             ebb_param,
@@ -0,0 +1,164 @@
+//! Verify the sizes of the encodings in emitted machine code.
+
+use crate::binemit::{Addend, CodeOffset, CodeSink, Reloc};
+use crate::ir::{ExternalName, Function, Inst, JumpTable, SourceLoc, TrapCode, Value};
+use crate::isa::{BranchRange, TargetIsa};
+use crate::regalloc::RegDiversions;
+use crate::timing;
+use crate::verifier::{VerifierErrors, VerifierStepResult};
+
+/// Verify that the sizes of the encodings of `func` match the layout of `code`.
+///
+/// The function must have been compiled for `isa`, and `code` is the output of `emit_to_memory`.
+///
+/// The layout of the code is recomputed from the sizes that the encoding tables give to each
+/// instruction, independently from the code emitters. The bytes of the instructions aren't
+/// decoded, so an emitter producing the wrong bytes of the right size isn't caught:
+///
+/// - Every instruction emitted on its own must produce as many bytes as the size of its
+///   encoding recipe, which is the size used by branch relaxation.
+/// - EBBs must start at the offsets computed by branch relaxation, which are the ones used to
+///   compute the branch displacements.
+/// - The destination of each branch must be in the range of its encoding.
+/// - The instructions must fit in `code`.
+///
+/// Finally, the jump tables must contain the offsets of their destinations.
+pub fn verify_encoding_sizes(
+    isa: &dyn TargetIsa,
+    func: &Function,
+    code: &[u8],
+    errors: &mut VerifierErrors,
+) -> VerifierStepResult<()> {
+    let _tt = timing::verify_encoding_sizes();
+    let encinfo = isa.encoding_info();
+    let mut divert = RegDiversions::new();
+    let mut offset = 0;
+
+    for ebb in func.layout.ebbs() {
+        divert.clear();
+        if func.offsets[ebb] != offset {
+            return fatal!(
+                errors,
+                "encoding-sizes",
+                ebb,
+                "starts at offset {:#x}, but branches to it use offset {:#x}",
+                offset,
+                func.offsets[ebb]
+            );
+        }
+
+        for inst in func.layout.ebb_insts(ebb) {
+            let enc = func.encodings[inst];
+            let size = encinfo.byte_size(enc, inst, &divert, func);
+            let mut sink = SizeSink { offset, size: 0 };
+            isa.emit_inst(func, inst, &mut divert, &mut sink);
+
+            if sink.size != size {
+                return fatal!(
+                    errors,
+                    "encoding-sizes",
+                    inst,
+                    "{} emitted {} bytes, but its size is {}",
+                    encinfo.display(enc),
+                    sink.size,
+                    size
+                );
+            }
+            if let Some(range) = encinfo.branch_range(enc) {
+                check_branch_range(func, inst, offset, range, errors)?;
+            }
+            if offset as usize + size as usize > code.len() {
+                return fatal!(
+                    errors,
+                    "encoding-sizes",
+                    inst,
+                    "ends at offset {:#x}, past the end of the code",
+                    offset + size
+                );
+            }
+            offset += size;
+        }
+    }
+
+    for (jt, jt_data) in func.jump_tables.iter() {
+        let jt_offset = func.jt_offsets[jt];
+        for (i, &ebb) in jt_data.iter().enumerate() {
+            let entry = jt_offset as usize + 4 * i;
+            let expected = func.offsets[ebb].wrapping_sub(jt_offset).to_ne_bytes();
+            if code.get(entry..entry + 4) != Some(&expected[..]) {
+                return fatal!(
+                    errors,
+                    "encoding-sizes",
+                    jt,
+                    "entry {} at offset {:#x} doesn't reference {}",
+                    i,
+                    entry,
+                    ebb
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the destination of the branch `inst` at `offset` is within `range`.
+fn check_branch_range(
+    func: &Function,
+    inst: Inst,
+    offset: CodeOffset,
+    range: BranchRange,
+    errors: &mut VerifierErrors,
+) -> VerifierStepResult<()> {
+    if let Some(dest) = func.dfg[inst].branch_destination() {
+        if !range.contains(offset, func.offsets[dest]) {
+            return fatal!(
+                errors,
+                "branch-range",
+                inst,
+                "{} at offset {:#x} is out of the {}-bit branch range",
+                dest,
+                func.offsets[dest],
+                range.bits
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Code sink counting the bytes of a single instruction emitted at `offset`.
+struct SizeSink {
+    offset: CodeOffset,
+    size: CodeOffset,
+}
+
+impl CodeSink for SizeSink {
+    fn offset(&self) -> CodeOffset {
+        self.offset + self.size
+    }
+
+    fn put1(&mut self, _: u8) {
+        self.size += 1;
+    }
+
+    fn put2(&mut self, _: u16) {
+        self.size += 2;
+    }
+
+    fn put4(&mut self, _: u32) {
+        self.size += 4;
+    }
+
+    fn put8(&mut self, _: u64) {
+        self.size += 8;
+    }
+
+    fn reloc_ebb(&mut self, _: Reloc, _: CodeOffset) {}
+    fn reloc_external(&mut self, _: Reloc, _: &ExternalName, _: Addend) {}
+    fn reloc_jt(&mut self, _: Reloc, _: JumpTable) {}
+    fn trap(&mut self, _: TrapCode, _: SourceLoc) {}
+    fn begin_jumptables(&mut self) {}
+    fn begin_rodata(&mut self) {}
+    fn end_codegen(&mut self) {}
+    fn add_stackmap(&mut self, _: &[Value], _: &Function, _: &dyn TargetIsa) {}
+}
@@ -69,6 +69,7 @@ impl<'a> FlagsVerifier<'a> {
                     Some(old) if old != value => {
                         return fatal!(
                             errors,
+                            "flags",
                             ebb,
                             "conflicting live-in CPU flags: {} and {}",
                             old,
@@ -104,7 +105,14 @@ impl<'a> FlagsVerifier<'a> {
                         // We've reached the def of `live_flags`, so it is no longer live above.
                         live_val = None;
                     } else if self.func.dfg.value_type(res).is_flags() {
-                        return fatal!(errors, inst, "{} clobbers live CPU flags in {}", res, live);
+                        return fatal!(
+                            errors,
+                            "flags",
+                            inst,
+                            "{} clobbers live CPU flags in {}",
+                            res,
+                            live
+                        );
                     }
                 }
 
@@ -116,7 +124,13 @@ impl<'a> FlagsVerifier<'a> {
                     .map_or(false, |c| c.clobbers_flags)
                     && live_val.is_some()
                 {
-                    return fatal!(errors, inst, "encoding clobbers live CPU flags in {}", live);
+                    return fatal!(
+                        errors,
+                        "flags",
+                        inst,
+                        "encoding clobbers live CPU flags in {}",
+                        live
+                    );
                 }
             }
 
@@ -164,7 +178,14 @@ fn merge(
 ) -> VerifierStepResult<()> {
     if let Some(va) = *a {
         if b != va {
-            return fatal!(errors, inst, "conflicting live CPU flags: {} and {}", va, b);
+            return fatal!(
+                errors,
+                "flags",
+                inst,
+                "conflicting live CPU flags: {} and {}",
+                va,
+                b
+            );
         }
     } else {
         *a = Some(b);
@@ -55,7 +55,15 @@ impl<'a> LivenessVerifier<'a> {
             for &val in self.func.dfg.ebb_params(ebb) {
                 let lr = match self.liveness.get(val) {
                     Some(lr) => lr,
-                    None => return fatal!(errors, ebb, "EBB arg {} has no live range", val),
+                    None => {
+                        return fatal!(
+                            errors,
+                            "liveness-ebb-params",
+                            ebb,
+                            "EBB arg {} has no live range",
+                            val
+                        )
+                    }
                 };
                 self.check_lr(ebb.into(), val, lr, errors)?;
             }
@@ -73,7 +81,15 @@ impl<'a> LivenessVerifier<'a> {
                 for &val in self.func.dfg.inst_results(inst) {
                     let lr = match self.liveness.get(val) {
                         Some(lr) => lr,
-                        None => return fatal!(errors, inst, "{} has no live range", val),
+                        None => {
+                            return fatal!(
+                                errors,
+                                "liveness-insts",
+                                inst,
+                                "{} has no live range",
+                                val
+                            )
+                        }
                     };
                     self.check_lr(inst.into(), val, lr, errors)?;
 
@@ -82,6 +98,7 @@ impl<'a> LivenessVerifier<'a> {
                         if lr.affinity.is_unassigned() {
                             return fatal!(
                                 errors,
+                                "liveness-insts",
                                 inst,
                                 "{} is a ghost value defined by a real [{}] instruction",
                                 val,
@@ -92,6 +109,7 @@ impl<'a> LivenessVerifier<'a> {
                         // A non-encoded instruction can only define ghost values.
                         return fatal!(
                             errors,
+                            "liveness-insts",
                             inst,
                             "{} is a real {} value defined by a ghost instruction",
                             val,
@@ -104,16 +122,31 @@ impl<'a> LivenessVerifier<'a> {
                 for &val in self.func.dfg.inst_args(inst) {
                     let lr = match self.liveness.get(val) {
                         Some(lr) => lr,
-                        None => return fatal!(errors, inst, "{} has no live range", val),
+                        None => {
+                            return fatal!(
+                                errors,
+                                "liveness-insts",
+                                inst,
+                                "{} has no live range",
+                                val
+                            )
+                        }
                     };
                     if !self.live_at_use(lr, inst) {
-                        return fatal!(errors, inst, "{} is not live at this use", val);
+                        return fatal!(
+                            errors,
+                            "liveness-insts",
+                            inst,
+                            "{} is not live at this use",
+                            val
+                        );
                     }
 
                     // A legal instruction is not allowed to depend on ghost values.
                     if encoding.is_legal() && lr.affinity.is_unassigned() {
                         return fatal!(
                             errors,
+                            "liveness-insts",
                             inst,
                             "{} is a ghost value used by a real [{}] instruction",
                             val,
@@ -161,6 +194,7 @@ impl<'a> LivenessVerifier<'a> {
         if lr.def() != def {
             return fatal!(
                 errors,
+                "live-ranges",
                 loc,
                 "Wrong live range def ({}) for {}",
                 lr.def(),
@@ -169,7 +203,13 @@ impl<'a> LivenessVerifier<'a> {
         }
         if lr.is_dead() {
             if !lr.is_local() {
-                return fatal!(errors, loc, "Dead live range {} should be local", val);
+                return fatal!(
+                    errors,
+                    "live-ranges",
+                    loc,
+                    "Dead live range {} should be local",
+                    val
+                );
             } else {
                 return Ok(());
             }
@@ -182,6 +222,7 @@ impl<'a> LivenessVerifier<'a> {
             ExpandedProgramPoint::Ebb(e) => {
                 return fatal!(
                     errors,
+                    "live-ranges",
                     loc,
                     "Def local range for {} can't end at {}",
                     val,
@@ -190,7 +231,13 @@ impl<'a> LivenessVerifier<'a> {
             }
             ExpandedProgramPoint::Inst(i) => {
                 if self.func.layout.inst_ebb(i) != Some(def_ebb) {
-                    return fatal!(errors, loc, "Def local end for {} in wrong ebb", val);
+                    return fatal!(
+                        errors,
+                        "live-ranges",
+                        loc,
+                        "Def local end for {} in wrong ebb",
+                        val
+                    );
                 }
             }
         }
@@ -200,6 +247,7 @@ impl<'a> LivenessVerifier<'a> {
             if !l.is_ebb_inserted(ebb) {
                 return fatal!(
                     errors,
+                    "live-ranges",
                     loc,
                     "{} livein at {} which is not in the layout",
                     val,
@@ -211,6 +259,7 @@ impl<'a> LivenessVerifier<'a> {
                 None => {
                     return fatal!(
                         errors,
+                        "live-ranges",
                         loc,
                         "{} livein for {} ends at {} which is not in the layout",
                         val,
@@ -227,6 +276,7 @@ impl<'a> LivenessVerifier<'a> {
                     if !self.live_at_use(lr, pred) {
                         return fatal!(
                             errors,
+                            "live-ranges",
                             pred,
                             "{} is live in to {} but not live at predecessor",
                             val,
@@ -243,6 +293,7 @@ impl<'a> LivenessVerifier<'a> {
                     None => {
                         return fatal!(
                             errors,
+                            "live-ranges",
                             loc,
                             "end of {} livein ({}) never reached",
                             val,
@@ -101,6 +101,7 @@ impl<'a> LocationVerifier<'a> {
         // TODO: We could give a better error message here.
         fatal!(
             errors,
+            "locations-constraints",
             inst,
             "{} constraints not satisfied",
             self.encinfo.display(enc)
@@ -121,6 +122,7 @@ impl<'a> LocationVerifier<'a> {
             if loc.is_assigned() {
                 return fatal!(
                     errors,
+                    "locations-ghost-results",
                     inst,
                     "ghost result {} value must not have a location ({}).",
                     res,
@@ -209,6 +211,7 @@ impl<'a> LocationVerifier<'a> {
                 if loc != ir::ValueLoc::Reg(reg) {
                     return fatal!(
                         errors,
+                        "locations-abi",
                         inst,
                         "ABI expects {} in {}, got {}",
                         value,
@@ -223,6 +226,7 @@ impl<'a> LocationVerifier<'a> {
                     if slot.kind != want_kind {
                         return fatal!(
                             errors,
+                            "locations-abi",
                             inst,
                             "call argument {} should be in a {} slot, but {} is {}",
                             value,
@@ -234,6 +238,7 @@ impl<'a> LocationVerifier<'a> {
                     if slot.offset.unwrap() != offset {
                         return fatal!(
                             errors,
+                            "locations-abi",
                             inst,
                             "ABI expects {} at stack offset {}, but {} is at {}",
                             value,
@@ -245,6 +250,7 @@ impl<'a> LocationVerifier<'a> {
                 } else {
                     return fatal!(
                         errors,
+                        "locations-abi",
                         inst,
                         "ABI expects {} at stack offset {}, got {}",
                         value,
@@ -276,6 +282,7 @@ impl<'a> LocationVerifier<'a> {
             if d.to != src {
                 return fatal!(
                     errors,
+                    "locations-diversions",
                     inst,
                     "inconsistent with current diversion to {}",
                     d.to.display(&self.reginfo)
@@ -284,6 +291,7 @@ impl<'a> LocationVerifier<'a> {
         } else if self.func.locations[arg] != src {
             return fatal!(
                 errors,
+                "locations-diversions",
                 inst,
                 "inconsistent with global location {}",
                 self.func.locations[arg].display(&self.reginfo)
@@ -323,6 +331,7 @@ impl<'a> LocationVerifier<'a> {
                     if lr.is_livein(ebb, liveness.context(&self.func.layout)) {
                         return fatal!(
                             errors,
+                            "locations-cfg-edges",
                             inst,
                             "{} is diverted to {} and live in to {}",
                             value,
@@ -339,6 +348,7 @@ impl<'a> LocationVerifier<'a> {
                         if lr.is_livein(ebb, liveness.context(&self.func.layout)) {
                             return fatal!(
                                 errors,
+                                "locations-cfg-edges",
                                 inst,
                                 "{} is diverted to {} and live in to {}",
                                 value,
@@ -351,6 +361,7 @@ impl<'a> LocationVerifier<'a> {
                         if lr.is_livein(*ebb, liveness.context(&self.func.layout)) {
                             return fatal!(
                                 errors,
+                                "locations-cfg-edges",
                                 inst,
                                 "{} is diverted to {} and live in to {}",
                                 value,
@@ -40,6 +40,8 @@
 //!   function.
 //! - All return instructions must have return value operands matching the current
 //!   function signature.
+//! - The OSR entry block must be inserted in the layout, it can't be the entry block, and its
+//!   parameters must have types that can be loaded from memory.
 //!
 //! Global values
 //!
@@ -65,11 +67,13 @@ use crate::ir;
 use crate::ir::entities::AnyEntity;
 use crate::ir::instructions::{BranchInfo, CallInfo, InstructionFormat, ResolvedConstraint};
 use crate::ir::{
-    types, ArgumentLoc, Ebb, FuncRef, Function, GlobalValue, Inst, InstructionData, JumpTable,
-    Opcode, SigRef, StackSlot, StackSlotKind, Type, Value, ValueDef, ValueList, ValueLoc,
+    types, ArgumentLoc, ArgumentPurpose, Ebb, FuncRef, Function, GlobalValue, Inst,
+    InstructionData, JumpTable, Opcode, RawCode, SigRef, Signature, StackSlot, StackSlotKind, Type,
+    Value, ValueDef, ValueList, ValueLoc,
 };
 use crate::isa::TargetIsa;
 use crate::iterators::IteratorExtras;
+use crate::print_errors::write_verifier_errors;
 use crate::settings::FlagsOrIsa;
 use crate::timing;
 use core::cmp::Ordering;
@@ -79,29 +83,32 @@ use std::collections::BTreeSet;
 use std::string::String;
 use std::vec::Vec;
 
+pub use self::abi::verify_abi;
 pub use self::cssa::verify_cssa;
+#[cfg(feature = "testing_hooks")]
+pub use self::encoding_sizes::verify_encoding_sizes;
 pub use self::liveness::verify_liveness;
 pub use self::locations::verify_locations;
+pub use self::safepoints::verify_safepoints;
 
 /// Report an error.
 ///
-/// The first argument must be a `&mut VerifierErrors` reference, and the following
-/// argument defines the location of the error and must implement `Into<AnyEntity>`.
-/// Finally, subsequent arguments will be formatted using `format!()` and set
-/// as the error message.
+/// The first argument must be a `&mut VerifierErrors` reference, and the second one is the
+/// identifier of the rule that was violated. The following argument defines the location of the
+/// error and must implement `Into<AnyEntity>`. Finally, subsequent arguments will be formatted
+/// using `format!()` and set as the error message.
 macro_rules! report {
-    ( $errors: expr, $loc: expr, $msg: tt ) => {
-        $errors.0.push(crate::verifier::VerifierError {
-            location: $loc.into(),
-            message: String::from($msg),
-        })
+    ( $errors: expr, $rule: expr, $loc: expr, $msg: tt ) => {
+        $errors.0.push(
+            crate::verifier::VerifierError::new($loc, String::from($msg)).with_rule($rule)
+        )
     };
 
-    ( $errors: expr, $loc: expr, $fmt: tt, $( $arg: expr ),+ ) => {
-        $errors.0.push(crate::verifier::VerifierError {
-            location: $loc.into(),
-            message: format!( $fmt, $( $arg ),+ ),
-        })
+    ( $errors: expr, $rule: expr, $loc: expr, $fmt: tt, $( $arg: expr ),+ ) => {
+        $errors.0.push(
+            crate::verifier::VerifierError::new($loc, format!( $fmt, $( $arg ),+ ))
+                .with_rule($rule)
+        )
     };
 }
 
@@ -121,18 +128,46 @@ macro_rules! nonfatal {
     });
 }
 
+mod abi;
 mod cssa;
+#[cfg(feature = "testing_hooks")]
+mod encoding_sizes;
 mod flags;
 mod liveness;
 mod locations;
+mod safepoints;
 
 /// A verifier error.
-#[derive(Fail, Debug, PartialEq, Eq)]
+#[derive(Fail, Debug, Clone, PartialEq, Eq)]
 pub struct VerifierError {
     /// The entity causing the verifier error.
     pub location: AnyEntity,
     /// The error message.
     pub message: String,
+    rule: &'static str,
+}
+
+impl VerifierError {
+    /// Create an error at `location`, with no rule.
+    pub fn new<L: Into<AnyEntity>>(location: L, message: String) -> Self {
+        Self {
+            location: location.into(),
+            message,
+            rule: "",
+        }
+    }
+
+    /// Set the identifier of the rule that was violated.
+    pub fn with_rule(self, rule: &'static str) -> Self {
+        Self { rule, ..self }
+    }
+
+    /// Get the identifier of the rule that was violated, like `typecheck` or `abi-locations`.
+    ///
+    /// This is empty if the error was created without a rule.
+    pub fn rule(&self) -> &'static str {
+        self.rule
+    }
 }
 
 impl Display for VerifierError {
@@ -160,7 +195,7 @@ pub type VerifierStepResult<T> = Result<T, ()>;
 pub type VerifierResult<T> = Result<T, VerifierErrors>;
 
 /// List of verifier errors.
-#[derive(Fail, Debug, Default, PartialEq, Eq)]
+#[derive(Fail, Debug, Clone, Default, PartialEq, Eq)]
 pub struct VerifierErrors(pub Vec<VerifierError>);
 
 impl VerifierErrors {
@@ -192,6 +227,35 @@ impl VerifierErrors {
             Err(())
         }
     }
+
+    /// Return an object that can display the errors in the context of `func`.
+    ///
+    /// The function is printed with each error annotated below the entity it refers to, followed
+    /// by the errors that don't refer to a printed entity.
+    pub fn display<'a, I: Into<Option<&'a dyn TargetIsa>>>(
+        &'a self,
+        func: &'a Function,
+        isa: I,
+    ) -> DisplayVerifierErrors<'a> {
+        DisplayVerifierErrors {
+            errors: self,
+            func,
+            isa: isa.into(),
+        }
+    }
+}
+
+/// Wrapper type for displaying verifier errors in the context of a function.
+pub struct DisplayVerifierErrors<'a> {
+    errors: &'a VerifierErrors,
+    func: &'a Function,
+    isa: Option<&'a dyn TargetIsa>,
+}
+
+impl<'a> Display for DisplayVerifierErrors<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write_verifier_errors(f, self.func, self.isa, None, self.errors.clone())
+    }
 }
 
 impl From<Vec<VerifierError>> for VerifierErrors {
@@ -262,6 +326,27 @@ pub fn verify_context<'a, FOI: Into<FlagsOrIsa<'a>>>(
     verifier.run(errors)
 }
 
+/// Verify the control flow and the SSA form of `func`.
+///
+/// This is a fast subset of `verify_function` which only checks that the EBBs end with a single
+/// terminator, that branches target valid EBBs, and that the values used by an instruction are
+/// defined by a dominating instruction or EBB.
+pub fn verify_function_fast<'a, FOI: Into<FlagsOrIsa<'a>>>(
+    func: &Function,
+    fisa: FOI,
+) -> VerifierResult<()> {
+    let _tt = timing::verifier_fast();
+    let mut errors = VerifierErrors::default();
+    let verifier = Verifier::new(func, fisa.into());
+    let result = verifier.run_fast(&mut errors);
+    if errors.is_empty() {
+        result.unwrap();
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
 struct Verifier<'a> {
     func: &'a Function,
     expected_cfg: ControlFlowGraph,
@@ -301,6 +386,7 @@ impl<'a> Verifier<'a> {
                             if !cycle_seen {
                                 report!(
                                     errors,
+                                    "global-values",
                                     gv,
                                     "global value cycle: {}",
                                     DisplayList(seen.as_slice())
@@ -324,7 +410,13 @@ impl<'a> Verifier<'a> {
                         .special_param(ir::ArgumentPurpose::VMContext)
                         .is_none()
                     {
-                        report!(errors, gv, "undeclared vmctx reference {}", gv);
+                        report!(
+                            errors,
+                            "global-values",
+                            gv,
+                            "undeclared vmctx reference {}",
+                            gv
+                        );
                     }
                 }
                 ir::GlobalValueData::IAddImm {
@@ -333,6 +425,7 @@ impl<'a> Verifier<'a> {
                     if !global_type.is_int() {
                         report!(
                             errors,
+                            "global-values",
                             gv,
                             "iadd_imm global value with non-int type {}",
                             global_type
@@ -342,6 +435,7 @@ impl<'a> Verifier<'a> {
                         if global_type != base_type {
                             report!(
                                 errors,
+                                "global-values",
                                 gv,
                                 "iadd_imm type {} differs from operand type {}",
                                 global_type,
@@ -357,6 +451,7 @@ impl<'a> Verifier<'a> {
                         if base_type != pointer_type {
                             report!(
                                 errors,
+                                "global-values",
                                 gv,
                                 "base {} has type {}, which is not the pointer type {}",
                                 base,
@@ -379,7 +474,7 @@ impl<'a> Verifier<'a> {
             for (heap, heap_data) in &self.func.heaps {
                 let base = heap_data.base;
                 if !self.func.global_values.is_valid(base) {
-                    return nonfatal!(errors, heap, "invalid base global value {}", base);
+                    return nonfatal!(errors, "heaps", heap, "invalid base global value {}", base);
                 }
 
                 let pointer_type = isa.pointer_type();
@@ -387,6 +482,7 @@ impl<'a> Verifier<'a> {
                 if base_type != pointer_type {
                     report!(
                         errors,
+                        "heaps",
                         heap,
                         "heap base has type {}, which is not the pointer type {}",
                         base_type,
@@ -396,14 +492,26 @@ impl<'a> Verifier<'a> {
 
                 if let ir::HeapStyle::Dynamic { bound_gv, .. } = heap_data.style {
                     if !self.func.global_values.is_valid(bound_gv) {
-                        return nonfatal!(errors, heap, "invalid bound global value {}", bound_gv);
+                        return nonfatal!(
+                            errors,
+                            "heaps",
+                            heap,
+                            "invalid bound global value {}",
+                            bound_gv
+                        );
                     }
 
+                    // A bound wider than a pointer can't be loaded in a single instruction, so
+                    // heaps with wider indexes may use a pointer-sized bound.
                     let index_type = heap_data.index_type;
                     let bound_type = self.func.global_values[bound_gv].global_type(isa);
-                    if index_type != bound_type {
+                    let pointer_type = isa.pointer_type();
+                    if index_type != bound_type
+                        && (bound_type != pointer_type || index_type.bits() < pointer_type.bits())
+                    {
                         report!(
                             errors,
+                            "heaps",
                             heap,
                             "heap index type {} differs from the type of its bound, {}",
                             index_type,
@@ -422,7 +530,13 @@ impl<'a> Verifier<'a> {
             for (table, table_data) in &self.func.tables {
                 let base = table_data.base_gv;
                 if !self.func.global_values.is_valid(base) {
-                    return nonfatal!(errors, table, "invalid base global value {}", base);
+                    return nonfatal!(
+                        errors,
+                        "tables",
+                        table,
+                        "invalid base global value {}",
+                        base
+                    );
                 }
 
                 let pointer_type = isa.pointer_type();
@@ -430,6 +544,7 @@ impl<'a> Verifier<'a> {
                 if base_type != pointer_type {
                     report!(
                         errors,
+                        "tables",
                         table,
                         "table base has type {}, which is not the pointer type {}",
                         base_type,
@@ -437,21 +552,29 @@ impl<'a> Verifier<'a> {
                     );
                 }
 
-                let bound_gv = table_data.bound_gv;
-                if !self.func.global_values.is_valid(bound_gv) {
-                    return nonfatal!(errors, table, "invalid bound global value {}", bound_gv);
-                }
+                if let ir::TableStyle::Dynamic { bound_gv } = table_data.style {
+                    if !self.func.global_values.is_valid(bound_gv) {
+                        return nonfatal!(
+                            errors,
+                            "tables",
+                            table,
+                            "invalid bound global value {}",
+                            bound_gv
+                        );
+                    }
 
-                let index_type = table_data.index_type;
-                let bound_type = self.func.global_values[bound_gv].global_type(isa);
-                if index_type != bound_type {
-                    report!(
-                        errors,
-                        table,
-                        "table index type {} differs from the type of its bound, {}",
-                        index_type,
-                        bound_type
-                    );
+                    let index_type = table_data.index_type;
+                    let bound_type = self.func.global_values[bound_gv].global_type(isa);
+                    if index_type != bound_type {
+                        report!(
+                            errors,
+                            "tables",
+                            table,
+                            "table index type {} differs from the type of its bound, {}",
+                            index_type,
+                            bound_type
+                        );
+                    }
                 }
             }
         }
@@ -468,13 +591,213 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    fn verify_attributes(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
+        if let Some(align) = self.func.attributes.align {
+            if !align.is_power_of_two() {
+                report!(
+                    errors,
+                    "attributes",
+                    AnyEntity::Function,
+                    "function alignment {} is not a power of two",
+                    align
+                );
+            }
+        }
+        if self.func.attributes.naked {
+            for (ss, data) in self.func.stack_slots.iter() {
+                if data.kind != StackSlotKind::IncomingArg {
+                    report!(
+                        errors,
+                        "attributes",
+                        ss,
+                        "{} in a naked function",
+                        data.kind
+                    );
+                }
+            }
+            for ebb in self.func.layout.ebbs() {
+                for inst in self.func.layout.ebb_insts(ebb) {
+                    // Raw machine code is meant for naked functions, it isn't a real call.
+                    let opcode = self.func.dfg[inst].opcode();
+                    if opcode.is_call() && opcode != Opcode::RawBytes {
+                        report!(errors, "attributes", inst, "{} in a naked function", opcode);
+                    }
+                }
+            }
+        }
+        for (func_ref, attributes) in self.func.dfg.ext_func_attributes.iter() {
+            if let Some(align) = attributes.align {
+                if !align.is_power_of_two() {
+                    report!(
+                        errors,
+                        "attributes",
+                        func_ref,
+                        "function alignment {} is not a power of two",
+                        align
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_signatures(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
+        if self.func.signature.variadic.is_some() {
+            report!(
+                errors,
+                "signatures",
+                AnyEntity::Function,
+                "variadic functions can be called, but not defined"
+            );
+        }
+        self.verify_struct_arguments(&self.func.signature, AnyEntity::Function, errors)?;
+        for (sig_ref, sig) in &self.func.dfg.signatures {
+            if let Some(fixed) = sig.variadic {
+                if fixed > sig.params.len() {
+                    report!(
+                        errors,
+                        "signatures",
+                        sig_ref,
+                        "{} fixed parameters, but only {} parameters",
+                        fixed,
+                        sig.params.len()
+                    );
+                }
+            }
+            self.verify_struct_arguments(sig, sig_ref.into(), errors)?;
+        }
+        Ok(())
+    }
+
+    fn verify_raw_code_decls(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
+        for (code, data) in &self.func.dfg.raw_code {
+            if data.bytes.len() > usize::from(u8::max_value()) {
+                report!(
+                    errors,
+                    "raw-code",
+                    code,
+                    "{} bytes of raw code, but at most {} are allowed",
+                    data.bytes.len(),
+                    u8::max_value()
+                );
+            }
+            let sig_ref = data.signature;
+            if !self.func.dfg.signatures.is_valid(sig_ref) {
+                return fatal!(
+                    errors,
+                    "raw-code",
+                    code,
+                    "invalid signature reference {}",
+                    sig_ref
+                );
+            }
+            let sig = &self.func.dfg.signatures[sig_ref];
+            for arg in sig.params.iter().chain(sig.returns.iter()) {
+                match arg.location {
+                    ArgumentLoc::Reg(_) => {}
+                    _ => {
+                        report!(
+                            errors,
+                            "raw-code",
+                            code,
+                            "raw code operands must be pinned to registers in {}",
+                            sig_ref
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_osr_entry(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
+        let ebb = match self.func.osr_entry {
+            Some(ebb) => ebb,
+            None => return Ok(()),
+        };
+        if !self.func.dfg.ebb_is_valid(ebb) || !self.func.layout.is_ebb_inserted(ebb) {
+            return fatal!(
+                errors,
+                "osr-entry",
+                ebb,
+                "OSR entry {} is not in the layout",
+                ebb
+            );
+        }
+        if self.func.layout.entry_block() == Some(ebb) {
+            return fatal!(
+                errors,
+                "osr-entry",
+                ebb,
+                "the entry block can't be the OSR entry"
+            );
+        }
+        for &param in self.func.dfg.ebb_params(ebb) {
+            let ty = self.func.dfg.value_type(param);
+            if !ir::is_valid_osr_state_type(ty) {
+                report!(
+                    errors,
+                    "osr-entry",
+                    ebb,
+                    "OSR entry parameter can't have type {}",
+                    ty
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_struct_arguments(
+        &self,
+        sig: &Signature,
+        entity: AnyEntity,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        for param in &sig.params {
+            if let ArgumentPurpose::StructArgument(layout) = param.purpose {
+                if !param.value_type.is_int() {
+                    report!(
+                        errors,
+                        "struct-arguments",
+                        entity,
+                        "struct argument must be a pointer, not {}",
+                        param.value_type
+                    );
+                }
+                if layout.size == 0 {
+                    report!(
+                        errors,
+                        "struct-arguments",
+                        entity,
+                        "struct argument can't be empty"
+                    );
+                }
+            }
+        }
+        for ret in &sig.returns {
+            match ret.purpose {
+                ArgumentPurpose::StructArgument(_) | ArgumentPurpose::StructArgumentPart(_) => {
+                    report!(
+                        errors,
+                        "struct-arguments",
+                        entity,
+                        "structs can't be returned by value"
+                    );
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     /// Check that the given EBB can be encoded as a BB, by checking that only
     /// branching instructions are ending the EBB.
     #[cfg(feature = "basic-blocks")]
     fn encodable_as_bb(&self, ebb: Ebb, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
         match self.func.is_ebb_basic(ebb) {
             Ok(()) => Ok(()),
-            Err((inst, message)) => fatal!(errors, inst, message),
+            Err((inst, message)) => fatal!(errors, "basic-blocks", inst, message),
         }
     }
 
@@ -491,6 +814,7 @@ impl<'a> Verifier<'a> {
             // Terminating instructions only occur at the end of blocks.
             return fatal!(
                 errors,
+                "ebb-integrity",
                 inst,
                 "a terminator instruction was encountered before the end of {}",
                 ebb
@@ -499,6 +823,7 @@ impl<'a> Verifier<'a> {
         if is_last_inst && !is_terminator {
             return fatal!(
                 errors,
+                "ebb-integrity",
                 ebb,
                 "block does not end in a terminator instruction"
             );
@@ -507,7 +832,14 @@ impl<'a> Verifier<'a> {
         // Instructions belong to the correct ebb.
         let inst_ebb = self.func.layout.inst_ebb(inst);
         if inst_ebb != Some(ebb) {
-            return fatal!(errors, inst, "should belong to {} not {:?}", ebb, inst_ebb);
+            return fatal!(
+                errors,
+                "ebb-integrity",
+                inst,
+                "should belong to {} not {:?}",
+                ebb,
+                inst_ebb
+            );
         }
 
         // Parameters belong to the correct ebb.
@@ -515,11 +847,16 @@ impl<'a> Verifier<'a> {
             match self.func.dfg.value_def(arg) {
                 ValueDef::Param(arg_ebb, _) => {
                     if ebb != arg_ebb {
-                        return fatal!(errors, arg, "does not belong to {}", ebb);
+                        return fatal!(errors, "ebb-integrity", arg, "does not belong to {}", ebb);
                     }
                 }
                 _ => {
-                    return fatal!(errors, arg, "expected an argument, found a result");
+                    return fatal!(
+                        errors,
+                        "ebb-integrity",
+                        arg,
+                        "expected an argument, found a result"
+                    );
                 }
             }
         }
@@ -539,6 +876,7 @@ impl<'a> Verifier<'a> {
         if inst_data.opcode().format() != InstructionFormat::from(inst_data) {
             return fatal!(
                 errors,
+                "inst-integrity",
                 inst,
                 "instruction opcode doesn't match instruction format"
             );
@@ -556,6 +894,7 @@ impl<'a> Verifier<'a> {
         if got_results != total_results {
             return fatal!(
                 errors,
+                "inst-integrity",
                 inst,
                 "expected {} result values, found {}",
                 total_results,
@@ -581,6 +920,7 @@ impl<'a> Verifier<'a> {
             if !self.func.dfg.value_is_attached(original) {
                 report!(
                     errors,
+                    "entity-references",
                     inst,
                     "argument {} -> {} is not attached",
                     arg,
@@ -642,12 +982,28 @@ impl<'a> Verifier<'a> {
                 self.verify_func_ref(inst, func_ref, errors)?;
                 self.verify_value_list(inst, args, errors)?;
             }
+            Invoke {
+                func_ref,
+                destination,
+                ref args,
+                ..
+            } => {
+                self.verify_func_ref(inst, func_ref, errors)?;
+                self.verify_ebb(inst, destination, errors)?;
+                self.verify_value_list(inst, args, errors)?;
+            }
             CallIndirect {
                 sig_ref, ref args, ..
             } => {
                 self.verify_sig_ref(inst, sig_ref, errors)?;
                 self.verify_value_list(inst, args, errors)?;
             }
+            RawBytes {
+                raw_code, ref args, ..
+            } => {
+                self.verify_raw_code(inst, raw_code, errors)?;
+                self.verify_value_list(inst, args, errors)?;
+            }
             FuncAddr { func_ref, .. } => {
                 self.verify_func_ref(inst, func_ref, errors)?;
             }
@@ -681,6 +1037,7 @@ impl<'a> Verifier<'a> {
             | UnaryImm { .. }
             | UnaryIeee32 { .. }
             | UnaryIeee64 { .. }
+            | UnaryUimm32 { .. }
             | UnaryBool { .. }
             | Binary { .. }
             | BinaryImm { .. }
@@ -714,11 +1071,23 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         if !self.func.dfg.ebb_is_valid(e) || !self.func.layout.is_ebb_inserted(e) {
-            return fatal!(errors, loc, "invalid ebb reference {}", e);
+            return fatal!(
+                errors,
+                "entity-references",
+                loc,
+                "invalid ebb reference {}",
+                e
+            );
         }
         if let Some(entry_block) = self.func.layout.entry_block() {
             if e == entry_block {
-                return fatal!(errors, loc, "invalid reference to entry ebb {}", e);
+                return fatal!(
+                    errors,
+                    "entity-references",
+                    loc,
+                    "invalid reference to entry ebb {}",
+                    e
+                );
             }
         }
         Ok(())
@@ -731,7 +1100,32 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         if !self.func.dfg.signatures.is_valid(s) {
-            fatal!(errors, inst, "invalid signature reference {}", s)
+            fatal!(
+                errors,
+                "entity-references",
+                inst,
+                "invalid signature reference {}",
+                s
+            )
+        } else {
+            Ok(())
+        }
+    }
+
+    fn verify_raw_code(
+        &self,
+        inst: Inst,
+        code: RawCode,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        if !self.func.dfg.raw_code.is_valid(code) {
+            fatal!(
+                errors,
+                "entity-references",
+                inst,
+                "invalid raw code reference {}",
+                code
+            )
         } else {
             Ok(())
         }
@@ -744,7 +1138,13 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         if !self.func.dfg.ext_funcs.is_valid(f) {
-            nonfatal!(errors, inst, "invalid function reference {}", f)
+            nonfatal!(
+                errors,
+                "entity-references",
+                inst,
+                "invalid function reference {}",
+                f
+            )
         } else {
             Ok(())
         }
@@ -757,7 +1157,13 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         if !self.func.stack_slots.is_valid(ss) {
-            nonfatal!(errors, inst, "invalid stack slot {}", ss)
+            nonfatal!(
+                errors,
+                "entity-references",
+                inst,
+                "invalid stack slot {}",
+                ss
+            )
         } else {
             Ok(())
         }
@@ -770,7 +1176,13 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         if !self.func.global_values.is_valid(gv) {
-            nonfatal!(errors, inst, "invalid global value {}", gv)
+            nonfatal!(
+                errors,
+                "entity-references",
+                inst,
+                "invalid global value {}",
+                gv
+            )
         } else {
             Ok(())
         }
@@ -783,7 +1195,7 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         if !self.func.heaps.is_valid(heap) {
-            nonfatal!(errors, inst, "invalid heap {}", heap)
+            nonfatal!(errors, "entity-references", inst, "invalid heap {}", heap)
         } else {
             Ok(())
         }
@@ -796,7 +1208,7 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         if !self.func.tables.is_valid(table) {
-            nonfatal!(errors, inst, "invalid table {}", table)
+            nonfatal!(errors, "entity-references", inst, "invalid table {}", table)
         } else {
             Ok(())
         }
@@ -809,7 +1221,13 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         if !l.is_valid(&self.func.dfg.value_lists) {
-            nonfatal!(errors, inst, "invalid value list reference {:?}", l)
+            nonfatal!(
+                errors,
+                "entity-references",
+                inst,
+                "invalid value list reference {:?}",
+                l
+            )
         } else {
             Ok(())
         }
@@ -822,12 +1240,47 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         if !self.func.jump_tables.is_valid(j) {
-            nonfatal!(errors, inst, "invalid jump table reference {}", j)
+            nonfatal!(
+                errors,
+                "entity-references",
+                inst,
+                "invalid jump table reference {}",
+                j
+            )
         } else {
             Ok(())
         }
     }
 
+    fn verify_branch_targets(
+        &self,
+        inst: Inst,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        match self.func.dfg.analyze_branch(inst) {
+            BranchInfo::SingleDest(ebb, _) => self.verify_ebb(inst, ebb, errors),
+            BranchInfo::Table(table, default) => {
+                if let Some(ebb) = default {
+                    self.verify_ebb(inst, ebb, errors)?;
+                }
+                if !self.func.jump_tables.is_valid(table) {
+                    return fatal!(
+                        errors,
+                        "entity-references",
+                        inst,
+                        "invalid jump table reference {}",
+                        table
+                    );
+                }
+                for &ebb in self.func.jump_tables[table].iter() {
+                    self.verify_ebb(inst, ebb, errors)?;
+                }
+                Ok(())
+            }
+            BranchInfo::NotABranch => Ok(()),
+        }
+    }
+
     fn verify_value(
         &self,
         loc_inst: Inst,
@@ -836,7 +1289,13 @@ impl<'a> Verifier<'a> {
     ) -> VerifierStepResult<()> {
         let dfg = &self.func.dfg;
         if !dfg.value_is_valid(v) {
-            nonfatal!(errors, loc_inst, "invalid value reference {}", v)
+            nonfatal!(
+                errors,
+                "entity-references",
+                loc_inst,
+                "invalid value reference {}",
+                v
+            )
         } else {
             Ok(())
         }
@@ -861,6 +1320,7 @@ impl<'a> Verifier<'a> {
                 if !dfg.inst_is_valid(def_inst) {
                     return fatal!(
                         errors,
+                        "inst-args",
                         loc_inst,
                         "{} is defined by invalid instruction {}",
                         v,
@@ -871,6 +1331,7 @@ impl<'a> Verifier<'a> {
                 if self.func.layout.inst_ebb(def_inst) == None {
                     return fatal!(
                         errors,
+                        "inst-args",
                         loc_inst,
                         "{} is defined by {} which has no EBB",
                         v,
@@ -885,6 +1346,7 @@ impl<'a> Verifier<'a> {
                     {
                         return fatal!(
                             errors,
+                            "inst-args",
                             loc_inst,
                             "uses value {} from non-dominating {}",
                             v,
@@ -892,19 +1354,51 @@ impl<'a> Verifier<'a> {
                         );
                     }
                     if def_inst == loc_inst {
-                        return fatal!(errors, loc_inst, "uses value {} from itself", v);
+                        return fatal!(
+                            errors,
+                            "inst-args",
+                            loc_inst,
+                            "uses value {} from itself",
+                            v
+                        );
+                    }
+                    // The results of an `invoke` are only defined when the callee returns, so
+                    // the uses must be dominated by the next instruction, not the landing pad.
+                    if dfg[def_inst].opcode() == Opcode::Invoke {
+                        let returned = self.func.layout.next_inst(def_inst).map_or(false, |next| {
+                            self.expected_domtree
+                                .dominates(next, loc_inst, &self.func.layout)
+                        });
+                        if !returned {
+                            return fatal!(
+                                errors,
+                                "inst-args",
+                                loc_inst,
+                                "uses value {} from {} on its unwinding path",
+                                v,
+                                def_inst
+                            );
+                        }
                     }
                 }
             }
             ValueDef::Param(ebb, _) => {
                 // Value is defined by an existing EBB.
                 if !dfg.ebb_is_valid(ebb) {
-                    return fatal!(errors, loc_inst, "{} is defined by invalid EBB {}", v, ebb);
+                    return fatal!(
+                        errors,
+                        "inst-args",
+                        loc_inst,
+                        "{} is defined by invalid EBB {}",
+                        v,
+                        ebb
+                    );
                 }
                 // Defining EBB is inserted in the layout
                 if !self.func.layout.is_ebb_inserted(ebb) {
                     return fatal!(
                         errors,
+                        "inst-args",
                         loc_inst,
                         "{} is defined by {} which is not in the layout",
                         v,
@@ -919,6 +1413,7 @@ impl<'a> Verifier<'a> {
                 {
                     return fatal!(
                         errors,
+                        "inst-args",
                         loc_inst,
                         "uses value arg from non-dominating {}",
                         ebb
@@ -942,6 +1437,7 @@ impl<'a> Verifier<'a> {
                 if def_inst != loc_inst {
                     fatal!(
                         errors,
+                        "inst-results",
                         loc_inst,
                         "instruction result {} is not defined by the instruction",
                         v
@@ -952,6 +1448,7 @@ impl<'a> Verifier<'a> {
             }
             ValueDef::Param(_, _) => fatal!(
                 errors,
+                "inst-results",
                 loc_inst,
                 "instruction result {} is not defined by the instruction",
                 v
@@ -973,6 +1470,7 @@ impl<'a> Verifier<'a> {
             if got != expected {
                 return fatal!(
                     errors,
+                    "domtree",
                     ebb,
                     "invalid domtree, expected idom({}) = {:?}, got {:?}",
                     ebb,
@@ -985,6 +1483,7 @@ impl<'a> Verifier<'a> {
         if domtree.cfg_postorder().len() != self.expected_domtree.cfg_postorder().len() {
             return fatal!(
                 errors,
+                "domtree",
                 AnyEntity::Function,
                 "incorrect number of Ebbs in postorder traversal"
             );
@@ -998,6 +1497,7 @@ impl<'a> Verifier<'a> {
             if test_ebb != true_ebb {
                 return fatal!(
                     errors,
+                    "domtree",
                     test_ebb,
                     "invalid domtree, postorder ebb number {} should be {}, got {}",
                     index,
@@ -1015,6 +1515,7 @@ impl<'a> Verifier<'a> {
             {
                 return fatal!(
                     errors,
+                    "domtree",
                     next_ebb,
                     "invalid domtree, rpo_cmp does not says {} is greater than {}",
                     prev_ebb,
@@ -1033,6 +1534,7 @@ impl<'a> Verifier<'a> {
             if ebb_param_count != expected_types.len() {
                 return fatal!(
                     errors,
+                    "typecheck",
                     ebb,
                     "entry block parameters ({}) must match function signature ({})",
                     ebb_param_count,
@@ -1045,6 +1547,7 @@ impl<'a> Verifier<'a> {
                 if arg_type != expected_types[i].value_type {
                     report!(
                         errors,
+                        "typecheck",
                         ebb,
                         "entry block parameter {} expected to have type {}, got {}",
                         i,
@@ -1069,6 +1572,7 @@ impl<'a> Verifier<'a> {
             if !value_typeset.contains(ctrl_type) {
                 report!(
                     errors,
+                    "typecheck",
                     inst,
                     "has an invalid controlling type {}",
                     ctrl_type
@@ -1109,6 +1613,7 @@ impl<'a> Verifier<'a> {
                 if result_type != expected_type {
                     report!(
                         errors,
+                        "typecheck",
                         inst,
                         "expected result {} ({}) to have type {}, found {}",
                         i,
@@ -1118,14 +1623,24 @@ impl<'a> Verifier<'a> {
                     );
                 }
             } else {
-                return nonfatal!(errors, inst, "has more result values than expected");
+                return nonfatal!(
+                    errors,
+                    "typecheck",
+                    inst,
+                    "has more result values than expected"
+                );
             }
             i += 1;
         }
 
         // There aren't any more result types left.
         if self.func.dfg.compute_result_type(inst, i, ctrl_type) != None {
-            return nonfatal!(errors, inst, "has fewer result values than expected");
+            return nonfatal!(
+                errors,
+                "typecheck",
+                inst,
+                "has fewer result values than expected"
+            );
         }
         Ok(())
     }
@@ -1145,6 +1660,7 @@ impl<'a> Verifier<'a> {
                     if arg_type != expected_type {
                         report!(
                             errors,
+                            "typecheck",
                             inst,
                             "arg {} ({}) has type {}, expected {}",
                             i,
@@ -1158,6 +1674,7 @@ impl<'a> Verifier<'a> {
                     if !type_set.contains(arg_type) {
                         report!(
                             errors,
+                            "typecheck",
                             inst,
                             "arg {} ({}) with type {} failed to satisfy type set {:?}",
                             i,
@@ -1178,6 +1695,20 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         match self.func.dfg.analyze_branch(inst) {
+            // The arguments of an `invoke` are checked against the callee's signature below.
+            BranchInfo::SingleDest(ebb, _) if self.func.dfg[inst].opcode() == Opcode::Invoke => {
+                let arg_count = self.func.dfg.num_ebb_params(ebb);
+                if arg_count != 0 {
+                    return nonfatal!(
+                        errors,
+                        "typecheck",
+                        inst,
+                        "landing pad {} can't have parameters, but has {}",
+                        ebb,
+                        arg_count
+                    );
+                }
+            }
             BranchInfo::SingleDest(ebb, _) => {
                 let iter = self
                     .func
@@ -1193,6 +1724,7 @@ impl<'a> Verifier<'a> {
                     if arg_count != 0 {
                         return nonfatal!(
                             errors,
+                            "typecheck",
                             inst,
                             "takes no arguments, but had target {} with {} arguments",
                             ebb,
@@ -1205,6 +1737,7 @@ impl<'a> Verifier<'a> {
                     if arg_count != 0 {
                         return nonfatal!(
                             errors,
+                            "typecheck",
                             inst,
                             "takes no arguments, but had target {} with {} arguments",
                             ebb,
@@ -1234,6 +1767,14 @@ impl<'a> Verifier<'a> {
                 self.typecheck_variable_args_iterator(inst, arg_types, errors)?;
                 self.check_outgoing_args(inst, sig_ref, errors)?;
             }
+            CallInfo::Raw(code, _) => {
+                let sig_ref = self.func.dfg.raw_code[code].signature;
+                let arg_types = self.func.dfg.signatures[sig_ref]
+                    .params
+                    .iter()
+                    .map(|a| a.value_type);
+                self.typecheck_variable_args_iterator(inst, arg_types, errors)?;
+            }
             CallInfo::NotACall => {}
         }
         Ok(())
@@ -1259,6 +1800,7 @@ impl<'a> Verifier<'a> {
             if expected_type != arg_type {
                 report!(
                     errors,
+                    "typecheck",
                     inst,
                     "arg {} ({}) has type {}, expected {}",
                     i,
@@ -1272,6 +1814,7 @@ impl<'a> Verifier<'a> {
         if i != variable_args.len() {
             return nonfatal!(
                 errors,
+                "typecheck",
                 inst,
                 "mismatched argument count for `{}`: got {}, expected {}",
                 self.func.dfg.display_inst(inst, None),
@@ -1308,6 +1851,7 @@ impl<'a> Verifier<'a> {
                     if slot.kind != StackSlotKind::OutgoingArg {
                         return fatal!(
                             errors,
+                            "outgoing-args",
                             inst,
                             "Outgoing stack argument {} in wrong stack slot: {} = {}",
                             arg,
@@ -1318,6 +1862,7 @@ impl<'a> Verifier<'a> {
                     if slot.offset != Some(offset) {
                         return fatal!(
                             errors,
+                            "outgoing-args",
                             inst,
                             "Outgoing stack argument {} should have offset {}: {} = {}",
                             arg,
@@ -1329,6 +1874,7 @@ impl<'a> Verifier<'a> {
                     if slot.size != abi.value_type.bytes() {
                         return fatal!(
                             errors,
+                            "outgoing-args",
                             inst,
                             "Outgoing stack argument {} wrong size for {}: {} = {}",
                             arg,
@@ -1341,6 +1887,7 @@ impl<'a> Verifier<'a> {
                     let reginfo = self.isa.map(|i| i.register_info());
                     return fatal!(
                         errors,
+                        "outgoing-args",
                         inst,
                         "Outgoing stack argument {} in wrong location: {}",
                         arg,
@@ -1354,11 +1901,15 @@ impl<'a> Verifier<'a> {
 
     fn typecheck_return(&self, inst: Inst, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
         if self.func.dfg[inst].opcode().is_return() {
+            if self.func.attributes.noreturn {
+                return nonfatal!(errors, "typecheck", inst, "return in a noreturn function");
+            }
             let args = self.func.dfg.inst_variable_args(inst);
             let expected_types = &self.func.signature.returns;
             if args.len() != expected_types.len() {
                 return nonfatal!(
                     errors,
+                    "typecheck",
                     inst,
                     "arguments of return must match function signature"
                 );
@@ -1368,6 +1919,7 @@ impl<'a> Verifier<'a> {
                 if arg_type != expected_type.value_type {
                     report!(
                         errors,
+                        "typecheck",
                         inst,
                         "arg {} ({}) has type {}, must match function signature of {}",
                         i,
@@ -1397,6 +1949,7 @@ impl<'a> Verifier<'a> {
                         if arg_type.lane_count() != ctrl_type.lane_count() {
                             return nonfatal!(
                                 errors,
+                                "typecheck",
                                 inst,
                                 "input {} and output {} must have same number of lanes",
                                 arg_type,
@@ -1406,6 +1959,7 @@ impl<'a> Verifier<'a> {
                         if arg_type.lane_bits() >= ctrl_type.lane_bits() {
                             return nonfatal!(
                                 errors,
+                                "typecheck",
                                 inst,
                                 "input {} must be smaller than output {}",
                                 arg_type,
@@ -1417,6 +1971,7 @@ impl<'a> Verifier<'a> {
                         if arg_type.lane_count() != ctrl_type.lane_count() {
                             return nonfatal!(
                                 errors,
+                                "typecheck",
                                 inst,
                                 "input {} and output {} must have same number of lanes",
                                 arg_type,
@@ -1426,6 +1981,7 @@ impl<'a> Verifier<'a> {
                         if arg_type.lane_bits() <= ctrl_type.lane_bits() {
                             return nonfatal!(
                                 errors,
+                                "typecheck",
                                 inst,
                                 "input {} must be larger than output {}",
                                 arg_type,
@@ -1442,6 +1998,7 @@ impl<'a> Verifier<'a> {
                 if index_type != heap_index_type {
                     return nonfatal!(
                         errors,
+                        "typecheck",
                         inst,
                         "index type {} differs from heap index type {}",
                         index_type,
@@ -1455,6 +2012,7 @@ impl<'a> Verifier<'a> {
                 if index_type != table_index_type {
                     return nonfatal!(
                         errors,
+                        "typecheck",
                         inst,
                         "index type {} differs from table index type {}",
                         index_type,
@@ -1468,7 +2026,7 @@ impl<'a> Verifier<'a> {
                     let global_type = self.func.global_values[global_value].global_type(isa);
                     if inst_type != global_type {
                         return nonfatal!(
-                        errors,
+                        errors, "typecheck",
                         inst,
                         "global_value instruction with type {} references global value with type {}",
                         inst_type,
@@ -1494,11 +2052,21 @@ impl<'a> Verifier<'a> {
         {
             let dst_vals = self.func.dfg.inst_results(inst);
             if dst_vals.len() != 1 {
-                return fatal!(errors, inst, "copy_nop must produce exactly one result");
+                return fatal!(
+                    errors,
+                    "typecheck",
+                    inst,
+                    "copy_nop must produce exactly one result"
+                );
             }
             let dst_val = dst_vals[0];
             if self.func.dfg.value_type(dst_val) != self.func.dfg.value_type(arg) {
-                return fatal!(errors, inst, "copy_nop src and dst types must be the same");
+                return fatal!(
+                    errors,
+                    "typecheck",
+                    inst,
+                    "copy_nop src and dst types must be the same"
+                );
             }
             let src_loc = self.func.locations[arg];
             let dst_loc = self.func.locations[dst_val];
@@ -1509,6 +2077,7 @@ impl<'a> Verifier<'a> {
             if !locs_ok {
                 return fatal!(
                     errors,
+                    "typecheck",
                     inst,
                     "copy_nop must refer to identical stack slots, but found {:?} vs {:?}",
                     src_loc,
@@ -1537,6 +2106,7 @@ impl<'a> Verifier<'a> {
             if !missing_succs.is_empty() {
                 report!(
                     errors,
+                    "cfg",
                     ebb,
                     "cfg lacked the following successor(s) {:?}",
                     missing_succs
@@ -1548,6 +2118,7 @@ impl<'a> Verifier<'a> {
             if !excess_succs.is_empty() {
                 report!(
                     errors,
+                    "cfg",
                     ebb,
                     "cfg had unexpected successor(s) {:?}",
                     excess_succs
@@ -1566,6 +2137,7 @@ impl<'a> Verifier<'a> {
             if !missing_preds.is_empty() {
                 report!(
                     errors,
+                    "cfg",
                     ebb,
                     "cfg lacked the following predecessor(s) {:?}",
                     missing_preds
@@ -1577,6 +2149,7 @@ impl<'a> Verifier<'a> {
             if !excess_preds.is_empty() {
                 report!(
                     errors,
+                    "cfg",
                     ebb,
                     "cfg had unexpected predecessor(s) {:?}",
                     excess_preds
@@ -1613,6 +2186,7 @@ impl<'a> Verifier<'a> {
             if self.func.dfg[inst].opcode().is_ghost() {
                 return nonfatal!(
                     errors,
+                    "encoding",
                     inst,
                     "Ghost instruction has an encoding: {}",
                     isa.encoding_info().display(encoding)
@@ -1630,6 +2204,7 @@ impl<'a> Verifier<'a> {
             if encodings.peek().is_none() {
                 return nonfatal!(
                     errors,
+                    "encoding",
                     inst,
                     "Instruction failed to re-encode {}",
                     isa.encoding_info().display(encoding)
@@ -1658,6 +2233,7 @@ impl<'a> Verifier<'a> {
 
                 return nonfatal!(
                     errors,
+                    "encoding",
                     inst,
                     "encoding {} should be {}{}",
                     isa.encoding_info().display(encoding),
@@ -1704,13 +2280,16 @@ impl<'a> Verifier<'a> {
                 Ok(enc) => {
                     return nonfatal!(
                         errors,
+                        "encoding",
                         inst,
                         "{} must have an encoding (e.g., {})",
                         text,
                         isa.encoding_info().display(enc)
                     );
                 }
-                Err(_) => return nonfatal!(errors, inst, "{} must have an encoding", text),
+                Err(_) => {
+                    return nonfatal!(errors, "encoding", inst, "{} must have an encoding", text)
+                }
             }
         }
 
@@ -1734,6 +2313,7 @@ impl<'a> Verifier<'a> {
         if memflags.readonly() {
             fatal!(
                 errors,
+                "immediates",
                 inst,
                 "A store instruction cannot have the `readonly` MemFlag"
             )
@@ -1752,6 +2332,7 @@ impl<'a> Verifier<'a> {
             {
                 return fatal!(
                     errors,
+                    "safepoint-unused",
                     inst,
                     "safepoint instruction cannot be used when it is not enabled."
                 );
@@ -1760,11 +2341,36 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    fn verify_branch_hint(
+        &self,
+        inst: Inst,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        if let Some(hint) = self.func.branch_hints[inst] {
+            let opcode = self.func.dfg[inst].opcode();
+            if !opcode.is_branch() || opcode.is_terminator() {
+                return nonfatal!(
+                    errors,
+                    "branch-hints",
+                    inst,
+                    "{} hint on {}, which is not a conditional branch",
+                    hint,
+                    opcode
+                );
+            }
+        }
+        Ok(())
+    }
+
     pub fn run(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
         self.verify_global_values(errors)?;
         self.verify_heaps(errors)?;
         self.verify_tables(errors)?;
         self.verify_jump_tables(errors)?;
+        self.verify_attributes(errors)?;
+        self.verify_signatures(errors)?;
+        self.verify_raw_code_decls(errors)?;
+        self.verify_osr_entry(errors)?;
         self.typecheck_entry_block_params(errors)?;
 
         for ebb in self.func.layout.ebbs() {
@@ -1772,6 +2378,7 @@ impl<'a> Verifier<'a> {
                 self.ebb_integrity(ebb, inst, errors)?;
                 self.instruction_integrity(inst, errors)?;
                 self.verify_safepoint_unused(inst, errors)?;
+                self.verify_branch_hint(inst, errors)?;
                 self.typecheck(inst, errors)?;
                 self.verify_encoding(inst, errors)?;
                 self.immediate_constraints(inst, errors)?;
@@ -1785,15 +2392,35 @@ impl<'a> Verifier<'a> {
 
         Ok(())
     }
+
+    /// Run the checks of `verify_function_fast`.
+    pub fn run_fast(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
+        for ebb in self.func.layout.ebbs() {
+            for inst in self.func.layout.ebb_insts(ebb) {
+                self.ebb_integrity(ebb, inst, errors)?;
+                self.verify_branch_targets(inst, errors)?;
+                for &arg in self.func.dfg.inst_args(inst) {
+                    self.verify_inst_arg(inst, arg, errors)?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Verifier, VerifierError, VerifierErrors};
+    use crate::cursor::{Cursor, FuncCursor};
     use crate::entity::EntityList;
     use crate::ir::instructions::{InstructionData, Opcode};
-    use crate::ir::Function;
+    use crate::ir::{
+        types, AbiParam, BranchHint, Function, InstBuilder, RawCodeData, Signature, Value,
+    };
+    use crate::isa::CallConv;
     use crate::settings;
+    use std::string::ToString;
+    use std::vec::Vec;
 
     macro_rules! assert_err_with_msg {
         ($e:expr, $msg:expr) => {
@@ -1851,4 +2478,161 @@ mod tests {
 
         assert_err_with_msg!(errors, "instruction format");
     }
+
+    #[test]
+    fn fast_non_dominating_use() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        let v1;
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().brnz(v0, ebb2, &[]);
+            pos.ins().jump(ebb1, &[]);
+            pos.insert_ebb(ebb1);
+            v1 = pos.ins().iadd_imm(v0, 1);
+            pos.ins().jump(ebb2, &[]);
+            pos.insert_ebb(ebb2);
+            pos.ins().iadd(v0, v1);
+            pos.ins().return_(&[]);
+        }
+        let flags = &settings::Flags::new(settings::builder());
+        let verifier = Verifier::new(&func, flags.into());
+        let mut errors = VerifierErrors::default();
+
+        let _ = verifier.run_fast(&mut errors);
+
+        assert_err_with_msg!(errors, "uses value v1 from non-dominating");
+    }
+
+    #[test]
+    fn fast_missing_terminator() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().iconst(types::I32, 0);
+        }
+        let flags = &settings::Flags::new(settings::builder());
+        let verifier = Verifier::new(&func, flags.into());
+        let mut errors = VerifierErrors::default();
+
+        let _ = verifier.run_fast(&mut errors);
+
+        assert_err_with_msg!(errors, "block does not end in a terminator instruction");
+    }
+
+    #[test]
+    fn branch_hint_on_jump() {
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I32));
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let brnz = pos.ins().brnz(v0, ebb1, &[]);
+            pos.func.branch_hints[brnz] = Some(BranchHint::Unlikely);
+            let jump = pos.ins().jump(ebb1, &[]);
+            pos.func.branch_hints[jump] = Some(BranchHint::Likely);
+            pos.insert_ebb(ebb1);
+            pos.ins().return_(&[]);
+        }
+        let flags = &settings::Flags::new(settings::builder());
+        let verifier = Verifier::new(&func, flags.into());
+        let mut errors = VerifierErrors::default();
+
+        let _ = verifier.run(&mut errors);
+
+        assert_eq!(errors.0.len(), 1);
+        assert_err_with_msg!(
+            errors,
+            "likely hint on jump, which is not a conditional branch"
+        );
+    }
+
+    #[test]
+    fn long_raw_code() {
+        let mut func = Function::new();
+        let sig_ref = func.import_signature(Signature::new(CallConv::SystemV));
+        func.dfg.raw_code.push(RawCodeData {
+            bytes: vec![0x90; 256],
+            signature: sig_ref,
+            clobbers: Vec::new(),
+        });
+        let flags = &settings::Flags::new(settings::builder());
+        let verifier = Verifier::new(&func, flags.into());
+        let mut errors = VerifierErrors::default();
+
+        let _ = verifier.run(&mut errors);
+
+        assert_eq!(errors.0.len(), 1);
+        assert_err_with_msg!(errors, "256 bytes of raw code, but at most 255 are allowed");
+    }
+
+    #[test]
+    fn variadic_signatures() {
+        let mut func = Function::new();
+        func.signature.variadic = Some(0);
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I64));
+        sig.variadic = Some(2);
+        func.import_signature(sig);
+        let ebb0 = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().return_(&[]);
+        }
+        let flags = &settings::Flags::new(settings::builder());
+        let verifier = Verifier::new(&func, flags.into());
+        let mut errors = VerifierErrors::default();
+
+        let _ = verifier.run(&mut errors);
+
+        assert_eq!(errors.0.len(), 2);
+        assert_err_with_msg!(errors, "variadic functions can be called, but not defined");
+        assert!(errors.0[1]
+            .message
+            .contains("2 fixed parameters, but only 1 parameters"));
+    }
+
+    #[test]
+    fn display_with_function() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().iconst(types::I32, 0);
+        }
+        let flags = &settings::Flags::new(settings::builder());
+        let verifier = Verifier::new(&func, flags.into());
+        let mut errors = VerifierErrors::default();
+
+        let _ = verifier.run(&mut errors);
+        errors.0.push(
+            VerifierError::new(
+                Value::with_number(7).unwrap(),
+                "not in the function".to_string(),
+            )
+            .with_rule("inst-args"),
+        );
+
+        assert_eq!(
+            errors.display(&func, None).to_string(),
+            "function u0:0() fast {\n\
+             ebb0:\n\
+             ;~~~~\n\
+             ; error: ebb0: block does not end in a terminator instruction [ebb-integrity]\n\
+             \n    v0 = iconst.i32 0\n\
+             }\n\
+             ; error: v7: not in the function [inst-args]\n"
+        );
+    }
 }
@@ -0,0 +1,135 @@
+//! Verify safepoints.
+
+use crate::ir::{Ebb, Function, Inst, InstructionData, Opcode, TrapCode, Value, ValueLoc};
+use crate::isa;
+use crate::regalloc::liveness::Liveness;
+use crate::timing;
+use crate::verifier::{VerifierErrors, VerifierStepResult};
+use std::vec::Vec;
+
+/// Verify the safepoints inserted in `func` by the register allocator.
+///
+/// Every reference value that is live across a call or an interrupt trap must be listed by a
+/// `safepoint` instruction immediately preceding it, and it must be assigned a stack slot so it
+/// can be recorded in the stackmap. Otherwise a garbage collection at this point would miss the
+/// reference, or fail to update it when the referenced object is moved.
+///
+/// This is meant to be run after the safepoints are inserted, so the value locations and the live
+/// ranges are final.
+pub fn verify_safepoints(
+    isa: &dyn isa::TargetIsa,
+    func: &Function,
+    liveness: &Liveness,
+    errors: &mut VerifierErrors,
+) -> VerifierStepResult<()> {
+    let _tt = timing::verify_safepoints();
+    let ref_values: Vec<Value> = func
+        .dfg
+        .values()
+        .filter(|&value| func.dfg.value_type(value).lane_type().is_ref())
+        .collect();
+    if ref_values.is_empty() {
+        return Ok(());
+    }
+
+    let verifier = SafepointVerifier {
+        func,
+        reginfo: isa.register_info(),
+        liveness,
+        ref_values,
+    };
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if verifier.needs_safepoint(inst) {
+                verifier.check_safepoint(inst, ebb, errors)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+struct SafepointVerifier<'a> {
+    func: &'a Function,
+    reginfo: isa::RegInfo,
+    liveness: &'a Liveness,
+    ref_values: Vec<Value>,
+}
+
+impl<'a> SafepointVerifier<'a> {
+    /// Is `inst` a point where the garbage collector can run?
+    fn needs_safepoint(&self, inst: Inst) -> bool {
+        match self.func.dfg[inst] {
+            InstructionData::Trap {
+                code: TrapCode::Interrupt,
+                ..
+            } => true,
+            ref data if data.opcode() == Opcode::Safepoint => {
+                // A safepoint in front of a call or an interrupt trap is checked along with it.
+                self.func
+                    .layout
+                    .next_inst(inst)
+                    .map_or(true, |next| !self.needs_safepoint(next))
+            }
+            ref data => data.opcode().is_call(),
+        }
+    }
+
+    /// Check that the reference values live across `inst` are recorded in its stackmap.
+    fn check_safepoint(
+        &self,
+        inst: Inst,
+        ebb: Ebb,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        let ctx = self.liveness.context(&self.func.layout);
+        let safepoint = if self.func.dfg[inst].opcode() == Opcode::Safepoint {
+            Some(inst)
+        } else {
+            self.func
+                .layout
+                .prev_inst(inst)
+                .filter(|&prev| self.func.dfg[prev].opcode() == Opcode::Safepoint)
+        };
+        let recorded = match safepoint {
+            Some(safepoint) => self.func.dfg.inst_args(safepoint),
+            None => &[],
+        };
+
+        for &value in &self.ref_values {
+            let lr = match self.liveness.get(value) {
+                Some(lr) => lr,
+                None => continue,
+            };
+            if !lr.reaches_use(inst, ebb, ctx) || lr.killed_at(inst, ebb, ctx) {
+                continue;
+            }
+
+            if !recorded.contains(&value) {
+                return fatal!(
+                    errors,
+                    "safepoint-stackmaps",
+                    inst,
+                    "reference {} is live across {} but missing from its safepoint",
+                    value,
+                    self.func.dfg.display_inst(inst, None)
+                );
+            }
+            match self.func.locations[value] {
+                ValueLoc::Stack(_) => {}
+                loc => {
+                    return fatal!(
+                        errors,
+                        "safepoint-stackmaps",
+                        inst,
+                        "reference {} is live across {} in {}, but only stack slots are recorded \
+                         in stackmaps",
+                        value,
+                        self.func.dfg.display_inst(inst, None),
+                        loc.display(&self.reginfo)
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -59,6 +59,13 @@ pub trait FuncWriter {
     ) -> Result<bool, fmt::Error> {
         let mut any = false;
 
+        for (name, symbol) in func.names.iter() {
+            any = true;
+            write!(w, "    symbol {} = ", name)?;
+            write_symbol(w, symbol)?;
+            writeln!(w)?;
+        }
+
         for (ss, slot) in func.stack_slots.iter() {
             any = true;
             self.write_entity_definition(w, func, ss.into(), slot)?;
@@ -93,15 +100,26 @@ pub trait FuncWriter {
         for (fnref, ext_func) in &func.dfg.ext_funcs {
             if ext_func.signature != SigRef::reserved_value() {
                 any = true;
-                self.write_entity_definition(w, func, fnref.into(), ext_func)?;
+                let attributes = &func.dfg.ext_func_attributes[fnref];
+                self.write_entity_definition(w, func, fnref.into(), &ext_func.display(attributes))?;
             }
         }
 
+        for (code, code_data) in &func.dfg.raw_code {
+            any = true;
+            self.write_entity_definition(w, func, code.into(), &code_data.display(regs))?;
+        }
+
         for (jt, jt_data) in &func.jump_tables {
             any = true;
             self.write_entity_definition(w, func, jt.into(), jt_data)?;
         }
 
+        if let Some(ebb) = func.osr_entry {
+            any = true;
+            writeln!(w, "    osr_entry {}", ebb)?;
+        }
+
         Ok(any)
     }
 
@@ -167,6 +185,18 @@ pub fn write_function(
     decorate_function(&mut PlainWriter, w, func, annotations)
 }
 
+/// Write `symbol` as a quoted string, escaping the quotes and backslashes.
+fn write_symbol(w: &mut dyn Write, symbol: &str) -> fmt::Result {
+    w.write_char('"')?;
+    for c in symbol.chars() {
+        if c == '"' || c == '\\' {
+            w.write_char('\\')?;
+        }
+        w.write_char(c)?;
+    }
+    w.write_char('"')
+}
+
 /// Create a reverse-alias map from a value to all aliases having that value as a direct target
 fn alias_map(func: &Function) -> SecondaryMap<Value, Vec<Value>> {
     let mut aliases = SecondaryMap::<_, Vec<_>>::new();
@@ -211,6 +241,9 @@ pub fn decorate_function<FW: FuncWriter>(
 // Function spec.
 
 fn write_spec(w: &mut dyn Write, func: &Function, regs: Option<&RegInfo>) -> fmt::Result {
+    if !func.attributes.is_empty() {
+        write!(w, "{} ", func.attributes)?;
+    }
     write!(w, "{}{}", func.name, func.signature.display(regs))
 }
 
@@ -457,7 +490,10 @@ fn write_instruction(
         write!(w, " = ")?;
     }
 
-    // Then the opcode, possibly with a '.type' suffix.
+    // Then the branch hint and the opcode, possibly with a '.type' suffix.
+    if let Some(hint) = func.branch_hints[inst] {
+        write!(w, "{} ", hint)?;
+    }
     let opcode = func.dfg[inst].opcode();
 
     match type_suffix(func, inst) {
@@ -491,6 +527,7 @@ pub fn write_operands(
         UnaryIeee64 { imm, .. } => write!(w, " {}", imm),
         UnaryBool { imm, .. } => write!(w, " {}", imm),
         UnaryGlobalValue { global_value, .. } => write!(w, " {}", global_value),
+        UnaryUimm32 { imm, .. } => write!(w, " {}", imm),
         Binary { args, .. } => write!(w, " {}, {}", args[0], args[1]),
         BinaryImm { arg, imm, .. } => write!(w, " {}, {}", arg, imm),
         Ternary { args, .. } => write!(w, " {}, {}, {}", args[0], args[1], args[2]),
@@ -573,6 +610,21 @@ pub fn write_operands(
         Call {
             func_ref, ref args, ..
         } => write!(w, " {}({})", func_ref, DisplayValues(args.as_slice(pool))),
+        RawBytes {
+            raw_code, ref args, ..
+        } => write!(w, " {}({})", raw_code, DisplayValues(args.as_slice(pool))),
+        Invoke {
+            func_ref,
+            destination,
+            ref args,
+            ..
+        } => write!(
+            w,
+            " {}({}), {}",
+            func_ref,
+            DisplayValues(args.as_slice(pool)),
+            destination
+        ),
         CallIndirect {
             sig_ref, ref args, ..
         } => {
@@ -42,6 +42,15 @@ impl binemit::TrapSink for FaerieTrapSink {
             code,
         });
     }
+
+    fn deopt(
+        &mut self,
+        _offset: binemit::CodeOffset,
+        _srcloc: ir::SourceLoc,
+        _locations: &[binemit::DeoptLocation],
+    ) {
+        // Deoptimization exits aren't part of the trap manifest.
+    }
 }
 
 /// Collection of all `FaerieTrapSink`s for the module
@@ -37,56 +37,56 @@ impl FunctionRunner {
     /// interpreted as a successful test execution and mapped to Ok whereas a 'false' value is
     /// interpreted as a failed test and mapped to Err.
     pub fn run(&self) -> Result<(), String> {
-        let func = self.function.clone();
-        if !(func.signature.params.is_empty()
-            && func.signature.returns.len() == 1
-            && func.signature.returns.first().unwrap().value_type.is_bool())
-        {
-            return Err(String::from(
-                "Functions must have a signature like: () -> boolean",
-            ));
-        }
+        let mut context = Context::for_function(self.function.clone());
+        compile_and_run(self.isa.as_ref(), &mut context)
+    }
 
-        if func.signature.call_conv != self.isa.default_call_conv()
-            && func.signature.call_conv != CallConv::Fast
-        {
-            // ideally we wouldn't have to also check for Fast here but currently there is no way to inform the filetest parser that we would like to use a default other than Fast
-            return Err(String::from(
-                "Functions only run on the host's default calling convention; remove the specified calling convention in the function signature to use the host's default.",
-            ));
-        }
+    /// Same as `run`, but move the function into `context` instead of copying it, and reuse the
+    /// memory `context` allocated for previous functions.
+    pub fn run_in_context(self, context: &mut Context) -> Result<(), String> {
+        context.clear();
+        context.func = self.function;
+        compile_and_run(self.isa.as_ref(), context)
+    }
+}
 
-        // set up the context
-        let mut context = Context::new();
-        context.func = func;
+/// Compile the function in `context` for `isa` and execute it.
+fn compile_and_run(isa: &dyn TargetIsa, context: &mut Context) -> Result<(), String> {
+    let signature = &context.func.signature;
+    if !(signature.params.is_empty()
+        && signature.returns.len() == 1
+        && signature.returns.first().unwrap().value_type.is_bool())
+    {
+        return Err(String::from(
+            "Functions must have a signature like: () -> boolean",
+        ));
+    }
 
-        // compile and encode the result to machine code
-        let relocs = &mut NullRelocSink {};
-        let traps = &mut NullTrapSink {};
-        let stackmaps = &mut NullStackmapSink {};
-        let code_info = context
-            .compile(self.isa.as_ref())
-            .map_err(|e| e.to_string())?;
-        let code_page = MemoryMap::new(code_info.total_size as usize, &[MapOption::MapWritable])
+    if signature.call_conv != isa.default_call_conv() && signature.call_conv != CallConv::Fast {
+        // ideally we wouldn't have to also check for Fast here but currently there is no way to inform the filetest parser that we would like to use a default other than Fast
+        return Err(String::from(
+            "Functions only run on the host's default calling convention; remove the specified calling convention in the function signature to use the host's default.",
+        ));
+    }
+
+    // compile and encode the result to machine code
+    let relocs = &mut NullRelocSink {};
+    let traps = &mut NullTrapSink {};
+    let stackmaps = &mut NullStackmapSink {};
+    let code_info = context.compile(isa).map_err(|e| e.to_string())?;
+    let code_page = MemoryMap::new(code_info.total_size as usize, &[MapOption::MapWritable])
+        .map_err(|e| e.to_string())?;
+    let callable_fn: fn() -> bool = unsafe {
+        context.emit_to_memory(isa, code_page.data(), relocs, traps, stackmaps);
+        region::protect(code_page.data(), code_page.len(), Protection::ReadExecute)
             .map_err(|e| e.to_string())?;
-        let callable_fn: fn() -> bool = unsafe {
-            context.emit_to_memory(
-                self.isa.as_ref(),
-                code_page.data(),
-                relocs,
-                traps,
-                stackmaps,
-            );
-            region::protect(code_page.data(), code_page.len(), Protection::ReadExecute)
-                .map_err(|e| e.to_string())?;
-            mem::transmute(code_page.data())
-        };
+        mem::transmute(code_page.data())
+    };
 
-        // execute
-        match callable_fn() {
-            true => Ok(()),
-            false => Err(format!("Failed: {}", context.func.name.to_string())),
-        }
+    // execute
+    match callable_fn() {
+        true => Ok(()),
+        false => Err(format!("Failed: {}", context.func.name.to_string())),
     }
 }
 
@@ -98,6 +98,22 @@ impl binemit::CodeSink for TextSink {
         write!(self.text, "{} ", code).unwrap();
     }
 
+    fn deopt(&mut self, _srcloc: ir::SourceLoc, locations: &[binemit::DeoptLocation]) {
+        write!(self.text, "deopt(").unwrap();
+        for (i, loc) in locations.iter().enumerate() {
+            if i > 0 {
+                write!(self.text, ", ").unwrap();
+            }
+            match *loc {
+                binemit::DeoptLocation::Reg(reg) => write!(self.text, "%{}", reg).unwrap(),
+                binemit::DeoptLocation::Stack(offset) => {
+                    write!(self.text, "sp{:+}", offset).unwrap()
+                }
+            }
+        }
+        write!(self.text, ") ").unwrap();
+    }
+
     fn begin_jumptables(&mut self) {
         self.code_size = self.offset
     }
@@ -312,8 +328,27 @@ impl SubTest for TestBinEmit {
             }
         }
 
+        let mut patches = Vec::new();
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                if let Some(id) = binemit::patch_site_id(&func, inst) {
+                    patches.push((inst, id));
+                }
+            }
+        }
+        if !patches.is_empty() {
+            while sink.offset % 8 != 0 {
+                sink.put1(0);
+            }
+        }
+
         sink.begin_rodata();
-        // TODO: Read-only (constant pool) data.
+
+        for (inst, id) in patches {
+            assert_eq!(sink.offset, func.patch_offsets[inst]);
+            sink.patch_site(id);
+            sink.put8(0);
+        }
 
         sink.end_codegen();
 
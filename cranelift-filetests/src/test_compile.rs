@@ -66,6 +66,22 @@ impl SubTest for TestCompile {
             ));
         }
 
+        // Verify the encoding sizes against the machine code emitted into memory.
+        let mut code = Vec::new();
+        code.resize(total_size as usize, 0);
+        unsafe {
+            comp_ctx.emit_to_memory(
+                isa,
+                code.as_mut_ptr(),
+                &mut binemit::NullRelocSink {},
+                &mut binemit::NullTrapSink {},
+                &mut binemit::NullStackmapSink {},
+            );
+        }
+        comp_ctx
+            .verify_encoding_sizes(isa, &code)
+            .map_err(|errors| errors.display(&comp_ctx.func, isa).to_string())?;
+
         // Run final code through filecheck.
         let text = comp_ctx.func.display(Some(isa)).to_string();
         run_filecheck(&text, context)
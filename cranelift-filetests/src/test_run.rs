@@ -34,11 +34,12 @@ impl SubTest for TestRun {
     }
 
     fn run(&self, func: Cow<ir::Function>, context: &Context) -> SubtestResult<()> {
+        let mut run_context = cranelift_codegen::Context::new();
         for comment in context.details.comments.iter() {
             if comment.text.contains("run") {
                 let runner =
                     FunctionRunner::with_host_isa(func.clone().into_owned(), context.flags.clone());
-                runner.run()?
+                runner.run_in_context(&mut run_context)?
             }
         }
         Ok(())
@@ -29,6 +29,14 @@ impl SubTest for TestSafepoint {
             .legalize(isa)
             .map_err(|e| pretty_error(&comp_ctx.func, context.isa, e))?;
         comp_ctx.compute_domtree();
+        if isa.flags().enable_auto_safepoints() {
+            comp_ctx
+                .insert_safepoints(isa)
+                .map_err(|e| pretty_error(&comp_ctx.func, context.isa, e))?;
+            comp_ctx
+                .eliminate_redundant_safepoints(isa)
+                .map_err(|e| pretty_error(&comp_ctx.func, context.isa, e))?;
+        }
         comp_ctx
             .regalloc(isa)
             .map_err(|e| pretty_error(&comp_ctx.func, context.isa, e))?;
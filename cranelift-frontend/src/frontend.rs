@@ -1,15 +1,19 @@
 //! A frontend for building Cranelift IR from other languages.
 use crate::ssa::{Block, SSABuilder, SideEffects};
 use crate::variable::Variable;
+use crate::HashMap;
+use core::fmt;
 use cranelift_codegen::cursor::{Cursor, FuncCursor};
-use cranelift_codegen::entity::{EntitySet, SecondaryMap};
+use cranelift_codegen::entity::{EntityRef, EntitySet, SecondaryMap};
 use cranelift_codegen::ir;
+use cranelift_codegen::ir::condcodes::IntCC;
 use cranelift_codegen::ir::function::DisplayFunction;
 use cranelift_codegen::ir::{
-    types, AbiParam, DataFlowGraph, Ebb, ExtFuncData, ExternalName, FuncRef, Function, GlobalValue,
-    GlobalValueData, Heap, HeapData, Inst, InstBuilder, InstBuilderBase, InstructionData,
-    JumpTable, JumpTableData, LibCall, MemFlags, SigRef, Signature, StackSlot, StackSlotData, Type,
-    Value, ValueLabel, ValueLabelAssignments, ValueLabelStart,
+    types, AbiParam, ArgumentPurpose, BranchHint, DataFlowGraph, Ebb, ExtFuncData, ExternalName,
+    FuncRef, Function, GlobalValue, GlobalValueData, Heap, HeapData, Inst, InstBuilder,
+    InstBuilderBase, InstructionData, JumpTable, JumpTableData, LibCall, MemFlags, SigRef,
+    Signature, StackSlot, StackSlotData, StackSlotKind, StackSlotLifetime, Type, Value, ValueLabel,
+    ValueLabelAssignments, ValueLabelStart,
 };
 use cranelift_codegen::isa::{TargetFrontendConfig, TargetIsa};
 use cranelift_codegen::packed_option::PackedOption;
@@ -24,6 +28,19 @@ pub struct FunctionBuilderContext {
     ssa: SSABuilder,
     ebbs: SecondaryMap<Ebb, EbbData>,
     types: SecondaryMap<Variable, Type>,
+    /// The `Ebb` in which each variable was last defined.
+    last_def_ebbs: SecondaryMap<Variable, PackedOption<Ebb>>,
+    /// The open stack slot scopes, with their first position and the index of their first slot
+    /// in `scoped_slots`.
+    slot_scopes: Vec<(u32, usize)>,
+    /// Explicit stack slots created in the open stack slot scopes.
+    scoped_slots: Vec<StackSlot>,
+    /// Next position to assign to the boundary of a stack slot scope.
+    next_scope_position: u32,
+    /// Signatures imported with `import_signature_cached`.
+    signatures: HashMap<Signature, SigRef>,
+    /// External functions imported with `import_function_cached`.
+    functions: HashMap<ExternalName, FuncRef>,
 }
 
 /// Temporary object used to build a single Cranelift IR `Function`.
@@ -37,6 +54,12 @@ pub struct FunctionBuilder<'a> {
 
     func_ctx: &'a mut FunctionBuilderContext,
     position: Position,
+
+    /// Assign a value label to the values of variables in `def_var` and `use_var`.
+    auto_value_labels: bool,
+
+    /// Check the uses and definitions of variables even in release builds.
+    strict_variables: bool,
 }
 
 #[derive(Clone, Default)]
@@ -53,6 +76,9 @@ struct EbbData {
 
     /// Count of parameters not supplied implicitly by the SSABuilder.
     user_param_count: usize,
+
+    /// Variables defined with `def_var` in this Ebb, possibly with duplicates.
+    defined_vars: Vec<Variable>,
 }
 
 #[derive(Default)]
@@ -82,6 +108,12 @@ impl FunctionBuilderContext {
             ssa: SSABuilder::new(),
             ebbs: SecondaryMap::new(),
             types: SecondaryMap::new(),
+            last_def_ebbs: SecondaryMap::new(),
+            slot_scopes: Vec::new(),
+            scoped_slots: Vec::new(),
+            next_scope_position: 0,
+            signatures: HashMap::new(),
+            functions: HashMap::new(),
         }
     }
 
@@ -89,10 +121,56 @@ impl FunctionBuilderContext {
         self.ssa.clear();
         self.ebbs.clear();
         self.types.clear();
+        self.last_def_ebbs.clear();
+        self.slot_scopes.clear();
+        self.scoped_slots.clear();
+        self.next_scope_position = 0;
+        self.signatures.clear();
+        self.functions.clear();
     }
 
     fn is_empty(&self) -> bool {
-        self.ssa.is_empty() && self.ebbs.is_empty() && self.types.is_empty()
+        self.ssa.is_empty()
+            && self.ebbs.is_empty()
+            && self.types.is_empty()
+            && self.last_def_ebbs.is_empty()
+            && self.slot_scopes.is_empty()
+            && self.scoped_slots.is_empty()
+            && self.next_scope_position == 0
+            && self.signatures.is_empty()
+            && self.functions.is_empty()
+    }
+}
+
+/// Error returned by [`FunctionBuilder::try_finalize`](struct.FunctionBuilder.html#method.try_finalize)
+/// when the function is not complete.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FinalizeError {
+    /// The `Ebb`s containing instructions that haven't been sealed.
+    pub unsealed_ebbs: Vec<Ebb>,
+    /// The `Ebb`s containing instructions that don't end with a terminator.
+    pub unfilled_ebbs: Vec<Ebb>,
+}
+
+impl fmt::Display for FinalizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_ebbs(f: &mut fmt::Formatter, ebbs: &[Ebb]) -> fmt::Result {
+            for (i, ebb) in ebbs.iter().enumerate() {
+                write!(f, "{}{}", if i == 0 { "" } else { ", " }, ebb)?;
+            }
+            Ok(())
+        }
+
+        f.write_str("all blocks should be sealed and filled")?;
+        if !self.unsealed_ebbs.is_empty() {
+            f.write_str("; unsealed: ")?;
+            write_ebbs(f, &self.unsealed_ebbs)?;
+        }
+        if !self.unfilled_ebbs.is_empty() {
+            f.write_str("; unfilled: ")?;
+            write_ebbs(f, &self.unfilled_ebbs)?;
+        }
+        Ok(())
     }
 }
 
@@ -226,6 +304,8 @@ impl<'a> FunctionBuilder<'a> {
             srcloc: Default::default(),
             func_ctx,
             position: Position::default(),
+            auto_value_labels: false,
+            strict_variables: false,
         }
     }
 
@@ -234,6 +314,31 @@ impl<'a> FunctionBuilder<'a> {
         self.srcloc = srcloc;
     }
 
+    /// Enable or disable the automatic labelling of variable values for debug info.
+    ///
+    /// When enabled, every value defined with `def_var` or returned by `use_var` is assigned the
+    /// `ValueLabel` with the same index as its variable, as if `set_val_label` had been called,
+    /// so `Context::build_value_labels_ranges` can locate each variable without further help
+    /// from the frontend. Like `set_val_label`, this has no effect unless
+    /// `func.collect_debug_info` has been called.
+    pub fn set_auto_value_labels(&mut self, enabled: bool) {
+        self.auto_value_labels = enabled;
+    }
+
+    /// Enable or disable the strict checking of variables.
+    ///
+    /// In strict mode, `def_var` panics right away if the variable hasn't been declared or if the
+    /// type of the value doesn't match its declared type, even in release builds, and `use_var`
+    /// panics if the variable hasn't been defined anywhere yet. The panic message gives the
+    /// current `Ebb` and source location instead of leaving the verifier to report an invalid
+    /// function later on.
+    ///
+    /// Strict mode assumes that the `Ebb`s are filled in an order where definitions come before
+    /// uses, as when translating structured control flow.
+    pub fn set_strict_variables(&mut self, enabled: bool) {
+        self.strict_variables = enabled;
+    }
+
     /// Creates a new `Ebb` and returns its reference.
     pub fn create_ebb(&mut self) -> Ebb {
         let ebb = self.func.dfg.make_ebb();
@@ -242,6 +347,7 @@ impl<'a> FunctionBuilder<'a> {
             filled: false,
             pristine: true,
             user_param_count: 0,
+            defined_vars: Vec::new(),
         };
         ebb
     }
@@ -302,6 +408,14 @@ impl<'a> FunctionBuilder<'a> {
     /// Returns the Cranelift IR value corresponding to the utilization at the current program
     /// position of a previously defined user variable.
     pub fn use_var(&mut self, var: Variable) -> Value {
+        if self.strict_variables {
+            if self.declared_type(var).is_none() {
+                self.variable_error(var, "is used but its type has not been declared");
+            }
+            if self.func_ctx.last_def_ebbs[var].is_none() {
+                self.variable_error(var, "is used before being defined");
+            }
+        }
         let (val, side_effects) = {
             let ty = *self.func_ctx.types.get(var).unwrap_or_else(|| {
                 panic!(
@@ -314,12 +428,27 @@ impl<'a> FunctionBuilder<'a> {
                 .use_var(self.func, var, ty, self.position.basic_block.unwrap())
         };
         self.handle_ssa_side_effects(side_effects);
+        self.auto_val_label(var, val);
         val
     }
 
     /// Register a new definition of a user variable. The type of the value must be
     /// the same as the type registered for the variable.
     pub fn def_var(&mut self, var: Variable, val: Value) {
+        if self.strict_variables {
+            let val_type = self.func.dfg.value_type(val);
+            match self.declared_type(var) {
+                None => self.variable_error(var, "is defined but its type has not been declared"),
+                Some(ty) if ty != val_type => self.variable_error(
+                    var,
+                    &format!(
+                        "of type {} is defined with {} of type {}",
+                        ty, val, val_type
+                    ),
+                ),
+                Some(_) => {}
+            }
+        }
         debug_assert_eq!(
             *self.func_ctx.types.get(var).unwrap_or_else(|| panic!(
                 "variable {:?} is used but its type has not been declared",
@@ -334,6 +463,12 @@ impl<'a> FunctionBuilder<'a> {
         self.func_ctx
             .ssa
             .def_var(var, val, self.position.basic_block.unwrap());
+        let ebb = self.position.ebb;
+        if self.func_ctx.last_def_ebbs[var] != ebb {
+            self.func_ctx.last_def_ebbs[var] = ebb;
+            self.func_ctx.ebbs[ebb.unwrap()].defined_vars.push(var);
+        }
+        self.auto_val_label(var, val);
     }
 
     /// Set label for Value
@@ -367,8 +502,47 @@ impl<'a> FunctionBuilder<'a> {
 
     /// Creates a stack slot in the function, to be used by `stack_load`, `stack_store` and
     /// `stack_addr` instructions.
+    ///
+    /// An explicit stack slot created inside a stack slot scope is only alive until the end of
+    /// the innermost scope.
     pub fn create_stack_slot(&mut self, data: StackSlotData) -> StackSlot {
-        self.func.create_stack_slot(data)
+        let scoped = data.kind == StackSlotKind::ExplicitSlot
+            && data.lifetime.is_none()
+            && !self.func_ctx.slot_scopes.is_empty();
+        let ss = self.func.create_stack_slot(data);
+        if scoped {
+            self.func_ctx.scoped_slots.push(ss);
+        }
+        ss
+    }
+
+    /// Opens a stack slot scope, typically corresponding to a lexical scope of the source
+    /// language.
+    ///
+    /// The explicit stack slots created before the matching `end_stack_slot_scope` are not
+    /// accessed after it, so their memory can be shared with stack slots of the following scopes.
+    /// Scopes can be nested; slots of scopes that are still open when the function is finalized
+    /// are alive in the whole function.
+    pub fn begin_stack_slot_scope(&mut self) {
+        let position = self.func_ctx.next_scope_position;
+        self.func_ctx.next_scope_position += 1;
+        let first_slot = self.func_ctx.scoped_slots.len();
+        self.func_ctx.slot_scopes.push((position, first_slot));
+    }
+
+    /// Closes the innermost stack slot scope, setting the lifetime of the stack slots created in
+    /// it.
+    pub fn end_stack_slot_scope(&mut self) {
+        let (begin, first_slot) = self
+            .func_ctx
+            .slot_scopes
+            .pop()
+            .expect("no stack slot scope to end");
+        let end = self.func_ctx.next_scope_position;
+        self.func_ctx.next_scope_position += 1;
+        for ss in self.func_ctx.scoped_slots.drain(first_slot..) {
+            self.func.stack_slots[ss].lifetime = Some(StackSlotLifetime::new(begin, end));
+        }
     }
 
     /// Adds a signature which can later be used to declare an external function import.
@@ -381,6 +555,72 @@ impl<'a> FunctionBuilder<'a> {
         self.func.import_function(data)
     }
 
+    /// Adds a signature like `import_signature`, unless an identical signature has already been
+    /// added with this method, in which case its reference is returned.
+    pub fn import_signature_cached(&mut self, signature: &Signature) -> SigRef {
+        if let Some(&sigref) = self.func_ctx.signatures.get(signature) {
+            return sigref;
+        }
+        let sigref = self.import_signature(signature.clone());
+        self.func_ctx.signatures.insert(signature.clone(), sigref);
+        sigref
+    }
+
+    /// Declares the external function `name` with `signature` like `import_function`, unless it
+    /// has already been declared with this method, in which case its reference is returned.
+    ///
+    /// A function declared both as colocated and as not colocated is not colocated, since that is
+    /// correct in both cases. The signature is added with `import_signature_cached`.
+    pub fn import_function_cached(
+        &mut self,
+        name: ExternalName,
+        signature: &Signature,
+        colocated: bool,
+    ) -> FuncRef {
+        if let Some(&funcref) = self.func_ctx.functions.get(&name) {
+            debug_assert_eq!(
+                self.func.dfg.signatures[self.func.dfg.ext_funcs[funcref].signature], *signature,
+                "function {} imported with different signatures",
+                name
+            );
+            self.func.dfg.ext_funcs[funcref].colocated &= colocated;
+            return funcref;
+        }
+        let sigref = self.import_signature_cached(signature);
+        let funcref = self.import_function(ExtFuncData {
+            name: name.clone(),
+            signature: sigref,
+            colocated,
+        });
+        self.func_ctx.functions.insert(name, funcref);
+        funcref
+    }
+
+    /// Calls the external function `name` with `signature`, declaring it on first use with
+    /// `import_function_cached`.
+    pub fn call_by_name(
+        &mut self,
+        name: ExternalName,
+        signature: &Signature,
+        colocated: bool,
+        args: &[Value],
+    ) -> Inst {
+        let funcref = self.import_function_cached(name, signature, colocated);
+        self.ins().call(funcref, args)
+    }
+
+    /// Calls the function pointed to by `callee` with `signature`, which is added on first use
+    /// with `import_signature_cached`.
+    pub fn call_indirect_by_signature(
+        &mut self,
+        signature: &Signature,
+        callee: Value,
+        args: &[Value],
+    ) -> Inst {
+        let sigref = self.import_signature_cached(signature);
+        self.ins().call_indirect(sigref, callee, args)
+    }
+
     /// Declares a global value accessible to the function.
     pub fn create_global_value(&mut self, data: GlobalValueData) -> GlobalValue {
         self.func.create_global_value(data)
@@ -464,20 +704,11 @@ impl<'a> FunctionBuilder<'a> {
     /// for another function.
     pub fn finalize(&mut self) {
         // Check that all the `Ebb`s are filled and sealed.
-        debug_assert!(
-            self.func_ctx
-                .ebbs
-                .iter()
-                .all(|(ebb, ebb_data)| ebb_data.pristine || self.func_ctx.ssa.is_sealed(ebb)),
-            "all blocks should be sealed before dropping a FunctionBuilder"
-        );
-        debug_assert!(
-            self.func_ctx
-                .ebbs
-                .values()
-                .all(|ebb_data| ebb_data.pristine || ebb_data.filled),
-            "all blocks should be filled before dropping a FunctionBuilder"
-        );
+        if cfg!(debug_assertions) {
+            if let Err(err) = self.check_finalize() {
+                panic!("{} before dropping a FunctionBuilder", err);
+            }
+        }
 
         // In debug mode, check that all blocks are valid basic blocks.
         #[cfg(feature = "basic-blocks")]
@@ -500,6 +731,29 @@ impl<'a> FunctionBuilder<'a> {
         self.srcloc = Default::default();
         self.position = Position::default();
     }
+
+    /// Like `finalize`, but returns an error listing the incomplete blocks instead of panicking
+    /// in debug mode when some blocks haven't been sealed or filled.
+    ///
+    /// The `FunctionBuilder` is left untouched when an error is returned.
+    pub fn try_finalize(&mut self) -> Result<(), FinalizeError> {
+        self.check_finalize()?;
+        self.finalize();
+        Ok(())
+    }
+
+    fn check_finalize(&self) -> Result<(), FinalizeError> {
+        let unsealed_ebbs = self.unsealed_ebbs();
+        let unfilled_ebbs = self.unfilled_ebbs();
+        if unsealed_ebbs.is_empty() && unfilled_ebbs.is_empty() {
+            Ok(())
+        } else {
+            Err(FinalizeError {
+                unsealed_ebbs,
+                unfilled_ebbs,
+            })
+        }
+    }
 }
 
 /// All the functions documented in the previous block are write-only and help you build a valid
@@ -554,6 +808,18 @@ impl<'a> FunctionBuilder<'a> {
             .declare_ebb_predecessor(new_dest, pred, inst);
     }
 
+    /// Tells how likely the conditional branch `inst` is to be taken.
+    ///
+    /// The hints are used to move the rarely executed code out of the way.
+    pub fn set_branch_hint(&mut self, inst: Inst, hint: BranchHint) {
+        let opcode = self.func.dfg[inst].opcode();
+        debug_assert!(
+            opcode.is_branch() && !opcode.is_terminator(),
+            "branch hints can only be set on conditional branches"
+        );
+        self.func.branch_hints[inst] = Some(hint);
+    }
+
     /// Returns `true` if and only if the current `Ebb` is sealed and has no predecessors declared.
     ///
     /// The entry block of a function is never unreachable.
@@ -582,6 +848,34 @@ impl<'a> FunctionBuilder<'a> {
         self.func_ctx.ebbs[self.position.ebb.unwrap()].filled
     }
 
+    /// Returns the `Ebb`s containing instructions that haven't been sealed yet.
+    pub fn unsealed_ebbs(&self) -> Vec<Ebb> {
+        self.func_ctx
+            .ebbs
+            .iter()
+            .filter(|&(ebb, ebb_data)| !ebb_data.pristine && !self.func_ctx.ssa.is_sealed(ebb))
+            .map(|(ebb, _)| ebb)
+            .collect()
+    }
+
+    /// Returns the `Ebb`s containing instructions that don't end with a terminator yet.
+    pub fn unfilled_ebbs(&self) -> Vec<Ebb> {
+        self.func_ctx
+            .ebbs
+            .iter()
+            .filter(|&(_, ebb_data)| !ebb_data.pristine && !ebb_data.filled)
+            .map(|(ebb, _)| ebb)
+            .collect()
+    }
+
+    /// Returns the variables defined with `def_var` in `ebb`, ordered by index.
+    pub fn defined_vars(&self, ebb: Ebb) -> Vec<Variable> {
+        let mut vars = self.func_ctx.ebbs[ebb].defined_vars.clone();
+        vars.sort_unstable_by_key(|var| var.index());
+        vars.dedup();
+        vars
+    }
+
     /// Returns a displayable object for the function as it is.
     ///
     /// Useful for debug purposes. Use it with `None` for standard printing.
@@ -608,21 +902,17 @@ impl<'a> FunctionBuilder<'a> {
         size: Value,
     ) {
         let pointer_type = config.pointer_type();
-        let signature = {
-            let mut s = Signature::new(config.default_call_conv);
-            s.params.push(AbiParam::new(pointer_type));
-            s.params.push(AbiParam::new(pointer_type));
-            s.params.push(AbiParam::new(pointer_type));
-            self.import_signature(s)
-        };
-
-        let libc_memcpy = self.import_function(ExtFuncData {
-            name: ExternalName::LibCall(LibCall::Memcpy),
-            signature,
-            colocated: false,
-        });
-
-        self.ins().call(libc_memcpy, &[dest, src, size]);
+        let mut signature = Signature::new(config.default_call_conv);
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.params.push(AbiParam::new(pointer_type));
+
+        self.call_by_name(
+            ExternalName::LibCall(LibCall::Memcpy),
+            &signature,
+            false,
+            &[dest, src, size],
+        );
     }
 
     /// Optimised memcpy for small copies.
@@ -687,22 +977,18 @@ impl<'a> FunctionBuilder<'a> {
         size: Value,
     ) {
         let pointer_type = config.pointer_type();
-        let signature = {
-            let mut s = Signature::new(config.default_call_conv);
-            s.params.push(AbiParam::new(pointer_type));
-            s.params.push(AbiParam::new(types::I32));
-            s.params.push(AbiParam::new(pointer_type));
-            self.import_signature(s)
-        };
-
-        let libc_memset = self.import_function(ExtFuncData {
-            name: ExternalName::LibCall(LibCall::Memset),
-            signature,
-            colocated: false,
-        });
+        let mut signature = Signature::new(config.default_call_conv);
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.params.push(AbiParam::new(types::I32));
+        signature.params.push(AbiParam::new(pointer_type));
 
         let ch = self.ins().uextend(types::I32, ch);
-        self.ins().call(libc_memset, &[buffer, ch, size]);
+        self.call_by_name(
+            ExternalName::LibCall(LibCall::Memset),
+            &signature,
+            false,
+            &[buffer, ch, size],
+        );
     }
 
     /// Calls libc.memset
@@ -781,21 +1067,17 @@ impl<'a> FunctionBuilder<'a> {
         size: Value,
     ) {
         let pointer_type = config.pointer_type();
-        let signature = {
-            let mut s = Signature::new(config.default_call_conv);
-            s.params.push(AbiParam::new(pointer_type));
-            s.params.push(AbiParam::new(pointer_type));
-            s.params.push(AbiParam::new(pointer_type));
-            self.import_signature(s)
-        };
-
-        let libc_memmove = self.import_function(ExtFuncData {
-            name: ExternalName::LibCall(LibCall::Memmove),
-            signature,
-            colocated: false,
-        });
-
-        self.ins().call(libc_memmove, &[dest, source, size]);
+        let mut signature = Signature::new(config.default_call_conv);
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.params.push(AbiParam::new(pointer_type));
+
+        self.call_by_name(
+            ExternalName::LibCall(LibCall::Memmove),
+            &signature,
+            false,
+            &[dest, source, size],
+        );
     }
 
     /// Optimised memmove for small moves.
@@ -848,6 +1130,164 @@ impl<'a> FunctionBuilder<'a> {
     }
 }
 
+/// Wide integer helpers
+///
+/// 128-bit integers are represented by pairs of `i64` values holding their low and high halves.
+/// A pair can be turned into an `i128` value with `iconcat` and back with `isplit`.
+impl<'a> FunctionBuilder<'a> {
+    /// Adds the 128-bit integers `x` and `y`, wrapping on overflow.
+    pub fn iadd_i128(&mut self, x: (Value, Value), y: (Value, Value)) -> (Value, Value) {
+        let (lo, carry) = self.ins().iadd_cout(x.0, y.0);
+        let hi = self.ins().iadd_cin(x.1, y.1, carry);
+        (lo, hi)
+    }
+
+    /// Subtracts the 128-bit integer `y` from `x`, wrapping on overflow.
+    pub fn isub_i128(&mut self, x: (Value, Value), y: (Value, Value)) -> (Value, Value) {
+        let (lo, borrow) = self.ins().isub_bout(x.0, y.0);
+        let hi = self.ins().isub_bin(x.1, y.1, borrow);
+        (lo, hi)
+    }
+
+    /// Multiplies the 128-bit integers `x` and `y`, wrapping on overflow.
+    ///
+    /// The low 128 bits of the product are the same for signed and unsigned operands.
+    pub fn imul_i128(&mut self, x: (Value, Value), y: (Value, Value)) -> (Value, Value) {
+        let lo = self.ins().imul(x.0, y.0);
+        let lo_carry = self.ins().umulhi(x.0, y.0);
+        let cross1 = self.ins().imul(x.0, y.1);
+        let cross2 = self.ins().imul(x.1, y.0);
+        let hi = self.ins().iadd(lo_carry, cross1);
+        let hi = self.ins().iadd(hi, cross2);
+        (lo, hi)
+    }
+
+    /// Compares the 128-bit integers `x` and `y` with `cond`, returning a `b1` value.
+    pub fn icmp_i128(&mut self, cond: IntCC, x: (Value, Value), y: (Value, Value)) -> Value {
+        match cond {
+            IntCC::Equal | IntCC::NotEqual => {
+                let lo = self.ins().icmp(cond, x.0, y.0);
+                let hi = self.ins().icmp(cond, x.1, y.1);
+                if cond == IntCC::Equal {
+                    self.ins().band(lo, hi)
+                } else {
+                    self.ins().bor(lo, hi)
+                }
+            }
+            _ => {
+                // The high halves decide unless they are equal, in which case the low halves
+                // are compared as unsigned numbers.
+                let (hi_cond, lo_cond) = match cond {
+                    IntCC::SignedLessThan => (cond, IntCC::UnsignedLessThan),
+                    IntCC::SignedLessThanOrEqual => {
+                        (IntCC::SignedLessThan, IntCC::UnsignedLessThanOrEqual)
+                    }
+                    IntCC::SignedGreaterThan => (cond, IntCC::UnsignedGreaterThan),
+                    IntCC::SignedGreaterThanOrEqual => {
+                        (IntCC::SignedGreaterThan, IntCC::UnsignedGreaterThanOrEqual)
+                    }
+                    IntCC::UnsignedLessThanOrEqual => (IntCC::UnsignedLessThan, cond),
+                    IntCC::UnsignedGreaterThanOrEqual => (IntCC::UnsignedGreaterThan, cond),
+                    _ => (cond, cond),
+                };
+                let hi = self.ins().icmp(hi_cond, x.1, y.1);
+                let hi_eq = self.ins().icmp(IntCC::Equal, x.1, y.1);
+                let lo = self.ins().icmp(lo_cond, x.0, y.0);
+                let lo = self.ins().band(hi_eq, lo);
+                self.ins().bor(hi, lo)
+            }
+        }
+    }
+}
+
+/// Struct-return helpers
+///
+/// Functions returning an aggregate that doesn't fit in registers receive the address of a
+/// caller-allocated buffer in a hidden `sret` parameter, write their result there and return the
+/// address again. Signatures of such functions are declared with
+/// [`add_sret_param`](fn.add_sret_param.html).
+impl<'a> FunctionBuilder<'a> {
+    /// Returns the `sret` parameter of the function being built, if its signature has one.
+    ///
+    /// The entry block parameters must have been created with
+    /// `append_ebb_params_for_function_params`, otherwise `None` is returned. The value has to be
+    /// returned along with the other results of the function.
+    pub fn sret_param(&self) -> Option<Value> {
+        let index = self
+            .func
+            .signature
+            .special_param_index(ArgumentPurpose::StructReturn)?;
+        let entry = self.func.layout.entry_block()?;
+        self.func.dfg.ebb_params(entry).get(index).copied()
+    }
+
+    /// Calls `func_ref`, whose signature has an `sret` parameter, with the address of a new
+    /// `size`-byte stack slot receiving the result.
+    ///
+    /// `args` are the arguments of the call without the `sret` pointer. Returns the call
+    /// instruction and the address of the result.
+    pub fn call_sret(&mut self, func_ref: FuncRef, size: u32, args: &[Value]) -> (Inst, Value) {
+        let sig_ref = self.func.dfg.ext_funcs[func_ref].signature;
+        let (args, addr) = self.sret_call_args(sig_ref, size, args);
+        let inst = self.ins().call(func_ref, &args);
+        (inst, addr)
+    }
+
+    /// Indirectly calls `callee`, whose signature `sig_ref` has an `sret` parameter, with the
+    /// address of a new `size`-byte stack slot receiving the result.
+    ///
+    /// `args` are the arguments of the call without the `sret` pointer. Returns the call
+    /// instruction and the address of the result.
+    pub fn call_indirect_sret(
+        &mut self,
+        sig_ref: SigRef,
+        callee: Value,
+        size: u32,
+        args: &[Value],
+    ) -> (Inst, Value) {
+        let (args, addr) = self.sret_call_args(sig_ref, size, args);
+        let inst = self.ins().call_indirect(sig_ref, callee, &args);
+        (inst, addr)
+    }
+
+    /// Allocates the result buffer of a call to `sig_ref` and inserts its address in `args`.
+    fn sret_call_args(
+        &mut self,
+        sig_ref: SigRef,
+        size: u32,
+        args: &[Value],
+    ) -> (Vec<Value>, Value) {
+        let sig = &self.func.dfg.signatures[sig_ref];
+        let index = sig
+            .special_param_index(ArgumentPurpose::StructReturn)
+            .expect("the callee signature has no sret parameter");
+        let pointer_type = sig.params[index].value_type;
+        let slot = self.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+        let addr = self.ins().stack_addr(pointer_type, slot, 0);
+        let mut call_args = args.to_vec();
+        call_args.insert(index, addr);
+        (call_args, addr)
+    }
+}
+
+/// Declares that the function with `signature` returns an aggregate through a hidden pointer
+/// of type `pointer_type`.
+///
+/// As in the System V and Windows x64 ABIs, the pointer is passed as the first parameter and
+/// returned again. Aggregate-returning functions usually have no other results, so the pointer
+/// ends up in the first return register.
+pub fn add_sret_param(signature: &mut Signature, pointer_type: Type) {
+    debug_assert!(
+        signature
+            .special_param_index(ArgumentPurpose::StructReturn)
+            .is_none(),
+        "the signature already has an sret parameter"
+    );
+    let param = AbiParam::special(pointer_type, ArgumentPurpose::StructReturn);
+    signature.params.insert(0, param);
+    signature.returns.push(param);
+}
+
 fn greatest_divisible_power_of_two(size: u64) -> u64 {
     (size as i64 & -(size as i64)) as u64
 }
@@ -875,6 +1315,29 @@ impl<'a> FunctionBuilder<'a> {
         );
     }
 
+    /// Returns the type `var` was declared with, if any.
+    fn declared_type(&self, var: Variable) -> Option<Type> {
+        self.func_ctx
+            .types
+            .get(var)
+            .cloned()
+            .filter(|&ty| ty != types::INVALID)
+    }
+
+    /// Panics with a `message` about `var`, located at the current position.
+    fn variable_error(&self, var: Variable, message: &str) -> ! {
+        match self.position.ebb.expand() {
+            Some(ebb) => panic!("{:?} {} in {} at {}", var, message, ebb, self.srcloc),
+            None => panic!("{:?} {} outside of any Ebb", var, message),
+        }
+    }
+
+    fn auto_val_label(&mut self, var: Variable, val: Value) {
+        if self.auto_value_labels {
+            self.set_val_label(val, ValueLabel::new(var.index()));
+        }
+    }
+
     fn handle_ssa_side_effects(&mut self, side_effects: SideEffects) {
         for split_ebb in side_effects.split_ebbs_created {
             self.func_ctx.ebbs[split_ebb].filled = true
@@ -888,15 +1351,21 @@ impl<'a> FunctionBuilder<'a> {
 #[cfg(test)]
 mod tests {
     use super::greatest_divisible_power_of_two;
-    use crate::frontend::{FunctionBuilder, FunctionBuilderContext};
+    use crate::frontend::{add_sret_param, FunctionBuilder, FunctionBuilderContext};
     use crate::Variable;
     use cranelift_codegen::entity::EntityRef;
+    use cranelift_codegen::ir::condcodes::IntCC;
     use cranelift_codegen::ir::types::*;
-    use cranelift_codegen::ir::{AbiParam, ExternalName, Function, InstBuilder, Signature};
-    use cranelift_codegen::isa::CallConv;
+    use cranelift_codegen::ir::{
+        AbiParam, ExtFuncData, ExternalName, Function, InstBuilder, MemFlags, Signature, SourceLoc,
+        StackSlotData, StackSlotKind, Value, ValueLabelAssignments,
+    };
+    use cranelift_codegen::isa::{CallConv, TargetFrontendConfig};
     use cranelift_codegen::settings;
     use cranelift_codegen::verifier::verify_function;
     use std::string::ToString;
+    use std::vec::Vec;
+    use target_lexicon::PointerWidth;
 
     fn sample_function(lazy_seal: bool) {
         let mut sig = Signature::new(CallConv::SystemV);
@@ -1288,6 +1757,481 @@ ebb0:
         );
     }
 
+    #[test]
+    fn auto_value_labels() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I32));
+        sig.returns.push(AbiParam::new(I32));
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        func.collect_debug_info();
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+            builder.set_auto_value_labels(true);
+
+            let block0 = builder.create_ebb();
+            let block1 = builder.create_ebb();
+            let x = Variable::new(0);
+            let y = Variable::new(1);
+            builder.declare_var(x, I32);
+            builder.declare_var(y, I32);
+            builder.append_ebb_params_for_function_params(block0);
+
+            builder.switch_to_block(block0);
+            builder.seal_block(block0);
+            let arg = builder.ebb_params(block0)[0];
+            builder.def_var(x, arg);
+            builder.set_srcloc(SourceLoc::new(4));
+            let tmp = builder.ins().iconst(I32, 2);
+            builder.def_var(y, tmp);
+            builder.ins().jump(block1, &[]);
+
+            builder.switch_to_block(block1);
+            builder.seal_block(block1);
+            builder.set_srcloc(SourceLoc::new(8));
+            let arg = builder.use_var(x);
+            builder.ins().return_(&[arg]);
+
+            builder.finalize();
+        }
+
+        let labels = func.dfg.values_labels.as_ref().unwrap();
+        let starts = |val| match labels[&val] {
+            ValueLabelAssignments::Starts(ref starts) => starts
+                .iter()
+                .map(|start| (start.label.index(), start.from.bits()))
+                .collect::<Vec<_>>(),
+            _ => panic!("unexpected value label assignments"),
+        };
+        assert_eq!(labels.len(), 2);
+        assert_eq!(starts(Value::new(0)), [(0, !0), (0, 8)]);
+        assert_eq!(starts(Value::new(1)), [(1, 4)]);
+    }
+
+    fn strict_function(def_type: Type, use_first: bool) {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(def_type));
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+        builder.set_strict_variables(true);
+
+        let block0 = builder.create_ebb();
+        let x = Variable::new(0);
+        builder.declare_var(x, I32);
+        builder.append_ebb_params_for_function_params(block0);
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+        builder.set_srcloc(SourceLoc::new(0x12));
+        if use_first {
+            builder.use_var(x);
+        }
+        let arg = builder.ebb_params(block0)[0];
+        builder.def_var(x, arg);
+        builder.use_var(x);
+        builder.ins().return_(&[]);
+        builder.finalize();
+    }
+
+    #[test]
+    fn strict_variables() {
+        strict_function(I32, false);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Variable(0) of type i32 is defined with v0 of type i64 in ebb0 at @0012"
+    )]
+    fn strict_variables_def_type() {
+        strict_function(I64, false);
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable(0) is used before being defined in ebb0 at @0012")]
+    fn strict_variables_use_before_def() {
+        strict_function(I32, true);
+    }
+
+    #[test]
+    fn cached_imports() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I64));
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+
+            let block0 = builder.create_ebb();
+            builder.append_ebb_params_for_function_params(block0);
+            builder.switch_to_block(block0);
+            builder.seal_block(block0);
+            let arg = builder.ebb_params(block0)[0];
+
+            let mut unary = Signature::new(CallConv::SystemV);
+            unary.params.push(AbiParam::new(I64));
+            let mut triple = unary.clone();
+            triple.params.push(AbiParam::new(I64));
+            triple.params.push(AbiParam::new(I64));
+            let config = TargetFrontendConfig {
+                default_call_conv: CallConv::SystemV,
+                pointer_width: PointerWidth::U64,
+            };
+
+            builder.call_by_name(ExternalName::testcase("foo"), &unary, false, &[arg]);
+            builder.call_by_name(ExternalName::testcase("bar"), &unary, true, &[arg]);
+            builder.call_by_name(ExternalName::testcase("foo"), &unary, false, &[arg]);
+            builder.call_by_name(ExternalName::testcase("baz"), &unary, true, &[arg]);
+            builder.call_by_name(ExternalName::testcase("baz"), &unary, false, &[arg]);
+            builder.call_indirect_by_signature(&triple, arg, &[arg, arg, arg]);
+            builder.call_memcpy(config, arg, arg, arg);
+            builder.call_memcpy(config, arg, arg, arg);
+            builder.ins().return_(&[]);
+
+            builder.finalize();
+        }
+
+        let flags = settings::Flags::new(settings::builder());
+        if let Err(errors) = verify_function(&func, &flags) {
+            panic!("{}\n{}", func.display(None), errors)
+        }
+        assert_eq!(
+            func.display(None).to_string(),
+            "function %sample(i64) system_v {
+    sig0 = (i64) system_v
+    sig1 = (i64, i64, i64) system_v
+    fn0 = %foo sig0
+    fn1 = colocated %bar sig0
+    fn2 = %baz sig0
+    fn3 = %Memcpy sig1
+
+ebb0(v0: i64):
+    call fn0(v0)
+    call fn1(v0)
+    call fn0(v0)
+    call fn2(v0)
+    call fn2(v0)
+    call_indirect sig1, v0(v0, v0, v0)
+    call fn3(v0, v0, v0)
+    call fn3(v0, v0, v0)
+    return
+}
+"
+        );
+    }
+
+    #[test]
+    fn stack_slot_scopes() {
+        let sig = Signature::new(CallConv::SystemV);
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+            let explicit = StackSlotData::new(StackSlotKind::ExplicitSlot, 8);
+
+            let block0 = builder.create_ebb();
+            builder.switch_to_block(block0);
+            builder.seal_block(block0);
+            builder.create_stack_slot(explicit.clone());
+            builder.begin_stack_slot_scope();
+            builder.create_stack_slot(explicit.clone());
+            builder.begin_stack_slot_scope();
+            builder.create_stack_slot(explicit.clone());
+            builder.end_stack_slot_scope();
+            builder.begin_stack_slot_scope();
+            builder.create_stack_slot(explicit.clone());
+            builder.end_stack_slot_scope();
+            builder.create_stack_slot(explicit.clone());
+            builder.end_stack_slot_scope();
+            builder.begin_stack_slot_scope();
+            builder.create_stack_slot(explicit);
+            builder.ins().return_(&[]);
+
+            builder.finalize();
+        }
+
+        assert_eq!(
+            func.display(None).to_string(),
+            "function %sample() system_v {
+    ss0 = explicit_slot 8
+    ss1 = explicit_slot 8, lifetime 0 5
+    ss2 = explicit_slot 8, lifetime 1 2
+    ss3 = explicit_slot 8, lifetime 3 4
+    ss4 = explicit_slot 8, lifetime 0 5
+    ss5 = explicit_slot 8
+
+ebb0:
+    return
+}
+"
+        );
+    }
+
+    #[test]
+    fn incomplete_blocks() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I32));
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+
+            let block0 = builder.create_ebb();
+            let block1 = builder.create_ebb();
+            let x = Variable::new(0);
+            let y = Variable::new(1);
+            builder.declare_var(x, I32);
+            builder.declare_var(y, I32);
+            builder.append_ebb_params_for_function_params(block0);
+
+            builder.switch_to_block(block0);
+            let arg = builder.ebb_params(block0)[0];
+            builder.def_var(y, arg);
+            builder.def_var(x, arg);
+            builder.def_var(y, arg);
+            builder.ins().jump(block1, &[]);
+
+            builder.switch_to_block(block1);
+            let arg = builder.use_var(y);
+            let sum = builder.ins().iadd(arg, arg);
+            builder.def_var(x, sum);
+
+            assert_eq!(builder.defined_vars(block0), [x, y]);
+            assert_eq!(builder.defined_vars(block1), [x]);
+            assert_eq!(builder.unsealed_ebbs(), [block0, block1]);
+            assert_eq!(builder.unfilled_ebbs(), [block1]);
+            let err = builder.try_finalize().unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "all blocks should be sealed and filled; unsealed: ebb0, ebb1; unfilled: ebb1"
+            );
+
+            builder.ins().return_(&[]);
+            builder.seal_all_blocks();
+            assert!(builder.unsealed_ebbs().is_empty());
+            assert!(builder.unfilled_ebbs().is_empty());
+            builder.try_finalize().unwrap();
+        }
+
+        let flags = settings::Flags::new(settings::builder());
+        if let Err(errors) = verify_function(&func, &flags) {
+            panic!("{}\n{}", func.display(None), errors)
+        }
+    }
+
+    #[test]
+    fn wide_arithmetic() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        for _ in 0..4 {
+            sig.params.push(AbiParam::new(I64));
+        }
+        sig.returns.push(AbiParam::new(I64));
+        sig.returns.push(AbiParam::new(I64));
+        sig.returns.push(AbiParam::new(B1));
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+
+            let block0 = builder.create_ebb();
+            builder.append_ebb_params_for_function_params(block0);
+            builder.switch_to_block(block0);
+            builder.seal_block(block0);
+
+            let params = builder.ebb_params(block0).to_vec();
+            let x = (params[0], params[1]);
+            let y = (params[2], params[3]);
+            let sum = builder.iadd_i128(x, y);
+            let difference = builder.isub_i128(sum, y);
+            let product = builder.imul_i128(difference, y);
+            let less = builder.icmp_i128(IntCC::SignedLessThanOrEqual, product, x);
+            builder.ins().return_(&[product.0, product.1, less]);
+
+            builder.finalize();
+        }
+
+        let flags = settings::Flags::new(settings::builder());
+        if let Err(errors) = verify_function(&func, &flags) {
+            panic!("{}\n{}", func.display(None), errors)
+        }
+        assert_eq!(
+            func.display(None).to_string(),
+            "function %sample(i64, i64, i64, i64) -> i64, i64, b1 system_v {
+ebb0(v0: i64, v1: i64, v2: i64, v3: i64):
+    v4, v5 = iadd_cout v0, v2
+    v6 = iadd_cin v1, v3, v5
+    v7, v8 = isub_bout v4, v2
+    v9 = isub_bin v6, v3, v8
+    v10 = imul v7, v2
+    v11 = umulhi v7, v2
+    v12 = imul v7, v3
+    v13 = imul v9, v2
+    v14 = iadd v11, v12
+    v15 = iadd v14, v13
+    v16 = icmp slt v15, v1
+    v17 = icmp eq v15, v1
+    v18 = icmp ule v10, v0
+    v19 = band v17, v18
+    v20 = bor v16, v19
+    return v10, v15, v20
+}
+"
+        );
+    }
+
+    #[test]
+    fn sret() {
+        let mut callee_sig = Signature::new(CallConv::SystemV);
+        callee_sig.params.push(AbiParam::new(I32));
+        add_sret_param(&mut callee_sig, I64);
+        assert_eq!(
+            callee_sig.to_string(),
+            "(i64 sret, i32) -> i64 sret system_v"
+        );
+
+        let mut sig = callee_sig.clone();
+        sig.params.push(AbiParam::new(I64));
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+
+            let block0 = builder.create_ebb();
+            builder.append_ebb_params_for_function_params(block0);
+            builder.switch_to_block(block0);
+            builder.seal_block(block0);
+
+            let arg = builder.ebb_params(block0)[1];
+            let callee = builder.ebb_params(block0)[2];
+            let sig_ref = builder.import_signature(callee_sig.clone());
+            let func_ref = builder.import_function(ExtFuncData {
+                name: ExternalName::testcase("callee"),
+                signature: sig_ref,
+                colocated: false,
+            });
+            let (_, first) = builder.call_sret(func_ref, 24, &[arg]);
+            let (_, second) = builder.call_indirect_sret(sig_ref, callee, 24, &[arg]);
+            let sret = builder.sret_param().unwrap();
+            let value = builder.ins().load(I64, MemFlags::new(), first, 0);
+            builder.ins().store(MemFlags::new(), value, sret, 0);
+            let value = builder.ins().load(I64, MemFlags::new(), second, 8);
+            builder.ins().store(MemFlags::new(), value, sret, 8);
+            builder.ins().return_(&[sret]);
+
+            builder.finalize();
+        }
+
+        let flags = settings::Flags::new(settings::builder());
+        if let Err(errors) = verify_function(&func, &flags) {
+            panic!("{}\n{}", func.display(None), errors)
+        }
+        assert_eq!(
+            func.display(None).to_string(),
+            "function %sample(i64 sret, i32, i64) -> i64 sret system_v {
+    ss0 = explicit_slot 24
+    ss1 = explicit_slot 24
+    sig0 = (i64 sret, i32) -> i64 sret system_v
+    fn0 = %callee sig0
+
+ebb0(v0: i64, v1: i32, v2: i64):
+    v3 = stack_addr.i64 ss0
+    v4 = call fn0(v3, v1)
+    v5 = stack_addr.i64 ss1
+    v6 = call_indirect sig0, v2(v5, v1)
+    v7 = load.i64 v3
+    store v7, v0
+    v8 = load.i64 v5+8
+    store v8, v0+8
+    return v0
+}
+"
+        );
+    }
+
+    #[test]
+    fn sret_param_without_entry_params() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        add_sret_param(&mut sig, I64);
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+
+        // The entry block is in the layout, but it doesn't have the parameters of the function.
+        let block0 = builder.create_ebb();
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+        builder.ins().nop();
+        assert_eq!(builder.sret_param(), None);
+    }
+
+    #[test]
+    fn invoke_landing_pad_variable() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I32));
+        sig.returns.push(AbiParam::new(I32));
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        let (call, landing_pad) = {
+            let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+
+            let block0 = builder.create_ebb();
+            let block1 = builder.create_ebb();
+            let landing_pad = builder.create_ebb();
+            let x = Variable::new(0);
+            builder.declare_var(x, I32);
+            builder.append_ebb_params_for_function_params(block0);
+            builder.switch_to_block(block0);
+            builder.seal_block(block0);
+            let arg = builder.ebb_params(block0)[0];
+            builder.def_var(x, arg);
+
+            let mut unary = Signature::new(CallConv::SystemV);
+            unary.params.push(AbiParam::new(I32));
+            let sig_ref = builder.import_signature(unary);
+            let func_ref = builder.import_function(ExtFuncData {
+                name: ExternalName::testcase("thrower"),
+                signature: sig_ref,
+                colocated: false,
+            });
+
+            // The landing pad is reached from the invoke and from `block1`, with different values
+            // of `x`, so it needs a parameter, which the invoke can't pass.
+            let call = builder.ins().invoke(func_ref, landing_pad, &[arg]);
+            builder.ins().jump(block1, &[]);
+
+            builder.switch_to_block(block1);
+            builder.seal_block(block1);
+            let one = builder.ins().iconst(I32, 1);
+            builder.def_var(x, one);
+            builder.ins().call(func_ref, &[one]);
+            builder.ins().jump(landing_pad, &[]);
+
+            builder.switch_to_block(landing_pad);
+            builder.seal_block(landing_pad);
+            let value = builder.use_var(x);
+            builder.ins().return_(&[value]);
+
+            builder.finalize();
+            (call, landing_pad)
+        };
+
+        let flags = settings::Flags::new(settings::builder());
+        if let Err(errors) = verify_function(&func, &flags) {
+            panic!("{}\n{}", func.display(None), errors)
+        }
+        assert_eq!(func.dfg.ebb_params(landing_pad).len(), 1);
+        let new_landing_pad = func.dfg[call].branch_destination().unwrap();
+        assert_ne!(new_landing_pad, landing_pad);
+        assert_eq!(func.dfg.num_ebb_params(new_landing_pad), 0);
+    }
+
     #[test]
     fn test_greatest_divisible_power_of_two() {
         assert_eq!(64, greatest_divisible_power_of_two(64));
@@ -9,11 +9,13 @@ use crate::Variable;
 use core::mem;
 use core::u32;
 use cranelift_codegen::cursor::{Cursor, FuncCursor};
-use cranelift_codegen::entity::{EntityRef, PrimaryMap, SecondaryMap};
+use cranelift_codegen::entity::{EntityList, EntityRef, ListPool, PrimaryMap, SecondaryMap};
 use cranelift_codegen::ir::immediates::{Ieee32, Ieee64};
 use cranelift_codegen::ir::instructions::BranchInfo;
 use cranelift_codegen::ir::types::{F32, F64};
-use cranelift_codegen::ir::{Ebb, Function, Inst, InstBuilder, InstructionData, Type, Value};
+use cranelift_codegen::ir::{
+    Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Type, Value,
+};
 use cranelift_codegen::packed_option::PackedOption;
 use cranelift_codegen::packed_option::ReservedValue;
 use std::vec::Vec;
@@ -43,6 +45,8 @@ pub struct SSABuilder {
     blocks: PrimaryMap<Block, BlockData>,
     // Records the basic blocks at the beginning of the `Ebb`s.
     ebb_headers: SecondaryMap<Ebb, PackedOption<Block>>,
+    // Memory pools for the lists of the `Ebb` header blocks.
+    lists: HeaderLists,
 
     // Call and result stacks for use in the `use_var`/`predecessors_lookup` state machine.
     calls: Vec<Call>,
@@ -86,52 +90,79 @@ enum BlockData {
 }
 
 impl BlockData {
-    fn add_predecessor(&mut self, pred: Block, inst: Inst) {
+    fn add_predecessor(&mut self, pred: Block, inst: Inst, lists: &mut HeaderLists) {
         match *self {
             BlockData::EbbBody { .. } => panic!("you can't add a predecessor to a body block"),
             BlockData::EbbHeader(ref mut data) => {
                 debug_assert!(!data.sealed, "sealed blocks cannot accept new predecessors");
-                data.predecessors.push(PredBlock::new(pred, inst));
+                data.pred_blocks.push(pred, &mut lists.blocks);
+                data.pred_branches.push(inst, &mut lists.insts);
             }
         }
     }
-    fn remove_predecessor(&mut self, inst: Inst) -> Block {
+    fn remove_predecessor(&mut self, inst: Inst, lists: &mut HeaderLists) -> Block {
         match *self {
             BlockData::EbbBody { .. } => panic!("should not happen"),
             BlockData::EbbHeader(ref mut data) => {
                 // This a linear complexity operation but the number of predecessors is low
                 // in all non-pathological cases
                 let pred: usize = data
-                    .predecessors
+                    .pred_branches
+                    .as_slice(&lists.insts)
                     .iter()
-                    .position(|&PredBlock { branch, .. }| branch == inst)
+                    .position(|&branch| branch == inst)
                     .expect("the predecessor you are trying to remove is not declared");
-                data.predecessors.swap_remove(pred).block
+                let block = data.pred_blocks.get(pred, &lists.blocks).unwrap();
+                data.pred_blocks.swap_remove(pred, &mut lists.blocks);
+                data.pred_branches.swap_remove(pred, &mut lists.insts);
+                block
             }
         }
     }
 }
 
-struct PredBlock {
-    block: Block,
-    branch: Inst,
+/// Memory pools for the lists of all the `Ebb` header blocks.
+///
+/// The lists of the blocks of a function are allocated from a few pools instead of separately, so
+/// they are freed all at once when the builder is cleared and their memory is reused by the next
+/// function.
+struct HeaderLists {
+    blocks: ListPool<Block>,
+    insts: ListPool<Inst>,
+    variables: ListPool<Variable>,
+    values: ListPool<Value>,
 }
 
-impl PredBlock {
-    fn new(block: Block, branch: Inst) -> Self {
-        Self { block, branch }
+impl HeaderLists {
+    fn new() -> Self {
+        Self {
+            blocks: ListPool::new(),
+            insts: ListPool::new(),
+            variables: ListPool::new(),
+            values: ListPool::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.blocks.clear();
+        self.insts.clear();
+        self.variables.clear();
+        self.values.clear();
     }
 }
 
 struct EbbHeaderBlockData {
-    // The predecessors of the Ebb header block, with the block and branch instruction.
-    predecessors: Vec<PredBlock>,
+    // The predecessors of the Ebb header block, and the branch instruction of each of them.
+    pred_blocks: EntityList<Block>,
+    pred_branches: EntityList<Inst>,
     // A ebb header block is sealed if all of its predecessors have been declared.
     sealed: bool,
     // The ebb which this block is part of.
     ebb: Ebb,
-    // List of current Ebb arguments for which an earlier def has not been found yet.
-    undef_variables: Vec<(Variable, Value)>,
+    // List of current Ebb arguments for which an earlier def has not been found yet, and the
+    // variable of each of them.
+    undef_variables: EntityList<Variable>,
+    undef_values: EntityList<Value>,
 }
 
 /// A opaque reference to a basic block.
@@ -161,6 +192,7 @@ impl SSABuilder {
             variables: SecondaryMap::with_default(SecondaryMap::new()),
             blocks: PrimaryMap::new(),
             ebb_headers: SecondaryMap::new(),
+            lists: HeaderLists::new(),
             calls: Vec::new(),
             results: Vec::new(),
             side_effects: SideEffects::new(),
@@ -170,9 +202,13 @@ impl SSABuilder {
     /// Clears a `SSABuilder` from all its data, letting it in a pristine state without
     /// deallocating memory.
     pub fn clear(&mut self) {
-        self.variables.clear();
+        // Keep the per-variable maps so their memory is reused by the next function.
+        for defs in self.variables.values_mut() {
+            defs.clear();
+        }
         self.blocks.clear();
         self.ebb_headers.clear();
+        self.lists.clear();
         debug_assert!(self.calls.is_empty());
         debug_assert!(self.results.is_empty());
         debug_assert!(self.side_effects.is_empty());
@@ -180,7 +216,7 @@ impl SSABuilder {
 
     /// Tests whether an `SSABuilder` is in a cleared state.
     pub fn is_empty(&self) -> bool {
-        self.variables.is_empty()
+        self.variables.values().all(SecondaryMap::is_empty)
             && self.blocks.is_empty()
             && self.ebb_headers.is_empty()
             && self.calls.is_empty()
@@ -311,16 +347,18 @@ impl SSABuilder {
                 // The block has multiple predecessors so we append an Ebb parameter that
                 // will serve as a value.
                 if data.sealed {
-                    if data.predecessors.len() == 1 {
+                    if data.pred_blocks.len(&self.lists.blocks) == 1 {
                         // Only one predecessor, straightforward case
-                        UseVarCases::SealedOnePredecessor(data.predecessors[0].block)
+                        let pred = data.pred_blocks.first(&self.lists.blocks).unwrap();
+                        UseVarCases::SealedOnePredecessor(pred)
                     } else {
                         let val = func.dfg.append_ebb_param(data.ebb, ty);
                         UseVarCases::SealedMultiplePredecessors(val, data.ebb)
                     }
                 } else {
                     let val = func.dfg.append_ebb_param(data.ebb, ty);
-                    data.undef_variables.push((var, val));
+                    data.undef_variables.push(var, &mut self.lists.variables);
+                    data.undef_values.push(val, &mut self.lists.values);
                     UseVarCases::Unsealed(val)
                 }
             }
@@ -368,10 +406,12 @@ impl SSABuilder {
     /// Predecessors have to be added with `declare_ebb_predecessor`.
     pub fn declare_ebb_header_block(&mut self, ebb: Ebb) -> Block {
         let block = self.blocks.push(BlockData::EbbHeader(EbbHeaderBlockData {
-            predecessors: Vec::new(),
+            pred_blocks: EntityList::new(),
+            pred_branches: EntityList::new(),
             sealed: false,
             ebb,
-            undef_variables: Vec::new(),
+            undef_variables: EntityList::new(),
+            undef_values: EntityList::new(),
         }));
         self.ebb_headers[ebb] = block.into();
         block
@@ -398,7 +438,7 @@ impl SSABuilder {
     pub fn declare_ebb_predecessor(&mut self, ebb: Ebb, pred: Block, inst: Inst) {
         debug_assert!(!self.is_sealed(ebb));
         let header_block = self.header_block(ebb);
-        self.blocks[header_block].add_predecessor(pred, inst)
+        self.blocks[header_block].add_predecessor(pred, inst, &mut self.lists)
     }
 
     /// Remove a previously declared Ebb predecessor by giving a reference to the jump
@@ -408,7 +448,7 @@ impl SSABuilder {
     pub fn remove_ebb_predecessor(&mut self, ebb: Ebb, inst: Inst) -> Block {
         debug_assert!(!self.is_sealed(ebb));
         let header_block = self.header_block(ebb);
-        self.blocks[header_block].remove_predecessor(inst)
+        self.blocks[header_block].remove_predecessor(inst, &mut self.lists)
     }
 
     /// Completes the global value numbering for an `Ebb`, all of its predecessors having been
@@ -444,7 +484,7 @@ impl SSABuilder {
     fn seal_one_ebb_header_block(&mut self, ebb: Ebb, func: &mut Function) {
         let block = self.header_block(ebb);
 
-        let undef_vars = match self.blocks[block] {
+        let (mut undef_vars, mut undef_vals) = match self.blocks[block] {
             BlockData::EbbBody { .. } => panic!("this should not happen"),
             BlockData::EbbHeader(ref mut data) => {
                 debug_assert!(
@@ -455,16 +495,20 @@ impl SSABuilder {
                 debug_assert_eq!(ebb, data.ebb);
                 // Extract the undef_variables data from the block so that we
                 // can iterate over it without borrowing the whole builder.
-                mem::replace(&mut data.undef_variables, Vec::new())
+                (data.undef_variables.take(), data.undef_values.take())
             }
         };
 
         // For each undef var we look up values in the predecessors and create an EBB parameter
         // only if necessary.
-        for (var, val) in undef_vars {
+        for i in 0..undef_vars.len(&self.lists.variables) {
+            let var = undef_vars.get(i, &self.lists.variables).unwrap();
+            let val = undef_vals.get(i, &self.lists.values).unwrap();
             let ty = func.dfg.value_type(val);
             self.predecessors_lookup(func, val, var, ty, ebb);
         }
+        undef_vars.clear(&mut self.lists.variables);
+        undef_vals.clear(&mut self.lists.values);
         self.mark_ebb_header_block_sealed(block);
     }
 
@@ -477,9 +521,6 @@ impl SSABuilder {
                 debug_assert!(!data.sealed);
                 debug_assert!(data.undef_variables.is_empty());
                 data.sealed = true;
-                // We could call data.predecessors.shrink_to_fit() here, if
-                // important, because no further predecessors will be added
-                // to this block.
             }
         }
     }
@@ -514,7 +555,7 @@ impl SSABuilder {
             self.predecessors(dest_ebb)
                 .iter()
                 .rev()
-                .map(|&PredBlock { block: pred, .. }| Call::UseVar(pred)),
+                .map(|&pred| Call::UseVar(pred)),
         );
         self.calls = calls;
     }
@@ -588,38 +629,36 @@ impl SSABuilder {
             ZeroOneOrMore::More => {
                 // There is disagreement in the predecessors on which value to use so we have
                 // to keep the ebb argument. To avoid borrowing `self` for the whole loop,
-                // temporarily detach the predecessors list and replace it with an empty list.
-                let mut preds = mem::replace(self.predecessors_mut(dest_ebb), Vec::new());
-                for &mut PredBlock {
-                    block: ref mut pred_block,
-                    branch: ref mut last_inst,
-                } in &mut preds
-                {
+                // temporarily detach the predecessors lists and replace them with empty lists.
+                let (mut pred_blocks, mut pred_branches) = self.take_predecessors(dest_ebb);
+                for i in 0..pred_blocks.len(&self.lists.blocks) {
+                    let pred_block = pred_blocks.get(i, &self.lists.blocks).unwrap();
+                    let last_inst = pred_branches.get(i, &self.lists.insts).unwrap();
                     // We already did a full `use_var` above, so we can do just the fast path.
                     let pred_val = self
                         .variables
                         .get(temp_arg_var)
                         .unwrap()
-                        .get(*pred_block)
+                        .get(pred_block)
                         .unwrap()
                         .unwrap();
                     let jump_arg = self.append_jump_argument(
                         func,
-                        *last_inst,
-                        *pred_block,
+                        last_inst,
+                        pred_block,
                         dest_ebb,
                         pred_val,
                         temp_arg_var,
                     );
                     if let Some((middle_ebb, middle_block, middle_jump_inst)) = jump_arg {
-                        *pred_block = middle_block;
-                        *last_inst = middle_jump_inst;
+                        pred_blocks.as_mut_slice(&mut self.lists.blocks)[i] = middle_block;
+                        pred_branches.as_mut_slice(&mut self.lists.insts)[i] = middle_jump_inst;
                         self.side_effects.split_ebbs_created.push(middle_ebb);
                     }
                 }
-                // Now that we're done, move the predecessors list back.
+                // Now that we're done, move the predecessors lists back.
                 debug_assert!(self.predecessors(dest_ebb).is_empty());
-                *self.predecessors_mut(dest_ebb) = preds;
+                self.restore_predecessors(dest_ebb, pred_blocks, pred_branches);
 
                 temp_arg_val
             }
@@ -643,6 +682,16 @@ impl SSABuilder {
             BranchInfo::NotABranch => {
                 panic!("you have declared a non-branch instruction as a predecessor to an ebb");
             }
+            // The landing pad of an `invoke` can't have parameters, and the arguments of the
+            // instruction go to the callee. We have to split the critical edge.
+            BranchInfo::SingleDest(_, _) if func.dfg[jump_inst].opcode() == Opcode::Invoke => {
+                let (middle_ebb, middle_block) = self.split_edge(func, jump_inst, jump_inst_block);
+                func.change_branch_destination(jump_inst, middle_ebb);
+                let mut cur = FuncCursor::new(func).at_bottom(middle_ebb);
+                let middle_jump_inst = cur.ins().jump(dest_ebb, &[val]);
+                self.def_var(var, val, middle_block);
+                Some((middle_ebb, middle_block, middle_jump_inst))
+            }
             // For a single destination appending a jump argument to the instruction
             // is sufficient.
             BranchInfo::SingleDest(_, _) => {
@@ -653,11 +702,7 @@ impl SSABuilder {
                 // In the case of a jump table, the situation is tricky because br_table doesn't
                 // support arguments.
                 // We have to split the critical edge
-                let middle_ebb = func.dfg.make_ebb();
-                func.layout.append_ebb(middle_ebb);
-                let middle_block = self.declare_ebb_header_block(middle_ebb);
-                self.blocks[middle_block].add_predecessor(jump_inst_block, jump_inst);
-                self.mark_ebb_header_block_sealed(middle_block);
+                let (middle_ebb, middle_block) = self.split_edge(func, jump_inst, jump_inst_block);
 
                 if let Some(default_ebb) = default_ebb {
                     if dest_ebb == default_ebb {
@@ -686,12 +731,28 @@ impl SSABuilder {
         }
     }
 
-    /// Returns the list of `Ebb`s that have been declared as predecessors of the argument.
-    fn predecessors(&self, ebb: Ebb) -> &[PredBlock] {
+    /// Creates a sealed EBB whose only predecessor is `jump_inst`, to be used as its new
+    /// destination.
+    fn split_edge(
+        &mut self,
+        func: &mut Function,
+        jump_inst: Inst,
+        jump_inst_block: Block,
+    ) -> (Ebb, Block) {
+        let middle_ebb = func.dfg.make_ebb();
+        func.layout.append_ebb(middle_ebb);
+        let middle_block = self.declare_ebb_header_block(middle_ebb);
+        self.blocks[middle_block].add_predecessor(jump_inst_block, jump_inst, &mut self.lists);
+        self.mark_ebb_header_block_sealed(middle_block);
+        (middle_ebb, middle_block)
+    }
+
+    /// Returns the list of `Block`s that have been declared as predecessors of the argument.
+    fn predecessors(&self, ebb: Ebb) -> &[Block] {
         let block = self.header_block(ebb);
         match self.blocks[block] {
             BlockData::EbbBody { .. } => panic!("should not happen"),
-            BlockData::EbbHeader(ref data) => &data.predecessors,
+            BlockData::EbbHeader(ref data) => data.pred_blocks.as_slice(&self.lists.blocks),
         }
     }
 
@@ -700,12 +761,32 @@ impl SSABuilder {
         !self.predecessors(ebb).is_empty()
     }
 
-    /// Same as predecessors, but for &mut.
-    fn predecessors_mut(&mut self, ebb: Ebb) -> &mut Vec<PredBlock> {
+    /// Detaches the predecessors of the argument and their branch instructions, leaving empty
+    /// lists in their place.
+    fn take_predecessors(&mut self, ebb: Ebb) -> (EntityList<Block>, EntityList<Inst>) {
         let block = self.header_block(ebb);
         match self.blocks[block] {
             BlockData::EbbBody { .. } => panic!("should not happen"),
-            BlockData::EbbHeader(ref mut data) => &mut data.predecessors,
+            BlockData::EbbHeader(ref mut data) => {
+                (data.pred_blocks.take(), data.pred_branches.take())
+            }
+        }
+    }
+
+    /// Moves back the predecessors detached by `take_predecessors`.
+    fn restore_predecessors(
+        &mut self,
+        ebb: Ebb,
+        pred_blocks: EntityList<Block>,
+        pred_branches: EntityList<Inst>,
+    ) {
+        let block = self.header_block(ebb);
+        match self.blocks[block] {
+            BlockData::EbbBody { .. } => panic!("should not happen"),
+            BlockData::EbbHeader(ref mut data) => {
+                data.pred_blocks = pred_blocks;
+                data.pred_branches = pred_branches;
+            }
         }
     }
 
@@ -1330,4 +1411,35 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn reuse_after_clear() {
+        let mut func = Function::new();
+        let mut ssa = SSABuilder::new();
+        let ebb0 = func.dfg.make_ebb();
+        let block = ssa.declare_ebb_header_block(ebb0);
+        ssa.seal_ebb_header_block(ebb0, &mut func);
+        let x_var = Variable::new(0);
+        let x_ssa = {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb0);
+            cur.ins().iconst(I32, 1)
+        };
+        ssa.def_var(x_var, x_ssa, block);
+        assert_eq!(ssa.use_var(&mut func, x_var, I32, block).0, x_ssa);
+
+        // The definitions of the previous function must not leak into the next one, even though
+        // the blocks are numbered the same.
+        ssa.clear();
+        assert!(ssa.is_empty());
+        func.clear();
+        let ebb0 = func.dfg.make_ebb();
+        let block = ssa.declare_ebb_header_block(ebb0);
+        ssa.seal_ebb_header_block(ebb0, &mut func);
+        FuncCursor::new(&mut func).insert_ebb(ebb0);
+        let x_use = ssa.use_var(&mut func, x_var, I32, block).0;
+        let def = func.dfg.value_def(x_use).unwrap_inst();
+        assert_eq!(func.dfg[def].opcode(), Opcode::Iconst);
+        assert_eq!(func.dfg.num_insts(), 1);
+    }
 }
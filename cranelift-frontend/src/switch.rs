@@ -7,9 +7,20 @@ use std::vec::Vec;
 
 type EntryIndex = u64;
 
+/// Default minimum density of a range of cases lowered to a single jump table, in percent.
+const DEFAULT_MIN_DENSITY: u8 = 50;
+
+/// Minimum number of cases in a jump table that has holes.
+///
+/// Fewer cases are better served by comparisons.
+const MIN_SPARSE_JUMP_TABLE_CASES: usize = 4;
+
 /// Unlike with `br_table`, `Switch` cases may be sparse or non-0-based.
 /// They emit efficient code using branches, jump tables, or a combination of both.
 ///
+/// Ranges of cases that are dense enough are lowered to a single jump table, with the missing
+/// entries going to the default block. See `set_min_density`.
+///
 /// # Example
 ///
 /// ```rust
@@ -39,9 +50,10 @@ type EntryIndex = u64;
 /// switch.set_entry(7, block2);
 /// switch.emit(&mut builder, val, fallback);
 /// ```
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Switch {
     cases: HashMap<EntryIndex, Ebb>,
+    min_density: u8,
 }
 
 impl Switch {
@@ -49,9 +61,25 @@ impl Switch {
     pub fn new() -> Self {
         Self {
             cases: HashMap::new(),
+            min_density: DEFAULT_MIN_DENSITY,
         }
     }
 
+    /// Set the minimum density, in percent, of a range of cases lowered to a single jump table.
+    ///
+    /// A range of entry indexes containing holes is lowered to a jump table when at least
+    /// `percent` of its entries are cases, and it has enough cases to make this worthwhile. The
+    /// default is 50. A density of 100 only uses jump tables for contiguous cases, while lower
+    /// values trade larger jump tables for fewer comparisons.
+    pub fn set_min_density(&mut self, percent: u8) {
+        assert!(
+            percent > 0 && percent <= 100,
+            "Invalid jump table density {}",
+            percent
+        );
+        self.min_density = percent;
+    }
+
     /// Set a switch entry
     pub fn set_entry(&mut self, index: EntryIndex, ebb: Ebb) {
         let prev = self.cases.insert(index, ebb);
@@ -64,30 +92,53 @@ impl Switch {
 
     /// Turn the `cases` `HashMap` into a list of `ContiguousCaseRange`s.
     ///
+    /// Holes in dense ranges of cases are filled with `otherwise`.
+    ///
     /// # Postconditions
     ///
     /// * Every entry will be represented.
     /// * The `ContiguousCaseRange`s will not overlap.
     /// * Between two `ContiguousCaseRange`s there will be at least one entry index.
     /// * No `ContiguousCaseRange`s will be empty.
-    fn collect_contiguous_case_ranges(self) -> Vec<ContiguousCaseRange> {
+    fn collect_contiguous_case_ranges(self, otherwise: Ebb) -> Vec<ContiguousCaseRange> {
         debug!("build_contiguous_case_ranges before: {:#?}", self.cases);
+        let min_density = self.min_density;
         let mut cases = self.cases.into_iter().collect::<Vec<(_, _)>>();
         cases.sort_by_key(|&(index, _)| index);
 
         let mut contiguous_case_ranges: Vec<ContiguousCaseRange> = vec![];
-        let mut last_index = None;
-        for (index, ebb) in cases {
-            match last_index {
-                None => contiguous_case_ranges.push(ContiguousCaseRange::new(index)),
-                Some(last_index) => {
-                    if index > last_index + 1 {
-                        contiguous_case_ranges.push(ContiguousCaseRange::new(index));
-                    }
+        let mut start = 0;
+        while start < cases.len() {
+            let first_index = cases[start].0;
+
+            // Extend the range as long as it stays dense enough, then only keep its contiguous
+            // prefix if it doesn't have enough cases for a jump table with holes.
+            let mut end = start + 1;
+            while end < cases.len()
+                && is_dense(first_index, cases[end].0, end + 1 - start, min_density)
+            {
+                end += 1;
+            }
+            let last_index = cases[end - 1].0;
+            if end - start < MIN_SPARSE_JUMP_TABLE_CASES
+                && last_index - first_index != (end - start - 1) as EntryIndex
+            {
+                end = start + 1;
+                while end < cases.len() && cases[end].0 == cases[end - 1].0 + 1 {
+                    end += 1;
                 }
             }
-            contiguous_case_ranges.last_mut().unwrap().ebbs.push(ebb);
-            last_index = Some(index);
+
+            let mut range = ContiguousCaseRange::new(first_index);
+            for &(index, ebb) in &cases[start..end] {
+                let next_index = first_index + range.ebbs.len() as EntryIndex;
+                for _ in next_index..index {
+                    range.ebbs.push(otherwise);
+                }
+                range.ebbs.push(ebb);
+            }
+            contiguous_case_ranges.push(range);
+            start = end;
         }
 
         debug!(
@@ -240,13 +291,32 @@ impl Switch {
             _ => val,
         };
 
-        let contiguous_case_ranges = self.collect_contiguous_case_ranges();
+        let contiguous_case_ranges = self.collect_contiguous_case_ranges(otherwise);
         let cases_and_jt_ebbs = Self::build_search_tree(bx, val, otherwise, contiguous_case_ranges);
         Self::build_jump_tables(bx, val, otherwise, cases_and_jt_ebbs);
     }
 }
 
-/// This represents a contiguous range of cases to switch on.
+impl Default for Switch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Is a range of `count` cases from `first_index` to `last_index` inclusive at least
+/// `min_density` percent full?
+fn is_dense(
+    first_index: EntryIndex,
+    last_index: EntryIndex,
+    count: usize,
+    min_density: u8,
+) -> bool {
+    let span = u128::from(last_index - first_index) + 1;
+    (count as u128) * 100 >= span * u128::from(min_density)
+}
+
+/// This represents a contiguous range of cases to switch on. Holes in a dense range of cases are
+/// filled with the default ebb.
 ///
 /// For example 10 => ebb1, 11 => ebb2, 12 => ebb7 will be represented as:
 ///
@@ -282,7 +352,10 @@ mod tests {
     use std::string::ToString;
 
     macro_rules! setup {
-        ($default:expr, [$($index:expr,)*]) => {{
+        ($default:expr, [$($index:expr,)*]) => {
+            setup!($default, [$($index,)*], min_density = DEFAULT_MIN_DENSITY)
+        };
+        ($default:expr, [$($index:expr,)*], min_density = $min_density:expr) => {{
             let mut func = Function::new();
             let mut func_ctx = FunctionBuilderContext::new();
             {
@@ -291,6 +364,7 @@ mod tests {
                 bx.switch_to_block(ebb);
                 let val = bx.ins().iconst(types::I8, 0);
                 let mut switch = Switch::new();
+                switch.set_min_density($min_density);
                 $(
                     let ebb = bx.create_ebb();
                     switch.set_entry($index, ebb);
@@ -366,7 +440,7 @@ ebb3:
 
     #[test]
     fn switch_many() {
-        let func = setup!(0, [0, 1, 5, 7, 10, 11, 12,]);
+        let func = setup!(0, [0, 1, 5, 7, 10, 11, 12,], min_density = 100);
         assert_eq!(
             func,
             "    jt0 = jump_table [ebb1, ebb2]
@@ -400,6 +474,92 @@ ebb10:
         );
     }
 
+    #[test]
+    fn switch_many_dense() {
+        let func = setup!(0, [0, 1, 5, 7, 10, 11, 12,]);
+        assert_eq!(
+            func,
+            "    jt0 = jump_table [ebb1, ebb2, ebb0, ebb0, ebb0, ebb3, ebb0, ebb4]
+    jt1 = jump_table [ebb5, ebb6, ebb7]
+
+ebb0:
+    v0 = iconst.i8 0
+    v1 = uextend.i32 v0
+    v2 = icmp_imm uge v1, 10
+    brnz v2, ebb8
+    jump ebb9
+
+ebb9:
+    br_table.i32 v1, ebb0, jt0
+
+ebb8:
+    v3 = iadd_imm.i32 v1, -10
+    br_table v3, ebb0, jt1"
+        );
+    }
+
+    #[test]
+    fn switch_sparse() {
+        // Every other entry index is a case, which is dense enough for a jump table.
+        let func = setup!(0, [3, 5, 7, 9, 11,]);
+        assert_eq!(
+            func,
+            "    jt0 = jump_table [ebb1, ebb0, ebb2, ebb0, ebb3, ebb0, ebb4, ebb0, ebb5]
+
+ebb0:
+    v0 = iconst.i8 0
+    v1 = uextend.i32 v0
+    v2 = icmp_imm uge v1, 3
+    brnz v2, ebb6
+    jump ebb0
+
+ebb6:
+    v3 = iadd_imm.i32 v1, -3
+    br_table v3, ebb0, jt0"
+        );
+    }
+
+    #[test]
+    fn switch_sparse_low_density() {
+        // Every third entry index is a case, which isn't dense enough for a jump table.
+        let func = setup!(0, [0, 3, 6, 9,]);
+        assert_eq!(
+            func,
+            "ebb0:
+    v0 = iconst.i8 0
+    v1 = uextend.i32 v0
+    v2 = icmp_imm uge v1, 6
+    brnz v2, ebb6
+    jump ebb5
+
+ebb6:
+    v3 = icmp_imm.i32 eq v1, 9
+    brnz v3, ebb4
+    v4 = icmp_imm.i32 eq v1, 6
+    brnz v4, ebb3
+    jump ebb0
+
+ebb5:
+    v5 = icmp_imm.i32 eq v1, 3
+    brnz v5, ebb2
+    brz.i32 v1, ebb1
+    jump ebb0"
+        );
+        let func = setup!(0, [0, 3, 6, 9,], min_density = 25);
+        assert_eq!(
+            func,
+            "    jt0 = jump_table [ebb1, ebb0, ebb0, ebb2, ebb0, ebb0, ebb3, ebb0, ebb0, ebb4]
+
+ebb0:
+    v0 = iconst.i8 0
+    v1 = uextend.i32 v0
+    jump ebb5
+
+ebb5:
+    br_table.i32 v1, ebb0, jt0"
+        );
+    }
+
     #[test]
     fn switch_min_index_value() {
         let func = setup!(0, [::core::i64::MIN as u64, 1,]);
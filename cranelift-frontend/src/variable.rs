@@ -7,6 +7,7 @@
 
 use core::u32;
 use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::packed_option::ReservedValue;
 
 ///! An opaque reference to a variable.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -30,3 +31,9 @@ impl EntityRef for Variable {
         self.0 as usize
     }
 }
+
+impl ReservedValue for Variable {
+    fn reserved_value() -> Self {
+        Variable(u32::MAX)
+    }
+}
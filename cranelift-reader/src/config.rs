@@ -0,0 +1,219 @@
+//! Settings configuration files.
+//!
+//! Settings can be loaded from a configuration file using a subset of TOML, which is also the
+//! format produced by the `Display` implementation of the `Flags` objects and `TargetIsa`:
+//!
+//! ```toml
+//! # Comments start with a hash sign.
+//! [shared]
+//! opt_level = "best"
+//! enable_verifier = false
+//!
+//! [x86]
+//! haswell = true
+//! ```
+//!
+//! Each table configures the settings group with the same name. Keys appearing before the first
+//! table header configure the shared settings. Presets are applied by setting them to `true`.
+
+use crate::error::{Location, ParseResult};
+use cranelift_codegen::isa;
+use cranelift_codegen::settings::{self, Configurable, SetError, Setting, SettingKind};
+
+/// Parse the settings configuration `text` and apply it to the `shared` settings and, if given,
+/// the ISA-specific settings in `isa`.
+///
+/// Unknown tables, unknown settings and invalid values are reported with the line where they
+/// appear.
+pub fn parse_settings_config(
+    text: &str,
+    shared: &mut settings::Builder,
+    mut isa: Option<&mut isa::Builder>,
+) -> ParseResult<()> {
+    let mut in_shared = true;
+    for (i, line) in text.lines().enumerate() {
+        let loc = Location { line_number: i + 1 };
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return err!(loc, "expected ']' at the end of the table header");
+            }
+            let name = line[1..line.len() - 1].trim();
+            in_shared = if name == shared.name() {
+                true
+            } else if isa.as_ref().map(|isa| isa.name()) == Some(name) {
+                false
+            } else {
+                return err!(loc, "unknown settings group '{}'", name);
+            };
+            continue;
+        }
+
+        let (name, value) = match line.find('=') {
+            Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+            None => return err!(loc, "expected 'name = value'"),
+        };
+        let value = parse_value(value, loc)?;
+        let is_preset = |s: Setting| s.name == name && s.kind == SettingKind::Preset;
+        let preset;
+        let config: &mut dyn Configurable = match isa {
+            Some(ref mut isa) if !in_shared => {
+                preset = isa.iter().any(is_preset);
+                &mut **isa
+            }
+            _ => {
+                preset = shared.iter().any(is_preset);
+                shared
+            }
+        };
+        apply_setting(config, name, value, preset, loc)?;
+    }
+    Ok(())
+}
+
+/// Remove the comment at the end of `line`, if any.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (pos, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..pos],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Parse a TOML value, removing the quotes around strings.
+fn parse_value(value: &str, loc: Location) -> ParseResult<&str> {
+    if value.starts_with('"') {
+        if value.len() < 2 || !value.ends_with('"') {
+            return err!(loc, "unterminated string {}", value);
+        }
+        Ok(&value[1..value.len() - 1])
+    } else if value.is_empty() {
+        err!(loc, "missing value")
+    } else {
+        Ok(value)
+    }
+}
+
+/// Apply the setting `name = value` to `config`, where `preset` tells if `name` is a preset.
+fn apply_setting(
+    config: &mut dyn Configurable,
+    name: &str,
+    value: &str,
+    preset: bool,
+    loc: Location,
+) -> ParseResult<()> {
+    let result = if !preset {
+        config.set(name, value)
+    } else if value == "true" {
+        config.enable(name)
+    } else {
+        return err!(loc, "preset '{}' can only be set to true", name);
+    };
+    match result {
+        Ok(()) => Ok(()),
+        Err(SetError::BadName(name)) => err!(loc, "unknown setting '{}'", name),
+        Err(SetError::BadType) => err!(loc, "invalid setting type: '{} = {}'", name, value),
+        Err(SetError::BadValue(expected)) => err!(
+            loc,
+            "invalid setting value for '{}', expected {}",
+            name,
+            expected
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_settings_config;
+    use cranelift_codegen::isa;
+    use cranelift_codegen::settings::{self, Flags, OptLevel};
+    use std::str::FromStr;
+    use target_lexicon::triple;
+
+    #[test]
+    fn shared_settings() {
+        let mut b = settings::builder();
+        parse_settings_config(
+            "# Tuned settings.\n\
+             opt_level = \"best\" # Slow compilation.\n\
+             \n\
+             [shared]\n\
+             enable_verifier = false\n\
+             probestack_size_log2 = 16\n\
+             deterministic = true\n",
+            &mut b,
+            None,
+        )
+        .unwrap();
+        let flags = Flags::new(b);
+        assert_eq!(flags.opt_level(), OptLevel::Best);
+        assert!(!flags.enable_verifier());
+        assert_eq!(flags.probestack_size_log2(), 16);
+        assert!(flags.avoid_div_traps());
+    }
+
+    #[test]
+    fn errors() {
+        let parse = |text| {
+            parse_settings_config(text, &mut settings::builder(), None)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(parse("[x86]\n"), "1: unknown settings group 'x86'");
+        assert_eq!(
+            parse("\n[shared\n"),
+            "2: expected ']' at the end of the table header"
+        );
+        assert_eq!(parse("opt_level\n"), "1: expected 'name = value'");
+        assert_eq!(
+            parse("opt_level = \"best\n"),
+            "1: unterminated string \"best"
+        );
+        assert_eq!(parse("not_there = 1\n"), "1: unknown setting 'not_there'");
+        assert_eq!(
+            parse("opt_level = \"slow\"\n"),
+            "1: invalid setting value for 'opt_level', expected any among default, best, fastest"
+        );
+        assert_eq!(
+            parse("deterministic = false\n"),
+            "1: preset 'deterministic' can only be set to true"
+        );
+    }
+
+    #[test]
+    fn isa_settings() {
+        let mut shared = settings::builder();
+        let mut isa_builder = match isa::lookup(triple!("riscv32")) {
+            Ok(isa_builder) => isa_builder,
+            Err(_) => return,
+        };
+        let config = "[riscv]\n\
+                      enable_m = false\n\
+                      [shared]\n\
+                      enable_simd = true\n";
+        parse_settings_config(config, &mut shared, Some(&mut isa_builder)).unwrap();
+        let isa = isa_builder.finish(Flags::new(shared));
+        assert!(isa.flags().enable_simd());
+        let enable_m = isa
+            .isa_flags()
+            .into_iter()
+            .find(|v| v.setting.name == "enable_m")
+            .unwrap();
+        assert_eq!(enable_m.as_bool(), Some(false));
+
+        // Loading the display of a `TargetIsa` gives the same settings.
+        let mut shared = settings::builder();
+        let mut isa_builder = isa::lookup(triple!("riscv32")).unwrap();
+        let text = isa.to_string();
+        parse_settings_config(&text, &mut shared, Some(&mut isa_builder)).unwrap();
+        assert_eq!(isa_builder.finish(Flags::new(shared)).to_string(), text);
+    }
+}
@@ -40,9 +40,11 @@ pub enum Token<'a> {
     JumpTable(u32),       // jt2
     FuncRef(u32),         // fn2
     SigRef(u32),          // sig2
+    RawCode(u32),         // code2
     UserRef(u32),         // u345
     Name(&'a str),        // %9arbitrary_alphanum, %x3, %0, %function ...
     HexSequence(&'a str), // #89AF
+    String(&'a str),      // "quoted \"string\"", with the escapes
     Identifier(&'a str),  // Unrecognized identifier (opcode, enumerator, ...)
     SourceLoc(&'a str),   // @00c7
 }
@@ -345,6 +347,7 @@ impl<'a> Lexer<'a> {
             "jt" => Some(Token::JumpTable(number)),
             "fn" => Some(Token::FuncRef(number)),
             "sig" => Some(Token::SigRef(number)),
+            "code" => Some(Token::RawCode(number)),
             "u" => Some(Token::UserRef(number)),
             _ => None,
         }
@@ -417,6 +420,31 @@ impl<'a> Lexer<'a> {
         token(Token::HexSequence(&self.source[begin..end]), loc)
     }
 
+    fn scan_string(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
+        let loc = self.loc();
+        let begin = self.pos + 1;
+
+        assert_eq!(self.lookahead, Some('"'));
+
+        loop {
+            match self.next_ch() {
+                None => return error(LexError::InvalidChar, loc),
+                Some('"') => break,
+                Some('\\') => {
+                    // Skip the escaped character.
+                    if self.next_ch().is_none() {
+                        return error(LexError::InvalidChar, loc);
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+
+        let end = self.pos;
+        self.next_ch();
+        token(Token::String(&self.source[begin..end]), loc)
+    }
+
     fn scan_srcloc(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
         let loc = self.loc();
         let begin = self.pos + 1;
@@ -465,6 +493,7 @@ impl<'a> Lexer<'a> {
                 Some('%') => Some(self.scan_name()),
                 Some('#') => Some(self.scan_hex_sequence()),
                 Some('@') => Some(self.scan_srcloc()),
+                Some('"') => Some(self.scan_string()),
                 Some(ch) if ch.is_whitespace() => {
                     self.next_ch();
                     continue;
@@ -633,6 +662,17 @@ mod tests {
         assert_eq!(lex.next(), token(Token::Name("_"), 1));
     }
 
+    #[test]
+    fn lex_strings() {
+        let mut lex = Lexer::new(r#""" "_ZN3foo3barE" "a\"b\\" "open"#);
+
+        assert_eq!(lex.next(), token(Token::String(""), 1));
+        assert_eq!(lex.next(), token(Token::String("_ZN3foo3barE"), 1));
+        assert_eq!(lex.next(), token(Token::String(r#"a\"b\\"#), 1));
+        assert_eq!(lex.next(), error(LexError::InvalidChar, 1));
+        assert_eq!(lex.next(), None);
+    }
+
     #[test]
     fn lex_userrefs() {
         let mut lex = Lexer::new("u0 u1 u234567890 u9:8765");
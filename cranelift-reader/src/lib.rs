@@ -26,6 +26,7 @@
     )
 )]
 
+pub use crate::config::parse_settings_config;
 pub use crate::error::{Location, ParseError, ParseResult};
 pub use crate::isaspec::{parse_options, IsaSpec};
 pub use crate::parser::{parse_functions, parse_test};
@@ -34,6 +35,8 @@ pub use crate::testcommand::{TestCommand, TestOption};
 pub use crate::testfile::{Comment, Details, TestFile};
 
 mod error;
+
+mod config;
 mod isaspec;
 mod lexer;
 mod parser;
@@ -13,10 +13,11 @@ use cranelift_codegen::ir::immediates::{Ieee32, Ieee64, Imm64, Offset32, Uimm32,
 use cranelift_codegen::ir::instructions::{InstructionData, InstructionFormat, VariableArgs};
 use cranelift_codegen::ir::types::INVALID;
 use cranelift_codegen::ir::{
-    AbiParam, ArgumentExtension, ArgumentLoc, Ebb, ExtFuncData, ExternalName, FuncRef, Function,
-    GlobalValue, GlobalValueData, Heap, HeapData, HeapStyle, JumpTable, JumpTableData, MemFlags,
-    Opcode, SigRef, Signature, StackSlot, StackSlotData, StackSlotKind, Table, TableData, Type,
-    Value, ValueLoc,
+    AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, BranchHint, Ebb, ExtFuncData,
+    ExternalName, ExternalNameTable, FuncAttributes, FuncRef, Function, GlobalValue,
+    GlobalValueData, Heap, HeapData, HeapStyle, JumpTable, JumpTableData, MemFlags, Opcode,
+    RawCode, RawCodeData, SigRef, Signature, StackSlot, StackSlotData, StackSlotKind,
+    StackSlotLifetime, StructLayout, Table, TableData, TableStyle, Type, Value, ValueLoc,
 };
 use cranelift_codegen::isa::{self, CallConv, Encoding, RegUnit, TargetIsa};
 use cranelift_codegen::packed_option::ReservedValue;
@@ -216,7 +217,9 @@ impl<'a> Context<'a> {
             self.function.create_table(TableData {
                 base_gv: GlobalValue::reserved_value(),
                 min_size: Uimm64::new(0),
-                bound_gv: GlobalValue::reserved_value(),
+                style: TableStyle::Static {
+                    bound: Uimm64::new(0),
+                },
                 element_size: Uimm64::new(0),
                 index_type: INVALID,
             });
@@ -255,7 +258,13 @@ impl<'a> Context<'a> {
     }
 
     // Allocate a new external function.
-    fn add_fn(&mut self, fn_: FuncRef, data: ExtFuncData, loc: Location) -> ParseResult<()> {
+    fn add_fn(
+        &mut self,
+        fn_: FuncRef,
+        data: ExtFuncData,
+        attributes: FuncAttributes,
+        loc: Location,
+    ) -> ParseResult<()> {
         self.map.def_fn(fn_, loc)?;
         while self.function.dfg.ext_funcs.next_key().index() <= fn_.index() {
             self.function.import_function(ExtFuncData {
@@ -265,6 +274,7 @@ impl<'a> Context<'a> {
             });
         }
         self.function.dfg.ext_funcs[fn_] = data;
+        self.function.dfg.ext_func_attributes[fn_] = attributes;
         Ok(())
     }
 
@@ -277,6 +287,29 @@ impl<'a> Context<'a> {
         }
     }
 
+    // Allocate new raw code.
+    fn add_code(&mut self, code: RawCode, data: RawCodeData, loc: Location) -> ParseResult<()> {
+        self.map.def_code(code, loc)?;
+        while self.function.dfg.raw_code.next_key().index() <= code.index() {
+            self.function.dfg.raw_code.push(RawCodeData {
+                bytes: Vec::new(),
+                signature: SigRef::reserved_value(),
+                clobbers: Vec::new(),
+            });
+        }
+        self.function.dfg.raw_code[code] = data;
+        Ok(())
+    }
+
+    // Resolve a reference to raw code.
+    fn check_code(&self, code: RawCode, loc: Location) -> ParseResult<()> {
+        if !self.map.contains_code(code) {
+            err!(loc, "undefined raw code {}", code)
+        } else {
+            Ok(())
+        }
+    }
+
     // Allocate a new jump table.
     fn add_jt(&mut self, jt: JumpTable, data: JumpTableData, loc: Location) -> ParseResult<()> {
         self.map.def_jt(jt, loc)?;
@@ -495,6 +528,17 @@ impl<'a> Parser<'a> {
         err!(self.loc, err_msg)
     }
 
+    // Match and consume a raw code reference.
+    fn match_code(&mut self, err_msg: &str) -> ParseResult<RawCode> {
+        if let Some(Token::RawCode(code)) = self.token() {
+            self.consume();
+            if let Some(code) = RawCode::with_number(code) {
+                return Ok(code);
+            }
+        }
+        err!(self.loc, err_msg)
+    }
+
     // Match and consume a jump table reference.
     fn match_jt(&mut self) -> ParseResult<JumpTable> {
         if let Some(Token::JumpTable(jt)) = self.token() {
@@ -907,7 +951,7 @@ impl<'a> Parser<'a> {
 
     // Parse a whole function definition.
     //
-    // function ::= * "function" name signature "{" preamble function-body "}"
+    // function ::= * "function" func-attrs name signature "{" preamble function-body "}"
     //
     fn parse_function(
         &mut self,
@@ -923,13 +967,17 @@ impl<'a> Parser<'a> {
 
         let location = self.loc;
 
-        // function ::= "function" * name signature "{" preamble function-body "}"
+        // function ::= "function" * func-attrs name signature "{" preamble function-body "}"
+        let attributes = self.parse_func_attributes()?;
+
+        // function ::= "function" func-attrs * name signature "{" preamble function-body "}"
         let name = self.parse_external_name()?;
 
-        // function ::= "function" name * signature "{" preamble function-body "}"
+        // function ::= "function" func-attrs name * signature "{" preamble function-body "}"
         let sig = self.parse_signature(unique_isa)?;
 
         let mut ctx = Context::new(Function::with_name_signature(name, sig), unique_isa);
+        ctx.function.attributes = attributes;
 
         // function ::= "function" name signature * "{" preamble function-body "}"
         self.match_token(Token::LBrace, "expected '{' before function body")?;
@@ -995,6 +1043,41 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Parse the attributes of a function or a function decl.
+    //
+    // func-attrs ::= * { "cold" | "noreturn" | "naked" | "align" "(" Integer ")" }
+    //
+    fn parse_func_attributes(&mut self) -> ParseResult<FuncAttributes> {
+        let mut attributes = FuncAttributes::default();
+        while let Some(Token::Identifier(text)) = self.token() {
+            match text {
+                "cold" => attributes.cold = true,
+                "noreturn" => attributes.noreturn = true,
+                "naked" => attributes.naked = true,
+                "align" => {
+                    self.consume();
+                    self.match_token(Token::LPar, "expected '(' after 'align'")?;
+                    let align: u32 = self
+                        .match_uimm32("expected function alignment in bytes")?
+                        .into();
+                    if !align.is_power_of_two() || align > 128 {
+                        return err!(
+                            self.loc,
+                            "function alignment must be a power of two up to 128: {}",
+                            align
+                        );
+                    }
+                    attributes.align = Some(align as u8);
+                    self.match_token(Token::RPar, "expected ')' after function alignment")?;
+                    continue;
+                }
+                _ => break,
+            }
+            self.consume();
+        }
+        Ok(attributes)
+    }
+
     // Parse a function signature.
     //
     // signature ::=  * "(" [paramlist] ")" ["->" retlist] [callconv]
@@ -1004,9 +1087,26 @@ impl<'a> Parser<'a> {
         let mut sig = Signature::new(CallConv::Fast);
 
         self.match_token(Token::LPar, "expected function signature: ( args... )")?;
-        // signature ::=  "(" * [abi-param-list] ")" ["->" retlist] [callconv]
+        // signature ::=  "(" * [param-list] ")" ["->" retlist] [callconv]
         if self.token() != Some(Token::RPar) {
-            sig.params = self.parse_abi_param_list(unique_isa)?;
+            // param-list ::= * param-or-ellipsis { "," param-or-ellipsis }
+            loop {
+                if self.token() == Some(Token::Dot) {
+                    // The variadic arguments follow the fixed parameters.
+                    if sig.variadic.is_some() {
+                        return err!(self.loc, "duplicate '...' in signature");
+                    }
+                    for _ in 0..3 {
+                        self.match_token(Token::Dot, "expected '...' in signature")?;
+                    }
+                    sig.variadic = Some(sig.params.len());
+                } else {
+                    sig.params.push(self.parse_abi_param(unique_isa)?);
+                }
+                if !self.optional(Token::Comma) {
+                    break;
+                }
+            }
         }
         self.match_token(Token::RPar, "expected ')' after function arguments")?;
         if self.optional(Token::Arrow) {
@@ -1059,6 +1159,16 @@ impl<'a> Parser<'a> {
             match s {
                 "uext" => arg.extension = ArgumentExtension::Uext,
                 "sext" => arg.extension = ArgumentExtension::Sext,
+                "sarg" => {
+                    self.consume();
+                    arg.purpose = ArgumentPurpose::StructArgument(self.parse_struct_layout()?);
+                    continue;
+                }
+                "sarg_part" => {
+                    self.consume();
+                    arg.purpose = ArgumentPurpose::StructArgumentPart(self.parse_struct_layout()?);
+                    continue;
+                }
                 _ => {
                     if let Ok(purpose) = s.parse() {
                         arg.purpose = purpose;
@@ -1076,6 +1186,30 @@ impl<'a> Parser<'a> {
         Ok(arg)
     }
 
+    // Parse the layout of a struct passed by value.
+    //
+    // struct-layout ::= "(" uimm32 [ "," eightbyte-classes ] ")"
+    fn parse_struct_layout(&mut self) -> ParseResult<StructLayout> {
+        self.match_token(Token::LPar, "expected '(' before struct layout")?;
+        let size: u32 = self.match_uimm32("expected struct size")?.into();
+        let mut layout = StructLayout::new(size);
+        if self.optional(Token::Comma) {
+            let classes = self.match_any_identifier("expected eightbyte classes")?;
+            if classes.len() > 8 {
+                return err!(self.loc, "too many eightbyte classes");
+            }
+            for (index, class) in classes.chars().enumerate() {
+                match class {
+                    'i' => {}
+                    'f' => layout.float_eightbytes |= 1 << index,
+                    _ => return err!(self.loc, "eightbyte classes must be 'i' or 'f'"),
+                }
+            }
+        }
+        self.match_token(Token::RPar, "expected ')' after struct layout")?;
+        Ok(layout)
+    }
+
     // Parse an argument location specifier; either a register or a byte offset into the stack.
     fn parse_argument_location(
         &mut self,
@@ -1124,6 +1258,10 @@ impl<'a> Parser<'a> {
     //                   * function-decl
     //                   * signature-decl
     //                   * jump-table-decl
+    //                   * osr-entry-decl
+    //                   * symbol-decl
+    //
+    // osr-entry-decl ::= "osr_entry" Ebb(ebb)
     //
     // The parsed decls are added to `ctx` rather than returned.
     fn parse_preamble(&mut self, ctx: &mut Context) -> ParseResult<()> {
@@ -1158,19 +1296,79 @@ impl<'a> Parser<'a> {
                 Some(Token::FuncRef(..)) => {
                     self.start_gathering_comments();
                     self.parse_function_decl(ctx)
-                        .and_then(|(fn_, dat)| ctx.add_fn(fn_, dat, self.loc))
+                        .and_then(|(fn_, dat, attrs)| ctx.add_fn(fn_, dat, attrs, self.loc))
                 }
                 Some(Token::JumpTable(..)) => {
                     self.start_gathering_comments();
                     self.parse_jump_table_decl()
                         .and_then(|(jt, dat)| ctx.add_jt(jt, dat, self.loc))
                 }
+                Some(Token::RawCode(..)) => {
+                    self.start_gathering_comments();
+                    self.parse_raw_code_decl(ctx)
+                        .and_then(|(code, dat)| ctx.add_code(code, dat, self.loc))
+                }
+                Some(Token::Identifier("osr_entry")) => {
+                    self.consume();
+                    self.match_ebb("expected OSR entry EBB")
+                        .map(|ebb| ctx.function.osr_entry = Some(ebb))
+                }
+                Some(Token::Identifier("symbol")) => self.parse_symbol_decl(ctx),
                 // More to come..
                 _ => return Ok(()),
             }?;
         }
     }
 
+    // Parse a symbol decl, and add the symbol to the names of the function.
+    //
+    // symbol-decl ::= * "symbol" UserRef(namespace) ":" Integer(index) "=" String(symbol)
+    //
+    // The symbols are interned in the order they are declared, so the names must be declared in
+    // order too, starting from index 0.
+    fn parse_symbol_decl(&mut self, ctx: &mut Context) -> ParseResult<()> {
+        self.consume();
+        let loc = self.loc;
+        let name = self.parse_external_name()?;
+        let namespace = match name {
+            ExternalName::User { namespace, .. } => namespace,
+            _ => return err!(loc, "expected user-defined name: u«namespace»:«index»"),
+        };
+        self.match_token(Token::Equal, "expected '=' in symbol declaration")?;
+        let symbol = match self.token() {
+            Some(Token::String(text)) => {
+                self.consume();
+                // The lexer guarantees that a character follows every backslash.
+                let mut symbol = String::with_capacity(text.len());
+                let mut chars = text.chars();
+                while let Some(c) = chars.next() {
+                    symbol.push(if c == '\\' { chars.next().unwrap() } else { c });
+                }
+                symbol
+            }
+            _ => return err!(self.loc, "expected quoted symbol"),
+        };
+
+        let names = &mut ctx.function.names;
+        if names.is_empty() {
+            *names = ExternalNameTable::new(namespace);
+        }
+        let expected = ExternalName::user(names.namespace(), names.len() as u32);
+        if name != expected {
+            return err!(loc, "expected symbol {}, got {}", expected, name);
+        }
+        if let Some(other) = names.get(&symbol) {
+            return err!(
+                loc,
+                "symbol \"{}\" is already declared as {}",
+                symbol,
+                other
+            );
+        }
+        names.intern(&symbol);
+        Ok(())
+    }
+
     // Parse a stack slot decl.
     //
     // stack-slot-decl ::= * StackSlot(ss) "=" stack-slot-kind Bytes {"," stack-slot-flag}
@@ -1199,6 +1397,14 @@ impl<'a> Parser<'a> {
         while self.optional(Token::Comma) {
             match self.match_any_identifier("expected stack slot flags")? {
                 "offset" => data.offset = Some(self.match_imm32("expected byte offset")?),
+                "lifetime" => {
+                    let begin = self.match_uimm32("expected lifetime begin")?.into();
+                    let end = self.match_uimm32("expected lifetime end")?.into();
+                    if begin > end {
+                        return err!(self.loc, "stack slot lifetime ends before it begins");
+                    }
+                    data.lifetime = Some(StackSlotLifetime::new(begin, end));
+                }
                 other => return err!(self.loc, "Unknown stack slot flag '{}'", other),
             }
         }
@@ -1359,10 +1565,10 @@ impl<'a> Parser<'a> {
     //
     // table-decl ::= * Table(table) "=" table-desc
     // table-desc ::= table-style table-base { "," table-attr }
-    // table-style ::= "dynamic"
+    // table-style ::= "static" | "dynamic"
     // table-base ::= GlobalValue(base)
-    // table-attr ::= "min" Imm64(bytes)
-    //              | "bound" Imm64(bytes)
+    // table-attr ::= "min" Imm64(elements)
+    //              | "bound" Imm64(elements)
     //              | "element_size" Imm64(bytes)
     //              | "index_type" type
     //
@@ -1386,7 +1592,7 @@ impl<'a> Parser<'a> {
         let mut data = TableData {
             base_gv: base,
             min_size: 0.into(),
-            bound_gv: GlobalValue::reserved_value(),
+            style: TableStyle::Static { bound: 0.into() },
             element_size: 0.into(),
             index_type: ir::types::I32,
         };
@@ -1398,8 +1604,13 @@ impl<'a> Parser<'a> {
                     data.min_size = self.match_uimm64("expected integer min size")?;
                 }
                 "bound" => {
-                    data.bound_gv = match style_name {
-                        "dynamic" => self.match_gv("expected gv bound")?,
+                    data.style = match style_name {
+                        "dynamic" => TableStyle::Dynamic {
+                            bound_gv: self.match_gv("expected gv bound")?,
+                        },
+                        "static" => TableStyle::Static {
+                            bound: self.match_uimm64("expected integer bound")?,
+                        },
                         t => return err!(self.loc, "unknown table style '{}'", t),
                     };
                 }
@@ -1443,28 +1654,34 @@ impl<'a> Parser<'a> {
     //
     // Two variants:
     //
-    // function-decl ::= FuncRef(fnref) "=" ["colocated"]" name function-decl-sig
+    // function-decl ::= FuncRef(fnref) "=" ["colocated"] func-attrs name function-decl-sig
     // function-decl-sig ::= SigRef(sig) | signature
     //
     // The first variant allocates a new signature reference. The second references an existing
     // signature which must be declared first.
     //
-    fn parse_function_decl(&mut self, ctx: &mut Context) -> ParseResult<(FuncRef, ExtFuncData)> {
+    fn parse_function_decl(
+        &mut self,
+        ctx: &mut Context,
+    ) -> ParseResult<(FuncRef, ExtFuncData, FuncAttributes)> {
         let fn_ = self.match_fn("expected function number: fn«n»")?;
         self.match_token(Token::Equal, "expected '=' in function decl")?;
 
         let loc = self.loc;
 
-        // function-decl ::= FuncRef(fnref) "=" * ["colocated"] name function-decl-sig
+        // function-decl ::= FuncRef(fnref) "=" * ["colocated"] func-attrs name function-decl-sig
         let colocated = self.optional(Token::Identifier("colocated"));
 
-        // function-decl ::= FuncRef(fnref) "=" ["colocated"] * name function-decl-sig
+        // function-decl ::= FuncRef(fnref) "=" ["colocated"] * func-attrs name function-decl-sig
+        let attributes = self.parse_func_attributes()?;
+
+        // function-decl ::= FuncRef(fnref) "=" ["colocated"] func-attrs * name function-decl-sig
         let name = self.parse_external_name()?;
 
-        // function-decl ::= FuncRef(fnref) "=" ["colocated"] name * function-decl-sig
+        // function-decl ::= FuncRef(fnref) "=" ["colocated"] func-attrs name * function-decl-sig
         let data = match self.token() {
             Some(Token::LPar) => {
-                // function-decl ::= FuncRef(fnref) "=" ["colocated"] name * signature
+                // function-decl ::= FuncRef(fnref) "=" ["colocated"] func-attrs name * signature
                 let sig = self.parse_signature(ctx.unique_isa)?;
                 let sigref = ctx.function.import_signature(sig);
                 ctx.map
@@ -1498,7 +1715,7 @@ impl<'a> Parser<'a> {
         self.token();
         self.claim_gathered_comments(fn_);
 
-        Ok((fn_, data))
+        Ok((fn_, data, attributes))
     }
 
     // Parse a jump table decl.
@@ -1547,6 +1764,71 @@ impl<'a> Parser<'a> {
         Ok((jt, data))
     }
 
+    // Parse a raw code decl.
+    //
+    // raw-code-decl ::= * RawCode(code) "=" "raw_code" "[" [byte {"," byte}] "]" "," SigRef(sig)
+    //                   ["," "clobbers" "[" regunit {"," regunit} "]"]
+    fn parse_raw_code_decl(&mut self, ctx: &Context) -> ParseResult<(RawCode, RawCodeData)> {
+        let code = self.match_code("expected raw code number: code«n»")?;
+        self.match_token(Token::Equal, "expected '=' in raw_code decl")?;
+        self.match_identifier("raw_code", "expected 'raw_code'")?;
+        self.match_token(Token::LBracket, "expected '[' before raw code bytes")?;
+
+        let mut bytes = Vec::new();
+        if self.token() != Some(Token::RBracket) {
+            loop {
+                let byte: u32 = self.match_uimm32("expected raw code byte")?.into();
+                if byte > 0xff {
+                    return err!(self.loc, "raw code byte out of range: {:#x}", byte);
+                }
+                bytes.push(byte as u8);
+                if !self.optional(Token::Comma) {
+                    break;
+                }
+            }
+        }
+        self.match_token(Token::RBracket, "expected ']' after raw code bytes")?;
+        if bytes.len() > usize::from(u8::max_value()) {
+            return err!(
+                self.loc,
+                "{} bytes of raw code, but at most {} are allowed",
+                bytes.len(),
+                u8::max_value()
+            );
+        }
+
+        // raw-code-decl ::= RawCode(code) "=" "raw_code" "[" ... "]" "," * SigRef(sig) ...
+        self.match_token(Token::Comma, "expected ',' before raw code signature")?;
+        let signature = self.match_sig("expected signature reference")?;
+        ctx.check_sig(signature, self.loc)?;
+
+        let mut clobbers = Vec::new();
+        if self.optional(Token::Comma) {
+            self.match_identifier("clobbers", "expected 'clobbers'")?;
+            self.match_token(Token::LBracket, "expected '[' before clobbered registers")?;
+            loop {
+                clobbers.push(self.match_regunit(ctx.unique_isa)?);
+                if !self.optional(Token::Comma) {
+                    break;
+                }
+            }
+            self.match_token(Token::RBracket, "expected ']' after clobbered registers")?;
+        }
+
+        // Collect any trailing comments.
+        self.token();
+        self.claim_gathered_comments(code);
+
+        Ok((
+            code,
+            RawCodeData {
+                bytes,
+                signature,
+                clobbers,
+            },
+        ))
+    }
+
     // Parse a function body, add contents to `ctx`.
     //
     // function-body ::= * { extended-basic-block }
@@ -1850,7 +2132,7 @@ impl<'a> Parser<'a> {
 
     // Parse an instruction, append it to `ebb`.
     //
-    // instruction ::= [inst-results "="] Opcode(opc) ["." Type] ...
+    // instruction ::= [inst-results "="] [branch-hint] Opcode(opc) ["." Type] ...
     //
     fn parse_instruction(
         &mut self,
@@ -1869,7 +2151,16 @@ impl<'a> Parser<'a> {
         // Collect comments for the next instruction.
         self.start_gathering_comments();
 
-        // instruction ::=  [inst-results "="] * Opcode(opc) ["." Type] ...
+        // instruction ::=  [inst-results "="] * [branch-hint] Opcode(opc) ["." Type] ...
+        let branch_hint = match self.token() {
+            Some(Token::Identifier(text)) => text.parse::<BranchHint>().ok(),
+            _ => None,
+        };
+        if branch_hint.is_some() {
+            self.consume();
+        }
+
+        // instruction ::=  [inst-results "="] [branch-hint] * Opcode(opc) ["." Type] ...
         let opcode = if let Some(Token::Identifier(text)) = self.token() {
             match text.parse() {
                 Ok(opc) => opc,
@@ -1912,6 +2203,17 @@ impl<'a> Parser<'a> {
             ctx.function.srclocs[inst] = srcloc;
         }
 
+        if branch_hint.is_some() {
+            if !opcode.is_branch() || opcode.is_terminator() {
+                return err!(
+                    opcode_loc,
+                    "branch hint on {}, which is not a conditional branch",
+                    opcode
+                );
+            }
+            ctx.function.branch_hints[inst] = branch_hint;
+        }
+
         if let Some(encoding) = encoding {
             ctx.function.encodings[inst] = encoding;
         }
@@ -2129,6 +2431,10 @@ impl<'a> Parser<'a> {
                     global_value: gv,
                 }
             }
+            InstructionFormat::UnaryUimm32 => InstructionData::UnaryUimm32 {
+                opcode,
+                imm: self.match_uimm32("expected immediate 32-bit integer operand")?,
+            },
             InstructionFormat::Binary => {
                 let lhs = self.match_value("expected SSA value first operand")?;
                 self.match_token(Token::Comma, "expected ',' between operands")?;
@@ -2360,6 +2666,33 @@ impl<'a> Parser<'a> {
                     args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
                 }
             }
+            InstructionFormat::Invoke => {
+                let func_ref = self.match_fn("expected function reference")?;
+                ctx.check_fn(func_ref, self.loc)?;
+                self.match_token(Token::LPar, "expected '(' before arguments")?;
+                let args = self.parse_value_list()?;
+                self.match_token(Token::RPar, "expected ')' after arguments")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let ebb_num = self.match_ebb("expected landing pad EBB")?;
+                InstructionData::Invoke {
+                    opcode,
+                    func_ref,
+                    destination: ebb_num,
+                    args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
+                }
+            }
+            InstructionFormat::RawBytes => {
+                let raw_code = self.match_code("expected raw code reference")?;
+                ctx.check_code(raw_code, self.loc)?;
+                self.match_token(Token::LPar, "expected '(' before arguments")?;
+                let args = self.parse_value_list()?;
+                self.match_token(Token::RPar, "expected ')' after arguments")?;
+                InstructionData::RawBytes {
+                    opcode,
+                    raw_code,
+                    args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
+                }
+            }
             InstructionFormat::CallIndirect => {
                 let sig_ref = self.match_sig("expected signature reference")?;
                 ctx.check_sig(sig_ref, self.loc)?;
@@ -2676,12 +3009,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn struct_argument_signature() {
+        let sig = Parser::new("(i64 sarg(24), i64 sarg(16, fi), i32) system_v")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(
+            sig.params[0].purpose,
+            ArgumentPurpose::StructArgument(StructLayout::new(24))
+        );
+        assert_eq!(
+            sig.to_string(),
+            "(i64 sarg(24), i64 sarg(16, fi), i32) system_v"
+        );
+        assert_eq!(
+            Parser::new("(i64 sarg(16, fx)) system_v")
+                .parse_signature(None)
+                .unwrap_err()
+                .to_string(),
+            "1: eightbyte classes must be 'i' or 'f'"
+        );
+    }
+
+    #[test]
+    fn variadic_signature() {
+        let sig = Parser::new("(i64, ..., f64, i32) system_v")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(sig.params.len(), 3);
+        assert_eq!(sig.variadic, Some(1));
+        assert_eq!(sig.to_string(), "(i64, ..., f64, i32) system_v");
+
+        let sig = Parser::new("(...) system_v").parse_signature(None).unwrap();
+        assert_eq!(sig.params.len(), 0);
+        assert_eq!(sig.variadic, Some(0));
+
+        assert_eq!(
+            Parser::new("(i64, ..., ...) system_v")
+                .parse_signature(None)
+                .unwrap_err()
+                .to_string(),
+            "1: duplicate '...' in signature"
+        );
+        assert_eq!(
+            Parser::new("(i64, .., f64) system_v")
+                .parse_signature(None)
+                .unwrap_err()
+                .to_string(),
+            "1: expected '...' in signature"
+        );
+    }
+
     #[test]
     fn stack_slot_decl() {
         let (func, _) = Parser::new(
             "function %foo() system_v {
                                        ss3 = incoming_arg 13
                                        ss1 = spill_slot 1
+                                       ss4 = explicit_slot 8, lifetime 2 5
                                      }",
         )
         .parse_function(None)
@@ -2698,6 +3083,11 @@ mod tests {
         assert_eq!(ss3.to_string(), "ss3");
         assert_eq!(func.stack_slots[ss3].kind, StackSlotKind::IncomingArg);
         assert_eq!(func.stack_slots[ss3].size, 13);
+        let ss4 = iter.next().unwrap();
+        assert_eq!(
+            func.stack_slots[ss4].lifetime,
+            Some(StackSlotLifetime::new(2, 5))
+        );
         assert_eq!(iter.next(), None);
 
         // Catch duplicate definitions.
@@ -2713,6 +3103,19 @@ mod tests {
             .to_string(),
             "3: duplicate entity: ss1"
         );
+
+        // Catch reversed lifetimes.
+        assert_eq!(
+            Parser::new(
+                "function %baz() system_v {
+                                    ss0 = explicit_slot 8, lifetime 5 2
+                                }",
+            )
+            .parse_function(None)
+            .unwrap_err()
+            .to_string(),
+            "2: stack slot lifetime ends before it begins"
+        );
     }
 
     #[test]
@@ -2738,6 +3141,40 @@ mod tests {
         assert_eq!(func.dfg.value_type(ebb4_args[0]), types::I32);
     }
 
+    #[test]
+    fn branch_hints() {
+        let (func, _) = Parser::new(
+            "function %hints(i32) system_v {
+                ebb0(v0: i32):
+                    unlikely brnz v0, ebb1
+                    jump ebb1
+                ebb1:
+                    return
+            }",
+        )
+        .parse_function(None)
+        .unwrap();
+        let insts: Vec<_> = func
+            .layout
+            .ebb_insts(Ebb::with_number(0).unwrap())
+            .collect();
+        assert_eq!(func.branch_hints[insts[0]], Some(BranchHint::Unlikely));
+        assert_eq!(func.branch_hints[insts[1]], None);
+
+        assert_eq!(
+            Parser::new(
+                "function %hints() system_v {
+                    ebb0:
+                        likely jump ebb0
+                }",
+            )
+            .parse_function(None)
+            .unwrap_err()
+            .to_string(),
+            "3: branch hint on jump, which is not a conditional branch"
+        );
+    }
+
     #[test]
     fn duplicate_ebb() {
         let ParseError {
@@ -2777,6 +3214,30 @@ mod tests {
         assert!(!is_warning);
     }
 
+    #[test]
+    fn long_raw_code() {
+        let bytes = vec!["0x90"; 256].join(", ");
+        let ParseError {
+            location,
+            message,
+            is_warning,
+        } = Parser::new(&format!(
+            "function %f() system_v {{
+                sig0 = ()
+                code0 = raw_code [{}], sig0",
+            bytes
+        ))
+        .parse_function(None)
+        .unwrap_err();
+
+        assert_eq!(location.line_number, 3);
+        assert_eq!(
+            message,
+            "256 bytes of raw code, but at most 255 are allowed"
+        );
+        assert!(!is_warning);
+    }
+
     #[test]
     fn duplicate_ss() {
         let ParseError {
@@ -2873,6 +3334,44 @@ mod tests {
         assert!(!is_warning);
     }
 
+    #[test]
+    fn duplicate_symbol() {
+        let ParseError {
+            location,
+            message,
+            is_warning,
+        } = Parser::new(
+            "function %ebbs() system_v {
+                symbol u1:0 = \"foo\"
+                symbol u1:1 = \"foo\"",
+        )
+        .parse_function(None)
+        .unwrap_err();
+
+        assert_eq!(location.line_number, 3);
+        assert_eq!(message, "symbol \"foo\" is already declared as u1:0");
+        assert!(!is_warning);
+    }
+
+    #[test]
+    fn unordered_symbol() {
+        let ParseError {
+            location,
+            message,
+            is_warning,
+        } = Parser::new(
+            "function %ebbs() system_v {
+                symbol u1:0 = \"foo\"
+                symbol u2:1 = \"bar\"",
+        )
+        .parse_function(None)
+        .unwrap_err();
+
+        assert_eq!(location.line_number, 3);
+        assert_eq!(message, "expected symbol u1:1, got u2:1");
+        assert!(!is_warning);
+    }
+
     #[test]
     fn comments() {
         let (func, Details { comments, .. }) = Parser::new(
@@ -10,7 +10,7 @@ use crate::error::{Location, ParseResult};
 use crate::lexer::split_entity_name;
 use cranelift_codegen::ir::entities::AnyEntity;
 use cranelift_codegen::ir::{
-    Ebb, FuncRef, GlobalValue, Heap, JumpTable, SigRef, StackSlot, Table, Value,
+    Ebb, FuncRef, GlobalValue, Heap, JumpTable, RawCode, SigRef, StackSlot, Table, Value,
 };
 use std::collections::HashMap;
 
@@ -63,6 +63,11 @@ impl SourceMap {
         self.locations.contains_key(&fn_.into())
     }
 
+    /// Look up a raw code entity.
+    pub fn contains_code(&self, code: RawCode) -> bool {
+        self.locations.contains_key(&code.into())
+    }
+
     /// Look up a jump table entity.
     pub fn contains_jt(&self, jt: JumpTable) -> bool {
         self.locations.contains_key(&jt.into())
@@ -128,6 +133,13 @@ impl SourceMap {
                     Some(fn_.into())
                 }
             }),
+            "code" => RawCode::with_number(num).and_then(|code| {
+                if !self.contains_code(code) {
+                    None
+                } else {
+                    Some(code.into())
+                }
+            }),
             "jt" => JumpTable::with_number(num).and_then(|jt| {
                 if !self.contains_jt(jt) {
                     None
@@ -193,6 +205,11 @@ impl SourceMap {
         self.def_entity(entity.into(), loc)
     }
 
+    /// Define the raw code `entity`.
+    pub fn def_code(&mut self, entity: RawCode, loc: Location) -> ParseResult<()> {
+        self.def_entity(entity.into(), loc)
+    }
+
     /// Define the jump table `entity`.
     pub fn def_jt(&mut self, entity: JumpTable, loc: Location) -> ParseResult<()> {
         self.def_entity(entity.into(), loc)
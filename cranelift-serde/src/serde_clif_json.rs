@@ -28,6 +28,10 @@ pub enum SerInstData {
         opcode: String,
         global_value: String,
     },
+    UnaryUimm32 {
+        opcode: String,
+        imm: String,
+    },
     Binary {
         opcode: String,
         args: [String; 2],
@@ -148,10 +152,21 @@ pub enum SerInstData {
         args: Vec<String>,
         sig_ref: String,
     },
+    Invoke {
+        opcode: String,
+        args: Vec<String>,
+        func_ref: String,
+        destination: String,
+    },
     FuncAddr {
         opcode: String,
         func_ref: String,
     },
+    RawBytes {
+        opcode: String,
+        args: Vec<String>,
+        raw_code: String,
+    },
     Load {
         opcode: String,
         arg: String,
@@ -276,6 +291,10 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
             opcode: opcode.to_string(),
             global_value: global_value.to_string(),
         },
+        InstructionData::UnaryUimm32 { opcode, imm } => SerInstData::UnaryUimm32 {
+            opcode: opcode.to_string(),
+            imm: imm.to_string(),
+        },
         InstructionData::Binary { opcode, args } => {
             let hold_args = [args[0].to_string(), args[1].to_string()];
             SerInstData::Binary {
@@ -512,6 +531,24 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
                 func_ref: func_ref.to_string(),
             }
         }
+        InstructionData::Invoke {
+            opcode,
+            ref args,
+            func_ref,
+            destination,
+        } => {
+            let mut hold_args = Vec::new();
+            let args_iter = args.as_slice(&func.dfg.value_lists);
+            for arg in args_iter {
+                hold_args.push(arg.to_string());
+            }
+            SerInstData::Invoke {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                func_ref: func_ref.to_string(),
+                destination: destination.to_string(),
+            }
+        }
         InstructionData::CallIndirect {
             opcode,
             ref args,
@@ -528,6 +565,22 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
                 sig_ref: sig_ref.to_string(),
             }
         }
+        InstructionData::RawBytes {
+            opcode,
+            ref args,
+            raw_code,
+        } => {
+            let mut hold_args = Vec::new();
+            let args_iter = args.as_slice(&func.dfg.value_lists);
+            for arg in args_iter {
+                hold_args.push(arg.to_string());
+            }
+            SerInstData::RawBytes {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                raw_code: raw_code.to_string(),
+            }
+        }
         InstructionData::FuncAddr { opcode, func_ref } => SerInstData::FuncAddr {
             opcode: opcode.to_string(),
             func_ref: func_ref.to_string(),
@@ -12,6 +12,7 @@ use cranelift_module::{
 use cranelift_native;
 #[cfg(not(windows))]
 use libc;
+use std::cmp;
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::io::Write;
@@ -243,11 +244,27 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         _namespace: &ModuleNamespace<Self>,
         code_size: u32,
     ) -> ModuleResult<Self::CompiledFunction> {
-        let size = code_size as usize;
-        let ptr = self
-            .code_memory
-            .allocate(size, EXECUTABLE_DATA_ALIGNMENT)
-            .expect("TODO: handle OOM etc.");
+        let align = cmp::max(
+            EXECUTABLE_DATA_ALIGNMENT,
+            ctx.func.attributes.align.unwrap_or(0),
+        );
+
+        let mut reloc_sink = SimpleJITRelocSink::new();
+        // Ignore traps for now. For now, frontends should just avoid generating code
+        // that traps.
+        let mut trap_sink = NullTrapSink {};
+        let mut stackmap_sink = SimpleJITStackmapSink::new();
+        let mut region = self.code_memory.stream(align);
+        let info = ctx.emit_to_code_memory(
+            &*self.isa,
+            &mut region,
+            &mut reloc_sink,
+            &mut trap_sink,
+            &mut stackmap_sink,
+        );
+        debug_assert_eq!(info.total_size, code_size);
+        let size = info.total_size as usize;
+        let ptr = region.finish(size);
 
         if cfg!(target_os = "linux") && ::std::env::var_os("PERF_BUILDID_DIR").is_some() {
             let mut map_file = ::std::fs::OpenOptions::new()
@@ -259,21 +276,6 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
             let _ = writeln!(map_file, "{:x} {:x} {}", ptr as usize, code_size, name);
         }
 
-        let mut reloc_sink = SimpleJITRelocSink::new();
-        // Ignore traps for now. For now, frontends should just avoid generating code
-        // that traps.
-        let mut trap_sink = NullTrapSink {};
-        let mut stackmap_sink = SimpleJITStackmapSink::new();
-        unsafe {
-            ctx.emit_to_memory(
-                &*self.isa,
-                ptr,
-                &mut reloc_sink,
-                &mut trap_sink,
-                &mut stackmap_sink,
-            )
-        };
-
         Ok(Self::CompiledFunction {
             code: ptr,
             size,
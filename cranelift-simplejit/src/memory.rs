@@ -7,9 +7,11 @@ use libc;
 #[cfg(feature = "selinux-fix")]
 use memmap::MmapMut;
 
+use cranelift_codegen::binemit::CodeMemory;
 use region;
 use std::mem;
 use std::ptr;
+use std::slice;
 
 /// Round `size` up to the nearest multiple of `page_size`.
 fn round_up_to_page_size(size: usize, page_size: usize) -> usize {
@@ -152,6 +154,19 @@ impl Memory {
         Ok(self.current.ptr)
     }
 
+    /// Start a region of memory aligned to `align` whose size isn't known in advance.
+    ///
+    /// The region grows as it's written, so code can be emitted into it directly.
+    pub fn stream(&mut self, align: u8) -> Region {
+        if self.position % align as usize != 0 {
+            self.position += align as usize - self.position % align as usize;
+        }
+        Region {
+            start: self.position,
+            memory: self,
+        }
+    }
+
     /// Set all memory allocated in this `Memory` up to now as readable and executable.
     pub fn set_readable_and_executable(&mut self) {
         self.finish_current();
@@ -213,10 +228,81 @@ impl Memory {
 
 // TODO: Implement Drop to unprotect and deallocate the memory?
 
+/// A contiguous region of a `Memory` that grows a page at a time.
+///
+/// The region starts at the end of the current allocation of its `Memory`, and it moves to a new,
+/// larger allocation when it outgrows it, so its address is only final after `finish`.
+pub struct Region<'a> {
+    memory: &'a mut Memory,
+    start: usize,
+}
+
+impl<'a> Region<'a> {
+    /// Keep the first `size` bytes of the region, and return a pointer to them.
+    ///
+    /// The rest of the region is returned to the `Memory` for the next allocations.
+    pub fn finish(self, size: usize) -> *mut u8 {
+        self.memory.position = self.start + size;
+        debug_assert!(self.memory.position <= self.memory.current.len);
+        unsafe { self.memory.current.ptr.add(self.start) }
+    }
+}
+
+impl<'a> CodeMemory for Region<'a> {
+    fn grow(&mut self, size: usize) -> &mut [u8] {
+        let memory = &mut *self.memory;
+        if self.start + size > memory.current.len {
+            let new = PtrLen::with_size(size).expect("TODO: handle OOM etc.");
+            if memory.position > self.start {
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        memory.current.ptr.add(self.start),
+                        new.ptr,
+                        memory.position - self.start,
+                    );
+                }
+            }
+            memory.finish_current();
+            memory.current = new;
+            self.start = 0;
+        }
+
+        // Reserve the whole allocation, so the region only grows again when it's full.
+        memory.position = memory.current.len;
+        unsafe {
+            slice::from_raw_parts_mut(
+                memory.current.ptr.add(self.start),
+                memory.current.len - self.start,
+            )
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_region() {
+        let page_size = region::page::size();
+        let mut memory = Memory::new();
+        let first = memory.allocate(8, 1).unwrap();
+        let allocations = memory.allocations.len();
+
+        // The region starts after the first allocation, then moves when it outgrows the page.
+        let mut region = memory.stream(16);
+        let bytes = region.grow(4);
+        assert_eq!(bytes.len(), page_size - 16);
+        bytes[0] = 42;
+        let bytes = region.grow(page_size);
+        assert_eq!(bytes.len(), page_size);
+        assert_eq!(bytes[0], 42);
+        let ptr = region.finish(page_size);
+        assert_ne!(ptr, unsafe { first.add(16) });
+        assert_eq!(memory.allocations.len(), allocations + 1);
+        assert_eq!(memory.position, page_size);
+    }
+
     #[test]
     fn test_round_up_to_page_size() {
         assert_eq!(round_up_to_page_size(0, 4096), 0);
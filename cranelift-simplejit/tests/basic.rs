@@ -13,7 +13,7 @@ fn error_on_incompatible_sig_in_declare_function() {
     let mut sig = Signature {
         params: vec![AbiParam::new(types::I64)],
         returns: vec![],
-        call_conv: CallConv::SystemV,
+        ..Signature::new(CallConv::SystemV)
     };
     module
         .declare_function("abc", Linkage::Local, &sig)
@@ -29,7 +29,7 @@ fn define_simple_function(module: &mut Module<SimpleJITBackend>) -> FuncId {
     let sig = Signature {
         params: vec![],
         returns: vec![],
-        call_conv: CallConv::SystemV,
+        ..Signature::new(CallConv::SystemV)
     };
 
     let func_id = module
@@ -82,7 +82,7 @@ fn switch_error() {
     let sig = Signature {
         params: vec![AbiParam::new(types::I32)],
         returns: vec![AbiParam::new(types::I32)],
-        call_conv: CallConv::SystemV,
+        ..Signature::new(CallConv::SystemV)
     };
 
     let mut func = Function::with_name_signature(ExternalName::user(0, 0), sig);
@@ -156,7 +156,7 @@ fn libcall_function() {
     let sig = Signature {
         params: vec![],
         returns: vec![],
-        call_conv: CallConv::SystemV,
+        ..Signature::new(CallConv::SystemV)
     };
 
     let func_id = module
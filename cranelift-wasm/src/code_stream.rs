@@ -0,0 +1,274 @@
+//! Incremental reading of the code section, so that function bodies can be translated as they
+//! arrive instead of once the whole module is available.
+//!
+//! A streaming compilation pipeline translates the sections that precede the code section with
+//! [`translate_module`](../fn.translate_module.html), which stops at the end of the bytes it is
+//! given. The payload of the code section is then fed to a `CodeSectionStream` chunk by chunk,
+//! and each complete function body is handed to a [`FuncTranslator`](../struct.FuncTranslator.html)
+//! or to the environment's `define_function_body` as soon as its last byte has been received.
+
+use crate::environ::{WasmError, WasmResult};
+use std::vec::Vec;
+
+/// Incremental reader for the payload of a WebAssembly code section.
+pub struct CodeSectionStream {
+    /// Bytes received, possibly preceded by some of the bodies already returned.
+    buffer: Vec<u8>,
+    /// Position in `buffer` of the first byte that hasn't been returned yet.
+    position: usize,
+    /// Offset in the module of the first byte in `buffer`.
+    base_offset: usize,
+    /// Number of function bodies in the section, once its header has been received.
+    count: Option<u32>,
+    /// Number of function bodies returned so far.
+    bodies_read: u32,
+}
+
+impl CodeSectionStream {
+    /// Create a stream for a code section whose payload starts at `offset` in the module, i.e.
+    /// just after the section id and payload size.
+    ///
+    /// The offset is only used to produce the same source locations and error offsets as
+    /// translating the whole module at once.
+    pub fn new(offset: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            position: 0,
+            base_offset: offset,
+            count: None,
+            bodies_read: 0,
+        }
+    }
+
+    /// Append the next chunk of the section payload.
+    pub fn push(&mut self, bytes: &[u8]) {
+        // Release the bytes of the returned bodies once they make up half of the buffer, so each
+        // byte is moved a bounded number of times.
+        if self.position * 2 >= self.buffer.len() {
+            self.buffer.drain(..self.position);
+            self.base_offset += self.position;
+            self.position = 0;
+        }
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Get the number of function bodies in the section, if its header has been received.
+    pub fn count(&self) -> Option<u32> {
+        self.count
+    }
+
+    /// Has every function body of the section been returned?
+    pub fn is_finished(&self) -> bool {
+        self.count == Some(self.bodies_read)
+    }
+
+    /// Get the next complete function body.
+    ///
+    /// Returns the body bytes together with their offset in the module, as expected by
+    /// `FuncTranslator::translate`, or `None` if more bytes must be pushed first or all the
+    /// bodies have been returned.
+    pub fn next_body(&mut self) -> WasmResult<Option<(&[u8], usize)>> {
+        let mut position = self.position;
+        if self.count.is_none() {
+            match read_var_u32(&self.buffer[position..], self.base_offset + position)? {
+                Some((count, size)) => {
+                    self.count = Some(count);
+                    position += size;
+                }
+                None => return Ok(None),
+            }
+        }
+        if self.is_finished() {
+            self.position = position;
+            return Ok(None);
+        }
+
+        let (body_size, size) =
+            match read_var_u32(&self.buffer[position..], self.base_offset + position)? {
+                Some(body_size) => body_size,
+                None => {
+                    self.position = position;
+                    return Ok(None);
+                }
+            };
+        let body_start = position + size;
+        let body_end = match body_start.checked_add(body_size as usize) {
+            Some(body_end) => body_end,
+            None => {
+                return Err(WasmError::InvalidWebAssembly {
+                    message: "function body too large",
+                    offset: self.base_offset + position,
+                });
+            }
+        };
+        if self.buffer.len() < body_end {
+            self.position = position;
+            return Ok(None);
+        }
+
+        self.position = body_end;
+        self.bodies_read += 1;
+        Ok(Some((
+            &self.buffer[body_start..body_end],
+            self.base_offset + body_start,
+        )))
+    }
+
+    /// Check that the section was received entirely, with no bytes past its last function body.
+    pub fn finish(&self) -> WasmResult<()> {
+        let offset = self.base_offset + self.position;
+        if !self.is_finished() {
+            return Err(WasmError::InvalidWebAssembly {
+                message: "unexpected end of code section",
+                offset,
+            });
+        }
+        if self.position != self.buffer.len() {
+            return Err(WasmError::InvalidWebAssembly {
+                message: "unexpected data at the end of the code section",
+                offset,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Decode the LEB128 `u32` at the start of `bytes`, returning it together with its encoded size,
+/// or `None` if `bytes` ends before the encoding does.
+fn read_var_u32(bytes: &[u8], offset: usize) -> WasmResult<Option<(u32, usize)>> {
+    let mut result = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if i == 4 && byte >= 0x10 {
+            return Err(WasmError::InvalidWebAssembly {
+                message: "Invalid var_u32",
+                offset,
+            });
+        }
+        result |= u32::from(byte & 0x7f) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Ok(Some((result, i + 1)));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CodeSectionStream;
+    use crate::environ::{DummyEnvironment, ModuleEnvironment, ReturnMode};
+    use crate::translate_module;
+    use cranelift_codegen::isa::{CallConv, TargetFrontendConfig};
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+    use target_lexicon::PointerWidth;
+
+    fn dummy_environment() -> DummyEnvironment {
+        DummyEnvironment::new(
+            TargetFrontendConfig {
+                default_call_conv: CallConv::SystemV,
+                pointer_width: PointerWidth::U64,
+            },
+            ReturnMode::NormalReturns,
+            false,
+        )
+    }
+
+    fn display_functions(environ: &DummyEnvironment) -> Vec<String> {
+        environ
+            .info
+            .function_bodies
+            .values()
+            .map(|func| func.display(None).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn streamed_bodies() {
+        // (module
+        //     (func (param i32) (result i32)
+        //         (i32.add (get_local 0) (i32.const 1)))
+        //     (func (result i32)
+        //         (local i32)
+        //         (loop (br_if 0 (get_local 0)))
+        //         (i32.const 7)))
+        const PREFIX: [u8; 25] = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, // type section, 2 entries
+            0x60, 0x01, 0x7f, 0x01, 0x7f, // (param i32) (result i32)
+            0x60, 0x00, 0x01, 0x7f, // (result i32)
+            0x03, 0x03, 0x02, 0x00, 0x01, // function section, 2 entries
+        ];
+        const CODE_HEADER: [u8; 2] = [0x0a, 0x17]; // code section, payload size
+        const PAYLOAD: [u8; 23] = [
+            0x02, // 2 bodies
+            0x07, 0x00, 0x20, 0x00, 0x41, 0x01, 0x6a, 0x0b, // first body
+            0x0d, 0x01, 0x01, 0x7f, // second body size, 1 i32 local
+            0x03, 0x40, 0x20, 0x00, 0x0d, 0x00, 0x0b, // loop, br_if
+            0x41, 0x07, // i32.const 7
+            0x0b, // end
+        ];
+
+        let module: Vec<u8> = PREFIX
+            .iter()
+            .chain(&CODE_HEADER)
+            .chain(&PAYLOAD)
+            .cloned()
+            .collect();
+
+        let mut whole = dummy_environment();
+        translate_module(&module, &mut whole).unwrap();
+
+        let mut streamed = dummy_environment();
+        translate_module(&PREFIX, &mut streamed).unwrap();
+        let mut stream = CodeSectionStream::new(PREFIX.len() + CODE_HEADER.len());
+        assert_eq!(stream.count(), None);
+        for byte in PAYLOAD.iter() {
+            assert!(!stream.is_finished());
+            stream.push(&[*byte]);
+            while let Some((body, offset)) = stream.next_body().unwrap() {
+                streamed.define_function_body(body, offset).unwrap();
+            }
+        }
+        assert_eq!(stream.count(), Some(2));
+        assert!(stream.is_finished());
+        stream.finish().unwrap();
+
+        assert_eq!(display_functions(&streamed), display_functions(&whole));
+    }
+
+    #[test]
+    fn truncated() {
+        let mut stream = CodeSectionStream::new(0);
+        stream.push(&[0x01, 0x03, 0x00]);
+        assert_eq!(stream.next_body().unwrap(), None);
+        assert!(stream.finish().is_err());
+
+        stream.push(&[0x0b, 0x0b]);
+        assert_eq!(
+            stream.next_body().unwrap(),
+            Some((&[0x00, 0x0b, 0x0b][..], 2))
+        );
+        assert_eq!(stream.next_body().unwrap(), None);
+        assert!(stream.finish().is_ok());
+
+        stream.push(&[0x00]);
+        assert_eq!(stream.next_body().unwrap(), None);
+        assert!(stream.finish().is_err());
+    }
+
+    #[test]
+    fn released_bodies() {
+        // The offsets stay correct when the returned bodies are released from the buffer.
+        let mut stream = CodeSectionStream::new(10);
+        stream.push(&[0x03]);
+        for i in 0..3 {
+            stream.push(&[0x02, 0x00, 0x0b]);
+            assert_eq!(
+                stream.next_body().unwrap(),
+                Some((&[0x00, 0x0b][..], 12 + 3 * i))
+            );
+            assert_eq!(stream.next_body().unwrap(), None);
+        }
+        assert!(stream.finish().is_ok());
+    }
+}
@@ -33,7 +33,7 @@ use crate::wasm_unsupported;
 use core::{i32, u32};
 use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
 use cranelift_codegen::ir::types::*;
-use cranelift_codegen::ir::{self, InstBuilder, JumpTableData, MemFlags, ValueLabel};
+use cranelift_codegen::ir::{self, InstBuilder, JumpTableData, MemFlags};
 use cranelift_codegen::packed_option::ReservedValue;
 use cranelift_frontend::{FunctionBuilder, Variable};
 use wasmparser::{MemoryImmediate, Operator};
@@ -62,20 +62,14 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         Operator::GetLocal { local_index } => {
             let val = builder.use_var(Variable::with_u32(*local_index));
             state.push1(val);
-            let label = ValueLabel::from_u32(*local_index);
-            builder.set_val_label(val, label);
         }
         Operator::SetLocal { local_index } => {
             let val = state.pop1();
             builder.def_var(Variable::with_u32(*local_index), val);
-            let label = ValueLabel::from_u32(*local_index);
-            builder.set_val_label(val, label);
         }
         Operator::TeeLocal { local_index } => {
             let val = state.peek1();
             builder.def_var(Variable::with_u32(*local_index), val);
-            let label = ValueLabel::from_u32(*local_index);
-            builder.set_val_label(val, label);
         }
         /********************************** Globals ****************************************
          *  `get_global` and `set_global` are handled by the environment.
@@ -1158,31 +1152,48 @@ fn translate_unreachable_operator(
     }
 }
 
-/// Get the address+offset to use for a heap access.
+/// Get the address+offset to use for a heap access of `access_size` bytes.
+///
+/// The wasm address `index` is an `i32` for classic linear memories and an `i64` for 64-bit
+/// memories. It is zero-extended when the heap uses a wider index type than the memory.
+///
+/// The `heap_addr` covers the whole access, and its legalization picks the bounds check strategy
+/// of the heap: no check, a check of every byte, or a check of the bytes the offset-guard pages
+/// don't cover.
+///
+/// The access size of a `heap_addr` is a `u32`, so when `offset + access_size` doesn't fit, the
+/// excess is added to the index first, trapping if that overflows, and removed from the offset.
 fn get_heap_addr(
     heap: ir::Heap,
-    addr32: ir::Value,
+    index: ir::Value,
     offset: u32,
+    access_size: u32,
     addr_ty: Type,
     builder: &mut FunctionBuilder,
 ) -> (ir::Value, i32) {
     use core::cmp::min;
 
-    let mut adjusted_offset = u64::from(offset);
-    let offset_guard_size: u64 = builder.func.heaps[heap].offset_guard_size.into();
-
-    // Generate `heap_addr` instructions that are friendly to CSE by checking offsets that are
-    // multiples of the offset-guard size. Add one to make sure that we check the pointer itself
-    // is in bounds.
-    if offset_guard_size != 0 {
-        adjusted_offset = adjusted_offset / offset_guard_size * offset_guard_size;
-    }
+    let index_ty = builder.func.heaps[heap].index_type;
+    let index = if builder.func.dfg.value_type(index).bits() < index_ty.bits() {
+        builder.ins().uextend(index_ty, index)
+    } else {
+        index
+    };
 
-    // For accesses on the outer skirts of the offset-guard pages, we expect that we get a trap
-    // even if the access goes beyond the offset-guard pages. This is because the first byte
-    // pointed to is inside the offset-guard pages.
-    let check_size = min(u64::from(u32::MAX), 1 + adjusted_offset) as u32;
-    let base = builder.ins().heap_addr(addr_ty, heap, addr32, check_size);
+    let end = u64::from(offset) + u64::from(access_size);
+    let (index, offset) = if end > u64::from(u32::MAX) {
+        let excess = end - u64::from(u32::MAX);
+        let excess_val = builder.ins().iconst(index_ty, excess as i64);
+        let (index, overflow) = builder.ins().iadd_cout(index, excess_val);
+        builder
+            .ins()
+            .trapnz(overflow, ir::TrapCode::HeapOutOfBounds);
+        (index, offset - excess as u32)
+    } else {
+        (index, offset)
+    };
+    let check_size = min(end, u64::from(u32::MAX)) as u32;
+    let base = builder.ins().heap_addr(addr_ty, heap, index, check_size);
 
     // Native load/store instructions take a signed `Offset32` immediate, so adjust the base
     // pointer if necessary.
@@ -1195,6 +1206,16 @@ fn get_heap_addr(
     }
 }
 
+/// Get the number of bytes accessed by the memory instruction `opcode` operating on a `ty` value.
+fn mem_access_size(opcode: ir::Opcode, ty: Type) -> u32 {
+    match opcode {
+        ir::Opcode::Istore8 | ir::Opcode::Sload8 | ir::Opcode::Uload8 => 1,
+        ir::Opcode::Istore16 | ir::Opcode::Sload16 | ir::Opcode::Uload16 => 2,
+        ir::Opcode::Istore32 | ir::Opcode::Sload32 | ir::Opcode::Uload32 => 4,
+        _ => ty.bytes(),
+    }
+}
+
 /// Translate a load instruction.
 fn translate_load<FE: FuncEnvironment + ?Sized>(
     offset: u32,
@@ -1204,10 +1225,18 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
     state: &mut TranslationState,
     environ: &mut FE,
 ) -> WasmResult<()> {
-    let addr32 = state.pop1();
+    let index = state.pop1();
     // We don't yet support multiple linear memories.
     let heap = state.get_heap(builder.func, 0, environ)?;
-    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
+    let access_size = mem_access_size(opcode, result_ty);
+    let (base, offset) = get_heap_addr(
+        heap,
+        index,
+        offset,
+        access_size,
+        environ.pointer_type(),
+        builder,
+    );
     // Note that we don't set `is_aligned` here, even if the load instruction's
     // alignment immediate says it's aligned, because WebAssembly's immediate
     // field is just a hint, while Cranelift's aligned flag needs a guarantee.
@@ -1227,12 +1256,20 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     state: &mut TranslationState,
     environ: &mut FE,
 ) -> WasmResult<()> {
-    let (addr32, val) = state.pop2();
+    let (index, val) = state.pop2();
     let val_ty = builder.func.dfg.value_type(val);
 
     // We don't yet support multiple linear memories.
     let heap = state.get_heap(builder.func, 0, environ)?;
-    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
+    let access_size = mem_access_size(opcode, val_ty);
+    let (base, offset) = get_heap_addr(
+        heap,
+        index,
+        offset,
+        access_size,
+        environ.pointer_type(),
+        builder,
+    );
     // See the comments in `translate_load` about the flags.
     let flags = MemFlags::new();
     builder
@@ -1291,3 +1328,118 @@ fn translate_br_if_args(
     let inputs = state.peekn(return_count);
     (br_destination, inputs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{get_heap_addr, mem_access_size};
+    use cranelift_codegen::ir::types::*;
+    use cranelift_codegen::ir::{self, InstBuilder};
+    use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+
+    /// Get the access size checked by the `heap_addr` generated for a memory access.
+    fn check_size(offset_guard_size: u64, offset: u32, access_size: u32) -> u32 {
+        let mut func = ir::Function::new();
+        let base = func.create_global_value(ir::GlobalValueData::VMContext);
+        let heap = func.create_heap(ir::HeapData {
+            base,
+            min_size: 0.into(),
+            offset_guard_size: offset_guard_size.into(),
+            style: ir::HeapStyle::Static {
+                bound: 0x1_0000.into(),
+            },
+            index_type: I32,
+        });
+        let mut func_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut func, &mut func_ctx);
+        let ebb = builder.create_ebb();
+        builder.switch_to_block(ebb);
+        let index = builder.ins().iconst(I32, 0);
+        let (addr, _) = get_heap_addr(heap, index, offset, access_size, I64, &mut builder);
+        let dfg = &builder.func.dfg;
+        let mut inst = dfg.value_def(addr).unwrap_inst();
+        if dfg[inst].opcode() == ir::Opcode::IaddImm {
+            inst = dfg.value_def(dfg.inst_args(inst)[0]).unwrap_inst();
+        }
+        match dfg[inst] {
+            ir::InstructionData::HeapAddr { imm, .. } => imm.into(),
+            _ => panic!("expected a heap_addr"),
+        }
+    }
+
+    #[test]
+    fn guardless_heap() {
+        assert_eq!(check_size(0, 0, 1), 1);
+        assert_eq!(check_size(0, 0, 8), 8);
+        assert_eq!(check_size(0, 0x1234, 4), 0x1238);
+        assert_eq!(check_size(0, 0x7fff_ffff, 8), 0x8000_0007);
+    }
+
+    #[test]
+    fn guardless_heap_end() {
+        // The access ends 5 bytes after the range a `heap_addr` can check, so the index is
+        // adjusted by 5 bytes first.
+        let mut func = ir::Function::new();
+        let base = func.create_global_value(ir::GlobalValueData::VMContext);
+        let heap = func.create_heap(ir::HeapData {
+            base,
+            min_size: 0.into(),
+            offset_guard_size: 0.into(),
+            style: ir::HeapStyle::Static {
+                bound: 0x1_0000_0000.into(),
+            },
+            index_type: I32,
+        });
+        let mut func_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut func, &mut func_ctx);
+        let ebb = builder.create_ebb();
+        builder.switch_to_block(ebb);
+        let index = builder.ins().iconst(I32, 1);
+        let (addr, offset) = get_heap_addr(heap, index, 0xffff_fffc, 8, I64, &mut builder);
+
+        // The base pointer is adjusted because the offset doesn't fit in an `Offset32`.
+        let dfg = &builder.func.dfg;
+        let adj = dfg.value_def(addr).unwrap_inst();
+        assert_eq!(dfg[adj].opcode(), ir::Opcode::IaddImm);
+        assert_eq!(i64::from(offset), 0xffff_fff7 - 0x8000_0000);
+
+        let heap_addr = dfg.value_def(dfg.inst_args(adj)[0]).unwrap_inst();
+        match dfg[heap_addr] {
+            ir::InstructionData::HeapAddr { imm, .. } => {
+                let imm: u32 = imm.into();
+                assert_eq!(imm, 0xffff_ffff);
+            }
+            _ => panic!("expected a heap_addr"),
+        }
+        let adjusted_index = dfg.value_def(dfg.inst_args(heap_addr)[0]).unwrap_inst();
+        assert_eq!(dfg[adjusted_index].opcode(), ir::Opcode::IaddCout);
+        let excess = dfg
+            .value_def(dfg.inst_args(adjusted_index)[1])
+            .unwrap_inst();
+        match dfg[excess] {
+            ir::InstructionData::UnaryImm { imm, .. } => {
+                let imm: i64 = imm.into();
+                assert_eq!(imm, 5);
+            }
+            _ => panic!("expected an iconst"),
+        }
+    }
+
+    #[test]
+    fn offset_guard_heap() {
+        assert_eq!(check_size(0x1000, 0, 8), 8);
+        assert_eq!(check_size(0x1000, 0x1234, 4), 0x1238);
+        assert_eq!(check_size(0x8000_0000, 0xffff_fffc, 8), 0xffff_ffff);
+    }
+
+    #[test]
+    fn access_sizes() {
+        assert_eq!(mem_access_size(ir::Opcode::Load, I64), 8);
+        assert_eq!(mem_access_size(ir::Opcode::Load, F32), 4);
+        assert_eq!(mem_access_size(ir::Opcode::Uload8, I64), 1);
+        assert_eq!(mem_access_size(ir::Opcode::Sload16, I32), 2);
+        assert_eq!(mem_access_size(ir::Opcode::Uload32, I64), 4);
+        assert_eq!(mem_access_size(ir::Opcode::Store, F64), 8);
+        assert_eq!(mem_access_size(ir::Opcode::Istore8, I32), 1);
+        assert_eq!(mem_access_size(ir::Opcode::Istore32, I64), 4);
+    }
+}
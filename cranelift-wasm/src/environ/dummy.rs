@@ -17,7 +17,7 @@ use cranelift_codegen::ir::immediates::{Offset32, Uimm64};
 use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{self, InstBuilder};
 use cranelift_codegen::isa::TargetFrontendConfig;
-use cranelift_entity::{EntityRef, PrimaryMap};
+use cranelift_entity::{EntityRef, PrimaryMap, SecondaryMap};
 use std::boxed::Box;
 use std::string::String;
 use std::vec::Vec;
@@ -73,6 +73,9 @@ pub struct DummyModuleInfo {
     /// Function bodies.
     pub function_bodies: PrimaryMap<DefinedFuncIndex, ir::Function>,
 
+    /// Function names as provided by `declare_func_name`, empty for unnamed functions.
+    pub function_names: SecondaryMap<FuncIndex, String>,
+
     /// Tables as provided by `declare_table`.
     pub tables: PrimaryMap<TableIndex, Exportable<Table>>,
 
@@ -98,6 +101,7 @@ impl DummyModuleInfo {
             imported_memories: Vec::new(),
             functions: PrimaryMap::new(),
             function_bodies: PrimaryMap::new(),
+            function_names: SecondaryMap::new(),
             tables: PrimaryMap::new(),
             memories: PrimaryMap::new(),
             globals: PrimaryMap::new(),
@@ -152,6 +156,37 @@ impl DummyEnvironment {
     pub fn get_num_func_imports(&self) -> usize {
         self.info.imported_funcs.len()
     }
+
+    /// Rename the defined functions and the functions they call after the names declared by
+    /// the name section.
+    ///
+    /// The name section follows the code section, so this must be called once the whole module
+    /// is translated. The named functions get user names in namespace 1, interned in the `names`
+    /// table of each function, and the unnamed ones keep the names given by their index.
+    pub fn attach_func_names(&mut self) {
+        let function_names = &self.info.function_names;
+        let rename = |names: &mut ir::ExternalNameTable, name: &mut ir::ExternalName| {
+            if let ir::ExternalName::User {
+                namespace: 0,
+                index,
+            } = *name
+            {
+                let func_name = &function_names[FuncIndex::from_u32(index)];
+                if !func_name.is_empty() {
+                    *name = names.intern(func_name);
+                }
+            }
+        };
+        for func in self.info.function_bodies.values_mut() {
+            if func.names.is_empty() {
+                func.names = ir::ExternalNameTable::new(1);
+            }
+            rename(&mut func.names, &mut func.name);
+            for ext_func in func.dfg.ext_funcs.values_mut() {
+                rename(&mut func.names, &mut ext_func.name);
+            }
+        }
+    }
 }
 
 /// The `FuncEnvironment` implementation for use by the `DummyEnvironment`.
@@ -169,6 +204,16 @@ impl<'dummy_environment> DummyFuncEnvironment<'dummy_environment> {
         }
     }
 
+    // Get the type of the addresses used to access the memory `index`, which is also the type of
+    // the page counts taken and returned by `memory.grow` and `memory.size`.
+    fn memory_index_type(&self, index: MemoryIndex) -> ir::Type {
+        if self.mod_info.memories[index].entity.memory64 {
+            I64
+        } else {
+            I32
+        }
+    }
+
     // Create a signature for `sigidx` amended with a `vmctx` argument after the standard wasm
     // arguments.
     fn vmctx_sig(&self, sigidx: SignatureIndex) -> ir::Signature {
@@ -205,7 +250,7 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         })
     }
 
-    fn make_heap(&mut self, func: &mut ir::Function, _index: MemoryIndex) -> WasmResult<ir::Heap> {
+    fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> WasmResult<ir::Heap> {
         // Create a static heap whose base address is stored at `vmctx+0`.
         let addr = func.create_global_value(ir::GlobalValueData::VMContext);
         let gv = func.create_global_value(ir::GlobalValueData::Load {
@@ -222,7 +267,7 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
             style: ir::HeapStyle::Static {
                 bound: 0x1_0000_0000.into(),
             },
-            index_type: I32,
+            index_type: self.memory_index_type(index),
         }))
     }
 
@@ -245,7 +290,7 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         Ok(func.create_table(ir::TableData {
             base_gv,
             min_size: Uimm64::new(0),
-            bound_gv,
+            style: ir::TableStyle::Dynamic { bound_gv },
             element_size: Uimm64::from(u64::from(self.pointer_bytes()) * 2),
             index_type: I32,
         }))
@@ -345,20 +390,20 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
     fn translate_memory_grow(
         &mut self,
         mut pos: FuncCursor,
-        _index: MemoryIndex,
+        index: MemoryIndex,
         _heap: ir::Heap,
         _val: ir::Value,
     ) -> WasmResult<ir::Value> {
-        Ok(pos.ins().iconst(I32, -1))
+        Ok(pos.ins().iconst(self.memory_index_type(index), -1))
     }
 
     fn translate_memory_size(
         &mut self,
         mut pos: FuncCursor,
-        _index: MemoryIndex,
+        index: MemoryIndex,
         _heap: ir::Heap,
     ) -> WasmResult<ir::Value> {
-        Ok(pos.ins().iconst(I32, -1))
+        Ok(pos.ins().iconst(self.memory_index_type(index), -1))
     }
 }
 
@@ -540,4 +585,9 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
         self.info.function_bodies.push(func);
         Ok(())
     }
+
+    fn declare_func_name(&mut self, func_index: FuncIndex, name: &'data str) -> WasmResult<()> {
+        self.info.function_names[func_index] = String::from(name);
+        Ok(())
+    }
 }
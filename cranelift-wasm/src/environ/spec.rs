@@ -159,6 +159,19 @@ pub trait FuncEnvironment {
     /// by `index`.
     ///
     /// The index space covers both imported and locally declared memories.
+    ///
+    /// The heap's `index_type` must be `I64` for memories declared with `memory64` set, since
+    /// their load and store addresses are `i64` values. Classic 32-bit memories may use either
+    /// `I32` or `I64`; in the latter case their addresses are zero-extended before each access.
+    ///
+    /// The heap also determines how accesses are bounds checked:
+    ///
+    /// - A static heap with a bound of at least 4 GB needs no checks for 32-bit addresses with
+    ///   offsets smaller than the offset-guard size, relying on the guard pages to trap.
+    /// - A heap without offset-guard pages gets explicit checks covering every byte of each
+    ///   access. This suits targets that can't reserve address space for guard pages.
+    /// - Other heaps get explicit checks of the bytes of each access that the offset-guard pages
+    ///   don't cover, and rely on the offset-guard pages to trap on the rest.
     fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> WasmResult<ir::Heap>;
 
     /// Set up the necessary preamble definitions in `func` to access the table identified
@@ -244,7 +257,8 @@ pub trait FuncEnvironment {
     ///
     /// The `val` value is the requested memory size in pages.
     ///
-    /// Returns the old size (in pages) of the memory.
+    /// Returns the old size (in pages) of the memory. Both values are `i64` for memories declared
+    /// with `memory64` set, and `i32` otherwise.
     fn translate_memory_grow(
         &mut self,
         pos: FuncCursor,
@@ -258,7 +272,8 @@ pub trait FuncEnvironment {
     /// The `index` provided identifies the linear memory to query, and `heap` is the heap reference
     /// returned by `make_heap` for the same index.
     ///
-    /// Returns the size in pages of the memory.
+    /// Returns the size in pages of the memory, as an `i64` for memories declared with `memory64`
+    /// set, and as an `i32` otherwise.
     fn translate_memory_size(
         &mut self,
         pos: FuncCursor,
@@ -275,6 +290,30 @@ pub trait FuncEnvironment {
         Ok(())
     }
 
+    /// Should the translated code be instrumented with gas metering?
+    ///
+    /// When this returns `true`, the function body is split into straight-line sequences of
+    /// operators ending at control flow operators and branches. The `operator_cost` of each
+    /// operator in a sequence is summed up and `translate_gas_charge` is called to charge the total
+    /// at the start of the sequence.
+    fn gas_metering(&self) -> bool {
+        false
+    }
+
+    /// Get the gas cost of executing the operator `op` when gas metering is enabled.
+    fn operator_cost(&self, _op: &Operator) -> u64 {
+        1
+    }
+
+    /// Emit code at `pos` that charges `cost` units of gas and traps if the gas is exhausted.
+    ///
+    /// This is only called when `gas_metering` returns `true`, and never with a zero `cost`. The
+    /// emitted code is inserted after the rest of the sequence has been translated, possibly in the
+    /// middle of an EBB, so it must not contain any branches. Use conditional traps instead.
+    fn translate_gas_charge(&mut self, _pos: FuncCursor, _cost: u64) -> WasmResult<()> {
+        wasm_unsupported!("gas metering");
+    }
+
     /// Optional callback for the `FunctionEnvironment` performing this translation to maintain
     /// internal state or prepare custom state for the operator to translate
     fn before_translate_operator(
@@ -467,4 +506,25 @@ pub trait ModuleEnvironment<'data> {
         offset: usize,
         data: &'data [u8],
     ) -> WasmResult<()>;
+
+    /// Declares the name of a function, as found in the name section. By default this does
+    /// nothing, but implementations can use this to give functions readable names in
+    /// disassembly, trap reports and profiles.
+    ///
+    /// Names may be declared for imported functions too, and may arrive after the function body
+    /// has been defined, since the name section usually follows the code section.
+    fn declare_func_name(&mut self, _func_index: FuncIndex, _name: &'data str) -> WasmResult<()> {
+        Ok(())
+    }
+
+    /// Declares the name of a local (including parameters) of a function, as found in the name
+    /// section. By default this does nothing.
+    fn declare_local_name(
+        &mut self,
+        _func_index: FuncIndex,
+        _local_index: u32,
+        _name: &'data str,
+    ) -> WasmResult<()> {
+        Ok(())
+    }
 }
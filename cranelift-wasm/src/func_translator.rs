@@ -6,15 +6,16 @@
 
 use crate::code_translator::translate_operator;
 use crate::environ::{FuncEnvironment, ReturnMode, WasmResult};
-use crate::state::{TranslationState, VisibleTranslationState};
+use crate::state::{GasCharge, TranslationState, VisibleTranslationState};
 use crate::translation_utils::get_vmctx_value_label;
 use crate::wasm_unsupported;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
 use cranelift_codegen::entity::EntityRef;
-use cranelift_codegen::ir::{self, Ebb, InstBuilder, ValueLabel};
+use cranelift_codegen::ir::{self, Ebb, InstBuilder};
 use cranelift_codegen::timing;
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
 use log::info;
-use wasmparser::{self, BinaryReader};
+use wasmparser::{self, BinaryReader, Operator};
 
 /// WebAssembly to Cranelift IR function translator.
 ///
@@ -87,6 +88,9 @@ impl FuncTranslator {
         // This clears the `FunctionBuilderContext`.
         let mut builder = FunctionBuilder::new(func, &mut self.func_ctx);
         builder.set_srcloc(cur_srcloc(&reader));
+        // Variables are numbered like Wasm locals, so their values are labelled with the index of
+        // the local they hold.
+        builder.set_auto_value_labels(true);
         let entry_block = builder.create_ebb();
         builder.append_ebb_params_for_function_params(entry_block);
         builder.switch_to_block(entry_block); // This also creates values for the arguments.
@@ -188,7 +192,6 @@ fn declare_locals<FE: FuncEnvironment + ?Sized>(
         let local = Variable::new(*next_local);
         builder.declare_var(local, ty);
         builder.def_var(local, zeroval);
-        builder.set_val_label(zeroval, ValueLabel::new(*next_local));
         *next_local += 1;
     }
     Ok(())
@@ -207,13 +210,22 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
     // The control stack is initialized with a single block representing the whole function.
     debug_assert_eq!(state.control_stack.len(), 1, "State not initialized");
 
+    let gas_metering = environ.gas_metering();
+
     // Keep going until the final `End` operator which pops the outermost block.
     while !state.control_stack.is_empty() {
-        builder.set_srcloc(cur_srcloc(&reader));
+        let srcloc = cur_srcloc(&reader);
+        builder.set_srcloc(srcloc);
         let op = reader.read_operator()?;
+        if gas_metering && state.reachable {
+            account_gas(&op, srcloc, builder, state, environ);
+        }
         environ.before_translate_operator(&op, builder, &VisibleTranslationState::new(state))?;
         translate_operator(&op, builder, state, environ)?;
         environ.after_translate_operator(&op, builder, &VisibleTranslationState::new(state))?;
+        if gas_metering && ends_straight_line_code(&op) {
+            charge_gas(builder, state, environ)?;
+        }
     }
 
     // The final `End` operator left us in the exit block where we need to manually add a return
@@ -240,6 +252,69 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
     Ok(())
 }
 
+/// Add the cost of `op` to the gas charge of the current straight-line sequence of operators,
+/// starting a new sequence at the current position if necessary.
+fn account_gas<FE: FuncEnvironment + ?Sized>(
+    op: &Operator,
+    srcloc: ir::SourceLoc,
+    builder: &mut FunctionBuilder,
+    state: &mut TranslationState,
+    environ: &FE,
+) {
+    if state.gas_charge.is_none() {
+        let ebb = builder
+            .cursor()
+            .current_ebb()
+            .expect("Translating outside of an EBB");
+        state.gas_charge = Some(GasCharge {
+            ebb,
+            after: builder.func.layout.last_inst(ebb),
+            srcloc,
+            cost: 0,
+        });
+    }
+    if let Some(ref mut charge) = state.gas_charge {
+        charge.cost = charge.cost.saturating_add(environ.operator_cost(op));
+    }
+}
+
+/// Insert the gas charge for the straight-line sequence of operators that just ended.
+fn charge_gas<FE: FuncEnvironment + ?Sized>(
+    builder: &mut FunctionBuilder,
+    state: &mut TranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    if let Some(charge) = state.gas_charge.take() {
+        if charge.cost != 0 {
+            let pos = FuncCursor::new(builder.func).with_srcloc(charge.srcloc);
+            let pos = match charge.after {
+                Some(inst) => pos.after_inst(inst),
+                None => pos.at_first_insertion_point(charge.ebb),
+            };
+            environ.translate_gas_charge(pos, charge.cost)?;
+        }
+    }
+    Ok(())
+}
+
+/// Does `op` end a straight-line sequence of operators for gas metering purposes?
+///
+/// These are the operators that branch or whose following code can be reached by a branch.
+fn ends_straight_line_code(op: &Operator) -> bool {
+    match *op {
+        Operator::Loop { .. }
+        | Operator::If { .. }
+        | Operator::Else
+        | Operator::End
+        | Operator::Br { .. }
+        | Operator::BrIf { .. }
+        | Operator::BrTable { .. }
+        | Operator::Return
+        | Operator::Unreachable => true,
+        _ => false,
+    }
+}
+
 /// Get the current source location from a reader.
 fn cur_srcloc(reader: &BinaryReader) -> ir::SourceLoc {
     // We record source locations as byte code offsets relative to the beginning of the file.
@@ -250,10 +325,19 @@ fn cur_srcloc(reader: &BinaryReader) -> ir::SourceLoc {
 #[cfg(test)]
 mod tests {
     use super::{FuncTranslator, ReturnMode};
-    use crate::environ::DummyEnvironment;
-    use cranelift_codegen::ir::types::I32;
+    use crate::environ::{
+        DummyEnvironment, FuncEnvironment, GlobalVariable, ModuleEnvironment, WasmResult,
+    };
+    use crate::translation_utils::{
+        FuncIndex, GlobalIndex, Memory, MemoryIndex, SignatureIndex, TableIndex,
+    };
+    use cranelift_codegen::cursor::FuncCursor;
+    use cranelift_codegen::ir::condcodes::IntCC;
+    use cranelift_codegen::ir::types::{I32, I64};
+    use cranelift_codegen::ir::InstBuilder;
     use cranelift_codegen::{ir, isa, settings, Context};
     use log::debug;
+    use std::vec::Vec;
     use target_lexicon::PointerWidth;
 
     #[test]
@@ -380,4 +464,242 @@ mod tests {
         debug!("{}", ctx.func.display(None));
         ctx.verify(&flags).unwrap();
     }
+
+    #[test]
+    fn memory64_load() {
+        // A load from a 64-bit memory uses an `i64` address.
+        //
+        // (memory i64 1)
+        // (func $memory64_load (param i64) (result i32)
+        //     (i32.load (get_local 0))
+        // )
+        const BODY: [u8; 8] = [
+            0x00, // local decl count
+            0x20, 0x00, // get_local 0
+            0x28, 0x02, 0x00, // i32.load align=2 offset=0
+            0x0f, // return
+            0x0b, // end
+        ];
+
+        let mut trans = FuncTranslator::new();
+        let flags = settings::Flags::new(settings::builder());
+        let mut runtime = DummyEnvironment::new(
+            isa::TargetFrontendConfig {
+                default_call_conv: isa::CallConv::Fast,
+                pointer_width: PointerWidth::U64,
+            },
+            ReturnMode::NormalReturns,
+            false,
+        );
+        runtime
+            .declare_memory(Memory {
+                minimum: 1,
+                maximum: None,
+                shared: false,
+                memory64: true,
+            })
+            .unwrap();
+        let mut ctx = Context::new();
+
+        ctx.func.name = ir::ExternalName::testcase("memory64_load");
+        ctx.func.signature.params.push(ir::AbiParam::new(I64));
+        ctx.func
+            .signature
+            .params
+            .push(ir::AbiParam::special(I64, ir::ArgumentPurpose::VMContext));
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+
+        trans
+            .translate(&BODY, 0, &mut ctx.func, &mut runtime.func_env())
+            .unwrap();
+        debug!("{}", ctx.func.display(None));
+        ctx.verify(&flags).unwrap();
+        let heap = ctx.func.heaps.keys().next().unwrap();
+        assert_eq!(ctx.func.heaps[heap].index_type, I64);
+    }
+
+    /// A function environment charging one unit of gas per operator from a counter stored at
+    /// `vmctx+0`.
+    struct MeteredEnvironment<FE: FuncEnvironment> {
+        inner: FE,
+    }
+
+    impl<FE: FuncEnvironment> FuncEnvironment for MeteredEnvironment<FE> {
+        fn target_config(&self) -> isa::TargetFrontendConfig {
+            self.inner.target_config()
+        }
+
+        fn make_global(
+            &mut self,
+            func: &mut ir::Function,
+            index: GlobalIndex,
+        ) -> WasmResult<GlobalVariable> {
+            self.inner.make_global(func, index)
+        }
+
+        fn make_heap(
+            &mut self,
+            func: &mut ir::Function,
+            index: MemoryIndex,
+        ) -> WasmResult<ir::Heap> {
+            self.inner.make_heap(func, index)
+        }
+
+        fn make_table(
+            &mut self,
+            func: &mut ir::Function,
+            index: TableIndex,
+        ) -> WasmResult<ir::Table> {
+            self.inner.make_table(func, index)
+        }
+
+        fn make_indirect_sig(
+            &mut self,
+            func: &mut ir::Function,
+            index: SignatureIndex,
+        ) -> WasmResult<ir::SigRef> {
+            self.inner.make_indirect_sig(func, index)
+        }
+
+        fn make_direct_func(
+            &mut self,
+            func: &mut ir::Function,
+            index: FuncIndex,
+        ) -> WasmResult<ir::FuncRef> {
+            self.inner.make_direct_func(func, index)
+        }
+
+        fn translate_call_indirect(
+            &mut self,
+            pos: FuncCursor,
+            table_index: TableIndex,
+            table: ir::Table,
+            sig_index: SignatureIndex,
+            sig_ref: ir::SigRef,
+            callee: ir::Value,
+            call_args: &[ir::Value],
+        ) -> WasmResult<ir::Inst> {
+            self.inner.translate_call_indirect(
+                pos,
+                table_index,
+                table,
+                sig_index,
+                sig_ref,
+                callee,
+                call_args,
+            )
+        }
+
+        fn translate_memory_grow(
+            &mut self,
+            pos: FuncCursor,
+            index: MemoryIndex,
+            heap: ir::Heap,
+            val: ir::Value,
+        ) -> WasmResult<ir::Value> {
+            self.inner.translate_memory_grow(pos, index, heap, val)
+        }
+
+        fn translate_memory_size(
+            &mut self,
+            pos: FuncCursor,
+            index: MemoryIndex,
+            heap: ir::Heap,
+        ) -> WasmResult<ir::Value> {
+            self.inner.translate_memory_size(pos, index, heap)
+        }
+
+        fn gas_metering(&self) -> bool {
+            true
+        }
+
+        fn translate_gas_charge(&mut self, mut pos: FuncCursor, cost: u64) -> WasmResult<()> {
+            let vmctx = pos
+                .func
+                .special_param(ir::ArgumentPurpose::VMContext)
+                .expect("Missing vmctx parameter");
+            let flags = ir::MemFlags::trusted();
+            let gas = pos.ins().load(I64, flags, vmctx, 0);
+            let gas = pos.ins().iadd_imm(gas, -(cost as i64));
+            let exhausted = pos.ins().icmp_imm(IntCC::SignedLessThan, gas, 0);
+            pos.ins().trapnz(exhausted, ir::TrapCode::User(0));
+            pos.ins().store(flags, gas, vmctx, 0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn gas_metering() {
+        // A loop counting down to zero, charged once before the loop, once per iteration and once
+        // after the loop.
+        //
+        // (func $gas_metering (param i32) (result i32)
+        //     (loop
+        //         (set_local 0 (i32.sub (get_local 0) (i32.const 1)))
+        //         (br_if 0 (get_local 0))
+        //     )
+        //     (get_local 0)
+        // )
+        const BODY: [u8; 18] = [
+            0x00, // local decl count
+            0x03, 0x40, // loop
+            0x20, 0x00, // get_local 0
+            0x41, 0x01, // i32.const 1
+            0x6b, // i32.sub
+            0x21, 0x00, // set_local 0
+            0x20, 0x00, // get_local 0
+            0x0d, 0x00, // br_if 0
+            0x0b, // end
+            0x20, 0x00, // get_local 0
+            0x0b, // end
+        ];
+
+        let mut trans = FuncTranslator::new();
+        let flags = settings::Flags::new(settings::builder());
+        let runtime = DummyEnvironment::new(
+            isa::TargetFrontendConfig {
+                default_call_conv: isa::CallConv::Fast,
+                pointer_width: PointerWidth::U64,
+            },
+            ReturnMode::NormalReturns,
+            false,
+        );
+        let mut ctx = Context::new();
+
+        ctx.func.name = ir::ExternalName::testcase("gas_metering");
+        ctx.func.signature.params.push(ir::AbiParam::new(I32));
+        ctx.func
+            .signature
+            .params
+            .push(ir::AbiParam::special(I64, ir::ArgumentPurpose::VMContext));
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+
+        let mut environ = MeteredEnvironment {
+            inner: runtime.func_env(),
+        };
+        trans
+            .translate(&BODY, 0, &mut ctx.func, &mut environ)
+            .unwrap();
+        debug!("{}", ctx.func.display(None));
+        ctx.verify(&flags).unwrap();
+
+        // Collect the gas charges in layout order.
+        let mut charges = Vec::new();
+        for ebb in ctx.func.layout.ebbs() {
+            for inst in ctx.func.layout.ebb_insts(ebb) {
+                if let ir::InstructionData::BinaryImm {
+                    opcode: ir::Opcode::IaddImm,
+                    imm,
+                    ..
+                } = ctx.func.dfg[inst]
+                {
+                    let imm: i64 = imm.into();
+                    charges.push(-imm);
+                }
+            }
+        }
+        // `loop`; `get_local`, `i32.const`, `i32.sub`, `set_local`, `get_local`, `br_if`;
+        // `end`; `get_local`, `end`.
+        assert_eq!(charges, [1, 6, 1, 2]);
+    }
 }
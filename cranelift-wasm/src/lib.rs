@@ -49,6 +49,7 @@ use std::collections::{
     HashMap,
 };
 
+mod code_stream;
 mod code_translator;
 mod environ;
 mod func_translator;
@@ -57,6 +58,7 @@ mod sections_translator;
 mod state;
 mod translation_utils;
 
+pub use crate::code_stream::CodeSectionStream;
 pub use crate::environ::{
     DummyEnvironment, FuncEnvironment, GlobalVariable, ModuleEnvironment, ReturnMode, WasmError,
     WasmResult,
@@ -4,10 +4,10 @@ use crate::environ::{ModuleEnvironment, WasmError, WasmResult};
 use crate::sections_translator::{
     parse_code_section, parse_data_section, parse_element_section, parse_export_section,
     parse_function_section, parse_global_section, parse_import_section, parse_memory_section,
-    parse_start_section, parse_table_section, parse_type_section,
+    parse_name_section, parse_start_section, parse_table_section, parse_type_section,
 };
 use cranelift_codegen::timing;
-use wasmparser::{ModuleReader, SectionCode};
+use wasmparser::{CustomSectionKind, ModuleReader, Section, SectionCode};
 
 /// Translate a sequence of bytes forming a valid Wasm binary into a list of valid Cranelift IR
 /// [`Function`](cranelift_codegen::ir::Function).
@@ -18,120 +18,107 @@ pub fn translate_module<'data>(
     let _tt = timing::wasm_translate_module();
     let mut reader = ModuleReader::new(data)?;
 
-    reader.skip_custom_sections()?;
-    if reader.eof() {
-        return Ok(());
-    }
-    let mut section = reader.read()?;
+    let mut section = match next_section(&mut reader, environ)? {
+        Some(section) => section,
+        None => return Ok(()),
+    };
 
     if let SectionCode::Type = section.code {
         let types = section.get_type_section_reader()?;
         parse_type_section(types, environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Import = section.code {
-        let imports = section.get_import_section_reader()?;
-        parse_import_section(imports, environ)?;
+        parse_import_section(section.get_binary_reader(), environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Function = section.code {
         let functions = section.get_function_section_reader()?;
         parse_function_section(functions, environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Table = section.code {
         let tables = section.get_table_section_reader()?;
         parse_table_section(tables, environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Memory = section.code {
-        let memories = section.get_memory_section_reader()?;
-        parse_memory_section(memories, environ)?;
+        parse_memory_section(section.get_binary_reader(), environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Global = section.code {
         let globals = section.get_global_section_reader()?;
         parse_global_section(globals, environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Export = section.code {
         let exports = section.get_export_section_reader()?;
         parse_export_section(exports, environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Start = section.code {
         let start = section.get_start_section_content()?;
         parse_start_section(start, environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Element = section.code {
         let elements = section.get_element_section_reader()?;
         parse_element_section(elements, environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Code = section.code {
         let code = section.get_code_section_reader()?;
         parse_code_section(code, environ)?;
 
-        reader.skip_custom_sections()?;
-        if reader.eof() {
-            return Ok(());
-        }
-        section = reader.read()?;
+        section = match next_section(&mut reader, environ)? {
+            Some(section) => section,
+            None => return Ok(()),
+        };
     }
 
     if let SectionCode::Data = section.code {
@@ -139,13 +126,38 @@ pub fn translate_module<'data>(
         parse_data_section(data, environ)?;
     }
 
-    reader.skip_custom_sections()?;
-    if !reader.eof() {
+    if let Some(section) = next_section(&mut reader, environ)? {
         return Err(WasmError::InvalidWebAssembly {
             message: "sections must occur at most once and in the prescribed order",
-            offset: reader.current_position(),
+            offset: section.range().start,
         });
     }
 
     Ok(())
 }
+
+/// Read the next section that isn't a custom section, or `None` at the end of the module.
+///
+/// Custom sections that carry information for the environment are parsed along the way.
+fn next_section<'data>(
+    reader: &mut ModuleReader<'data>,
+    environ: &mut dyn ModuleEnvironment<'data>,
+) -> WasmResult<Option<Section<'data>>> {
+    while !reader.eof() {
+        let section = reader.read()?;
+        match section.code {
+            SectionCode::Custom {
+                kind: CustomSectionKind::Name,
+                ..
+            } => {
+                // The name section is purely informative, so a malformed one is ignored.
+                if let Ok(names) = section.get_name_section_reader() {
+                    parse_name_section(names, environ)?;
+                }
+            }
+            SectionCode::Custom { .. } => {}
+            _ => return Ok(Some(section)),
+        }
+    }
+    Ok(None)
+}
@@ -7,7 +7,7 @@
 //! The special case of the initialize expressions for table elements offsets or global variables
 //! is handled, according to the semantics of WebAssembly, to only specific expressions that are
 //! interpreted on the fly.
-use crate::environ::{ModuleEnvironment, WasmResult};
+use crate::environ::{ModuleEnvironment, WasmError, WasmResult};
 use crate::translation_utils::{
     tabletype_to_type, type_to_type, FuncIndex, Global, GlobalIndex, GlobalInit, Memory,
     MemoryIndex, SignatureIndex, Table, TableElementType, TableIndex,
@@ -18,11 +18,10 @@ use cranelift_codegen::ir::{self, AbiParam, Signature};
 use cranelift_entity::EntityRef;
 use std::vec::Vec;
 use wasmparser::{
-    self, CodeSectionReader, Data, DataKind, DataSectionReader, Element, ElementKind,
+    self, BinaryReader, CodeSectionReader, Data, DataKind, DataSectionReader, Element, ElementKind,
     ElementSectionReader, Export, ExportSectionReader, ExternalKind, FuncType,
-    FunctionSectionReader, GlobalSectionReader, GlobalType, ImportSectionEntryType,
-    ImportSectionReader, MemorySectionReader, MemoryType, Operator, TableSectionReader,
-    TypeSectionReader,
+    FunctionSectionReader, GlobalSectionReader, GlobalType, Name, NameSectionReader, Naming,
+    NamingReader, Operator, TableSectionReader, TypeSectionReader,
 };
 
 /// Parses the Type section of the wasm module.
@@ -59,63 +58,74 @@ pub fn parse_type_section(
 }
 
 /// Parses the Import section of the wasm module.
+///
+/// The entries are decoded from the raw section rather than with an `ImportSectionReader`, which
+/// rejects the memory types of 64-bit memories.
 pub fn parse_import_section<'data>(
-    imports: ImportSectionReader<'data>,
+    mut imports: BinaryReader<'data>,
     environ: &mut dyn ModuleEnvironment<'data>,
 ) -> WasmResult<()> {
-    environ.reserve_imports(imports.get_count())?;
+    let count = imports.read_var_u32()?;
+    environ.reserve_imports(count)?;
 
-    for entry in imports {
-        let import = entry?;
-        let module_name = import.module;
-        let field_name = import.field;
+    for _ in 0..count {
+        let module_name = imports.read_string()?;
+        let field_name = imports.read_string()?;
+        let offset = imports.original_position();
 
-        match import.ty {
-            ImportSectionEntryType::Function(sig) => {
+        match imports.read_u8()? {
+            0 => {
+                let sig = imports.read_var_u32()?;
                 environ.declare_func_import(
                     SignatureIndex::from_u32(sig),
                     module_name,
                     field_name,
                 )?;
             }
-            ImportSectionEntryType::Memory(MemoryType {
-                limits: ref memlimits,
-                shared,
-            }) => {
-                environ.declare_memory_import(
-                    Memory {
-                        minimum: memlimits.initial,
-                        maximum: memlimits.maximum,
-                        shared,
+            1 => {
+                let element_type = imports.read_type()?;
+                let flags = read_limits_flags(&mut imports, 0x1)?;
+                let minimum = imports.read_var_u32()?;
+                let maximum = if flags & 0x1 != 0 {
+                    Some(imports.read_var_u32()?)
+                } else {
+                    None
+                };
+                environ.declare_table_import(
+                    Table {
+                        ty: match tabletype_to_type(element_type)? {
+                            Some(t) => TableElementType::Val(t),
+                            None => TableElementType::Func,
+                        },
+                        minimum,
+                        maximum,
                     },
                     module_name,
                     field_name,
                 )?;
             }
-            ImportSectionEntryType::Global(ref ty) => {
+            2 => {
+                let memory = read_memory_type(&mut imports)?;
+                environ.declare_memory_import(memory, module_name, field_name)?;
+            }
+            3 => {
+                let content_type = imports.read_type()?;
+                let mutability = imports.read_var_u32()? != 0;
                 environ.declare_global_import(
                     Global {
-                        ty: type_to_type(ty.content_type).unwrap(),
-                        mutability: ty.mutable,
+                        ty: type_to_type(content_type).unwrap(),
+                        mutability,
                         initializer: GlobalInit::Import,
                     },
                     module_name,
                     field_name,
                 )?;
             }
-            ImportSectionEntryType::Table(ref tab) => {
-                environ.declare_table_import(
-                    Table {
-                        ty: match tabletype_to_type(tab.element_type)? {
-                            Some(t) => TableElementType::Val(t),
-                            None => TableElementType::Func,
-                        },
-                        minimum: tab.limits.initial,
-                        maximum: tab.limits.maximum,
-                    },
-                    module_name,
-                    field_name,
-                )?;
+            _ => {
+                return Err(WasmError::InvalidWebAssembly {
+                    message: "invalid external kind",
+                    offset,
+                })
             }
         }
     }
@@ -124,6 +134,70 @@ pub fn parse_import_section<'data>(
     Ok(())
 }
 
+/// Read the flags of a table or memory type, rejecting the ones that aren't in `allowed`.
+fn read_limits_flags(reader: &mut BinaryReader, allowed: u32) -> WasmResult<u32> {
+    let offset = reader.original_position();
+    let flags = reader.read_var_u32()?;
+    if flags & !allowed != 0 {
+        return Err(WasmError::InvalidWebAssembly {
+            message: "invalid resizable limits flags",
+            offset,
+        });
+    }
+    Ok(flags)
+}
+
+/// Read a memory type.
+///
+/// The flags have `0x1` set when the memory has a maximum size, `0x2` when it is shared, and
+/// `0x4` when it is a 64-bit memory, whose limits are encoded as 64-bit integers.
+fn read_memory_type(reader: &mut BinaryReader) -> WasmResult<Memory> {
+    let flags = read_limits_flags(reader, 0x7)?;
+    let memory64 = flags & 0x4 != 0;
+    let minimum = read_memory_limit(reader, memory64)?;
+    let maximum = if flags & 0x1 != 0 {
+        Some(read_memory_limit(reader, memory64)?)
+    } else {
+        None
+    };
+    Ok(Memory {
+        minimum,
+        maximum,
+        shared: flags & 0x2 != 0,
+        memory64,
+    })
+}
+
+/// Read a number of pages in the limits of a memory type.
+fn read_memory_limit(reader: &mut BinaryReader, memory64: bool) -> WasmResult<u32> {
+    if !memory64 {
+        return Ok(reader.read_var_u32()?);
+    }
+
+    // `BinaryReader` has no unsigned 64-bit LEB128 reader.
+    let offset = reader.original_position();
+    let mut pages = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = reader.read_u8()?;
+        pages |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift >= 64 {
+            return Err(WasmError::InvalidWebAssembly {
+                message: "invalid var_u64",
+                offset,
+            });
+        }
+    }
+    match u32::try_from(pages) {
+        Ok(pages) => Ok(pages),
+        Err(_) => wasm_unsupported!("64-bit memory with {} pages", pages),
+    }
+}
+
 /// Parses the Function section of the wasm module.
 pub fn parse_function_section(
     functions: FunctionSectionReader,
@@ -162,19 +236,18 @@ pub fn parse_table_section(
 }
 
 /// Parses the Memory section of the wasm module.
+///
+/// The memory types are decoded from the raw section, like in `parse_import_section`.
 pub fn parse_memory_section(
-    memories: MemorySectionReader,
+    mut memories: BinaryReader,
     environ: &mut dyn ModuleEnvironment,
 ) -> WasmResult<()> {
-    environ.reserve_memories(memories.get_count())?;
-
-    for entry in memories {
-        let memory = entry?;
-        environ.declare_memory(Memory {
-            minimum: memory.limits.initial,
-            maximum: memory.limits.maximum,
-            shared: memory.shared,
-        })?;
+    let count = memories.read_var_u32()?;
+    environ.reserve_memories(count)?;
+
+    for _ in 0..count {
+        let memory = read_memory_type(&mut memories)?;
+        environ.declare_memory(memory)?;
     }
 
     Ok(())
@@ -351,3 +424,50 @@ pub fn parse_data_section<'data>(
 
     Ok(())
 }
+
+/// Parses the Name section of the wasm module.
+///
+/// The name section is purely informative, so subsections that fail to parse are skipped rather
+/// than reported as errors.
+pub fn parse_name_section<'data>(
+    names: NameSectionReader<'data>,
+    environ: &mut dyn ModuleEnvironment<'data>,
+) -> WasmResult<()> {
+    for subsection in names {
+        match subsection {
+            Ok(Name::Function(function_names)) => {
+                let naming = function_names.get_map().ok().and_then(read_naming);
+                for Naming { index, name } in naming.into_iter().flatten() {
+                    environ.declare_func_name(FuncIndex::from_u32(index), name)?;
+                }
+            }
+            Ok(Name::Local(local_names)) => {
+                let mut functions = match local_names.get_function_local_reader() {
+                    Ok(functions) => functions,
+                    Err(_) => continue,
+                };
+                for _ in 0..functions.get_count() {
+                    let function = match functions.read() {
+                        Ok(function) => function,
+                        Err(_) => break,
+                    };
+                    let func_index = FuncIndex::from_u32(function.func_index);
+                    let naming = function.get_map().ok().and_then(read_naming);
+                    for Naming { index, name } in naming.into_iter().flatten() {
+                        environ.declare_local_name(func_index, index, name)?;
+                    }
+                }
+            }
+            Ok(Name::Module(_)) => {}
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+/// Reads all the entries of a name map, or `None` if any of them is malformed.
+fn read_naming(mut naming: NamingReader) -> Option<Vec<Naming>> {
+    (0..naming.get_count())
+        .map(|_| naming.read().ok())
+        .collect()
+}
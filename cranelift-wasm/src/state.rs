@@ -146,6 +146,21 @@ impl<'a> VisibleTranslationState<'a> {
     }
 }
 
+/// A gas charge for a straight-line sequence of operators that is being translated.
+///
+/// The total cost of the sequence is only known once its last operator has been translated, so the
+/// charge is inserted after the fact at the position recorded when the sequence started.
+pub struct GasCharge {
+    /// The EBB containing the start of the sequence.
+    pub ebb: Ebb,
+    /// The last instruction in `ebb` before the sequence started, or `None` if it was empty.
+    pub after: Option<Inst>,
+    /// The source location of the first operator in the sequence.
+    pub srcloc: ir::SourceLoc,
+    /// The accumulated cost of the operators in the sequence.
+    pub cost: u64,
+}
+
 /// Contains information passed along during the translation and that records:
 ///
 /// - The current value and control stacks.
@@ -160,6 +175,9 @@ pub struct TranslationState {
     /// Is the current translation state still reachable? This is false when translating operators
     /// like End, Return, or Unreachable.
     pub reachable: bool,
+    /// The pending gas charge for the current straight-line sequence of operators, when gas
+    /// metering is enabled.
+    pub gas_charge: Option<GasCharge>,
 
     // Map of global variables that have already been created by `FuncEnvironment::make_global`.
     globals: HashMap<GlobalIndex, GlobalVariable>,
@@ -188,6 +206,7 @@ impl TranslationState {
             stack: Vec::new(),
             control_stack: Vec::new(),
             reachable: true,
+            gas_charge: None,
             globals: HashMap::new(),
             heaps: HashMap::new(),
             tables: HashMap::new(),
@@ -200,6 +219,7 @@ impl TranslationState {
         debug_assert!(self.stack.is_empty());
         debug_assert!(self.control_stack.is_empty());
         self.reachable = true;
+        self.gas_charge = None;
         self.globals.clear();
         self.heaps.clear();
         self.tables.clear();
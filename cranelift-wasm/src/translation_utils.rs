@@ -111,6 +111,8 @@ pub struct Memory {
     pub maximum: Option<u32>,
     /// Whether the memory may be shared between multiple threads.
     pub shared: bool,
+    /// Whether the memory is indexed with 64-bit addresses, as in the memory64 proposal.
+    pub memory64: bool,
 }
 
 /// Helper function translating wasmparser types to Cranelift types when possible.
@@ -2,7 +2,7 @@ use cranelift_codegen::isa;
 use cranelift_codegen::print_errors::pretty_verifier_error;
 use cranelift_codegen::settings::{self, Flags};
 use cranelift_codegen::verifier;
-use cranelift_wasm::{translate_module, DummyEnvironment, ReturnMode};
+use cranelift_wasm::{translate_module, DefinedFuncIndex, DummyEnvironment, FuncIndex, ReturnMode};
 use std::fs;
 use std::fs::File;
 use std::io;
@@ -10,7 +10,7 @@ use std::io::prelude::*;
 use std::path::Path;
 use std::str::FromStr;
 use target_lexicon::triple;
-use wabt::wat2wasm;
+use wabt::{wat2wasm, Wat2Wasm};
 
 #[test]
 fn testsuite() {
@@ -45,6 +45,37 @@ fn use_fallthrough_return() {
     );
 }
 
+#[test]
+fn name_section() {
+    let wasm = Wat2Wasm::new()
+        .write_debug_names(true)
+        .convert(
+            r#"
+            (module
+              (import "env" "imported" (func $imported))
+              (func $foo::bar (param $x i32)
+                (call $imported)))
+            "#,
+        )
+        .unwrap();
+    let flags = Flags::new(settings::builder());
+    let isa = isa::lookup(triple!("riscv64")).unwrap().finish(flags);
+    let mut dummy_environ =
+        DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, false);
+
+    translate_module(wasm.as_ref(), &mut dummy_environ).unwrap();
+
+    let names = &dummy_environ.info.function_names;
+    assert_eq!(names[FuncIndex::from_u32(0)], "imported");
+    assert_eq!(names[FuncIndex::from_u32(1)], "foo::bar");
+
+    dummy_environ.attach_func_names();
+    let func = &dummy_environ.info.function_bodies[DefinedFuncIndex::from_u32(0)];
+    assert_eq!(func.names.name(&func.name), Some("foo::bar"));
+    let callee = func.dfg.ext_funcs.values().next().unwrap();
+    assert_eq!(func.names.name(&callee.name), Some("imported"));
+}
+
 fn read_file(path: &Path) -> io::Result<Vec<u8>> {
     let mut buf: Vec<u8> = Vec::new();
     let mut file = File::open(path)?;
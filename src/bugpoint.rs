@@ -353,6 +353,7 @@ impl Mutator for RemoveUnusedEntities {
                         match func.dfg[inst] {
                             // Add new cases when there are new instruction formats taking a `FuncRef`.
                             InstructionData::Call { func_ref, .. }
+                            | InstructionData::Invoke { func_ref, .. }
                             | InstructionData::FuncAddr { func_ref, .. } => {
                                 ext_func_usage_map
                                     .entry(func_ref)
@@ -375,6 +376,9 @@ impl Mutator for RemoveUnusedEntities {
                                 InstructionData::Call {
                                     ref mut func_ref, ..
                                 }
+                                | InstructionData::Invoke {
+                                    ref mut func_ref, ..
+                                }
                                 | InstructionData::FuncAddr {
                                     ref mut func_ref, ..
                                 } => {
@@ -61,6 +61,7 @@ fn handle_module(
         context.func = func;
 
         let mut relocs = PrintRelocs::new(flag_print);
+        relocs.names = context.func.names.clone();
         let mut traps = PrintTraps::new(flag_print);
         let mut stackmaps = PrintStackmaps::new(flag_print);
         let mut mem = vec![];
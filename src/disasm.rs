@@ -6,6 +6,8 @@ use std::fmt::Write;
 pub struct PrintRelocs {
     pub flag_print: bool,
     pub text: String,
+    /// The symbols of the user-defined names, for demangling them.
+    pub names: ir::ExternalNameTable,
 }
 
 impl PrintRelocs {
@@ -13,6 +15,7 @@ impl PrintRelocs {
         Self {
             flag_print,
             text: String::new(),
+            names: ir::ExternalNameTable::new(0),
         }
     }
 }
@@ -44,10 +47,14 @@ impl binemit::RelocSink for PrintRelocs {
         if self.flag_print {
             write!(
                 &mut self.text,
-                "reloc_external: {} {} {} at {}\n",
+                "reloc_external: {} {} {} at {}",
                 r, name, addend, where_
             )
             .unwrap();
+            if let Some(demangled) = name.demangled(&self.names) {
+                write!(&mut self.text, " ; {}", demangled).unwrap();
+            }
+            self.text.push('\n');
         }
     }
 
@@ -78,6 +85,17 @@ impl binemit::TrapSink for PrintTraps {
             write!(&mut self.text, "trap: {} at {}\n", code, offset).unwrap();
         }
     }
+
+    fn deopt(
+        &mut self,
+        offset: binemit::CodeOffset,
+        _srcloc: ir::SourceLoc,
+        locations: &[binemit::DeoptLocation],
+    ) {
+        if self.flag_print {
+            write!(&mut self.text, "deopt: {:?} at {}\n", locations, offset).unwrap();
+        }
+    }
 }
 
 pub struct PrintStackmaps {
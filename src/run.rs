@@ -2,6 +2,7 @@
 
 use crate::utils::read_to_string;
 use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::Context;
 use cranelift_filetests::FunctionRunner;
 use cranelift_native::builder as host_isa_builder;
 use cranelift_reader::{parse_test, Details, IsaSpec};
@@ -75,10 +76,11 @@ fn run_single_file(path: &PathBuf) -> Result<(), String> {
 /// Main body of `run_single_file` separated for testing
 fn run_file_contents(file_contents: String) -> Result<(), String> {
     let test_file = parse_test(&file_contents, None, None).map_err(|e| e.to_string())?;
+    let mut context = Context::new();
     for (func, Details { comments, .. }) in test_file.functions {
         if comments.iter().any(|c| c.text.contains("run")) {
             let isa = create_target_isa(&test_file.isa_spec)?;
-            FunctionRunner::new(func, isa).run()?
+            FunctionRunner::new(func, isa).run_in_context(&mut context)?
         }
     }
     Ok(())
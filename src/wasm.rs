@@ -115,6 +115,7 @@ fn handle_module(
     let debug_info = flag_calc_value_ranges;
     let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), ReturnMode::NormalReturns, debug_info);
     translate_module(&module_binary, &mut dummy_environ).map_err(|e| e.to_string())?;
+    dummy_environ.attach_func_names();
 
     let _ = terminal.fg(term::color::GREEN);
     vprintln!(flag_verbose, "ok");
@@ -136,6 +137,10 @@ fn handle_module(
                 }
             }
             vprintln!(flag_verbose, "");
+            let func_name = &dummy_environ.info.function_names[FuncIndex::new(func_index)];
+            if !func_name.is_empty() {
+                println!("; Named \"{}\"", func_name);
+            }
             for export_name in
                 &dummy_environ.info.functions[FuncIndex::new(func_index)].export_names
             {
@@ -206,6 +211,10 @@ fn handle_module(
                     println!("; Selected as wasm start function");
                 }
             }
+            let func_name = &dummy_environ.info.function_names[FuncIndex::new(func_index)];
+            if !func_name.is_empty() {
+                println!("; Named \"{}\"", func_name);
+            }
             for export_name in
                 &dummy_environ.info.functions[FuncIndex::new(func_index)].export_names
             {
@@ -47,6 +47,12 @@ cargo build
 banner "Rust unit tests"
 RUST_BACKTRACE=1 cargo test --all
 
+# The compact encoding tables are looked up differently, so run the unit tests and the filetests
+# with them too.
+banner "Rust unit tests with compact encoding tables"
+RUST_BACKTRACE=1 cargo test --features compact-tables
+(cd cranelift-codegen && RUST_BACKTRACE=1 cargo test --features compact-tables)
+
 has_toolchain() {
     rustup toolchain list | grep -q $1
 }